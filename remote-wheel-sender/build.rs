@@ -0,0 +1,44 @@
+use std::env;
+use std::path::Path;
+use std::process::Command;
+
+fn main() {
+    let hash = git(&["rev-parse", "--short", "HEAD"]).unwrap_or_else(|| "unknown".into());
+    println!("cargo:rustc-env=REMOTE_WHEEL_GIT_HASH={hash}");
+
+    let date = time::OffsetDateTime::now_utc().date();
+    println!("cargo:rustc-env=REMOTE_WHEEL_BUILD_DATE={date}");
+
+    // Only rebuild when the commit changes, rather than on every build.
+    if let Some(git_dir) = git(&["rev-parse", "--git-dir"]) {
+        println!("cargo:rerun-if-changed={git_dir}/HEAD");
+        println!("cargo:rerun-if-changed={git_dir}/refs");
+        println!("cargo:rerun-if-changed={git_dir}/packed-refs");
+    }
+
+    // The OpenVR API library isn't bundled, so it has to be found when linking. OPENVR_LIB_DIR may
+    // point to where it is, e.g. the lib/win64 directory of the OpenVR SDK.
+    if env::var_os("CARGO_FEATURE_OPENVR").is_some() {
+        if let Some(dir) = env::var_os("OPENVR_LIB_DIR") {
+            println!(
+                "cargo:rustc-link-search=native={}",
+                Path::new(&dir).display()
+            );
+        }
+    }
+    println!("cargo:rerun-if-env-changed=OPENVR_LIB_DIR");
+
+    println!("cargo:rerun-if-changed=build.rs");
+}
+
+/// Runs a git command, returning its output if it succeeded. Sources that aren't a git checkout
+/// (or builds without git) still build, just without the details.
+fn git(args: &[&str]) -> Option<String> {
+    let output = Command::new("git").args(args).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+
+    let out = String::from_utf8(output.stdout).ok()?;
+    Some(out.trim().to_string())
+}
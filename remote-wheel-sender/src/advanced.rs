@@ -0,0 +1,173 @@
+use std::fmt::Display;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::check::Report;
+
+#[derive(Debug, Default, Deserialize, JsonSchema, Serialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct AdvancedConfig {
+    priority: Priority,
+    timer_resolution_ms: Option<u32>,
+    output_rate: Option<f64>,
+
+    #[serde(deserialize_with = "crate::units::optional_seconds")]
+    #[schemars(schema_with = "crate::units::optional_seconds_schema")]
+    flush_window: Option<f64>,
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, JsonSchema, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+enum Priority {
+    #[default]
+    Normal,
+    AboveNormal,
+    High,
+}
+
+impl Display for Priority {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Priority::Normal => "normal",
+            Priority::AboveNormal => "above-normal",
+            Priority::High => "high",
+        })
+    }
+}
+
+/// Keeps the requested timer resolution in effect until it's dropped.
+pub struct Tuning {
+    #[cfg_attr(not(windows), allow(unused))]
+    timer_resolution_ms: Option<u32>,
+}
+
+impl AdvancedConfig {
+    /// How many times per second outputs are sent to, if they're sent at a fixed rate rather than
+    /// whenever inputs change.
+    pub fn output_rate(&self) -> Option<f64> {
+        self.output_rate
+    }
+
+    /// How long to wait for other flushes after one arrives, so that they're sent as one. Not used
+    /// with a fixed output rate, which already combines them.
+    pub fn flush_window(&self) -> Option<f64> {
+        self.flush_window
+    }
+
+    pub fn validate(&self, report: &mut Report) {
+        if self.output_rate.is_some_and(|r| r <= 0.0) {
+            report.error("advanced.output-rate", "Output rate must be positive");
+        }
+
+        if self
+            .flush_window
+            .is_some_and(|w| !(w > 0.0 && w.is_finite()))
+        {
+            report.error("advanced.flush-window", "Flush window must be positive");
+        } else if self.flush_window.is_some() && self.output_rate.is_some() {
+            report.warning(
+                "advanced.flush-window",
+                "Flush window has no effect when an output rate is set",
+            );
+        }
+
+        if self.timer_resolution_ms == Some(0) {
+            report.error(
+                "advanced.timer-resolution-ms",
+                "Timer resolution must be at least 1 ms",
+            );
+        }
+    }
+
+    /// Applies the process priority and timer resolution. Failures (e.g. for lack of permission)
+    /// are only logged, since the application works without them.
+    pub fn apply(&self) -> Tuning {
+        let mut tuning = Tuning {
+            timer_resolution_ms: None,
+        };
+
+        if self.priority != Priority::Normal {
+            match sys::set_priority(self.priority) {
+                Ok(()) => log::info!("Process priority set to {}.", self.priority),
+                Err(e) => log::warn!("Failed to set process priority to {}: {e:#}", self.priority),
+            }
+        }
+
+        if let Some(ms) = self.timer_resolution_ms {
+            match sys::begin_timer_resolution(ms) {
+                Ok(()) => {
+                    log::info!("Timer resolution set to {ms} ms.");
+                    tuning.timer_resolution_ms = Some(ms);
+                }
+                Err(e) => log::warn!("Failed to set timer resolution to {ms} ms: {e:#}"),
+            }
+        }
+
+        tuning
+    }
+}
+
+impl Drop for Tuning {
+    fn drop(&mut self) {
+        if let Some(ms) = self.timer_resolution_ms.take() {
+            sys::end_timer_resolution(ms);
+        }
+    }
+}
+
+#[cfg(windows)]
+mod sys {
+    use anyhow::{ensure, Result as AnyResult};
+    use windows_sys::Win32::Media::{timeBeginPeriod, timeEndPeriod, TIMERR_NOERROR};
+    use windows_sys::Win32::System::Threading::{
+        GetCurrentProcess, SetPriorityClass, ABOVE_NORMAL_PRIORITY_CLASS, HIGH_PRIORITY_CLASS,
+        NORMAL_PRIORITY_CLASS,
+    };
+
+    use super::Priority;
+
+    pub fn set_priority(priority: Priority) -> AnyResult<()> {
+        let class = match priority {
+            Priority::Normal => NORMAL_PRIORITY_CLASS,
+            Priority::AboveNormal => ABOVE_NORMAL_PRIORITY_CLASS,
+            Priority::High => HIGH_PRIORITY_CLASS,
+        };
+
+        let ok = unsafe { SetPriorityClass(GetCurrentProcess(), class) };
+        ensure!(ok != 0, std::io::Error::last_os_error());
+        Ok(())
+    }
+
+    pub fn begin_timer_resolution(ms: u32) -> AnyResult<()> {
+        let result = unsafe { timeBeginPeriod(ms) };
+        ensure!(
+            result == TIMERR_NOERROR,
+            "the resolution is out of the supported range"
+        );
+        Ok(())
+    }
+
+    pub fn end_timer_resolution(ms: u32) {
+        unsafe {
+            timeEndPeriod(ms);
+        }
+    }
+}
+
+#[cfg(not(windows))]
+mod sys {
+    use anyhow::{bail, Result as AnyResult};
+
+    use super::Priority;
+
+    pub fn set_priority(_: Priority) -> AnyResult<()> {
+        bail!("this is only supported on Windows")
+    }
+
+    pub fn begin_timer_resolution(_: u32) -> AnyResult<()> {
+        bail!("this is only supported on Windows")
+    }
+
+    pub fn end_timer_resolution(_: u32) {}
+}
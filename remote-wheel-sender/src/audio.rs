@@ -0,0 +1,163 @@
+mod config;
+
+use std::fs::File;
+use std::io::BufReader;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, OnceLock};
+
+use anyhow::{anyhow, Context as _, Result as AnyResult};
+use async_broadcast::{Receiver as BroadcastRx, RecvError as BroadcastRxErr};
+use hashbrown::{HashMap, HashSet};
+use rodio::buffer::SamplesBuffer;
+use rodio::{Decoder, OutputStream, Source};
+use smol::channel::{Receiver as ChannelRx, Sender as ChannelTx};
+
+pub use config::{ButtonOutputConfig, Config};
+
+use crate::config::MappingConfig;
+use crate::output::{OutputEvent, Timestamped};
+use crate::route::Routes;
+use crate::stats::OutputStats;
+
+use self::config::SoundConfig;
+
+pub async fn run(
+    _config: Config,
+    mappings: Arc<MappingConfig>,
+    mut recv: BroadcastRx<Timestamped<OutputEvent>>,
+    stats: Arc<OutputStats>,
+) -> AnyResult<()> {
+    log::info!("Audio task starting...");
+
+    let (play_tx, play_rx) = smol::channel::bounded(16);
+    let (ready_tx, ready_rx) = smol::channel::bounded(1);
+
+    let thread_stats = stats.clone();
+    let thread = std::thread::Builder::new()
+        .name(String::from("audio"))
+        .spawn(move || run_sync(play_rx, ready_tx, thread_stats))
+        .context("Failed to start audio playback thread")?;
+
+    ready_rx
+        .recv()
+        .await
+        .context("Audio playback thread stopped unexpectedly")??;
+
+    let outputs = Routes::buttons(&mappings, |_, b| Some(&b.output.audio));
+    log::info!("Audio task started.");
+
+    loop {
+        let msg = recv.recv().await;
+        match msg.map(|e| stats.received(e, recv.len())) {
+            Ok(OutputEvent::UpdateButton(id, pressed)) => {
+                let Some(output) = outputs.get(&id) else {
+                    continue;
+                };
+                let event = if pressed {
+                    &output.on_press
+                } else {
+                    &output.on_release
+                };
+
+                if let Some(ref sound) = event.sound {
+                    if play_tx.send(sound.clone()).await.is_err() {
+                        break;
+                    }
+                }
+            }
+
+            Ok(_) => {}
+
+            Err(BroadcastRxErr::Overflowed(n)) => {
+                log::warn!("Audio task missed {n} update(s)!");
+                stats.missed(n);
+            }
+
+            Err(BroadcastRxErr::Closed) => {
+                log::info!("Audio task stopping (no inputs remaining)...");
+                break;
+            }
+        }
+    }
+
+    drop(play_tx);
+    smol::unblock(move || thread.join())
+        .await
+        .map_err(|_| anyhow!("Audio playback thread panicked"))?;
+
+    log::info!("Audio task stopped.");
+    Ok(())
+}
+
+fn run_sync(
+    play_rx: ChannelRx<SoundConfig>,
+    ready_tx: ChannelTx<AnyResult<()>>,
+    stats: Arc<OutputStats>,
+) {
+    // The output stream must stay on the thread that created it.
+    let (_stream, handle) = match OutputStream::try_default() {
+        Ok(s) => s,
+        Err(e) => {
+            let _ = ready_tx.send_blocking(Err(anyhow!("Failed to open audio output: {e}")));
+            return;
+        }
+    };
+
+    let _ = ready_tx.send_blocking(Ok(()));
+    let mut failed = HashSet::new();
+
+    while let Ok(sound) = play_rx.recv_blocking() {
+        let source = SamplesBuffer::new(
+            sound.sound.channels,
+            sound.sound.sample_rate,
+            sound.sound.samples.clone(),
+        )
+        .amplify(sound.volume)
+        .convert_samples();
+
+        // Each sound is added to the mixer separately, so quick retriggers overlap.
+        match handle.play_raw(source) {
+            Ok(()) => stats.sent(),
+            Err(e) => {
+                stats.send_failed();
+                if failed.insert(sound.sound.path.clone()) {
+                    log::warn!("Failed to play sound <{}>: {e}", sound.sound.path.display());
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Sound {
+    path: PathBuf,
+    channels: u16,
+    sample_rate: u32,
+    samples: Vec<i16>,
+}
+
+impl Sound {
+    fn load(path: PathBuf) -> AnyResult<Arc<Sound>> {
+        static CACHE: OnceLock<Mutex<HashMap<PathBuf, Arc<Sound>>>> = OnceLock::new();
+
+        let mut cache = CACHE.get_or_init(Mutex::default).lock().unwrap();
+        if let Some(sound) = cache.get(&path) {
+            return Ok(sound.clone());
+        }
+
+        let file = File::open(&path)
+            .map_err(|e| anyhow!("Failed to open sound file <{}>: {e}", path.display()))?;
+        let decoder = Decoder::new(BufReader::new(file))
+            .map_err(|e| anyhow!("Failed to decode sound file <{}>: {e}", path.display()))?;
+
+        let sound = Arc::new(Sound {
+            channels: decoder.channels(),
+            sample_rate: decoder.sample_rate(),
+            samples: decoder.collect(),
+            path: path.clone(),
+        });
+
+        cache.insert(path, sound.clone());
+        Ok(sound)
+    }
+}
@@ -0,0 +1,84 @@
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use anyhow::Error as AnyError;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use super::Sound;
+
+#[derive(Clone, Debug, Default, Deserialize, JsonSchema, Serialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Config {
+    enabled: bool,
+}
+
+#[derive(Debug, Default, Deserialize, JsonSchema, Serialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct ButtonOutputConfig {
+    pub(super) on_press: EventConfig,
+    pub(super) on_release: EventConfig,
+}
+
+#[derive(Debug, Default, Deserialize, JsonSchema, Serialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct EventConfig {
+    pub(super) sound: Option<SoundConfig>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(try_from = "RawSoundConfig", into = "RawSoundConfig")]
+pub struct SoundConfig {
+    pub(super) sound: Arc<Sound>,
+    pub(super) volume: f32,
+    file: PathBuf,
+}
+
+#[derive(Debug, Deserialize, JsonSchema, Serialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+struct RawSoundConfig {
+    file: PathBuf,
+    #[serde(default = "volume_default")]
+    volume: f32,
+}
+
+impl Config {
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+}
+
+impl JsonSchema for SoundConfig {
+    fn schema_name() -> String {
+        "SoundConfig".into()
+    }
+
+    fn json_schema(gen: &mut schemars::gen::SchemaGenerator) -> schemars::schema::Schema {
+        RawSoundConfig::json_schema(gen)
+    }
+}
+
+impl TryFrom<RawSoundConfig> for SoundConfig {
+    type Error = AnyError;
+
+    fn try_from(raw: RawSoundConfig) -> Result<Self, AnyError> {
+        Ok(SoundConfig {
+            sound: Sound::load(crate::config::resolve_path(&raw.file))?,
+            volume: raw.volume,
+            file: raw.file,
+        })
+    }
+}
+
+impl From<SoundConfig> for RawSoundConfig {
+    fn from(config: SoundConfig) -> Self {
+        RawSoundConfig {
+            file: config.file,
+            volume: config.volume,
+        }
+    }
+}
+
+fn volume_default() -> f32 {
+    1.0
+}
@@ -0,0 +1,99 @@
+mod config;
+mod sys;
+
+use std::time::{Duration, Instant};
+
+use anyhow::Result as AnyResult;
+use async_broadcast::{Receiver as BroadcastRx, RecvError as BroadcastRxErr};
+use smol::Timer;
+
+pub use config::Config;
+
+use crate::output::{OutputEvent, Timestamped};
+
+use self::sys::Request;
+
+/// Keeps the system awake while inputs are producing events, and lets it sleep again once they've
+/// been idle for the configured timeout.
+pub async fn run(config: Config, mut recv: BroadcastRx<Timestamped<OutputEvent>>) -> AnyResult<()> {
+    log::info!("Keep-awake task starting...");
+
+    if !sys::SUPPORTED {
+        log::warn!("Keep-awake task stopped (this is only supported on Windows).");
+        return Ok(());
+    }
+
+    let idle_timeout = Duration::from_secs_f64(config.idle_timeout);
+    let mut request: Option<Request> = None;
+    let mut last_active = Instant::now();
+    let mut idle_timer = Timer::at(last_active);
+
+    log::info!("Keep-awake task started.");
+
+    loop {
+        // The timer is only reset once it fires, rather than on every event.
+        let msg = if request.is_some() {
+            let idle = async {
+                (&mut idle_timer).await;
+                None
+            };
+            smol::future::or(async { Some(recv.recv().await) }, idle).await
+        } else {
+            Some(recv.recv().await)
+        };
+
+        match msg.map(|m| m.map(|e| e.event)) {
+            Some(Ok(OutputEvent::UpdateAxis(..) | OutputEvent::UpdateButton(..))) => {
+                last_active = Instant::now();
+                if request.is_none() {
+                    match Request::new(config.display) {
+                        Ok(r) => {
+                            log::info!("Keeping the system awake (inputs are active).");
+                            request = Some(r);
+                            idle_timer.set_at(last_active + idle_timeout);
+                        }
+                        Err(e) => {
+                            log::warn!(
+                                "Keep-awake task stopped (failed to keep the system awake: {e:#})."
+                            );
+                            return Ok(());
+                        }
+                    }
+                }
+            }
+
+            Some(Ok(
+                OutputEvent::RestoreButton(..)
+                | OutputEvent::ButtonGesture(..)
+                | OutputEvent::SwitchProfile(_)
+                | OutputEvent::Flush,
+            )) => {}
+
+            None => {
+                let idle_until = last_active + idle_timeout;
+                if Instant::now() < idle_until {
+                    idle_timer.set_at(idle_until);
+                } else {
+                    log::info!(
+                        "Allowing the system to sleep (inputs have been idle for {idle_timeout:?})."
+                    );
+                    request = None;
+                }
+            }
+
+            Some(Err(BroadcastRxErr::Overflowed(_))) => {}
+
+            Some(Err(BroadcastRxErr::Closed)) => {
+                log::info!("Keep-awake task stopping (no inputs remaining)...");
+                break;
+            }
+        }
+    }
+
+    if request.take().is_some() {
+        log::info!("Allowing the system to sleep (stopping).");
+    }
+
+    log::info!("Keep-awake task stopped.");
+    Ok(())
+}
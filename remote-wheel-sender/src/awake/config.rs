@@ -0,0 +1,38 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::check::Report;
+
+#[derive(Clone, Debug, Deserialize, JsonSchema, Serialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Config {
+    enabled: bool,
+
+    pub(super) display: bool,
+
+    #[serde(deserialize_with = "crate::units::seconds")]
+    #[schemars(schema_with = "crate::units::seconds_schema")]
+    pub(super) idle_timeout: f64,
+}
+
+impl Config {
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn validate(&self, report: &mut Report) {
+        if self.idle_timeout <= 0.0 {
+            report.error("keep-awake.idle-timeout", "Idle timeout must be positive");
+        }
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            enabled: false,
+            display: true,
+            idle_timeout: 300.0,
+        }
+    }
+}
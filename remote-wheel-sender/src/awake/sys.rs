@@ -0,0 +1,46 @@
+use anyhow::Result as AnyResult;
+
+#[cfg(windows)]
+pub const SUPPORTED: bool = true;
+
+#[cfg(not(windows))]
+pub const SUPPORTED: bool = false;
+
+/// A request for the system (and optionally the display) to stay awake, which lasts until it's
+/// dropped.
+///
+/// Windows tracks this per thread, so it must be dropped on the thread that created it.
+pub struct Request(());
+
+impl Request {
+    #[cfg(windows)]
+    pub fn new(display: bool) -> AnyResult<Request> {
+        use windows_sys::Win32::System::Power::{
+            SetThreadExecutionState, ES_CONTINUOUS, ES_DISPLAY_REQUIRED, ES_SYSTEM_REQUIRED,
+        };
+
+        let mut flags = ES_CONTINUOUS | ES_SYSTEM_REQUIRED;
+        if display {
+            flags |= ES_DISPLAY_REQUIRED;
+        }
+
+        let previous = unsafe { SetThreadExecutionState(flags) };
+        anyhow::ensure!(previous != 0, "SetThreadExecutionState failed");
+        Ok(Request(()))
+    }
+
+    #[cfg(not(windows))]
+    pub fn new(_: bool) -> AnyResult<Request> {
+        anyhow::bail!("Keeping the system awake is only supported on Windows")
+    }
+}
+
+impl Drop for Request {
+    fn drop(&mut self) {
+        #[cfg(windows)]
+        unsafe {
+            use windows_sys::Win32::System::Power::{SetThreadExecutionState, ES_CONTINUOUS};
+            SetThreadExecutionState(ES_CONTINUOUS);
+        }
+    }
+}
@@ -0,0 +1,128 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_broadcast::Sender as BroadcastTx;
+use hashbrown::HashMap;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::check::Report;
+use crate::config::MappingConfig;
+use crate::output::{OutputEvent, Timestamped};
+use crate::route::{MappingId, Routes};
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, JsonSchema, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ButtonMode {
+    /// The button is pressed for as long as its input is held.
+    #[default]
+    Hold,
+
+    /// Each press of the input flips the button between pressed and released.
+    Toggle,
+
+    /// Each press of the input presses the button, and releases it again after a delay.
+    Pulse,
+}
+
+/// Turns the physical state of button inputs into the logical state of buttons, according to their
+/// modes. Each input task keeps its own.
+pub struct ButtonStates {
+    /// The mode and pulse duration of each button that isn't simply held.
+    modes: Routes<(ButtonMode, f64)>,
+    output_tx: BroadcastTx<Timestamped<OutputEvent>>,
+
+    held: HashMap<MappingId, bool>,
+    toggled: HashMap<MappingId, bool>,
+}
+
+impl ButtonStates {
+    pub fn new(
+        mappings: Arc<MappingConfig>,
+        output_tx: BroadcastTx<Timestamped<OutputEvent>>,
+    ) -> ButtonStates {
+        let modes = Routes::buttons(&mappings, |_, b| {
+            (b.mode != ButtonMode::Hold).then_some((b.mode, b.pulse_duration))
+        });
+
+        ButtonStates {
+            modes,
+            output_tx,
+
+            held: HashMap::new(),
+            toggled: HashMap::new(),
+        }
+    }
+
+    /// Updates the physical state of a button, returning its new logical state if an update should
+    /// be sent for it.
+    pub fn update(&mut self, id: &MappingId, pressed: bool) -> Option<bool> {
+        let Some(&(mode, pulse_duration)) = self.modes.get(id) else {
+            return Some(pressed);
+        };
+
+        // Inputs may repeat their state (e.g. OSC), so only act on actual presses.
+        let was_held = self.held.insert(id.clone(), pressed).unwrap_or(false);
+        if !pressed || was_held {
+            return None;
+        }
+
+        if mode == ButtonMode::Toggle {
+            let toggled = self.toggled.entry(id.clone()).or_insert(false);
+            *toggled = !*toggled;
+            return Some(*toggled);
+        }
+
+        let id = id.clone();
+        let output_tx = self.output_tx.clone();
+        let delay = Duration::from_secs_f64(pulse_duration.max(0.0));
+
+        smol::spawn(async move {
+            smol::Timer::after(delay).await;
+
+            let release = OutputEvent::UpdateButton(id, false);
+            if output_tx.broadcast(release.into()).await.is_ok() {
+                let _ = output_tx.broadcast(OutputEvent::Flush.into()).await;
+            }
+        })
+        .detach();
+
+        Some(true)
+    }
+}
+
+/// Turns an axis value into the physical state of a button. The button is pressed once the value
+/// reaches the threshold, and released once it falls below it by more than the hysteresis, so that
+/// a value hovering around the threshold doesn't chatter.
+#[derive(Clone, Copy, Debug)]
+pub struct Threshold {
+    pub threshold: f64,
+    pub hysteresis: f64,
+}
+
+impl Threshold {
+    pub fn pressed(&self, value: f64, was_pressed: bool) -> bool {
+        if was_pressed {
+            value >= self.threshold - self.hysteresis
+        } else {
+            value >= self.threshold
+        }
+    }
+
+    pub fn validate(&self, path: &str, report: &mut Report) {
+        if self.hysteresis < 0.0 {
+            report.error(
+                format!("{path}.hysteresis"),
+                "Hysteresis must not be negative",
+            );
+        }
+    }
+}
+
+pub fn threshold_default() -> f64 {
+    0.6
+}
+
+pub fn hysteresis_default() -> f64 {
+    0.05
+}
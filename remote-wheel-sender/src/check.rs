@@ -0,0 +1,103 @@
+use std::fmt::Display;
+use std::net::SocketAddr;
+
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+#[derive(Debug)]
+pub struct Problem {
+    pub severity: Severity,
+    pub path: String,
+    pub message: String,
+}
+
+/// Collects the problems found while validating a configuration, without acting on any of it.
+#[derive(Debug, Default)]
+pub struct Report {
+    problems: Vec<Problem>,
+    bound: Vec<(String, SocketAddr)>,
+    targets: Vec<(String, SocketAddr)>,
+}
+
+impl Report {
+    pub fn error(&mut self, path: impl Into<String>, message: impl Display) {
+        self.push(Severity::Error, path.into(), message);
+    }
+
+    pub fn warning(&mut self, path: impl Into<String>, message: impl Display) {
+        self.push(Severity::Warning, path.into(), message);
+    }
+
+    /// Records an address that will be bound for input.
+    pub fn bind(&mut self, path: impl Into<String>, addr: SocketAddr) {
+        self.bound.push((path.into(), addr));
+    }
+
+    /// Records an address that output will be sent to.
+    pub fn target(&mut self, path: impl Into<String>, addr: SocketAddr) {
+        self.targets.push((path.into(), addr));
+    }
+
+    pub fn finish(mut self) -> Vec<Problem> {
+        for (i, (path, addr)) in self.bound.iter().enumerate() {
+            // Port 0 asks the OS for any free port, so it can never collide.
+            if addr.port() == 0 {
+                continue;
+            }
+
+            for (other_path, other_addr) in &self.bound[..i] {
+                if overlaps(*addr, *other_addr) {
+                    self.problems.push(Problem {
+                        severity: Severity::Error,
+                        path: path.clone(),
+                        message: format!("Address {addr} is also bound by {other_path}"),
+                    });
+                }
+            }
+
+            for (target_path, target) in &self.targets {
+                if overlaps(*addr, *target) {
+                    self.problems.push(Problem {
+                        severity: Severity::Warning,
+                        path: target_path.clone(),
+                        message: format!("Output is sent to {target}, which is bound by {path}"),
+                    });
+                }
+            }
+        }
+
+        self.problems
+            .sort_by(|a, b| (a.severity, &a.path).cmp(&(b.severity, &b.path)));
+        self.problems
+    }
+
+    fn push(&mut self, severity: Severity, path: String, message: impl Display) {
+        self.problems.push(Problem {
+            severity,
+            path,
+            message: message.to_string(),
+        });
+    }
+}
+
+impl Display for Problem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.path, self.message)
+    }
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+fn overlaps(a: SocketAddr, b: SocketAddr) -> bool {
+    a.port() == b.port() && (a.ip() == b.ip() || a.ip().is_unspecified() || b.ip().is_unspecified())
+}
@@ -0,0 +1,312 @@
+use std::time::{Duration, Instant};
+
+use anyhow::Result as AnyResult;
+use async_broadcast::{
+    Receiver as BroadcastRx, RecvError as BroadcastRxErr, Sender as BroadcastTx,
+};
+use futures::prelude::*;
+use hashbrown::HashMap;
+
+use crate::output::{OutputEvent, Timestamped};
+use crate::route::MappingId;
+use crate::stats;
+
+/// Sends output events at a fixed rate, however often inputs produce them. Axis updates are
+/// coalesced, so that only the latest value of each axis is sent on each tick, followed by a single
+/// flush. Button updates are sent on straight away, so that no press or release is lost or delayed.
+pub async fn run(
+    rate: f64,
+    mut input_rx: BroadcastRx<Timestamped<OutputEvent>>,
+    output_tx: BroadcastTx<Timestamped<OutputEvent>>,
+) -> AnyResult<()> {
+    log::info!("Output clock task starting...");
+
+    let period = Duration::from_secs_f64(rate.recip());
+    let mut timer = smol::Timer::interval(period);
+
+    // The latest update for each axis since the last tick.
+    let mut pending = HashMap::<MappingId, Timestamped<OutputEvent>>::new();
+    let mut buttons_sent = false;
+
+    log::info!("Output clock task started. Sending at {rate} Hz.");
+
+    loop {
+        futures::select_biased! {
+            scheduled = timer.next().fuse() => {
+                let Some(scheduled) = scheduled else { break };
+
+                // After a stall, the ticks that were missed are skipped rather than all sent at
+                // once, keeping to the same schedule.
+                let missed = missed_ticks(scheduled, Instant::now(), period);
+                if missed > 0 {
+                    log::debug!("Output clock missed {missed} tick(s).");
+                    timer.set_interval_at(scheduled + period * (missed + 1), period);
+                }
+
+                if !send_pending(&mut pending, &output_tx).await {
+                    log::info!("Output clock task stopping (no remaining outputs).");
+                    return Ok(());
+                }
+            },
+
+            event = input_rx.recv().fuse() => {
+                let event = match event {
+                    Ok(event) => event,
+
+                    Err(BroadcastRxErr::Overflowed(n)) => {
+                        log::warn!("Output clock missed {n} update(s)!");
+                        stats::counter("Output clock", "missed events").add(n);
+                        continue;
+                    }
+
+                    Err(BroadcastRxErr::Closed) => {
+                        log::info!("Output clock task stopping (no remaining inputs).");
+
+                        // The outputs are left with the final value of each axis, rather than
+                        // whatever was sent on the last tick.
+                        send_pending(&mut pending, &output_tx).await;
+                        break;
+                    }
+                };
+
+                let event = match event.event {
+                    OutputEvent::UpdateAxis(ref id, _) => {
+                        pending.insert(id.clone(), event);
+                        continue;
+                    }
+
+                    OutputEvent::UpdateButton(..)
+                    | OutputEvent::RestoreButton(..)
+                    | OutputEvent::ButtonGesture(..)
+                    | OutputEvent::SwitchProfile(_) => {
+                        buttons_sent = true;
+                        event
+                    }
+
+                    // Inputs flush after their updates, which only needs passing on for buttons (and
+                    // profile switches).
+                    // Axis updates are flushed on the next tick instead.
+                    OutputEvent::Flush if std::mem::take(&mut buttons_sent) => event,
+                    OutputEvent::Flush => continue,
+                };
+
+                if output_tx.broadcast(event).await.is_err() {
+                    log::info!("Output clock task stopping (no remaining outputs).");
+                    return Ok(());
+                }
+            },
+        }
+    }
+
+    log::info!("Output clock task stopped.");
+    Ok(())
+}
+
+/// Sends the latest update for each axis since the last tick, followed by a flush. Returns whether
+/// there are still outputs to send to.
+async fn send_pending(
+    pending: &mut HashMap<MappingId, Timestamped<OutputEvent>>,
+    output_tx: &BroadcastTx<Timestamped<OutputEvent>>,
+) -> bool {
+    if pending.is_empty() {
+        return true;
+    }
+
+    let mut events: Vec<_> = pending.drain().map(|(_, event)| event).collect();
+    events.push(OutputEvent::Flush.into());
+
+    for event in events {
+        if output_tx.broadcast(event).await.is_err() {
+            return false;
+        }
+    }
+
+    true
+}
+
+/// How many whole ticks have passed since a tick that was `scheduled` for an earlier time.
+fn missed_ticks(scheduled: Instant, now: Instant, period: Duration) -> u32 {
+    let late = now.saturating_duration_since(scheduled);
+    (late.as_nanos() / period.as_nanos().max(1)).min(u32::MAX as u128) as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PERIOD: Duration = Duration::from_millis(10);
+
+    fn axis(name: &str, index: u32, value: f64) -> Timestamped<OutputEvent> {
+        OutputEvent::UpdateAxis(MappingId::new(name, index), value).into()
+    }
+
+    /// Receives events until the next flush, returning the events before it.
+    async fn until_flush(rx: &mut BroadcastRx<Timestamped<OutputEvent>>) -> Vec<OutputEvent> {
+        let mut events = Vec::new();
+        loop {
+            match rx.recv().await.unwrap().event {
+                OutputEvent::Flush => return events,
+                event => events.push(event),
+            }
+        }
+    }
+
+    /// Runs the clock at 100 Hz alongside `test`, on the same thread, so that `test` can stall the
+    /// clock by blocking.
+    fn with_clock<F: Future<Output = ()>>(
+        test: impl FnOnce(
+            BroadcastTx<Timestamped<OutputEvent>>,
+            BroadcastRx<Timestamped<OutputEvent>>,
+        ) -> F,
+    ) {
+        let (input_tx, input_rx) = async_broadcast::broadcast(64);
+        let (output_tx, output_rx) = async_broadcast::broadcast(64);
+
+        let clock = run(PERIOD.as_secs_f64().recip(), input_rx, output_tx);
+        let (result, ()) = smol::block_on(future::join(clock, test(input_tx, output_rx)));
+        result.unwrap();
+    }
+
+    #[test]
+    fn counts_whole_missed_ticks() {
+        let start = Instant::now();
+
+        assert_eq!(missed_ticks(start, start, PERIOD), 0);
+        assert_eq!(missed_ticks(start + PERIOD, start, PERIOD), 0);
+        assert_eq!(missed_ticks(start, start + PERIOD / 2, PERIOD), 0);
+        assert_eq!(missed_ticks(start, start + PERIOD, PERIOD), 1);
+        assert_eq!(missed_ticks(start, start + PERIOD * 29 / 10, PERIOD), 2);
+        assert_eq!(missed_ticks(start, start + PERIOD * 1000, PERIOD), 1000);
+    }
+
+    #[test]
+    fn counts_missed_ticks_without_a_period() {
+        let start = Instant::now();
+        let late = start + Duration::from_secs(1);
+
+        assert_eq!(missed_ticks(start, late, Duration::ZERO), 1_000_000_000);
+        assert_eq!(
+            missed_ticks(start, start + Duration::from_secs(10), Duration::ZERO),
+            u32::MAX
+        );
+    }
+
+    #[test]
+    fn sends_latest_value_per_axis() {
+        with_clock(|input_tx, mut output_rx| async move {
+            for event in [axis("a", 0, 1.0), axis("a", 0, 2.0), axis("b", 1, 3.0)] {
+                input_tx.broadcast(event).await.unwrap();
+            }
+            input_tx.broadcast(OutputEvent::Flush.into()).await.unwrap();
+
+            let mut events = until_flush(&mut output_rx).await;
+            events.sort_by_key(|e| match e {
+                OutputEvent::UpdateAxis(id, _) => id.index(),
+                _ => usize::MAX,
+            });
+            assert_eq!(
+                events,
+                [
+                    OutputEvent::UpdateAxis(MappingId::new("a", 0), 2.0),
+                    OutputEvent::UpdateAxis(MappingId::new("b", 1), 3.0),
+                ]
+            );
+        });
+    }
+
+    #[test]
+    fn passes_buttons_on_straight_away() {
+        let (input_tx, input_rx) = async_broadcast::broadcast(64);
+        let (output_tx, mut output_rx) = async_broadcast::broadcast(64);
+
+        // At 1 Hz, anything that arrives before the first tick wasn't waiting for it.
+        let clock = smol::spawn(run(1.0, input_rx, output_tx));
+        smol::block_on(async {
+            let start = Instant::now();
+            let button = OutputEvent::UpdateButton(MappingId::new("a", 0), true);
+            input_tx.broadcast(axis("b", 0, 1.0)).await.unwrap();
+            input_tx.broadcast(button.clone().into()).await.unwrap();
+            input_tx.broadcast(OutputEvent::Flush.into()).await.unwrap();
+
+            assert_eq!(until_flush(&mut output_rx).await, [button]);
+            assert!(start.elapsed() < Duration::from_millis(500));
+
+            drop(input_tx);
+            clock.await.unwrap();
+        });
+    }
+
+    #[test]
+    fn sends_pending_values_when_inputs_stop() {
+        let (input_tx, input_rx) = async_broadcast::broadcast(64);
+        let (output_tx, mut output_rx) = async_broadcast::broadcast(64);
+
+        // At 1 Hz, the update can only be sent before the first tick because the inputs stopped.
+        let clock = smol::spawn(run(1.0, input_rx, output_tx));
+        smol::block_on(async {
+            let start = Instant::now();
+            input_tx.broadcast(axis("a", 0, 1.0)).await.unwrap();
+            input_tx.broadcast(axis("a", 0, 2.0)).await.unwrap();
+            drop(input_tx);
+            clock.await.unwrap();
+
+            assert_eq!(
+                until_flush(&mut output_rx).await,
+                [OutputEvent::UpdateAxis(MappingId::new("a", 0), 2.0)]
+            );
+            assert!(start.elapsed() < Duration::from_millis(500));
+            assert!(output_rx.recv().await.is_err());
+        });
+    }
+
+    #[test]
+    fn keeps_to_its_schedule_after_a_stall() {
+        with_clock(|input_tx, mut output_rx| async move {
+            input_tx.broadcast(axis("a", 0, 1.0)).await.unwrap();
+            until_flush(&mut output_rx).await;
+
+            // Stall the clock for several ticks, leaving it with an update to send.
+            input_tx.broadcast(axis("a", 0, 2.0)).await.unwrap();
+            std::thread::sleep(PERIOD * 52 / 10);
+            until_flush(&mut output_rx).await;
+
+            // The ticks that were missed aren't made up with a burst of flushes, so this waits for
+            // the next tick on the original schedule.
+            let start = Instant::now();
+            input_tx.broadcast(axis("a", 0, 3.0)).await.unwrap();
+            until_flush(&mut output_rx).await;
+            assert!(start.elapsed() >= PERIOD / 4, "{:?}", start.elapsed());
+        });
+    }
+
+    #[test]
+    fn keeps_to_its_rate() {
+        const TICKS: u32 = 20;
+
+        with_clock(|input_tx, mut output_rx| async move {
+            input_tx.broadcast(axis("a", 0, 0.0)).await.unwrap();
+            until_flush(&mut output_rx).await;
+
+            let start = Instant::now();
+            let mut longest = Duration::ZERO;
+            let mut last = start;
+            for i in 1..=TICKS {
+                input_tx.broadcast(axis("a", 0, i.into())).await.unwrap();
+                until_flush(&mut output_rx).await;
+
+                let now = Instant::now();
+                longest = longest.max(now - last);
+                last = now;
+            }
+
+            // Ticks are kept to the schedule, so however late any one is, they average out to the
+            // rate.
+            let average = start.elapsed() / TICKS;
+            assert!(
+                average > PERIOD * 8 / 10 && average < PERIOD * 12 / 10,
+                "{average:?}"
+            );
+            assert!(longest < PERIOD * 3, "{longest:?}");
+        });
+    }
+}
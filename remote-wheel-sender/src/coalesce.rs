@@ -0,0 +1,207 @@
+use std::time::Duration;
+
+use anyhow::Result as AnyResult;
+use async_broadcast::{
+    Receiver as BroadcastRx, RecvError as BroadcastRxErr, Sender as BroadcastTx,
+};
+use futures::prelude::*;
+
+use crate::output::{OutputEvent, Timestamped};
+use crate::stats;
+
+/// Passes events on to the outputs, combining flushes that arrive within `window` seconds of each
+/// other (e.g. from several inputs changing at once) into one. Updates are passed on straight away,
+/// and are always flushed no later than `window` after the first flush that follows them. Flushes
+/// with no updates since the last one are dropped.
+pub async fn run(
+    window: f64,
+    mut input_rx: BroadcastRx<Timestamped<OutputEvent>>,
+    output_tx: BroadcastTx<Timestamped<OutputEvent>>,
+) -> AnyResult<()> {
+    log::info!("Flush coalescing task starting...");
+
+    let window = Duration::from_secs_f64(window);
+    let mut timer = smol::Timer::never();
+
+    // The first flush being held back, and whether anything has been sent since the last flush.
+    let mut held = None;
+    let mut updated = false;
+
+    log::info!("Flush coalescing task started.");
+
+    loop {
+        let event = futures::select_biased! {
+            _ = future::FutureExt::fuse(&mut timer) => {
+                // A timer that has fired stays ready, so it's replaced until the next flush.
+                timer = smol::Timer::never();
+                updated = false;
+                held.take()
+            },
+
+            event = input_rx.recv().fuse() => match event {
+                Ok(event) => match event.event {
+                    OutputEvent::Flush if updated && held.is_none() => {
+                        held = Some(event);
+                        timer.set_after(window);
+                        None
+                    }
+
+                    OutputEvent::Flush => None,
+
+                    _ => {
+                        updated = true;
+                        Some(event)
+                    }
+                },
+
+                Err(BroadcastRxErr::Overflowed(n)) => {
+                    log::warn!("Flush coalescing task missed {n} update(s)!");
+                    stats::counter("Flush coalescing", "missed events").add(n);
+                    None
+                }
+
+                Err(BroadcastRxErr::Closed) => {
+                    log::info!("Flush coalescing task stopping (no remaining inputs).");
+                    break;
+                }
+            },
+        };
+
+        if let Some(event) = event {
+            if output_tx.broadcast(event).await.is_err() {
+                log::info!("Flush coalescing task stopping (no remaining outputs).");
+                return Ok(());
+            }
+        }
+    }
+
+    // Anything that was waiting for its flush is still sent.
+    if let Some(event) = held {
+        let _ = output_tx.broadcast(event).await;
+    }
+
+    log::info!("Flush coalescing task stopped.");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use super::*;
+    use crate::route::MappingId;
+
+    const WINDOW: Duration = Duration::from_millis(50);
+
+    fn axis(value: f64) -> Timestamped<OutputEvent> {
+        OutputEvent::UpdateAxis(MappingId::new("a", 0), value).into()
+    }
+
+    /// Sends `events` through the task, then stops it, returning everything that it sent.
+    async fn coalesce(
+        events: impl IntoIterator<Item = Timestamped<OutputEvent>>,
+    ) -> Vec<OutputEvent> {
+        let (input_tx, input_rx) = async_broadcast::broadcast(64);
+        let (output_tx, mut output_rx) = async_broadcast::broadcast(64);
+        let task = smol::spawn(run(WINDOW.as_secs_f64(), input_rx, output_tx));
+
+        for event in events {
+            input_tx.broadcast(event).await.unwrap();
+        }
+        drop(input_tx);
+        task.await.unwrap();
+
+        let mut sent = Vec::new();
+        while let Ok(event) = output_rx.recv().await {
+            sent.push(event.event);
+        }
+        sent
+    }
+
+    #[test]
+    fn holds_flushes_for_the_window() {
+        let (input_tx, input_rx) = async_broadcast::broadcast(64);
+        let (output_tx, mut output_rx) = async_broadcast::broadcast(64);
+        let task = smol::spawn(run(WINDOW.as_secs_f64(), input_rx, output_tx));
+
+        smol::block_on(async {
+            let start = Instant::now();
+            input_tx.broadcast(axis(1.0)).await.unwrap();
+            input_tx.broadcast(OutputEvent::Flush.into()).await.unwrap();
+
+            // The update is passed on straight away, but its flush waits for the window to end.
+            assert_eq!(output_rx.recv().await.unwrap().event, axis(1.0).event);
+            assert!(start.elapsed() < WINDOW);
+
+            assert_eq!(output_rx.recv().await.unwrap().event, OutputEvent::Flush);
+            assert!(start.elapsed() >= WINDOW, "{:?}", start.elapsed());
+
+            drop(input_tx);
+            task.await.unwrap();
+        });
+    }
+
+    #[test]
+    fn flushes_updates_within_the_window_together() {
+        let sent = smol::block_on(coalesce([
+            axis(1.0),
+            OutputEvent::Flush.into(),
+            axis(2.0),
+            OutputEvent::Flush.into(),
+            axis(3.0),
+            OutputEvent::Flush.into(),
+        ]));
+
+        assert_eq!(
+            sent,
+            [
+                axis(1.0).event,
+                axis(2.0).event,
+                axis(3.0).event,
+                OutputEvent::Flush,
+            ]
+        );
+    }
+
+    #[test]
+    fn flushes_again_after_the_window() {
+        let (input_tx, input_rx) = async_broadcast::broadcast(64);
+        let (output_tx, mut output_rx) = async_broadcast::broadcast(64);
+        let task = smol::spawn(run(WINDOW.as_secs_f64(), input_rx, output_tx));
+
+        smol::block_on(async {
+            for value in [1.0, 2.0] {
+                input_tx.broadcast(axis(value)).await.unwrap();
+                input_tx.broadcast(OutputEvent::Flush.into()).await.unwrap();
+
+                assert_eq!(output_rx.recv().await.unwrap().event, axis(value).event);
+                assert_eq!(output_rx.recv().await.unwrap().event, OutputEvent::Flush);
+            }
+
+            // With nothing new since the last flush, this one isn't needed.
+            input_tx.broadcast(OutputEvent::Flush.into()).await.unwrap();
+
+            drop(input_tx);
+            task.await.unwrap();
+            assert!(output_rx.recv().await.is_err());
+        });
+    }
+
+    #[test]
+    fn drops_empty_flushes() {
+        let sent = smol::block_on(coalesce([
+            OutputEvent::Flush.into(),
+            OutputEvent::Flush.into(),
+        ]));
+        assert_eq!(sent, []);
+    }
+
+    #[test]
+    fn sends_held_flush_when_stopping() {
+        let start = Instant::now();
+        let sent = smol::block_on(coalesce([axis(1.0), OutputEvent::Flush.into()]));
+
+        assert_eq!(sent, [axis(1.0).event, OutputEvent::Flush]);
+        assert!(start.elapsed() < WINDOW);
+    }
+}
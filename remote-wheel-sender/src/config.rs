@@ -1,130 +1,658 @@
 use std::borrow::Cow;
+use std::cell::RefCell;
 use std::fmt::Debug;
 use std::io::ErrorKind;
-use std::path::Path;
-use std::sync::Arc;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, OnceLock};
 
-use anyhow::{Context as _, Result as AnyResult};
+use anyhow::{anyhow, bail, Context as _, Result as AnyResult};
 use hashbrown::HashMap;
-use serde::Deserialize;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
 use string_cache::DefaultAtom;
 
+mod format;
+mod include;
+mod profile;
+mod schema;
+mod template;
+mod unknown;
+
+pub use format::{find_existing, find_file};
+pub use schema::dump as schema;
+
+use self::format::Format;
+use crate::advanced::AdvancedConfig;
+use crate::button::ButtonMode;
+use crate::check::{Problem, Report};
 use crate::controller;
+use crate::logging::LogConfig;
+use crate::presets::PRESETS;
+use crate::profile::DEFAULT as DEFAULT_PROFILE;
+use crate::route::{MappingId, MappingIds};
+use crate::session::SessionConfig;
+use crate::state::StateConfig;
+use crate::stats::StatsConfig;
+use crate::status::StatusConfig;
+use crate::supervisor::{RestartConfig, ShutdownConfig};
+use crate::test_signal::TestSignalConfig;
+
+#[cfg(feature = "audio")]
+use crate::audio::{ButtonOutputConfig as AudioButtonOutputConfig, Config as AudioConfig};
+#[cfg(not(feature = "audio"))]
+use schema::{Disabled as AudioConfig, Disabled as AudioButtonOutputConfig};
+
+#[cfg(feature = "exec")]
+use crate::exec::{ButtonOutputConfig as ExecButtonOutputConfig, Config as ExecConfig};
+#[cfg(not(feature = "exec"))]
+use schema::{Disabled as ExecConfig, Disabled as ExecButtonOutputConfig};
+
+#[cfg(feature = "keep-awake")]
+use crate::awake::Config as KeepAwakeConfig;
+#[cfg(not(feature = "keep-awake"))]
+use schema::Disabled as KeepAwakeConfig;
+
+#[cfg(feature = "keyboard")]
+use crate::keyboard::ButtonInputConfig as KeyboardButtonInputConfig;
+#[cfg(not(feature = "keyboard"))]
+use schema::Disabled as KeyboardButtonInputConfig;
+
+#[cfg(feature = "keystroke")]
+use crate::keystroke::ButtonOutputConfig as KeystrokeButtonOutputConfig;
+#[cfg(not(feature = "keystroke"))]
+use schema::Disabled as KeystrokeButtonOutputConfig;
+
+#[cfg(feature = "midi")]
+use crate::midi::{
+    AxisInputConfig as MidiAxisInputConfig, ButtonInputConfig as MidiButtonInputConfig,
+};
+#[cfg(not(feature = "midi"))]
+use schema::{Disabled as MidiAxisInputConfig, Disabled as MidiButtonInputConfig};
+
+#[cfg(feature = "openvr")]
+use crate::openvr::{AxisOutputConfig as OpenVrAxisOutputConfig, Config as OpenVrConfig};
+#[cfg(not(feature = "openvr"))]
+use schema::{Disabled as OpenVrConfig, Disabled as OpenVrAxisOutputConfig};
 
 #[cfg(feature = "osc")]
 use crate::osc::{
-    AxisInputConfig as OscAxisInputConfig, AxisOutputConfig as OscAxisOutputConfig,
-    ButtonInputConfig as OscButtonInputConfig, ButtonOutputConfig as OscButtonOutputConfig,
-    Config as OscConfig,
+    AxisButtonInputConfig as OscAxisButtonInputConfig, AxisInputConfig as OscAxisInputConfig,
+    AxisOutputConfig as OscAxisOutputConfig, ButtonInputConfig as OscButtonInputConfig,
+    ButtonOutputConfig as OscButtonOutputConfig, Config as OscConfig,
+    RelativeAxisInputConfig as OscRelativeAxisInputConfig,
 };
 #[cfg(not(feature = "osc"))]
-use serde::de::{
-    IgnoredAny as OscConfig, IgnoredAny as OscAxisInputConfig, IgnoredAny as OscAxisOutputConfig,
-    IgnoredAny as OscButtonInputConfig, IgnoredAny as OscButtonOutputConfig,
+use schema::{
+    Disabled as OscConfig, Disabled as OscAxisInputConfig, Disabled as OscAxisOutputConfig,
+    Disabled as OscAxisButtonInputConfig, Disabled as OscButtonInputConfig,
+    Disabled as OscButtonOutputConfig, Disabled as OscRelativeAxisInputConfig,
+};
+
+#[cfg(feature = "tray")]
+use crate::tray::Config as TrayConfig;
+#[cfg(not(feature = "tray"))]
+use schema::Disabled as TrayConfig;
+
+#[cfg(feature = "update-check")]
+use crate::update::Config as UpdateCheckConfig;
+#[cfg(not(feature = "update-check"))]
+use schema::Disabled as UpdateCheckConfig;
+
+#[cfg(feature = "vjoy")]
+use crate::vjoy::{
+    AxisOutputConfig as VJoyAxisOutputConfig, ButtonOutputConfig as VJoyButtonOutputConfig,
+    Config as VJoyConfig,
+};
+#[cfg(not(feature = "vjoy"))]
+use schema::{
+    Disabled as VJoyConfig, Disabled as VJoyAxisOutputConfig, Disabled as VJoyButtonOutputConfig,
 };
 
 #[cfg(feature = "vmc")]
 use crate::vmc::{
     AxisOutputConfig as VmcAxisOutputConfig, ButtonOutputConfig as VmcButtonOutputConfig,
-    Config as VmcConfig,
+    Config as VmcConfig, ProfileConfig as VmcProfileConfig,
 };
 #[cfg(not(feature = "vmc"))]
-use serde::de::{
-    IgnoredAny as VmcConfig, IgnoredAny as VmcAxisOutputConfig, IgnoredAny as VmcButtonOutputConfig,
+use schema::{
+    Disabled as VmcConfig, Disabled as VmcAxisOutputConfig, Disabled as VmcButtonOutputConfig,
+    Disabled as VmcProfileConfig,
 };
 
-#[derive(Debug, Default, Deserialize)]
+#[derive(Debug, Default, Deserialize, JsonSchema, Serialize)]
 #[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
 pub struct AppConfig {
+    // These are described as `Option`s, since fields that aren't serialized would otherwise be
+    // required by the schema.
+
+    // Handled by `include::load` before the rest is parsed.
+    #[serde(rename = "include", skip_serializing)]
+    #[schemars(with = "Option<Vec<String>>")]
+    _include: serde::de::IgnoredAny,
+
+    // Handled by `template::expand` before the rest is parsed.
+    #[serde(rename = "template", skip_serializing)]
+    #[schemars(with = "Option<schema::Templates>")]
+    _template: serde::de::IgnoredAny,
+
+    // Handled by `profile::parse` once the rest has been parsed.
+    #[serde(rename = "profile", skip_serializing)]
+    #[schemars(with = "Option<schema::Map<schema::Profile>>")]
+    _profile: serde::de::IgnoredAny,
+
+    pub allow_exec: bool,
+    pub strictness: Strictness,
+
+    pub audio: AudioConfig,
+    pub exec: ExecConfig,
+    pub keep_awake: KeepAwakeConfig,
+    pub openvr: OpenVrConfig,
     pub osc: OscConfig,
+    pub tray: TrayConfig,
+    pub update_check: UpdateCheckConfig,
+    pub vjoy: VJoyConfig,
     pub vmc: VmcConfig,
 
+    pub advanced: AdvancedConfig,
+    pub log: LogConfig,
+    pub restart: RestartConfig,
+    pub session: SessionConfig,
+    pub shutdown: ShutdownConfig,
+    pub state: StateConfig,
+    pub stats: StatsConfig,
+    pub status: StatusConfig,
+    pub test_signal: TestSignalConfig,
+
     #[serde(flatten)]
     pub mappings: Arc<MappingConfig>,
+
+    /// The key paths of unknown settings that were ignored, since the configuration is lenient.
+    #[serde(skip)]
+    #[schemars(skip)]
+    ignored: Vec<String>,
+
+    /// The key paths of mappings that are defined in more than one file, and the files that define
+    /// them.
+    #[serde(skip)]
+    #[schemars(skip)]
+    redefined: Vec<(String, Vec<PathBuf>)>,
+
+    /// Each profile in full, as the rest of the configuration with the profile laid over it.
+    #[serde(skip)]
+    #[schemars(skip)]
+    pub profiles: HashMap<DefaultAtom, AppConfig>,
 }
 
-#[derive(Debug, Default, Deserialize)]
+/// What to do with settings that aren't known, e.g. ones from a newer version of the Sender.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, JsonSchema, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Strictness {
+    /// Refuse to load the configuration.
+    #[default]
+    Strict,
+
+    /// Ignore them, with a warning.
+    Lenient,
+}
+
+#[derive(Debug, Default, Deserialize, JsonSchema, Serialize)]
 #[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
 pub struct MappingConfig {
+    #[schemars(with = "schema::Map<AxisConfig>")]
     pub axis: HashMap<DefaultAtom, AxisConfig>,
+    #[schemars(with = "schema::Map<ButtonConfig>")]
     pub button: HashMap<DefaultAtom, ButtonConfig>,
+
+    /// Numbered when first needed, since the mappings don't change once they've been read.
+    #[serde(skip)]
+    #[schemars(skip)]
+    ids: OnceLock<MappingIds>,
 }
 
-#[derive(Debug, Default, Deserialize)]
+#[derive(Debug, Default, Deserialize, JsonSchema, Serialize)]
 #[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
 pub struct AxisConfig {
+    #[serde(rename = "extends", skip_serializing)]
+    #[schemars(with = "Option<String>")]
+    _extends: serde::de::IgnoredAny,
+
     pub input: Vec<AxisInputConfig>,
     pub output: AxisOutputConfig,
+
+    #[serde(deserialize_with = "crate::units::seconds")]
+    #[schemars(schema_with = "crate::units::seconds_schema")]
+    pub smoothing: f64,
+    /// Changes smaller than this, from the last value that was used, are ignored.
+    #[serde(alias = "deadzone")]
+    pub change_threshold: f64,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, JsonSchema, Serialize)]
 #[serde(rename_all = "kebab-case")]
 pub enum AxisInputConfig {
     Controller(controller::AxisInputConfig),
+    Midi(MidiAxisInputConfig),
     Osc(OscAxisInputConfig),
+    OscRelative(OscRelativeAxisInputConfig),
 }
 
-#[derive(Debug, Default, Deserialize)]
+#[derive(Debug, Default, Deserialize, JsonSchema, Serialize)]
 #[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
 pub struct AxisOutputConfig {
+    pub controller: Option<controller::RumbleConfig>,
+    pub openvr: OpenVrAxisOutputConfig,
     pub osc: OscAxisOutputConfig,
+    pub vjoy: VJoyAxisOutputConfig,
     pub vmc: VmcAxisOutputConfig,
 }
 
-#[derive(Debug, Default, Deserialize)]
+#[derive(Debug, Default, Deserialize, JsonSchema, Serialize)]
 #[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
 pub struct ButtonConfig {
+    #[serde(rename = "extends", skip_serializing)]
+    #[schemars(with = "Option<String>")]
+    _extends: serde::de::IgnoredAny,
+
     pub input: Vec<ButtonInputConfig>,
     pub output: ButtonOutputConfig,
+
+    pub mode: ButtonMode,
+    #[serde(
+        default = "pulse_duration_default",
+        deserialize_with = "crate::units::seconds"
+    )]
+    #[schemars(schema_with = "crate::units::seconds_schema")]
+    pub pulse_duration: f64,
+
+    /// How long the button must be held to send a long press.
+    #[serde(deserialize_with = "crate::units::optional_milliseconds")]
+    #[schemars(schema_with = "crate::units::optional_milliseconds_schema")]
+    pub long_press_ms: Option<u64>,
+    /// How soon after a press another must come to send a double press.
+    #[serde(deserialize_with = "crate::units::optional_milliseconds")]
+    #[schemars(schema_with = "crate::units::optional_milliseconds_schema")]
+    pub double_press_ms: Option<u64>,
+    /// Whether long and double presses aren't also sent as ordinary presses.
+    pub suppress_press: bool,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, JsonSchema, Serialize)]
 #[serde(deny_unknown_fields, rename_all = "kebab-case")]
 pub enum ButtonInputConfig {
     Controller(controller::ButtonInputConfig),
+    ControllerAxis(controller::AxisButtonInputConfig),
+    ControllerChord(controller::ChordInputConfig),
+    Keyboard(KeyboardButtonInputConfig),
+    Midi(MidiButtonInputConfig),
     Osc(OscButtonInputConfig),
+    OscAxis(OscAxisButtonInputConfig),
 }
 
-#[derive(Debug, Default, Deserialize)]
+#[derive(Debug, Default, Deserialize, JsonSchema, Serialize)]
 #[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
 pub struct ButtonOutputConfig {
+    pub audio: AudioButtonOutputConfig,
+    pub controller: Option<controller::RumbleConfig>,
+    pub exec: ExecButtonOutputConfig,
+    pub keystroke: KeystrokeButtonOutputConfig,
     pub osc: OscButtonOutputConfig,
+    /// The profile to switch to when the button is pressed.
+    #[schemars(with = "Option<String>")]
+    pub profile: Option<DefaultAtom>,
+    pub vjoy: VJoyButtonOutputConfig,
     pub vmc: VmcButtonOutputConfig,
 }
 
 impl AppConfig {
-    pub async fn read_from(path: impl AsRef<Path>) -> AnyResult<AppConfig> {
-        Self::read_from_path(path.as_ref()).await
+    pub async fn read_from(path: impl AsRef<Path>, missing: Missing) -> AnyResult<AppConfig> {
+        Self::read_from_path(path.as_ref(), missing).await
     }
 
-    async fn read_from_path(path: &Path) -> AnyResult<AppConfig> {
-        let raw: Cow<str> = match smol::fs::read_to_string(path).await {
-            Ok(s) => Cow::Owned(s),
-            Err(e) if e.kind() == ErrorKind::NotFound => {
-                log::info!("No configuration file found at <{}>. Prompting for which configuration to write.", path.display());
-                log::logger().flush();
+    /// Reads the configuration without offering to write a sample one if it's missing.
+    pub async fn read_existing(path: impl AsRef<Path>) -> AnyResult<AppConfig> {
+        Self::read_from_path(path.as_ref(), Missing::Fail).await
+    }
+
+    /// Writes out the configuration as TOML, including every setting that was left as its default.
+    /// Each profile is written as what it changes from the rest of the configuration.
+    pub fn dump(&self) -> AnyResult<String> {
+        let mut value =
+            toml::Value::try_from(self).context("Failed to write the configuration as TOML")?;
+        shorten_floats(&mut value);
+
+        if !self.profiles.is_empty() {
+            let mut names = self.profiles.keys().collect::<Vec<_>>();
+            names.sort_unstable_by(|a, b| a.as_ref().cmp(b.as_ref()));
+
+            let mut profiles = toml::Table::new();
+            for name in names {
+                let mut profile = toml::Value::try_from(&self.profiles[name])
+                    .with_context(|| format!("Failed to write profile {name:?} as TOML"))?;
+                shorten_floats(&mut profile);
+
+                let (Some(base), Some(profile)) = (value.as_table(), profile.as_table()) else {
+                    bail!("Failed to write profile {name:?} as TOML");
+                };
+                let changes = changes(base, profile, "").map_err(|path| {
+                    anyhow!("Profile {name:?} can't be written, since it removes {path}")
+                })?;
+                profiles.insert(name.to_string(), toml::Value::Table(changes));
+            }
+
+            if let Some(table) = value.as_table_mut() {
+                table.insert("profile".into(), toml::Value::Table(profiles));
+            }
+        }
+
+        toml::to_string(&value).context("Failed to write the configuration as TOML")
+    }
 
-                let theme = dialoguer::theme::ColorfulTheme::default();
-                let mut select = dialoguer::Select::with_theme(&theme);
-                select.with_prompt("Which best describes how you want to use this application?");
-                select.report(false);
+    /// The names of the outputs that this configuration enables.
+    pub fn enabled_outputs(&self) -> Vec<&'static str> {
+        #[allow(unused_mut)]
+        let mut outputs = Vec::new();
 
-                for sample in CONFIGS {
-                    select.item(sample.desc);
+        #[cfg(feature = "osc")]
+        if self.osc.enabled() {
+            outputs.push("OSC");
+        }
+
+        #[cfg(feature = "vmc")]
+        if self.vmc.enabled() {
+            outputs.push("VMC");
+        }
+
+        #[cfg(feature = "openvr")]
+        if self.openvr.enabled() {
+            outputs.push("OpenVR");
+        }
+
+        #[cfg(feature = "audio")]
+        if self.audio.enabled() {
+            outputs.push("audio");
+        }
+
+        #[cfg(feature = "exec")]
+        if self.allow_exec {
+            outputs.push("exec");
+        }
+
+        #[cfg(feature = "vjoy")]
+        if self.vjoy.enabled() {
+            outputs.push("vJoy");
+        }
+
+        outputs
+    }
+
+    pub fn validate(&self) -> Vec<Problem> {
+        let mut problems = self.validate_with(&self.profiles);
+
+        // Only what a profile changes is reported for it, rather than every problem again.
+        let base_problems = problems.len();
+        for (name, profile) in &self.profiles {
+            for problem in profile.validate_with(&self.profiles) {
+                let repeated = problems[..base_problems].iter().any(|p| {
+                    (p.severity, &p.path, &p.message)
+                        == (problem.severity, &problem.path, &problem.message)
+                });
+
+                if !repeated {
+                    problems.push(Problem {
+                        path: format!("profile.{name}.{}", problem.path),
+                        ..problem
+                    });
                 }
+            }
+        }
+
+        problems.sort_by(|a, b| (a.severity, &a.path).cmp(&(b.severity, &b.path)));
+        problems
+    }
 
-                let index = select
-                    .interact()
-                    .context("Failed to prompt for default configuration")?;
-                let config = &CONFIGS[index];
+    /// Validates the configuration, apart from its profiles. Profiles are switched to by name from
+    /// among `profiles`.
+    fn validate_with(&self, profiles: &HashMap<DefaultAtom, AppConfig>) -> Vec<Problem> {
+        let mut report = Report::default();
 
-                log::info!(
-                    "{} configuration was selected. Saving to <{}>.",
-                    config.name,
-                    path.display(),
+        for path in &self.ignored {
+            report.warning(
+                path,
+                "This setting is not known, and has been ignored since strictness is lenient",
+            );
+        }
+
+        for (path, files) in &self.redefined {
+            let files = files
+                .iter()
+                .map(|f| format!("<{}>", f.display()))
+                .collect::<Vec<_>>();
+            report.warning(
+                path,
+                format!(
+                    "This mapping is defined in more than one file ({}), so their settings are merged",
+                    files.join(", ")
+                ),
+            );
+        }
+
+        for (id, axis) in &self.mappings.axis {
+            if axis.input.is_empty() {
+                report.warning(
+                    format!("axis.{id}.input"),
+                    "No inputs are configured for this axis",
                 );
+            } else if is_default(&axis.output) {
+                report.warning(
+                    format!("axis.{id}.output"),
+                    "No outputs are configured for this axis, so it has no effect",
+                );
+            }
+
+            if self.mappings.button.contains_key(id) {
+                report.warning(
+                    format!("axis.{id}"),
+                    "A button has the same name, which makes the two hard to tell apart in logs and status",
+                );
+            }
+
+            if axis.smoothing < 0.0 {
+                report.error(
+                    format!("axis.{id}.smoothing"),
+                    "Smoothing must not be negative",
+                );
+            }
+
+            if axis.change_threshold < 0.0 {
+                report.error(
+                    format!("axis.{id}.change-threshold"),
+                    "Change threshold must not be negative",
+                );
+            }
+
+            for (i, input) in axis.input.iter().enumerate() {
+                if let AxisInputConfig::Controller(c) = input {
+                    c.validate(format!("axis.{id}.input[{i}].controller"), &mut report);
+                }
+            }
+
+            if let Some(ref rumble) = axis.output.controller {
+                rumble.validate(format!("axis.{id}.output.controller"), &mut report);
+            }
+        }
+
+        for (id, button) in &self.mappings.button {
+            if button.input.is_empty() {
+                report.warning(
+                    format!("button.{id}.input"),
+                    "No inputs are configured for this button",
+                );
+            } else if is_default(&button.output) {
+                report.warning(
+                    format!("button.{id}.output"),
+                    "No outputs are configured for this button, so it has no effect",
+                );
+            }
+
+            if button.mode == ButtonMode::Pulse && button.pulse_duration <= 0.0 {
+                report.error(
+                    format!("button.{id}.pulse-duration"),
+                    "Pulse duration must be positive",
+                );
+            }
+
+            if button.long_press_ms == Some(0) {
+                report.error(
+                    format!("button.{id}.long-press-ms"),
+                    "Long press time must be positive",
+                );
+            }
+
+            if button.double_press_ms == Some(0) {
+                report.error(
+                    format!("button.{id}.double-press-ms"),
+                    "Double press time must be positive",
+                );
+            }
+
+            let gestures = button.long_press_ms.is_some() || button.double_press_ms.is_some();
+            if gestures && button.mode != ButtonMode::Hold {
+                report.warning(
+                    format!("button.{id}.mode"),
+                    "Long and double presses are timed from the button's state, which only follows its inputs in hold mode",
+                );
+            }
 
-                smol::fs::write(path, config.contents)
+            if button.suppress_press && !gestures {
+                report.warning(
+                    format!("button.{id}.suppress-press"),
+                    "Neither long-press-ms nor double-press-ms is set, so there's nothing to suppress",
+                );
+            }
+
+            for (i, input) in button.input.iter().enumerate() {
+                match input {
+                    ButtonInputConfig::Controller(c) => {
+                        c.validate(format!("button.{id}.input[{i}].controller"), &mut report);
+                    }
+                    ButtonInputConfig::ControllerAxis(c) => {
+                        c.validate(
+                            format!("button.{id}.input[{i}].controller-axis"),
+                            &mut report,
+                        );
+                    }
+                    ButtonInputConfig::ControllerChord(c) => {
+                        c.validate(
+                            format!("button.{id}.input[{i}].controller-chord"),
+                            &mut report,
+                        );
+                    }
+                    _ => {}
+                }
+            }
+
+            if let Some(ref rumble) = button.output.controller {
+                rumble.validate(format!("button.{id}.output.controller"), &mut report);
+            }
+
+            if let Some(ref name) = button.output.profile {
+                if &**name != DEFAULT_PROFILE && !profiles.contains_key(name) {
+                    report.error(
+                        format!("button.{id}.output.profile"),
+                        format!("There is no profile named \"{name}\""),
+                    );
+                }
+            }
+        }
+
+        self.advanced.validate(&mut report);
+        self.log.validate(&mut report);
+        self.restart.validate(&mut report);
+        self.shutdown.validate(&mut report);
+        self.state.validate(&mut report);
+        self.stats.validate(&mut report);
+        self.status.validate(&mut report);
+        self.test_signal.validate(&self.mappings, &mut report);
+
+        #[cfg(feature = "exec")]
+        self.exec.validate(&mut report);
+
+        #[cfg(feature = "exec")]
+        if !self.allow_exec && crate::exec::has_commands(&self.mappings) {
+            report.warning(
+                "allow-exec",
+                "Commands are configured for some buttons, but will not be run since allow-exec is \
+                not enabled",
+            );
+        }
+
+        #[cfg(feature = "keep-awake")]
+        self.keep_awake.validate(&mut report);
+
+        #[cfg(feature = "midi")]
+        crate::midi::validate(&self.mappings, &mut report);
+
+        #[cfg(feature = "openvr")]
+        self.openvr.validate(&mut report);
+
+        #[cfg(feature = "osc")]
+        self.osc.validate(&self.mappings, &mut report);
+
+        #[cfg(feature = "tray")]
+        self.tray.validate(&mut report);
+
+        #[cfg(feature = "vjoy")]
+        self.vjoy.validate(&self.mappings, &mut report);
+
+        #[cfg(feature = "vmc")]
+        self.vmc.validate(&self.mappings, &mut report);
+
+        report.finish()
+    }
+
+    async fn read_from_path(path: &Path, missing: Missing) -> AnyResult<AppConfig> {
+        let format = Format::from_path(path);
+        let raw: Cow<str> = match smol::fs::read_to_string(path).await {
+            Ok(s) => Cow::Owned(s),
+            // The sample configurations are only available as TOML.
+            Err(e)
+                if missing == Missing::WriteReference
+                    && format == Format::Toml
+                    && e.kind() == ErrorKind::NotFound =>
+            {
+                smol::fs::write(path, REFERENCE_CONFIG.as_bytes())
+                    .await
+                    .with_context(|| {
+                        format!(
+                            "Failed to write reference configuration to <{}>",
+                            path.display()
+                        )
+                    })?;
+
+                bail!(
+                    "No configuration file was found, so the reference configuration has been saved to <{}>. Edit it, then start again.",
+                    path.display()
+                );
+            }
+            Err(e)
+                if missing == Missing::Prompt
+                    && format == Format::Toml
+                    && e.kind() == ErrorKind::NotFound =>
+            {
+                log::info!("No configuration file found at <{}>. Prompting for which configuration to write.", path.display());
+                log::logger().flush();
+
+                // Without a terminal (e.g. when started by double-clicking), the console prompts can't
+                // be answered, so ask with dialogs instead.
+                let contents = if console::Term::stderr().is_term() {
+                    Self::prompt_console()?
+                } else {
+                    Self::prompt_dialog(path)?
+                };
+
+                log::info!("Saving configuration to <{}>.", path.display());
+
+                smol::fs::write(path, contents.as_bytes())
                     .await
                     .with_context(|| {
                         format!(
@@ -138,53 +666,552 @@ impl AppConfig {
                     path.display()
                 );
 
-                Cow::Borrowed(config.contents)
+                contents
             }
             Err(e) => Err(e).with_context(|| {
-                format!("Failed to read configuration from <{}>", path.display())
+                format!(
+                    "Failed to read {format} configuration from <{}>",
+                    path.display()
+                )
             })?,
         };
 
-        let config = toml::from_str(&raw)
-            .with_context(|| format!("Failed to parse configuration from <{}>", path.display()))?;
+        let path = path.to_path_buf();
+        let raw = raw.into_owned();
+        smol::unblock(move || Self::parse(&path, raw)).await
+    }
+
+    fn prompt_console() -> AnyResult<Cow<'static, str>> {
+        let theme = dialoguer::theme::ColorfulTheme::default();
+        let mut select = dialoguer::Select::with_theme(&theme);
+        select.with_prompt("Which best describes how you want to use this application?");
+        select.report(false);
+
+        for sample in CONFIGS {
+            select.item(sample.desc);
+        }
+
+        let index = select
+            .interact()
+            .context("Failed to prompt for default configuration")?;
+        let config = &CONFIGS[index];
+
+        log::info!("{} configuration was selected.", config.name);
+
+        if config.preset {
+            Self::prompt_preset(config.contents)
+        } else {
+            Ok(Cow::Borrowed(config.contents))
+        }
+    }
+
+    /// Asks which configuration to write using message dialogs, offering each one in turn.
+    #[cfg(windows)]
+    fn prompt_dialog(path: &Path) -> AnyResult<Cow<'static, str>> {
+        let config = CONFIGS
+            .iter()
+            .find(|sample| {
+                rfd::MessageDialog::new()
+                    .set_title("Remote Wheel Sender")
+                    .set_description(&format!(
+                        "No configuration file was found. Which best describes how you want to use this application?\n\n{}\n\nChoose No to see the next option.",
+                        sample.desc
+                    ))
+                    .set_level(rfd::MessageLevel::Info)
+                    .set_buttons(rfd::MessageButtons::YesNo)
+                    .show()
+            })
+            .context("No sample configuration was selected")?;
+
+        log::info!("{} configuration was selected.", config.name);
+
+        let confirmed = rfd::MessageDialog::new()
+            .set_title("Remote Wheel Sender")
+            .set_description(&format!(
+                "The {} configuration will be saved to <{}>. It can be changed by editing that file.",
+                config.name,
+                path.display()
+            ))
+            .set_level(rfd::MessageLevel::Info)
+            .set_buttons(rfd::MessageButtons::OkCancel)
+            .show();
+        anyhow::ensure!(confirmed, "Saving the sample configuration was cancelled");
+
+        if config.preset {
+            log::info!("The controller name and axis/button numbers in the configuration will need to be changed by hand. Connected controllers will be listed when the Sender starts.");
+        }
+
+        Ok(Cow::Borrowed(config.contents))
+    }
+
+    /// Without dialogs, there's no way to ask which configuration to write without a terminal.
+    #[cfg(not(windows))]
+    fn prompt_dialog(path: &Path) -> AnyResult<Cow<'static, str>> {
+        bail!(
+            "No configuration file was found at <{}>, and there is no terminal to ask which one to write. Start the Sender from a terminal to choose one.",
+            path.display()
+        );
+    }
+
+    /// Asks which device will be used, and fills in its inputs in a sample configuration.
+    fn prompt_preset(contents: &'static str) -> AnyResult<Cow<'static, str>> {
+        let theme = dialoguer::theme::ColorfulTheme::default();
+        let mut select = dialoguer::Select::with_theme(&theme);
+        select.with_prompt("Which controller will you be using?");
+        select.report(false);
+
+        for preset in PRESETS {
+            select.item(preset);
+        }
+        select.item("My device isn't listed");
+
+        let index = select
+            .interact()
+            .context("Failed to prompt for controller preset")?;
+
+        let Some(preset) = PRESETS.get(index) else {
+            log::info!("No controller preset was selected. The controller name and axis/button numbers in the configuration will need to be changed by hand. Connected controllers will be listed when the Sender starts.");
+            return Ok(Cow::Borrowed(contents));
+        };
+
+        log::info!(
+            "{preset} was selected. Using controller {:?}.",
+            preset.controller
+        );
+
+        let (contents, skipped) = preset.apply(contents);
+        for section in skipped {
+            log::warn!(
+                "{preset} has no input for {section}. It will need to be configured by hand."
+            );
+        }
+
+        Ok(Cow::Owned(contents))
+    }
+
+    fn parse(path: &Path, raw: String) -> AnyResult<AppConfig> {
+        let _base_dir = BaseDir::enter(path.parent().unwrap_or(Path::new("")));
+        let mut sources = include::load(path, raw)?;
+
+        let redefined = include::redefined(&sources);
+        let mut merged = toml::Table::new();
+        for source in &mut sources {
+            include::merge(&mut merged, std::mem::take(&mut source.table));
+        }
+
+        let strictness: Strictness = match merged.get("strictness") {
+            Some(value) => value
+                .clone()
+                .try_into()
+                .context("strictness must be \"strict\" or \"lenient\"")?,
+            None => Strictness::default(),
+        };
+
+        let ignored = unknown::find(&mut merged, strictness == Strictness::Lenient);
+        if strictness == Strictness::Strict && !ignored.is_empty() {
+            bail!(
+                "Unknown settings in configuration from <{}>: {}. If this configuration is for a newer version of the Sender, set strictness = \"lenient\" to ignore them.",
+                path.display(),
+                ignored.join(", ")
+            );
+        }
+
+        // Profiles are laid over the rest of the configuration as it is before templates are
+        // expanded, so that their mappings can extend templates too.
+        let profiles = merged.remove("profile");
+        let base = profiles.as_ref().map(|_| merged.clone());
+
+        let templated = template::expand(&mut merged).with_context(|| {
+            format!(
+                "Failed to apply templates in configuration from <{}>",
+                path.display()
+            )
+        })?;
+
+        // Parsing the text directly gives better errors, with line numbers.
+        let stripped = !ignored.is_empty();
+        let mut config = if let (false, false, [ref source]) = (templated, stripped, &sources[..]) {
+            source.format.parse(&source.raw).with_context(|| {
+                format!(
+                    "Failed to parse {} configuration from <{}>",
+                    source.format,
+                    path.display()
+                )
+            })?
+        } else {
+            Self::parse_merged(path, &sources, merged, stripped)?
+        };
+
+        if let (Some(base), Some(profiles)) = (base, profiles) {
+            config.profiles = profile::parse(&base, profiles).with_context(|| {
+                format!(
+                    "Failed to parse profiles in configuration from <{}>",
+                    path.display()
+                )
+            })?;
+        }
+
+        config.ignored = ignored;
+        config.redefined = redefined;
         Ok(config)
     }
+
+    fn parse_merged(
+        path: &Path,
+        sources: &[include::Source],
+        merged: toml::Table,
+        stripped: bool,
+    ) -> AnyResult<AppConfig> {
+        toml::Value::Table(merged).try_into().or_else(|e| {
+            // Find the file that the problem came from, if it's a problem in one file alone. This
+            // can't be done once unknown settings have been removed, since the files still have
+            // them.
+            if !stripped {
+                for source in sources {
+                    source
+                        .format
+                        .parse::<AppConfig>(&source.raw)
+                        .with_context(|| {
+                            format!(
+                                "Failed to parse {} configuration from <{}>",
+                                source.format,
+                                source.path.display()
+                            )
+                        })?;
+                }
+            }
+
+            Err(e).with_context(|| {
+                format!(
+                    "Failed to parse configuration from <{}> and the files it includes",
+                    path.display()
+                )
+            })
+        })
+    }
+}
+
+impl MappingConfig {
+    pub fn ids(&self) -> &MappingIds {
+        self.ids.get_or_init(|| MappingIds::new(self))
+    }
+
+    pub fn axis_id(&self, name: &DefaultAtom) -> Option<MappingId> {
+        self.ids().axis(name).cloned()
+    }
+
+    pub fn button_id(&self, name: &DefaultAtom) -> Option<MappingId> {
+        self.ids().button(name).cloned()
+    }
+
+    /// Every axis, along with its ID.
+    pub fn axes(&self) -> impl Iterator<Item = (MappingId, &AxisConfig)> {
+        let ids = self.ids();
+        self.axis
+            .iter()
+            .map(move |(name, axis)| (ids.axis(name).unwrap().clone(), axis))
+    }
+
+    /// Every button, along with its ID.
+    pub fn buttons(&self) -> impl Iterator<Item = (MappingId, &ButtonConfig)> {
+        let ids = self.ids();
+        self.button
+            .iter()
+            .map(move |(name, button)| (ids.button(name).unwrap().clone(), button))
+    }
+}
+
+thread_local! {
+    /// The directory of the configuration file being parsed on this thread.
+    static BASE_DIR: RefCell<PathBuf> = const { RefCell::new(PathBuf::new()) };
+}
+
+/// Resolves a path given in the configuration (e.g. a sound file) against the directory of the
+/// configuration file, rather than the working directory. Absolute paths are left as they are.
+#[cfg_attr(not(any(feature = "audio", feature = "openvr")), allow(dead_code))]
+pub fn resolve_path(path: &Path) -> PathBuf {
+    BASE_DIR.with(|dir| dir.borrow().join(path))
+}
+
+/// Sets the directory that [`resolve_path`] resolves against while a configuration is parsed.
+struct BaseDir(PathBuf);
+
+impl BaseDir {
+    fn enter(dir: &Path) -> BaseDir {
+        BaseDir(BASE_DIR.with(|d| d.replace(dir.to_path_buf())))
+    }
+}
+
+impl Drop for BaseDir {
+    fn drop(&mut self) {
+        BASE_DIR.with(|d| d.replace(std::mem::take(&mut self.0)));
+    }
 }
 
 struct SampleConfig {
     name: &'static str,
     desc: &'static str,
     contents: &'static str,
+
+    /// Whether a controller preset should be offered for this configuration.
+    preset: bool,
+}
+
+/// What to do when the configuration file doesn't exist.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Missing {
+    Fail,
+    /// Ask which sample configuration to write.
+    Prompt,
+    /// Write the reference configuration without asking. Since it needs to be filled in, reading
+    /// still fails.
+    WriteReference,
 }
 
+const REFERENCE_CONFIG: &str = include_str!("config/reference.toml");
+
 static CONFIGS: &[SampleConfig] = &[
     SampleConfig {
         name: "Viewer",
         desc: "2D wheel overlay with the Viewer application",
         contents: include_str!("config/with-viewer.toml"),
+        preset: true,
     },
 
     SampleConfig {
         name: "VNyan (Single PC)",
         desc: "3D wheel overlay with VNyan (this PC has the controller connected and is also running VNyan)",
         contents: include_str!("config/with-vnyan-single.toml"),
+        preset: true,
     },
 
     SampleConfig {
         name: "VNyan (Game PC)",
         desc: "3D wheel overlay with VNyan (this PC has the controller connected; VNyan is on another PC)",
         contents: include_str!("config/with-vnyan-game.toml"),
+        preset: true,
     },
 
     SampleConfig {
         name: "VNyan (Stream PC)",
         desc: "3D wheel overlay with VNyan (this PC is running VNyan; the controller is connected to another PC)",
         contents: include_str!("config/with-vnyan-stream.toml"),
+        preset: false,
     },
 
     SampleConfig {
         name: "Reference",
         desc: "Other (manual configuration)",
-        contents: include_str!("config/reference.toml"),
+        contents: REFERENCE_CONFIG,
+        preset: false,
     },
 ];
+
+fn pulse_duration_default() -> f64 {
+    0.1
+}
+
+/// Whether every setting in `value` is left as its default. This compares them as they would be
+/// written, so that each output doesn't need to say whether it's empty, whether or not its feature
+/// is built in.
+fn is_default<T: Default + Serialize>(value: &T) -> bool {
+    toml::Value::try_from(value).ok() == toml::Value::try_from(T::default()).ok()
+}
+
+/// Writes floats that are exactly an `f32` as that `f32` would be written, since most settings are
+/// `f32`s and would otherwise be written with every digit of the nearest `f64` (e.g. 1.1 would be
+/// written as 1.100000023841858).
+fn shorten_floats(value: &mut toml::Value) {
+    match value {
+        toml::Value::Float(f) => {
+            let single = *f as f32;
+            if f64::from(single) == *f {
+                *f = single.to_string().parse().unwrap_or(*f);
+            }
+        }
+
+        toml::Value::Array(values) => values.iter_mut().for_each(shorten_floats),
+        toml::Value::Table(table) => table.iter_mut().for_each(|(_, v)| shorten_floats(v)),
+        _ => {}
+    }
+}
+
+/// What `over` changes from `base`, as a table that gives `over` when laid over `base` the way a
+/// profile is. Tables are compared by key, and anything else is replaced in full. Fails with the path
+/// of anything `base` has that `over` doesn't, since a profile can't remove a setting.
+fn changes(base: &toml::Table, over: &toml::Table, path: &str) -> Result<toml::Table, String> {
+    if let Some(key) = base.keys().find(|k| !over.contains_key(*k)) {
+        return Err(format!("{path}{key}"));
+    }
+
+    let mut out = toml::Table::new();
+    for (key, value) in over {
+        match (base.get(key), value) {
+            (Some(b), v) if b == v => {}
+            (Some(toml::Value::Table(b)), toml::Value::Table(o)) => {
+                let table = changes(b, o, &format!("{path}{key}."))?;
+                out.insert(key.clone(), toml::Value::Table(table));
+            }
+            (_, v) => {
+                out.insert(key.clone(), v.clone());
+            }
+        }
+    }
+
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TOML: &str = r#"
+        [osc]
+        enabled = true
+        output.address = "127.0.0.1:19794"
+
+        [[axis.wheel.input]]
+        controller.name = "Controller Name"
+        controller.axis = 1
+
+        [axis.wheel.output.osc]
+        viewer = { address = "/wheel/rotation", range = [-450, 450] }
+
+        [[button.horn.input]]
+        controller.name = "Controller Name"
+        controller.button = 3
+    "#;
+
+    const YAML: &str = r#"
+osc:
+  enabled: true
+  output:
+    address: "127.0.0.1:19794"
+axis:
+  wheel:
+    input:
+      - controller:
+          name: Controller Name
+          axis: 1
+    output:
+      osc:
+        viewer:
+          address: /wheel/rotation
+          range: [-450, 450]
+button:
+  horn:
+    input:
+      - controller:
+          name: Controller Name
+          button: 3
+"#;
+
+    const JSON: &str = r#"{
+        "osc": { "enabled": true, "output": { "address": "127.0.0.1:19794" } },
+        "axis": {
+            "wheel": {
+                "input": [{ "controller": { "name": "Controller Name", "axis": 1 } }],
+                "output": {
+                    "osc": { "viewer": { "address": "/wheel/rotation", "range": [-450, 450] } }
+                }
+            }
+        },
+        "button": {
+            "horn": {
+                "input": [{ "controller": { "name": "Controller Name", "button": 3 } }]
+            }
+        }
+    }"#;
+
+    fn parse(name: &str, raw: &str) -> AppConfig {
+        AppConfig::parse(Path::new(name), raw.to_owned()).unwrap()
+    }
+
+    #[test]
+    fn formats_parse_the_same() {
+        let toml = parse("config.toml", TOML).dump().unwrap();
+        let yaml = parse("config.yaml", YAML).dump().unwrap();
+        let json = parse("config.json", JSON).dump().unwrap();
+
+        assert!(toml.contains("/wheel/rotation"));
+        assert_eq!(toml, yaml);
+        assert_eq!(toml, json);
+    }
+
+    #[test]
+    fn samples_match_schema() {
+        let schema = serde_json::from_str(&schema()).unwrap();
+        let schema = jsonschema::JSONSchema::compile(&schema).unwrap();
+
+        for sample in CONFIGS {
+            let table: toml::Table = toml::from_str(sample.contents).unwrap();
+            let config = serde_json::to_value(table).unwrap();
+
+            let errors = match schema.validate(&config) {
+                Ok(()) => continue,
+                Err(errors) => errors
+                    .map(|e| format!("{}: {e}", e.instance_path))
+                    .collect::<Vec<_>>(),
+            };
+            panic!("{} configuration: {}", sample.name, errors.join("\n"));
+        }
+    }
+
+    #[test]
+    fn reads_deadzone_as_change_threshold() {
+        let config = parse("config.toml", "[axis.wheel]\ndeadzone = 0.1\n");
+        let wheel = &config.mappings.axis[&DefaultAtom::from("wheel")];
+        assert_eq!(wheel.change_threshold, 0.1);
+    }
+
+    #[test]
+    fn dump_round_trips() {
+        for sample in CONFIGS {
+            let config = parse("config.toml", sample.contents);
+            let dump = config.dump().unwrap();
+
+            let reparsed = parse("config.toml", &dump);
+            assert_eq!(
+                dump,
+                reparsed.dump().unwrap(),
+                "{} configuration",
+                sample.name
+            );
+        }
+
+        // Profiles are written as what they change, which should give the same profiles back.
+        let raw = format!(
+            r#"{TOML}
+            [profile.game.axis.wheel.output.osc]
+            viewer = {{ address = "/wheel/rotation", range = [-900, 900] }}
+
+            [profile.empty]
+            "#
+        );
+        let config = parse("config.toml", &raw);
+        let dump = config.dump().unwrap();
+
+        let reparsed = parse("config.toml", &dump);
+        assert_eq!(dump, reparsed.dump().unwrap());
+
+        let mut names = reparsed.profiles.keys().map(|n| &**n).collect::<Vec<_>>();
+        names.sort_unstable();
+        assert_eq!(names, ["empty", "game"]);
+        for (name, profile) in &config.profiles {
+            assert_eq!(
+                profile.dump().unwrap(),
+                reparsed.profiles[name].dump().unwrap(),
+                "profile {name}"
+            );
+        }
+
+        // Only what the profile changes is written for it.
+        let table: toml::Table = toml::from_str(&dump).unwrap();
+        assert_eq!(
+            table["profile"]["empty"],
+            toml::Value::Table(toml::Table::new())
+        );
+        let game = table["profile"]["game"].as_table().unwrap();
+        assert_eq!(game.keys().collect::<Vec<_>>(), ["axis"]);
+    }
+}
@@ -0,0 +1,64 @@
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
+
+use anyhow::Result as AnyResult;
+use serde::de::DeserializeOwned;
+
+/// The extensions that are tried, in order, when looking for a configuration file.
+const EXTENSIONS: &[&str] = &["toml", "yaml", "yml", "json"];
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Format {
+    Toml,
+    Yaml,
+    Json,
+}
+
+impl Format {
+    /// Detects the format of a file from its extension. Anything unrecognized is assumed to be TOML.
+    pub fn from_path(path: &Path) -> Format {
+        let ext = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+
+        if ext.eq_ignore_ascii_case("yaml") || ext.eq_ignore_ascii_case("yml") {
+            Format::Yaml
+        } else if ext.eq_ignore_ascii_case("json") {
+            Format::Json
+        } else {
+            Format::Toml
+        }
+    }
+
+    pub fn parse<T: DeserializeOwned>(self, raw: &str) -> AnyResult<T> {
+        Ok(match self {
+            Format::Toml => toml::from_str(raw)?,
+            Format::Yaml => serde_yaml::from_str(raw)?,
+            Format::Json => serde_json::from_str(raw)?,
+        })
+    }
+}
+
+impl Display for Format {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Format::Toml => write!(f, "TOML"),
+            Format::Yaml => write!(f, "YAML"),
+            Format::Json => write!(f, "JSON"),
+        }
+    }
+}
+
+/// Finds the configuration file named `stem` with any supported extension, preferring TOML. If none
+/// exist, the TOML file name is returned. It is looked for wherever the Sender keeps its files (see
+/// [`crate::paths`]).
+pub fn find_file(stem: &str) -> PathBuf {
+    let stem = crate::paths::file(stem);
+    find_existing(&stem).unwrap_or_else(|| stem.with_extension("toml"))
+}
+
+/// Finds an existing configuration file at `stem` with any supported extension, preferring TOML.
+pub fn find_existing(stem: &Path) -> Option<PathBuf> {
+    EXTENSIONS
+        .iter()
+        .map(|ext| stem.with_extension(ext))
+        .find(|p| p.is_file())
+}
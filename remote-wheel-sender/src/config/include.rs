@@ -0,0 +1,210 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, ensure, Context as _, Result as AnyResult};
+use toml::{Table, Value};
+
+use super::format::Format;
+
+/// A single configuration file, with its `include` key removed.
+pub(super) struct Source {
+    pub(super) path: PathBuf,
+    pub(super) format: Format,
+    pub(super) raw: String,
+    pub(super) table: Table,
+}
+
+/// Loads a configuration file and every file it includes, in the order that they should be merged:
+/// included files in the order listed, followed by the file that included them.
+///
+/// Include paths are relative to the directory of the main configuration file, and may use `*` and
+/// `?` wildcards in their file names. Each file is parsed according to its own extension, so TOML,
+/// YAML, and JSON files may include each other.
+pub(super) fn load(path: &Path, raw: String) -> AnyResult<Vec<Source>> {
+    let base = path.parent().unwrap_or(Path::new("")).to_path_buf();
+    let mut stack = vec![std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())];
+    let mut sources = Vec::new();
+
+    collect(&base, path.to_path_buf(), raw, &mut stack, &mut sources)?;
+    Ok(sources)
+}
+
+/// Merges `over` into `base`. Tables are merged by key, arrays are concatenated, and any other
+/// values are replaced.
+pub(super) fn merge(base: &mut Table, over: Table) {
+    for (key, value) in over {
+        match (base.get_mut(&key), value) {
+            (Some(Value::Table(b)), Value::Table(o)) => merge(b, o),
+            (Some(Value::Array(b)), Value::Array(o)) => b.extend(o),
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+/// The mappings that are defined in more than one of `sources`, as their key paths (e.g.
+/// `axis.wheel`) and the files that define them. Their settings are merged, which is easy to do by
+/// mistake, e.g. by copying a mapping into a new file without renaming it.
+pub(super) fn redefined(sources: &[Source]) -> Vec<(String, Vec<PathBuf>)> {
+    let mut defined: Vec<(String, Vec<PathBuf>)> = Vec::new();
+
+    for source in sources {
+        for kind in ["axis", "button"] {
+            let Some(Value::Table(mappings)) = source.table.get(kind) else {
+                continue;
+            };
+
+            for name in mappings.keys() {
+                let key = format!("{kind}.{name}");
+                match defined.iter_mut().find(|(k, _)| *k == key) {
+                    Some((_, files)) => files.push(source.path.clone()),
+                    None => defined.push((key, vec![source.path.clone()])),
+                }
+            }
+        }
+    }
+
+    defined.retain(|(_, files)| files.len() > 1);
+    defined
+}
+
+fn collect(
+    base: &Path,
+    path: PathBuf,
+    raw: String,
+    stack: &mut Vec<PathBuf>,
+    sources: &mut Vec<Source>,
+) -> AnyResult<()> {
+    let format = Format::from_path(&path);
+    let mut table: Table = format.parse(&raw).with_context(|| {
+        format!(
+            "Failed to parse {format} configuration from <{}>",
+            path.display()
+        )
+    })?;
+
+    let patterns = match table.remove("include") {
+        None => Vec::new(),
+        Some(Value::Array(a)) => a
+            .into_iter()
+            .map(|v| match v {
+                Value::String(s) => Ok(s),
+                _ => bail!(
+                    "include must be a list of file names in <{}>",
+                    path.display()
+                ),
+            })
+            .collect::<AnyResult<_>>()?,
+        Some(_) => bail!(
+            "include must be a list of file names in <{}>",
+            path.display()
+        ),
+    };
+
+    for pattern in patterns {
+        let included = expand(base, &pattern).with_context(|| {
+            format!(
+                "Failed to find included files <{pattern}> from <{}>",
+                path.display()
+            )
+        })?;
+
+        for file in included {
+            let canonical = std::fs::canonicalize(&file).unwrap_or_else(|_| file.clone());
+            if let Some(start) = stack.iter().position(|p| *p == canonical) {
+                let cycle = stack[start..]
+                    .iter()
+                    .chain(std::iter::once(&canonical))
+                    .map(|p| format!("<{}>", p.display()))
+                    .collect::<Vec<_>>();
+                bail!(
+                    "Configuration includes form a cycle: {}",
+                    cycle.join(" -> ")
+                );
+            }
+
+            let raw = std::fs::read_to_string(&file).with_context(|| {
+                format!(
+                    "Failed to read configuration <{}> included from <{}>",
+                    file.display(),
+                    path.display()
+                )
+            })?;
+
+            stack.push(canonical);
+            collect(base, file, raw, stack, sources)?;
+            stack.pop();
+        }
+    }
+
+    sources.push(Source {
+        path,
+        format,
+        raw,
+        table,
+    });
+    Ok(())
+}
+
+fn expand(base: &Path, pattern: &str) -> AnyResult<Vec<PathBuf>> {
+    let path = base.join(pattern);
+    let name = path
+        .file_name()
+        .and_then(|n| n.to_str())
+        .context("Include path has no file name")?;
+    let dir = path.parent().unwrap_or(base);
+
+    if !is_wildcard(name) {
+        return Ok(vec![path]);
+    }
+
+    ensure!(
+        !dir.to_string_lossy().chars().any(|c| c == '*' || c == '?'),
+        "Wildcards are only supported in file names"
+    );
+
+    let mut files = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let file_name = entry.file_name();
+        let matched = file_name
+            .to_str()
+            .map(|n| wildcard_match(name.as_bytes(), n.as_bytes()));
+
+        if matched == Some(true) && entry.file_type()?.is_file() {
+            files.push(entry.path());
+        }
+    }
+
+    if files.is_empty() {
+        log::warn!("No configuration files match <{}>.", path.display());
+    }
+
+    files.sort();
+    Ok(files)
+}
+
+fn is_wildcard(name: &str) -> bool {
+    name.contains(['*', '?'])
+}
+
+fn wildcard_match(pattern: &[u8], name: &[u8]) -> bool {
+    match (pattern.split_first(), name.split_first()) {
+        (None, None) => true,
+        (Some((b'*', rest)), _) => {
+            wildcard_match(rest, name) || (!name.is_empty() && wildcard_match(pattern, &name[1..]))
+        }
+        (Some((b'?', rest)), Some((_, name_rest))) => wildcard_match(rest, name_rest),
+        (Some((p, rest)), Some((n, name_rest))) => {
+            // File names aren't case-sensitive on Windows.
+            let same = if cfg!(windows) {
+                p.eq_ignore_ascii_case(n)
+            } else {
+                p == n
+            };
+
+            same && wildcard_match(rest, name_rest)
+        }
+        _ => false,
+    }
+}
@@ -0,0 +1,69 @@
+use anyhow::{bail, ensure, Context as _, Result as AnyResult};
+use hashbrown::HashMap;
+use string_cache::DefaultAtom;
+use toml::{Table, Value};
+
+use super::{template, AppConfig};
+use crate::profile::DEFAULT;
+
+/// Parses each profile in the `profile` table, by laying it over the rest of the configuration
+/// (`base`, before its templates are expanded). Tables are merged by key, and any other values
+/// (including lists) are replaced.
+///
+/// A profile can only change mappings that the rest of the configuration has, so that each mapping
+/// is numbered the same in every profile.
+pub(super) fn parse(base: &Table, profiles: Value) -> AnyResult<HashMap<DefaultAtom, AppConfig>> {
+    let Value::Table(profiles) = profiles else {
+        bail!("profile must be a table of profiles");
+    };
+
+    let mut parsed = HashMap::new();
+    for (name, profile) in profiles {
+        ensure!(
+            name != DEFAULT,
+            "[profile.{DEFAULT}] can't be defined, since \"{DEFAULT}\" is the name of the profile made up of the rest of the configuration"
+        );
+
+        let Value::Table(profile) = profile else {
+            bail!("profile.{name} must be a table");
+        };
+
+        for kind in ["axis", "button"] {
+            let Some(Value::Table(mappings)) = profile.get(kind) else {
+                continue;
+            };
+
+            for mapping in mappings.keys() {
+                ensure!(
+                    base.get(kind)
+                        .and_then(Value::as_table)
+                        .is_some_and(|t| t.contains_key(mapping)),
+                    "profile.{name}.{kind}.{mapping} is not defined outside of the profile. Profiles can only change existing mappings."
+                );
+            }
+        }
+
+        let mut config = base.clone();
+        overlay(&mut config, profile);
+        template::expand(&mut config)
+            .with_context(|| format!("Failed to apply templates in profile {name:?}"))?;
+
+        let config: AppConfig = Value::Table(config)
+            .try_into()
+            .with_context(|| format!("Failed to parse profile {name:?}"))?;
+        parsed.insert(DefaultAtom::from(name), config);
+    }
+
+    Ok(parsed)
+}
+
+fn overlay(base: &mut Table, over: Table) {
+    for (key, value) in over {
+        match (base.get_mut(&key), value) {
+            (Some(Value::Table(b)), Value::Table(o)) => overlay(b, o),
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
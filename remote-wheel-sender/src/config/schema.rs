@@ -0,0 +1,54 @@
+use std::collections::BTreeMap;
+
+use schemars::JsonSchema;
+use serde::de::IgnoredAny;
+use serde::{Deserialize, Serialize, Serializer};
+
+use super::{AppConfig, AxisConfig, ButtonConfig, VmcProfileConfig};
+
+/// How maps keyed by name (e.g. by `DefaultAtom`) are described in the schema.
+pub(super) type Map<T> = BTreeMap<String, T>;
+
+/// Generates a JSON Schema describing the configuration, which editors can use to check and complete
+/// configuration files.
+pub fn dump() -> String {
+    let schema = schemars::schema_for!(AppConfig);
+    serde_json::to_string_pretty(&schema).expect("Failed to serialize configuration schema")
+}
+
+/// Settings for a feature that this build doesn't include. These are accepted, but ignored.
+#[allow(unused)]
+#[derive(Clone, Copy, Debug, Default, Deserialize, JsonSchema)]
+#[serde(transparent)]
+pub struct Disabled(#[schemars(with = "serde_json::Value")] IgnoredAny);
+
+// Since these settings are ignored, they're left out when the configuration is dumped.
+impl Serialize for Disabled {
+    fn serialize<S: Serializer>(&self, ser: S) -> Result<S::Ok, S::Error> {
+        ser.serialize_none()
+    }
+}
+
+/// The `template` table, which is handled by `template::expand` rather than deserialized.
+#[allow(unused)]
+#[derive(JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub(super) struct Templates {
+    #[serde(default)]
+    axis: Map<AxisConfig>,
+    #[serde(default)]
+    button: Map<ButtonConfig>,
+}
+
+/// A table in `profile`, which is laid over the rest of the configuration when the profile is used.
+#[allow(unused)]
+#[derive(JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub(super) struct Profile {
+    #[serde(default)]
+    axis: Map<AxisConfig>,
+    #[serde(default)]
+    button: Map<ButtonConfig>,
+    #[serde(default)]
+    vmc: VmcProfileConfig,
+}
@@ -0,0 +1,205 @@
+use anyhow::{bail, Context as _, Result as AnyResult};
+use hashbrown::HashMap;
+use serde::de::DeserializeOwned;
+use toml::{Table, Value};
+
+use super::{AxisConfig, ButtonConfig};
+
+/// Where each value in an expanded mapping came from, by its key path within the mapping. `None`
+/// means that the mapping set the value itself.
+type Origins<'t> = HashMap<String, Option<&'t str>>;
+
+/// Expands every mapping that `extends` a template, and removes the templates from the
+/// configuration. Returns whether any templates were defined or used.
+///
+/// A mapping's own values take priority over those of its template, which in turn take priority
+/// over those of any template that it extends. Tables are merged by key, and any other values
+/// (including lists) are replaced.
+pub(super) fn expand(config: &mut Table) -> AnyResult<bool> {
+    let templates = match config.remove("template") {
+        None => Table::new(),
+        Some(Value::Table(t)) => t,
+        Some(_) => bail!("template must be a table of templates"),
+    };
+
+    let mut used = !templates.is_empty();
+    used |= expand_kind::<AxisConfig>(config, &templates, "axis")?;
+    used |= expand_kind::<ButtonConfig>(config, &templates, "button")?;
+    Ok(used)
+}
+
+fn expand_kind<T: DeserializeOwned>(
+    config: &mut Table,
+    templates: &Table,
+    kind: &str,
+) -> AnyResult<bool> {
+    let templates = match templates.get(kind) {
+        None => None,
+        Some(Value::Table(t)) => Some(t),
+        Some(_) => bail!("template.{kind} must be a table of templates"),
+    };
+
+    let Some(Value::Table(mappings)) = config.get_mut(kind) else {
+        return Ok(false);
+    };
+
+    let mut used = false;
+    for (name, mapping) in mappings.iter_mut() {
+        // Anything that isn't a table will be reported when the configuration is parsed.
+        let Value::Table(mapping) = mapping else {
+            continue;
+        };
+
+        let Some(extends) = mapping.remove("extends") else {
+            continue;
+        };
+
+        let path = format!("{kind}.{name}");
+        let chain = resolve(&path, kind, extends, templates)?;
+
+        let mut expanded = Table::new();
+        let mut origins = Origins::new();
+        for &(template_name, template) in chain.iter().rev() {
+            let mut template = template.clone();
+            template.remove("extends");
+            overlay(
+                &mut expanded,
+                template,
+                "",
+                Some(template_name),
+                &mut origins,
+            );
+        }
+
+        overlay(
+            &mut expanded,
+            std::mem::take(mapping),
+            "",
+            None,
+            &mut origins,
+        );
+
+        check::<T>(&path, &expanded, &origins)?;
+        *mapping = expanded;
+        used = true;
+    }
+
+    Ok(used)
+}
+
+/// Finds the template that a mapping extends, followed by each template that it extends in turn.
+fn resolve<'t>(
+    path: &str,
+    kind: &str,
+    extends: Value,
+    templates: Option<&'t Table>,
+) -> AnyResult<Vec<(&'t str, &'t Table)>> {
+    let mut chain: Vec<(&str, &Table)> = Vec::new();
+    let mut path = path.to_string();
+    let mut next = Some(extends);
+
+    while let Some(extends) = next {
+        let name = match extends {
+            Value::String(s) => s,
+            Value::Array(_) => bail!(
+                "{path}.extends must name a single template. To combine templates, have one template \
+                extend another."
+            ),
+            _ => bail!("{path}.extends must be the name of a template"),
+        };
+
+        let (name, template) = templates
+            .and_then(|t| t.iter().find(|&(k, _)| *k == name))
+            .with_context(|| {
+                format!("{path} extends template \"{name}\", but [template.{kind}.{name}] is not defined")
+            })?;
+
+        let Value::Table(template) = template else {
+            bail!("template.{kind}.{name} must be a table");
+        };
+
+        if chain.iter().any(|&(n, _)| n == name) {
+            let cycle = chain
+                .iter()
+                .map(|&(n, _)| format!("\"{n}\""))
+                .chain(std::iter::once(format!("\"{name}\"")))
+                .collect::<Vec<_>>();
+            bail!(
+                "Templates extend each other in a cycle: {}",
+                cycle.join(" -> ")
+            );
+        }
+
+        chain.push((name, template));
+        path = format!("template.{kind}.{name}");
+        next = template.get("extends").cloned();
+    }
+
+    Ok(chain)
+}
+
+/// Merges `over` into `base`, noting where each value came from.
+fn overlay<'t>(
+    base: &mut Table,
+    over: Table,
+    prefix: &str,
+    origin: Option<&'t str>,
+    origins: &mut Origins<'t>,
+) {
+    for (key, value) in over {
+        let path = if prefix.is_empty() {
+            key.clone()
+        } else {
+            format!("{prefix}.{key}")
+        };
+
+        match (base.get_mut(&key), value) {
+            (Some(Value::Table(b)), Value::Table(o)) => overlay(b, o, &path, origin, origins),
+            (_, value) => {
+                let nested = format!("{path}.");
+                origins.retain(|k, _| !k.starts_with(&nested));
+                origins.insert(path, origin);
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+/// Checks that an expanded mapping is valid, so that any problem can be reported along with the
+/// template that it came from.
+fn check<T: DeserializeOwned>(path: &str, mapping: &Table, origins: &Origins) -> AnyResult<()> {
+    let Err(e) = Value::Table(mapping.clone()).try_into::<T>() else {
+        return Ok(());
+    };
+
+    // The key path isn't otherwise exposed, but is included in the error's description.
+    let text = e.to_string();
+    let Some(key) = text
+        .lines()
+        .find_map(|l| l.strip_prefix("in `")?.strip_suffix('`'))
+    else {
+        bail!("Invalid configuration for {path}: {}", e.message());
+    };
+
+    let mut origin = None;
+    let mut prefix = key;
+    loop {
+        if let Some(&o) = origins.get(prefix) {
+            origin = o;
+            break;
+        }
+
+        match prefix.rsplit_once('.') {
+            Some((p, _)) => prefix = p,
+            None => break,
+        }
+    }
+
+    match origin {
+        Some(template) => bail!(
+            "Invalid value for {path}.{key}, from template \"{template}\": {}",
+            e.message()
+        ),
+        None => bail!("Invalid value for {path}.{key}: {}", e.message()),
+    }
+}
@@ -0,0 +1,196 @@
+use schemars::schema::{
+    InstanceType, ObjectValidation, RootSchema, Schema, SchemaObject, SingleOrVec,
+};
+use toml::{Table, Value};
+
+use super::AppConfig;
+
+/// Older names that settings are still read by, along with their current names. Serde accepts these
+/// as aliases, but they aren't in the schema.
+const ALIASES: &[(&str, &str)] = &[("deadzone", "change-threshold")];
+
+/// Finds the settings in a configuration that aren't known, by comparing it against the schema,
+/// and returns their key paths. If `remove` is set, they're also removed, so that the rest of the
+/// configuration can still be parsed.
+///
+/// This only finds settings in tables that would refuse them when parsed. Anything else that's
+/// wrong (e.g. a value of the wrong type) is left to be reported when the configuration is parsed.
+pub(super) fn find(config: &mut Table, remove: bool) -> Vec<String> {
+    let root = schemars::schema_for!(AppConfig);
+    let finder = Finder {
+        root: &root,
+        remove,
+    };
+
+    let mut unknown = Vec::new();
+    if let Some(ref object) = root.schema.object {
+        finder.table(object, config, "", &mut unknown);
+    }
+
+    unknown
+}
+
+struct Finder<'s> {
+    root: &'s RootSchema,
+    remove: bool,
+}
+
+impl<'s> Finder<'s> {
+    fn value(&self, schema: &'s Schema, value: &mut Value, path: &str, unknown: &mut Vec<String>) {
+        // Anything is accepted where there's no schema to compare against.
+        let Some(schema) = self.resolve(schema) else {
+            return;
+        };
+
+        if let Some(ref subschemas) = schema.subschemas {
+            for schema in subschemas.all_of.iter().flatten() {
+                self.value(schema, value, path, unknown);
+            }
+
+            // Only the alternative that fits best is compared against, since the others are
+            // probably not what was meant. For enums, this is the one with the same name.
+            let alternatives = subschemas.any_of.iter().chain(&subschemas.one_of);
+            let best = alternatives
+                .flatten()
+                .filter(|s| self.fits(s, value))
+                .min_by_key(|s| {
+                    let finder = Finder {
+                        root: self.root,
+                        remove: true,
+                    };
+
+                    let mut found = Vec::new();
+                    finder.value(s, &mut value.clone(), path, &mut found);
+                    found.len()
+                });
+
+            if let Some(best) = best {
+                self.value(best, value, path, unknown);
+            }
+        }
+
+        match value {
+            Value::Table(table) => {
+                if let Some(ref object) = schema.object {
+                    self.table(object, table, path, unknown);
+                }
+            }
+
+            Value::Array(array) => {
+                let Some(items) = schema.array.as_ref().and_then(|a| a.items.as_ref()) else {
+                    return;
+                };
+
+                let mut emptied = Vec::new();
+                for (i, item) in array.iter_mut().enumerate() {
+                    let item_schema = match items {
+                        SingleOrVec::Single(s) => s,
+                        SingleOrVec::Vec(v) => match v.get(i) {
+                            Some(s) => s,
+                            None => continue,
+                        },
+                    };
+
+                    let was_empty = item.as_table().is_some_and(Table::is_empty);
+                    self.value(item_schema, item, &format!("{path}[{i}]"), unknown);
+
+                    // An entry made up only of unknown settings (e.g. an input of a kind that
+                    // isn't known) can't be used, so it's removed along with them.
+                    if !was_empty && item.as_table().is_some_and(Table::is_empty) {
+                        emptied.push(i);
+                    }
+                }
+
+                for i in emptied.into_iter().rev() {
+                    array.remove(i);
+                }
+            }
+
+            _ => {}
+        }
+    }
+
+    fn table(
+        &self,
+        object: &'s ObjectValidation,
+        table: &mut Table,
+        path: &str,
+        unknown: &mut Vec<String>,
+    ) {
+        let mut removed = Vec::new();
+
+        for (key, value) in table.iter_mut() {
+            let path = if path.is_empty() {
+                key.clone()
+            } else {
+                format!("{path}.{key}")
+            };
+
+            let property = object.properties.get(key).or_else(|| {
+                let (_, name) = ALIASES.iter().find(|(alias, _)| alias == key)?;
+                object.properties.get(*name)
+            });
+
+            match (property, object.additional_properties.as_deref()) {
+                (Some(schema), _) | (None, Some(schema @ Schema::Object(_))) => {
+                    self.value(schema, value, &path, unknown);
+                }
+
+                (None, Some(Schema::Bool(false))) => {
+                    unknown.push(path);
+                    removed.push(key.clone());
+                }
+
+                (None, _) => {}
+            }
+        }
+
+        if self.remove {
+            for key in removed {
+                table.remove(&key);
+            }
+        }
+    }
+
+    /// Whether `value` is of a type that `schema` allows.
+    fn fits(&self, schema: &'s Schema, value: &Value) -> bool {
+        let Some(schema) = self.resolve(schema) else {
+            return true;
+        };
+
+        let Some(ref types) = schema.instance_type else {
+            return true;
+        };
+
+        let fits = |t: &InstanceType| match value {
+            Value::String(_) | Value::Datetime(_) => *t == InstanceType::String,
+            Value::Integer(_) => matches!(t, InstanceType::Integer | InstanceType::Number),
+            Value::Float(_) => *t == InstanceType::Number,
+            Value::Boolean(_) => *t == InstanceType::Boolean,
+            Value::Array(_) => *t == InstanceType::Array,
+            Value::Table(_) => *t == InstanceType::Object,
+        };
+
+        match types {
+            SingleOrVec::Single(t) => fits(t),
+            SingleOrVec::Vec(ts) => ts.iter().any(fits),
+        }
+    }
+
+    /// Follows references to definitions. Returns `None` for schemas that aren't objects, i.e. ones
+    /// that accept anything.
+    fn resolve(&self, mut schema: &'s Schema) -> Option<&'s SchemaObject> {
+        loop {
+            let Schema::Object(object) = schema else {
+                return None;
+            };
+
+            let Some(ref reference) = object.reference else {
+                return Some(object);
+            };
+
+            let name = reference.strip_prefix("#/definitions/")?;
+            schema = self.root.definitions.get(name)?;
+        }
+    }
+}
@@ -1,34 +1,166 @@
 use std::fmt::Display;
 use std::num::{NonZeroU32, NonZeroU8};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use anyhow::{bail, Context as _, Result as AnyResult};
-use async_broadcast::Sender as BroadcastTx;
-use hashbrown::HashMap;
-use sdl2::event::Event as SdlEvent;
-use serde::Deserialize;
+use async_broadcast::{
+    InactiveReceiver as InactiveBroadcastRx, Receiver as BroadcastRx, RecvError as BroadcastRxErr,
+    Sender as BroadcastTx,
+};
+use hashbrown::{HashMap, HashSet};
+use schemars::JsonSchema;
+use sdl2::controller::{Axis as GameControllerAxis, Button as GameControllerButton};
+use sdl2::event::{Event as SdlEvent, EventSender as SdlEventSender};
+use sdl2::joystick::HatState;
+use serde::{Deserialize, Serialize};
 use smol::channel::Receiver as ChannelRx;
 use string_cache::DefaultAtom;
 
-use crate::config::MappingConfig;
-use crate::output::OutputEvent;
+use crate::button::{hysteresis_default, threshold_default, ButtonStates, Threshold};
+use crate::check::Report;
+use crate::config::{AxisConfig, MappingConfig};
+use crate::output::{OutputEvent, Timestamped};
+use crate::presets::Preset;
+use crate::route::{MappingId, Routes};
+use crate::stats;
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, JsonSchema, Serialize)]
 #[serde(deny_unknown_fields, rename_all = "kebab-case")]
 pub struct AxisInputConfig {
-    name: DefaultAtom,
+    #[schemars(with = "Option<String>")]
+    name: Option<DefaultAtom>,
+    guid: Option<String>,
+    index: Option<u32>,
+    #[schemars(with = "NumberOrName<NonZeroU8>")]
     axis: Axis,
+
+    /// The part of the axis's travel that's used, as raw values from 0 to 1.
+    #[serde(default = "range_default")]
+    range: [f64; 2],
+    #[serde(default)]
+    invert: bool,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, JsonSchema, Serialize)]
 #[serde(deny_unknown_fields, rename_all = "kebab-case")]
 pub struct ButtonInputConfig {
-    name: DefaultAtom,
-    button: NonZeroU32,
+    #[schemars(with = "Option<String>")]
+    name: Option<DefaultAtom>,
+    guid: Option<String>,
+    index: Option<u32>,
+    #[schemars(with = "Option<NumberOrName<NonZeroU32>>")]
+    button: Option<Button>,
+    hat: Option<HatConfig>,
+}
+
+/// A direction of a POV hat (e.g. a D-pad) which is read as a button.
+#[derive(Debug, Deserialize, JsonSchema, Serialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct HatConfig {
+    index: NonZeroU8,
+    direction: HatDirection,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, JsonSchema, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum HatDirection {
+    Up,
+    UpRight,
+    Right,
+    DownRight,
+    Down,
+    DownLeft,
+    Left,
+    UpLeft,
 }
 
-#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
-pub struct Axis(NonZeroU8);
+/// A button that's pressed by moving a controller axis past a threshold.
+#[derive(Debug, Deserialize, JsonSchema, Serialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct AxisButtonInputConfig {
+    #[schemars(with = "Option<String>")]
+    name: Option<DefaultAtom>,
+    guid: Option<String>,
+    index: Option<u32>,
+    #[schemars(with = "NumberOrName<NonZeroU8>")]
+    axis: Axis,
+
+    #[serde(default = "threshold_default")]
+    threshold: f64,
+    #[serde(default = "hysteresis_default")]
+    hysteresis: f64,
+}
+
+/// A button that's pressed while every one of several controller buttons is held.
+#[derive(Debug, Deserialize, JsonSchema, Serialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct ChordInputConfig {
+    #[schemars(with = "Option<String>")]
+    name: Option<DefaultAtom>,
+    guid: Option<String>,
+    index: Option<u32>,
+    buttons: Vec<NonZeroU32>,
+
+    /// Whether the mappings of the buttons themselves are released while the chord is held.
+    #[serde(default)]
+    suppress: bool,
+}
+
+#[derive(Debug, Deserialize, JsonSchema, Serialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct RumbleConfig {
+    #[schemars(with = "Option<String>")]
+    name: Option<DefaultAtom>,
+    guid: Option<String>,
+    index: Option<u32>,
+
+    #[serde(default = "strength_default")]
+    strength: f64,
+
+    #[serde(default = "duration_default")]
+    duration_ms: u32,
+}
+
+/// An axis of a controller, either by its number, or by its name if SDL knows the controller as a
+/// game controller. Names stay the same when a device's numbering changes, e.g. between modes.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Axis {
+    Raw(NonZeroU8),
+    Named(sdl2::controller::Axis),
+}
+
+/// A button of a controller, by number or by name, as for [`Axis`].
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Button {
+    Raw(NonZeroU32),
+    Named(sdl2::controller::Button),
+}
+
+/// How an axis or a button is given in the configuration, for the schema.
+#[allow(dead_code)]
+#[derive(JsonSchema)]
+#[serde(untagged)]
+enum NumberOrName<N> {
+    Number(N),
+    Name(String),
+}
+
+/// Which connected controllers a configuration refers to. Every part that's given must match.
+#[derive(Clone, Debug, Default, Eq, Hash, PartialEq)]
+pub struct Selector {
+    name: Option<DefaultAtom>,
+    guid: Option<String>,
+    index: Option<u32>,
+}
+
+/// What a connected controller can be selected by.
+#[derive(Debug)]
+struct DeviceInfo {
+    name: DefaultAtom,
+    guid: String,
+    index: u32,
+}
 
 impl<'de> Deserialize<'de> for Axis {
     fn deserialize<D: serde::de::Deserializer<'de>>(de: D) -> Result<Self, D::Error> {
@@ -36,9 +168,21 @@ impl<'de> Deserialize<'de> for Axis {
     }
 }
 
+impl Serialize for Axis {
+    fn serialize<S: serde::Serializer>(&self, ser: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Axis::Raw(number) => number.serialize(ser),
+            Axis::Named(axis) => axis.string().serialize(ser),
+        }
+    }
+}
+
 impl Display for Axis {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "Axis #{}", self.0)
+        match self {
+            Axis::Raw(number) => write!(f, "Axis #{number}"),
+            Axis::Named(axis) => write!(f, "Axis {}", axis.string()),
+        }
     }
 }
 
@@ -49,16 +193,258 @@ impl TryFrom<u8> for Axis {
         value
             .checked_add(1)
             .and_then(NonZeroU8::new)
-            .map(Axis)
+            .map(Axis::Raw)
             .ok_or(())
     }
 }
 
+impl Axis {
+    /// Which axis an SDL event moved, and its raw value from 0 to 1, if it's an axis event.
+    fn motion(event: &SdlEvent) -> Option<(u32, Axis, f64)> {
+        match *event {
+            SdlEvent::JoyAxisMotion {
+                which,
+                axis_idx,
+                value,
+                ..
+            } => Some((which, Axis::try_from(axis_idx).ok()?, joy_axis_raw(value))),
+
+            SdlEvent::ControllerAxisMotion {
+                which, axis, value, ..
+            } => {
+                // Triggers only go from 0 to the maximum, rather than across the whole range.
+                let raw = match axis {
+                    GameControllerAxis::TriggerLeft | GameControllerAxis::TriggerRight => {
+                        value.max(0) as f64 / 32767.0
+                    }
+                    _ => joy_axis_raw(value),
+                };
+                Some((which, Axis::Named(axis), raw))
+            }
+
+            _ => None,
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Button {
+    fn deserialize<D: serde::de::Deserializer<'de>>(de: D) -> Result<Self, D::Error> {
+        de.deserialize_any(ButtonVisitor)
+    }
+}
+
+impl Serialize for Button {
+    fn serialize<S: serde::Serializer>(&self, ser: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Button::Raw(number) => number.serialize(ser),
+            Button::Named(button) => button.string().serialize(ser),
+        }
+    }
+}
+
+impl AxisInputConfig {
+    pub fn validate(&self, path: String, report: &mut Report) {
+        self.selector().validate(&path, report);
+
+        let [min, max] = self.range;
+        if !(0.0..=1.0).contains(&min) || !(0.0..=1.0).contains(&max) || min == max {
+            report.error(
+                format!("{path}.range"),
+                "Range must be two different values from 0 to 1",
+            );
+        }
+    }
+
+    fn scaling(&self) -> AxisScaling {
+        AxisScaling {
+            range: self.range,
+            invert: self.invert,
+        }
+    }
+
+    fn selector(&self) -> Selector {
+        Selector::new(&self.name, &self.guid, self.index)
+    }
+}
+
+impl ButtonInputConfig {
+    pub fn validate(&self, path: String, report: &mut Report) {
+        self.selector().validate(&path, report);
+
+        match (self.button, &self.hat) {
+            (Some(_), Some(_)) => report.error(
+                path,
+                "A controller button input must read either a button or a hat, not both",
+            ),
+            (None, None) => report.error(
+                path,
+                "A controller button input must read a button or a hat",
+            ),
+            _ => {}
+        }
+    }
+
+    fn selector(&self) -> Selector {
+        Selector::new(&self.name, &self.guid, self.index)
+    }
+}
+
+impl HatDirection {
+    /// Whether a hat in the given state presses this direction. A diagonal state presses both of the
+    /// directions next to it, as well as the diagonal itself.
+    fn pressed(self, state: HatState) -> bool {
+        // Each bit of the state is a cardinal direction.
+        let bits = state as u8;
+
+        match self {
+            HatDirection::Up => bits & HatState::Up as u8 != 0,
+            HatDirection::Right => bits & HatState::Right as u8 != 0,
+            HatDirection::Down => bits & HatState::Down as u8 != 0,
+            HatDirection::Left => bits & HatState::Left as u8 != 0,
+            HatDirection::UpRight => matches!(state, HatState::RightUp),
+            HatDirection::DownRight => matches!(state, HatState::RightDown),
+            HatDirection::DownLeft => matches!(state, HatState::LeftDown),
+            HatDirection::UpLeft => matches!(state, HatState::LeftUp),
+        }
+    }
+}
+
+impl AxisButtonInputConfig {
+    pub fn validate(&self, path: String, report: &mut Report) {
+        self.selector().validate(&path, report);
+        self.threshold().validate(&path, report);
+    }
+
+    fn selector(&self) -> Selector {
+        Selector::new(&self.name, &self.guid, self.index)
+    }
+
+    fn threshold(&self) -> Threshold {
+        Threshold {
+            threshold: self.threshold,
+            hysteresis: self.hysteresis,
+        }
+    }
+}
+
+impl ChordInputConfig {
+    pub fn validate(&self, path: String, report: &mut Report) {
+        self.selector().validate(&path, report);
+
+        if self.buttons.len() < 2 {
+            report.error(
+                format!("{path}.buttons"),
+                "A chord must have at least two buttons",
+            );
+        }
+
+        let unique = self.buttons.iter().collect::<HashSet<_>>();
+        if unique.len() != self.buttons.len() {
+            report.error(
+                format!("{path}.buttons"),
+                "A chord must not have the same button more than once",
+            );
+        }
+    }
+
+    fn selector(&self) -> Selector {
+        Selector::new(&self.name, &self.guid, self.index)
+    }
+}
+
+impl RumbleConfig {
+    pub fn validate(&self, path: String, report: &mut Report) {
+        self.selector().validate(&path, report);
+
+        if !(0.0..=1.0).contains(&self.strength) {
+            report.warning(
+                format!("{path}.strength"),
+                "Rumble strength should be between 0 and 1, and will be clamped",
+            );
+        }
+    }
+
+    fn selector(&self) -> Selector {
+        Selector::new(&self.name, &self.guid, self.index)
+    }
+
+    fn rumble(&self, scale: f64) -> Rumble {
+        Rumble {
+            selector: self.selector(),
+            strength: (scale * self.strength).clamp(0.0, 1.0),
+            duration_ms: self.duration_ms,
+        }
+    }
+}
+
+impl Selector {
+    fn new(name: &Option<DefaultAtom>, guid: &Option<String>, index: Option<u32>) -> Selector {
+        Selector {
+            name: name.clone(),
+            // SDL formats GUIDs in lowercase.
+            guid: guid.as_ref().map(|g| g.to_ascii_lowercase()),
+            index,
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.name.is_none() && self.guid.is_none() && self.index.is_none()
+    }
+
+    fn validate(&self, path: &str, report: &mut Report) {
+        if self.is_empty() {
+            report.error(
+                path,
+                "A controller must be selected by name, guid, or index",
+            );
+        }
+
+        if let Some(ref guid) = self.guid {
+            if guid.len() != 32 || !guid.bytes().all(|b| b.is_ascii_hexdigit()) {
+                report.error(
+                    format!("{path}.guid"),
+                    "A controller GUID must be 32 hexadecimal digits, as logged when it's detected",
+                );
+            }
+        }
+    }
+
+    fn matches(&self, device: &DeviceInfo) -> bool {
+        self.name.as_ref().is_none_or(|n| *n == device.name)
+            && self.guid.as_ref().is_none_or(|g| *g == device.guid)
+            && self.index.is_none_or(|i| i == device.index)
+    }
+
+    /// How specifically this selects a controller, for choosing between several that match. An
+    /// index picks out a single device, a GUID picks out a model of device, and a name may be shared
+    /// by several models.
+    fn specificity(&self) -> u8 {
+        4 * self.index.is_some() as u8 + 2 * self.guid.is_some() as u8 + self.name.is_some() as u8
+    }
+}
+
+impl Display for Selector {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut parts = Vec::new();
+        if let Some(ref name) = self.name {
+            parts.push(name.escape_default().to_string());
+        }
+        if let Some(ref guid) = self.guid {
+            parts.push(format!("GUID {guid}"));
+        }
+        if let Some(index) = self.index {
+            parts.push(format!("index {index}"));
+        }
+
+        f.write_str(&parts.join(", "))
+    }
+}
+
 struct AxisVisitor;
 
 impl AxisVisitor {
     fn visit_int<I: TryInto<u8>>(self, v: I) -> Option<Axis> {
-        v.try_into().ok().and_then(NonZeroU8::new).map(Axis)
+        v.try_into().ok().and_then(NonZeroU8::new).map(Axis::Raw)
     }
 }
 
@@ -66,7 +452,7 @@ impl<'de> serde::de::Visitor<'de> for AxisVisitor {
     type Value = Axis;
 
     fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(f, "axis number (1-255)")
+        write!(f, "axis number (1-255) or name")
     }
 
     fn visit_i64<E: serde::de::Error>(self, v: i64) -> Result<Self::Value, E> {
@@ -84,44 +470,278 @@ impl<'de> serde::de::Visitor<'de> for AxisVisitor {
     fn visit_str<E: serde::de::Error>(self, v: &str) -> Result<Self::Value, E> {
         // For backwards-compatibility
         // These names don't necessarily correspond to the correct axes!
-        match v {
-            "x" => Ok(Axis(NonZeroU8::new(1).unwrap())),
-            "y" => Ok(Axis(NonZeroU8::new(2).unwrap())),
-            "z" => Ok(Axis(NonZeroU8::new(3).unwrap())),
-            "rx" => Ok(Axis(NonZeroU8::new(4).unwrap())),
-            "ry" => Ok(Axis(NonZeroU8::new(5).unwrap())),
-            "rz" => Ok(Axis(NonZeroU8::new(6).unwrap())),
-            "slider" => Ok(Axis(NonZeroU8::new(7).unwrap())),
-            "dial" => Ok(Axis(NonZeroU8::new(8).unwrap())),
-            _ => Err(E::unknown_variant(
-                v,
-                &["x", "y", "z", "rx", "ry", "rz", "slider", "dial"],
-            )),
+        let number = match v {
+            "x" => 1,
+            "y" => 2,
+            "z" => 3,
+            "rx" => 4,
+            "ry" => 5,
+            "rz" => 6,
+            "slider" => 7,
+            "dial" => 8,
+            _ => {
+                return GameControllerAxis::from_string(v)
+                    .map(Axis::Named)
+                    .ok_or_else(|| E::unknown_variant(v, AXIS_NAMES));
+            }
+        };
+
+        Ok(Axis::Raw(NonZeroU8::new(number).unwrap()))
+    }
+}
+
+const AXIS_NAMES: &[&str] = &[
+    "x",
+    "y",
+    "z",
+    "rx",
+    "ry",
+    "rz",
+    "slider",
+    "dial",
+    "leftx",
+    "lefty",
+    "rightx",
+    "righty",
+    "lefttrigger",
+    "righttrigger",
+];
+
+struct ButtonVisitor;
+
+impl ButtonVisitor {
+    fn visit_int<I: TryInto<u32>>(self, v: I) -> Option<Button> {
+        v.try_into().ok().and_then(NonZeroU32::new).map(Button::Raw)
+    }
+}
+
+impl<'de> serde::de::Visitor<'de> for ButtonVisitor {
+    type Value = Button;
+
+    fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "button number or name")
+    }
+
+    fn visit_i64<E: serde::de::Error>(self, v: i64) -> Result<Self::Value, E> {
+        self.visit_int(v)
+            .ok_or_else(|| E::invalid_value(serde::de::Unexpected::Signed(v), &"button number"))
+    }
+
+    fn visit_u64<E: serde::de::Error>(self, v: u64) -> Result<Self::Value, E> {
+        self.visit_int(v)
+            .ok_or_else(|| E::invalid_value(serde::de::Unexpected::Unsigned(v), &"button number"))
+    }
+
+    fn visit_str<E: serde::de::Error>(self, v: &str) -> Result<Self::Value, E> {
+        GameControllerButton::from_string(v)
+            .map(Button::Named)
+            .ok_or_else(|| E::unknown_variant(v, BUTTON_NAMES))
+    }
+}
+
+const BUTTON_NAMES: &[&str] = &[
+    "a",
+    "b",
+    "x",
+    "y",
+    "back",
+    "guide",
+    "start",
+    "leftstick",
+    "rightstick",
+    "leftshoulder",
+    "rightshoulder",
+    "dpup",
+    "dpdown",
+    "dpleft",
+    "dpright",
+    "misc1",
+    "paddle1",
+    "paddle2",
+    "paddle3",
+    "paddle4",
+    "touchpad",
+];
+
+/// Converts a joystick axis's value from SDL's range to 0 to 1.
+fn joy_axis_raw(value: i16) -> f64 {
+    value.wrapping_add_unsigned(32768) as u16 as f64 / 65535.0
+}
+
+/// Describes which of a game controller's numbered axes, buttons, and hats each of its named ones
+/// is read from, according to its SDL mapping, so that either can be configured.
+fn describe_mapping(mapping: &str) -> String {
+    // The mapping starts with the controller's GUID and name.
+    let parts = mapping.split(',').skip(2).filter_map(|part| {
+        let (name, source) = part.split_once(':')?;
+        let source = source.trim_start_matches(['+', '-']).trim_end_matches('~');
+        let number = |n: &str| n.parse::<u32>().ok().map(|n| n + 1);
+
+        let source = match source.split_at_checked(1)? {
+            ("a", n) => format!("axis {}", number(n)?),
+            ("b", n) => format!("button {}", number(n)?),
+            ("h", n) => {
+                let (hat, bit) = n.split_once('.')?;
+                let direction = match bit {
+                    "1" => "up",
+                    "2" => "right",
+                    "4" => "down",
+                    "8" => "left",
+                    _ => return None,
+                };
+                format!("hat {} {direction}", number(hat)?)
+            }
+
+            // e.g. the platform that the mapping is for.
+            _ => return None,
+        };
+
+        Some(format!("{name} = {source}"))
+    });
+
+    parts.collect::<Vec<_>>().join(", ")
+}
+
+/// How often smoothed axes are updated while they're still settling.
+const SMOOTHING_INTERVAL: Duration = Duration::from_millis(10);
+
+/// How often to check whether the task has been cancelled while waiting for events.
+const CANCEL_CHECK_INTERVAL: Duration = Duration::from_millis(500);
+
+/// A request for a controller to rumble, passed to the SDL thread as a custom event.
+#[derive(Debug)]
+struct Rumble {
+    selector: Selector,
+    strength: f64,
+    duration_ms: u32,
+}
+
+/// How a controller axis's raw value is converted for one of the inputs that reads it.
+#[derive(Clone, Copy, Debug)]
+pub struct AxisScaling {
+    range: [f64; 2],
+    invert: bool,
+}
+
+impl AxisScaling {
+    /// Maps the range onto 0 to 1, clamping anything outside of it, then inverts it if needed.
+    fn apply(self, raw: f64) -> f64 {
+        let [min, max] = self.range;
+        let value = ((raw - min) / (max - min)).clamp(0.0, 1.0);
+        if self.invert {
+            1.0 - value
+        } else {
+            value
         }
     }
 }
 
 #[derive(Debug, Default)]
 pub struct ControllerMap {
-    pub axes: HashMap<Axis, Vec<DefaultAtom>>,
-    pub buttons: HashMap<u32, Vec<DefaultAtom>>,
+    pub axes: HashMap<Axis, Vec<(MappingId, AxisScaling)>>,
+    pub buttons: HashMap<u32, Vec<MappingId>>,
+    pub named_buttons: HashMap<GameControllerButton, Vec<MappingId>>,
+    pub axis_buttons: HashMap<Axis, Vec<(MappingId, Threshold)>>,
+    pub hats: HashMap<u8, Vec<(MappingId, HatDirection)>>,
+    pub chords: Vec<Chord>,
+}
+
+#[derive(Debug)]
+pub struct Chord {
+    id: MappingId,
+    buttons: Vec<u32>,
+    suppress: bool,
+}
+
+/// Which buttons of a joystick are held, and so which of its chords are.
+#[derive(Debug, Default)]
+struct ChordState {
+    held: HashSet<u32>,
+    /// The indices of the chords that are held.
+    active: HashSet<usize>,
+    /// Buttons whose own mappings were released by a chord. They stay released until the button
+    /// itself is.
+    suppressed: HashSet<u32>,
+}
+
+/// What changed when a button of a joystick with chords was pressed or released.
+#[derive(Debug, Default)]
+struct ChordUpdate {
+    /// Chords that were pressed or released, by index.
+    chords: Vec<(usize, bool)>,
+    /// Other buttons whose own mappings should be released, since a chord is suppressing them.
+    released: Vec<u32>,
+    /// Whether the button's own mappings should be left alone.
+    suppressed: bool,
+}
+
+impl ChordState {
+    fn update(&mut self, chords: &[Chord], button: u32, pressed: bool) -> ChordUpdate {
+        if pressed {
+            self.held.insert(button);
+        } else {
+            self.held.remove(&button);
+        }
+
+        let mut update = ChordUpdate::default();
+        for (index, chord) in chords.iter().enumerate() {
+            let active = chord.buttons.iter().all(|b| self.held.contains(b));
+            if active == self.active.contains(&index) {
+                continue;
+            }
+
+            if active {
+                self.active.insert(index);
+            } else {
+                self.active.remove(&index);
+            }
+            update.chords.push((index, active));
+
+            if active && chord.suppress {
+                for &b in &chord.buttons {
+                    if self.suppressed.insert(b) && b != button {
+                        update.released.push(b);
+                    }
+                }
+            }
+        }
+
+        update.suppressed = if pressed {
+            self.suppressed.contains(&button)
+        } else {
+            self.suppressed.remove(&button)
+        };
+        update
+    }
+
+    /// Forgets everything that's held, returning the chords that were, e.g. when the joystick is
+    /// removed.
+    fn release(self) -> impl Iterator<Item = usize> {
+        self.active.into_iter()
+    }
 }
 
 pub async fn run(
     exec: Arc<smol::Executor<'static>>,
     mappings: Arc<MappingConfig>,
-    output_tx: BroadcastTx<OutputEvent>,
+    output_tx: BroadcastTx<Timestamped<OutputEvent>>,
+    output_rx: InactiveBroadcastRx<Timestamped<OutputEvent>>,
     cancel_rx: ChannelRx<()>,
 ) -> AnyResult<()> {
     log::info!("Controller input task starting...");
 
     let mut controllers = HashMap::new();
+    let mut filters = HashMap::new();
 
-    for (id, axes) in &mappings.axis {
+    for (id, axes) in mappings.axes() {
         for axis in &axes.input {
             if let crate::config::AxisInputConfig::Controller(c) = axis {
+                if let Some(filter) = AxisFilter::new(axes) {
+                    filters.insert(id.clone(), filter);
+                }
+
                 let controller = controllers
-                    .entry(c.name.clone())
+                    .entry(c.selector())
                     .or_insert_with(|| Arc::new(ControllerMap::default()));
 
                 Arc::get_mut(controller)
@@ -129,53 +749,111 @@ pub async fn run(
                     .axes
                     .entry(c.axis)
                     .or_insert_with(Vec::new)
-                    .push(id.clone());
+                    .push((id.clone(), c.scaling()));
             }
         }
     }
 
-    for (id, buttons) in &mappings.button {
+    for (id, buttons) in mappings.buttons() {
         for button in &buttons.input {
             if let crate::config::ButtonInputConfig::Controller(c) = button {
                 let controller = controllers
-                    .entry(c.name.clone())
+                    .entry(c.selector())
+                    .or_insert_with(|| Arc::new(ControllerMap::default()));
+                let controller = Arc::get_mut(controller).unwrap();
+
+                match c.button {
+                    Some(Button::Raw(button)) => controller
+                        .buttons
+                        .entry(button.get())
+                        .or_insert_with(Vec::new)
+                        .push(id.clone()),
+                    Some(Button::Named(button)) => controller
+                        .named_buttons
+                        .entry(button)
+                        .or_insert_with(Vec::new)
+                        .push(id.clone()),
+                    None => {}
+                }
+
+                if let Some(ref hat) = c.hat {
+                    controller
+                        .hats
+                        .entry(hat.index.get())
+                        .or_insert_with(Vec::new)
+                        .push((id.clone(), hat.direction));
+                }
+            } else if let crate::config::ButtonInputConfig::ControllerAxis(c) = button {
+                let controller = controllers
+                    .entry(c.selector())
                     .or_insert_with(|| Arc::new(ControllerMap::default()));
 
                 Arc::get_mut(controller)
                     .unwrap()
-                    .buttons
-                    .entry(c.button.get())
+                    .axis_buttons
+                    .entry(c.axis)
                     .or_insert_with(Vec::new)
-                    .push(id.clone());
+                    .push((id.clone(), c.threshold()));
+            } else if let crate::config::ButtonInputConfig::ControllerChord(c) = button {
+                let controller = controllers
+                    .entry(c.selector())
+                    .or_insert_with(|| Arc::new(ControllerMap::default()));
+
+                Arc::get_mut(controller).unwrap().chords.push(Chord {
+                    id: id.clone(),
+                    buttons: c.buttons.iter().map(|b| b.get()).collect(),
+                    suppress: c.suppress,
+                });
             }
         }
     }
 
-    if controllers.is_empty() {
+    // Only listen for output events if there's something to do with them, so that other outputs
+    // aren't held up waiting for this task to receive them.
+    let rumble_rx = has_rumble(&mappings).then(|| output_rx.activate());
+
+    if controllers.is_empty() && rumble_rx.is_none() {
         log::info!("No controllers configured. Controller input task not starting.");
         return Ok(());
     }
 
-    smol::unblock(move || run_sync(exec, controllers, output_tx, cancel_rx)).await
+    smol::unblock(move || {
+        run_sync(
+            exec,
+            mappings,
+            controllers,
+            filters,
+            output_tx,
+            rumble_rx,
+            cancel_rx,
+        )
+    })
+    .await
+}
+
+fn has_rumble(mappings: &MappingConfig) -> bool {
+    mappings
+        .axis
+        .values()
+        .any(|a| a.output.controller.is_some())
+        || mappings
+            .button
+            .values()
+            .any(|b| b.output.controller.is_some())
 }
 
 fn run_sync(
     exec: Arc<smol::Executor>,
-    controllers: HashMap<DefaultAtom, Arc<ControllerMap>>,
-    output_tx: BroadcastTx<OutputEvent>,
+    mappings: Arc<MappingConfig>,
+    controllers: HashMap<Selector, Arc<ControllerMap>>,
+    mut filters: HashMap<MappingId, AxisFilter>,
+    output_tx: BroadcastTx<Timestamped<OutputEvent>>,
+    rumble_rx: Option<BroadcastRx<Timestamped<OutputEvent>>>,
     cancel_rx: ChannelRx<()>,
 ) -> AnyResult<()> {
     log::info!("Controller input task started.");
 
-    // Devices that support both DirectInput and XInput are broken when any of the following are enabled
-    sdl2::hint::set("SDL_JOYSTICK_HIDAPI", "0");
-    //sdl2::hint::set("SDL_JOYSTICK_HIDAPI_XBOX", "0");
-    sdl2::hint::set("SDL_JOYSTICK_RAWINPUT", "0");
-    sdl2::hint::set("SDL_XINPUT_ENABLED", "0");
-
-    let sdl = sdl2::init()
-        .or_else(|e| bail!(e))
-        .context("Failed to initialize SDL")?;
+    let sdl = init_sdl()?;
 
     let sdl_event = sdl
         .event()
@@ -187,24 +865,73 @@ fn run_sync(
         .or_else(|e| bail!(e))
         .context("Failed to initialize SDL joystick subsystem")?;
 
+    // Controllers that SDL knows as game controllers are opened as such too, so that their axes and
+    // buttons can be read by name.
+    let sdl_game_controller = sdl
+        .game_controller()
+        .or_else(|e| bail!(e))
+        .context("Failed to initialize SDL game controller subsystem")?;
+
     let sender = sdl_event.event_sender();
+    let quit_cancel_rx = cancel_rx.clone();
     exec.spawn(async move {
-        let _ = cancel_rx.recv().await;
+        let _ = quit_cancel_rx.recv().await;
         sender
             .push_event(SdlEvent::Quit { timestamp: 0 })
             .expect("Failed to push Quit event to Controller input task");
     })
     .detach();
 
+    let mut buttons = ButtonStates::new(mappings.clone(), output_tx.clone());
+
+    // Dropped (and so cancelled) when this task stops.
+    let _rumble_task = match rumble_rx {
+        Some(rumble_rx) => {
+            sdl_event
+                .register_custom_event::<Rumble>()
+                .or_else(|e| bail!(e))
+                .context("Failed to register SDL rumble event")?;
+
+            let sender = sdl_event.event_sender();
+            Some(exec.spawn(forward_rumble(mappings, rumble_rx, sender)))
+        }
+
+        None => None,
+    };
+
     let mut sdl_event_pump = sdl
         .event_pump()
         .or_else(|e| bail!(e))
         .context("Failed to initialize SDL event pump subsystem")?;
 
     let mut connected_map = HashMap::new();
+    let input_stats = stats::InputStats::new("Controller");
+    let joysticks = input_stats.gauge("joysticks");
+    joysticks.set(0);
+
+    // Whether each button pressed by a controller axis is currently past its threshold, by
+    // joystick.
+    let mut axis_pressed = HashMap::<(u32, Axis, MappingId), bool>::new();
+
+    // Likewise for each button pressed by a hat direction.
+    let mut hat_pressed = HashMap::<(u32, u8, MappingId), bool>::new();
+
+    // The buttons held on each joystick that has chords.
+    let mut chord_states = HashMap::<u32, ChordState>::new();
 
     'outer: loop {
-        let mut event = Some(sdl_event_pump.wait_event());
+        let mut event = if filters.values().all(AxisFilter::is_settled) {
+            // This doesn't wait indefinitely, in case the Quit event that's pushed when cancelled
+            // never arrives, since that would hold up shutting down.
+            sdl_event_pump.wait_event_timeout(CANCEL_CHECK_INTERVAL.as_millis() as u32)
+        } else {
+            sdl_event_pump.wait_event_timeout(SMOOTHING_INTERVAL.as_millis() as u32)
+        };
+
+        if event.is_none() && cancel_rx.is_closed() {
+            log::info!("Controller input task stopping (shutdown)...");
+            break;
+        }
         let mut flush = false;
 
         while let Some(e) = event {
@@ -216,44 +943,165 @@ fn run_sync(
                         .context("Failed to open joystick")?;
 
                     let id = joystick.instance_id();
-                    let name = DefaultAtom::from(joystick.name());
-                    let controller = controllers.get(&name).cloned();
+                    let game_controller = if sdl_game_controller.is_game_controller(which) {
+                        match sdl_game_controller.open(which) {
+                            Ok(game_controller) => Some(game_controller),
+                            Err(e) => {
+                                log::warn!("Failed to open joystick as a game controller: {e}");
+                                None
+                            }
+                        }
+                    } else {
+                        None
+                    };
+                    let device = DeviceInfo {
+                        name: DefaultAtom::from(joystick.name()),
+                        guid: joystick.guid().to_string(),
+                        index: which,
+                    };
+
+                    // Only the most specific match is used, so that e.g. two controllers with the
+                    // same name can be told apart by index.
+                    let controller = controllers
+                        .iter()
+                        .filter(|(s, _)| s.matches(&device))
+                        .max_by_key(|(s, _)| s.specificity())
+                        .map(|(_, m)| m.clone());
 
                     log::info!(
-                        "Joystick detected: {} (configured axes: {}, buttons: {})",
-                        name.escape_default(),
+                        "Joystick detected: {} (GUID: {}, index: {}, configured axes: {}, buttons: {})",
+                        device.name.escape_default(),
+                        device.guid,
+                        device.index,
                         controller.as_ref().map(|m| m.axes.len()).unwrap_or(0),
-                        controller.as_ref().map(|m| m.buttons.len()).unwrap_or(0)
+                        controller
+                            .as_ref()
+                            .map(|m| {
+                                m.buttons.len()
+                                    + m.named_buttons.len()
+                                    + m.axis_buttons.len()
+                                    + m.hats.len()
+                                    + m.chords.len()
+                            })
+                            .unwrap_or(0)
                     );
-                    connected_map.insert(id, (joystick, name, controller));
+                    if let Some(ref game_controller) = game_controller {
+                        log::info!(
+                            "Joystick {} is a game controller, with named inputs: {}",
+                            device.name.escape_default(),
+                            describe_mapping(&game_controller.mapping())
+                        );
+                    }
+                    if let Some(preset) = Preset::find(&device.name) {
+                        log::debug!(
+                            "Joystick {} is a known device: {preset}",
+                            device.name.escape_default()
+                        );
+                    }
+
+                    connected_map.insert(id, ((joystick, game_controller), device, controller));
+                    joysticks.set(connected_map.len() as u64);
                 }
 
                 SdlEvent::JoyDeviceRemoved { which, .. } => {
-                    if let Some((_, name, _)) = connected_map.remove(&which) {
-                        log::info!("Joystick removed: {}", name.escape_default());
+                    // Anything still held past an axis threshold or on a hat is released, since
+                    // it won't be let go of now.
+                    let mut released = Vec::new();
+                    axis_pressed.retain(|(w, _, input), pressed| {
+                        if *w == which && *pressed {
+                            released.push(input.clone());
+                        }
+                        *w != which
+                    });
+
+                    hat_pressed.retain(|(w, _, input), pressed| {
+                        if *w == which && *pressed {
+                            released.push(input.clone());
+                        }
+                        *w != which
+                    });
+
+                    // Likewise for chords.
+                    if let (Some(state), Some((_, _, Some(controller)))) =
+                        (chord_states.remove(&which), connected_map.get(&which))
+                    {
+                        released.extend(state.release().map(|i| controller.chords[i].id.clone()));
                     }
-                }
 
-                SdlEvent::JoyAxisMotion {
-                    which,
-                    axis_idx,
-                    value,
-                    ..
-                } => {
-                    if let Some((_, _, Some(controller))) = connected_map.get(&which) {
-                        if let Ok(axis) = Axis::try_from(axis_idx) {
-                            let value = value.wrapping_add_unsigned(32768) as u16 as f64 / 65535.0;
+                    for input in released {
+                        let Some(state) = buttons.update(&input, false) else {
+                            continue;
+                        };
+
+                        let event = OutputEvent::UpdateButton(input, state);
+                        input_stats.event(&event);
+
+                        if smol::block_on(output_tx.broadcast(event.into())).is_err() {
+                            log::info!("Controller input task stopping (no remaining outputs).");
+                            break 'outer;
+                        }
+
+                        flush = true;
+                    }
+
+                    match connected_map.remove(&which) {
+                        // Losing a controller that's in use is worth drawing attention to.
+                        Some((_, device, Some(_))) => {
+                            log::warn!("Joystick removed: {}", device.name.escape_default());
+                        }
+                        Some((_, device, None)) => {
+                            log::info!("Joystick removed: {}", device.name.escape_default());
+                        }
+                        None => {}
+                    }
+                    joysticks.set(connected_map.len() as u64);
+                }
 
+                SdlEvent::JoyAxisMotion { .. } | SdlEvent::ControllerAxisMotion { .. } => {
+                    if let Some((which, axis, raw)) = Axis::motion(&e) {
+                        if let Some((_, _, Some(controller))) = connected_map.get(&which) {
                             if let Some(inputs) = controller.axes.get(&axis) {
-                                for input in inputs {
-                                    if smol::block_on(
-                                        output_tx.broadcast(OutputEvent::UpdateAxis(
-                                            input.clone(),
-                                            value,
-                                        )),
-                                    )
-                                    .is_err()
-                                    {
+                                for (input, scaling) in inputs {
+                                    let raw = scaling.apply(raw);
+                                    let value = match filters.get_mut(input) {
+                                        Some(filter) => match filter.set(raw, Instant::now()) {
+                                            Some(value) => value,
+                                            None => continue,
+                                        },
+                                        None => raw,
+                                    };
+
+                                    let event = OutputEvent::UpdateAxis(input.clone(), value);
+                                    input_stats.event(&event);
+
+                                    if smol::block_on(output_tx.broadcast(event.into())).is_err() {
+                                        log::info!("Controller input task stopping (no remaining outputs).");
+                                        break 'outer;
+                                    }
+
+                                    flush = true;
+                                }
+                            }
+
+                            if let Some(inputs) = controller.axis_buttons.get(&axis) {
+                                for (input, threshold) in inputs {
+                                    let was_pressed = axis_pressed
+                                        .entry((which, axis, input.clone()))
+                                        .or_insert(false);
+                                    let pressed = threshold.pressed(raw, *was_pressed);
+                                    if pressed == *was_pressed {
+                                        continue;
+                                    }
+
+                                    *was_pressed = pressed;
+                                    let Some(state) = buttons.update(input, pressed) else {
+                                        continue;
+                                    };
+
+                                    let event = OutputEvent::UpdateButton(input.clone(), state);
+                                    input_stats.event(&event);
+
+                                    if smol::block_on(output_tx.broadcast(event.into())).is_err() {
                                         log::info!("Controller input task stopping (no remaining outputs).");
                                         break 'outer;
                                     }
@@ -273,19 +1121,105 @@ fn run_sync(
                 } => {
                     if let Some((_, _, Some(controller))) = connected_map.get(&which) {
                         let button = button_idx as u32 + 1;
+                        let pressed = matches!(e, SdlEvent::JoyButtonDown { .. });
+                        let own_inputs = controller.buttons.get(&button).into_iter().flatten();
 
-                        if let Some(inputs) = controller.buttons.get(&button) {
-                            let pressed = matches!(e, SdlEvent::JoyButtonDown { .. });
+                        let mut changes = Vec::new();
+                        if controller.chords.is_empty() {
+                            changes.extend(own_inputs.map(|i| (i, pressed)));
+                        } else {
+                            let state = chord_states.entry(which).or_default();
+                            let update = state.update(&controller.chords, button, pressed);
+
+                            for b in update.released {
+                                let inputs = controller.buttons.get(&b).into_iter().flatten();
+                                changes.extend(inputs.map(|i| (i, false)));
+                            }
+
+                            if !update.suppressed {
+                                changes.extend(own_inputs.map(|i| (i, pressed)));
+                            }
+
+                            for (index, active) in update.chords {
+                                changes.push((&controller.chords[index].id, active));
+                            }
+                        }
+
+                        for (input, pressed) in changes {
+                            let Some(state) = buttons.update(input, pressed) else {
+                                continue;
+                            };
+
+                            let event = OutputEvent::UpdateButton(input.clone(), state);
+                            input_stats.event(&event);
+
+                            if smol::block_on(output_tx.broadcast(event.into())).is_err() {
+                                log::info!(
+                                    "Controller input task stopping (no remaining outputs)."
+                                );
+                                break 'outer;
+                            }
 
+                            flush = true;
+                        }
+                    }
+                }
+
+                SdlEvent::ControllerButtonDown { which, button, .. }
+                | SdlEvent::ControllerButtonUp { which, button, .. } => {
+                    if let Some((_, _, Some(controller))) = connected_map.get(&which) {
+                        let pressed = matches!(e, SdlEvent::ControllerButtonDown { .. });
+
+                        if let Some(inputs) = controller.named_buttons.get(&button) {
                             for input in inputs {
-                                if smol::block_on(
-                                    output_tx.broadcast(OutputEvent::UpdateButton(
-                                        input.clone(),
-                                        pressed,
-                                    )),
-                                )
-                                .is_err()
-                                {
+                                let Some(state) = buttons.update(input, pressed) else {
+                                    continue;
+                                };
+
+                                let event = OutputEvent::UpdateButton(input.clone(), state);
+                                input_stats.event(&event);
+
+                                if smol::block_on(output_tx.broadcast(event.into())).is_err() {
+                                    log::info!(
+                                        "Controller input task stopping (no remaining outputs)."
+                                    );
+                                    break 'outer;
+                                }
+
+                                flush = true;
+                            }
+                        }
+                    }
+                }
+
+                SdlEvent::JoyHatMotion {
+                    which,
+                    hat_idx,
+                    state,
+                    ..
+                } => {
+                    if let Some((_, _, Some(controller))) = connected_map.get(&which) {
+                        let hat = hat_idx.saturating_add(1);
+
+                        if let Some(inputs) = controller.hats.get(&hat) {
+                            for (input, direction) in inputs {
+                                let was_pressed = hat_pressed
+                                    .entry((which, hat, input.clone()))
+                                    .or_insert(false);
+                                let pressed = direction.pressed(state);
+                                if pressed == *was_pressed {
+                                    continue;
+                                }
+
+                                *was_pressed = pressed;
+                                let Some(state) = buttons.update(input, pressed) else {
+                                    continue;
+                                };
+
+                                let event = OutputEvent::UpdateButton(input.clone(), state);
+                                input_stats.event(&event);
+
+                                if smol::block_on(output_tx.broadcast(event.into())).is_err() {
                                     log::info!(
                                         "Controller input task stopping (no remaining outputs)."
                                     );
@@ -298,6 +1232,26 @@ fn run_sync(
                     }
                 }
 
+                SdlEvent::User { .. } => {
+                    if let Some(rumble) = e.as_user_event_type::<Rumble>() {
+                        let joystick = connected_map
+                            .values_mut()
+                            .find(|(_, device, _)| rumble.selector.matches(device));
+
+                        if let Some(((joystick, _), _, _)) = joystick {
+                            let level = (rumble.strength * u16::MAX as f64) as u16;
+                            if let Err(e) = joystick.set_rumble(level, level, rumble.duration_ms) {
+                                log::debug!("Failed to rumble joystick {}: {e}", rumble.selector);
+                            }
+                        } else {
+                            log::debug!(
+                                "Rumble requested for joystick {}, which is not connected.",
+                                rumble.selector
+                            );
+                        }
+                    }
+                }
+
                 SdlEvent::Quit { .. } => {
                     log::info!("Controller input task stopping (shutdown)...");
                     break 'outer;
@@ -309,7 +1263,22 @@ fn run_sync(
             event = sdl_event_pump.poll_event();
         }
 
-        if flush && smol::block_on(output_tx.broadcast(OutputEvent::Flush)).is_err() {
+        let now = Instant::now();
+        for (input, filter) in &mut filters {
+            if let Some(value) = filter.advance(now) {
+                let event = OutputEvent::UpdateAxis(input.clone(), value);
+                input_stats.event(&event);
+
+                if smol::block_on(output_tx.broadcast(event.into())).is_err() {
+                    log::info!("Controller input task stopping (no remaining outputs).");
+                    break 'outer;
+                }
+
+                flush = true;
+            }
+        }
+
+        if flush && smol::block_on(output_tx.broadcast(OutputEvent::Flush.into())).is_err() {
             log::info!("Controller input task stopping (no remaining outputs).");
             break;
         }
@@ -318,3 +1287,537 @@ fn run_sync(
     log::info!("Controller input task stopped.");
     Ok(())
 }
+
+/// Initializes SDL, with the hints that the configured axis and button numbers depend on.
+fn init_sdl() -> AnyResult<sdl2::Sdl> {
+    // On Windows, devices that support both DirectInput and XInput are broken when HIDAPI is
+    // enabled. On Linux, HIDAPI needs access to the hidraw devices, which usually isn't given, so
+    // it's left to evdev instead.
+    sdl2::hint::set("SDL_JOYSTICK_HIDAPI", "0");
+    //sdl2::hint::set("SDL_JOYSTICK_HIDAPI_XBOX", "0");
+
+    #[cfg(windows)]
+    {
+        // These are likewise broken with devices that support both DirectInput and XInput.
+        sdl2::hint::set("SDL_JOYSTICK_RAWINPUT", "0");
+        sdl2::hint::set("SDL_XINPUT_ENABLED", "0");
+    }
+
+    #[cfg(target_os = "linux")]
+    {
+        // The classic /dev/input/js* interface numbers axes and buttons differently, so the
+        // configured numbers only match when evdev is used.
+        sdl2::hint::set("SDL_JOYSTICK_LINUX_CLASSIC", "0");
+    }
+
+    sdl2::init()
+        .or_else(|e| bail!(e))
+        .context("Failed to initialize SDL")
+}
+
+/// How long to wait for the controllers that are already connected to be reported.
+const LIST_WAIT: Duration = Duration::from_millis(500);
+
+/// How long to show axis movement for once the controllers have been listed.
+const LIST_WATCH: Duration = Duration::from_secs(15);
+
+/// How far an axis must move from the value last shown for it to be shown again.
+const LIST_MOTION_STEP: f64 = 0.05;
+
+/// Prints every connected controller, with what it can be configured by, and then its axes as
+/// they're moved, so that the right name and axis numbers can be found for the configuration.
+pub fn list_devices() -> AnyResult<()> {
+    let sdl = init_sdl()?;
+
+    let sdl_joystick = sdl
+        .joystick()
+        .or_else(|e| bail!(e))
+        .context("Failed to initialize SDL joystick subsystem")?;
+
+    let sdl_game_controller = sdl
+        .game_controller()
+        .or_else(|e| bail!(e))
+        .context("Failed to initialize SDL game controller subsystem")?;
+
+    let mut sdl_event_pump = sdl
+        .event_pump()
+        .or_else(|e| bail!(e))
+        .context("Failed to initialize SDL event pump subsystem")?;
+
+    let mut connected = HashMap::new();
+    // The value last shown for each axis, by joystick and axis.
+    let mut shown = HashMap::<(u32, Axis), f64>::new();
+
+    let start = Instant::now();
+    let mut listed = false;
+
+    loop {
+        let elapsed = start.elapsed();
+        if !listed && elapsed >= LIST_WAIT {
+            listed = true;
+            if connected.is_empty() {
+                println!("No controllers found.");
+                return Ok(());
+            }
+
+            println!(
+                "Move an axis to see its number (for {} seconds, or until Ctrl-C is pressed).",
+                LIST_WATCH.as_secs()
+            );
+        }
+
+        let deadline = if listed {
+            LIST_WAIT + LIST_WATCH
+        } else {
+            LIST_WAIT
+        };
+        let Some(remaining) = deadline.checked_sub(elapsed) else {
+            break;
+        };
+
+        // Connected controllers are reported as added as soon as events are read.
+        let timeout = remaining.as_millis().max(1) as u32;
+        let Some(event) = sdl_event_pump.wait_event_timeout(timeout) else {
+            continue;
+        };
+
+        match event {
+            SdlEvent::JoyDeviceAdded { which, .. } => {
+                let joystick = sdl_joystick
+                    .open(which)
+                    .or_else(|e| bail!(e))
+                    .context("Failed to open joystick")?;
+                let id = joystick.instance_id();
+
+                println!(
+                    "Controller {which}: \"{}\" (GUID: {}, axes: {}, buttons: {}, hats: {})",
+                    joystick.name().escape_default(),
+                    joystick.guid(),
+                    joystick.num_axes(),
+                    joystick.num_buttons(),
+                    joystick.num_hats(),
+                );
+
+                for i in 0..joystick.num_axes() {
+                    let Ok(axis) = u8::try_from(i).map_err(drop).and_then(Axis::try_from) else {
+                        continue;
+                    };
+                    let Ok(value) = joystick.axis(i).map(joy_axis_raw) else {
+                        continue;
+                    };
+
+                    println!("    {axis}: {value:.3}");
+                    shown.insert((id, axis), value);
+                }
+
+                if sdl_game_controller.is_game_controller(which) {
+                    if let Ok(game_controller) = sdl_game_controller.open(which) {
+                        println!(
+                            "    Named inputs: {}",
+                            describe_mapping(&game_controller.mapping())
+                        );
+                    }
+                }
+
+                connected.insert(id, joystick);
+            }
+
+            SdlEvent::JoyDeviceRemoved { which, .. } => {
+                if let Some(joystick) = connected.remove(&which) {
+                    println!(
+                        "Controller removed: \"{}\"",
+                        joystick.name().escape_default()
+                    );
+                }
+            }
+
+            ref event @ SdlEvent::JoyAxisMotion { .. } if listed => {
+                let Some((which, axis, value)) = Axis::motion(event) else {
+                    continue;
+                };
+                let Some(joystick) = connected.get(&which) else {
+                    continue;
+                };
+
+                let last = shown.entry((which, axis)).or_insert(value);
+                if (value - *last).abs() >= LIST_MOTION_STEP {
+                    *last = value;
+                    println!(
+                        "\"{}\" {axis}: {value:.3}",
+                        joystick.name().escape_default()
+                    );
+                }
+            }
+
+            // SDL turns Ctrl-C into this.
+            SdlEvent::Quit { .. } => break,
+
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+/// Passes rumble requests for output events on to the SDL thread, which owns the joysticks.
+async fn forward_rumble(
+    mappings: Arc<MappingConfig>,
+    mut recv: BroadcastRx<Timestamped<OutputEvent>>,
+    sender: SdlEventSender,
+) {
+    let axes = Routes::axes(&mappings, |_, a| a.output.controller.as_ref());
+    let buttons = Routes::buttons(&mappings, |_, b| b.output.controller.as_ref());
+
+    loop {
+        let rumble = match recv.recv().await.map(|e| e.event) {
+            Ok(OutputEvent::UpdateAxis(id, value)) => axes.get(&id).map(|r| r.rumble(value)),
+            Ok(OutputEvent::UpdateButton(id, true)) => buttons.get(&id).map(|r| r.rumble(1.0)),
+
+            Ok(_) => None,
+
+            Err(BroadcastRxErr::Overflowed(n)) => {
+                log::warn!("Controller rumble missed {n} update(s)!");
+                stats::counter("Controller rumble", "missed events").add(n);
+                None
+            }
+
+            Err(BroadcastRxErr::Closed) => break,
+        };
+
+        if let Some(rumble) = rumble {
+            if let Err(e) = sender.push_custom_event(rumble) {
+                log::warn!("Failed to pass rumble to controller task: {e}");
+            }
+        }
+    }
+}
+
+/// Smoothing and a change threshold for a single axis mapping.
+///
+/// Smoothing is exponential with a time constant, so it behaves the same regardless of how often the
+/// controller reports values.
+struct AxisFilter {
+    smoothing: f64,
+    /// Changes smaller than this from the current target are ignored, so that a noisy controller
+    /// jittering around a value doesn't move it.
+    change_threshold: f64,
+
+    target: f64,
+    value: Option<f64>,
+    updated: Instant,
+}
+
+impl AxisFilter {
+    /// Close enough to the target for smoothing to stop.
+    const SETTLED: f64 = 1e-4;
+
+    fn new(config: &AxisConfig) -> Option<AxisFilter> {
+        if config.smoothing <= 0.0 && config.change_threshold <= 0.0 {
+            return None;
+        }
+
+        Some(AxisFilter {
+            smoothing: config.smoothing.max(0.0),
+            change_threshold: config.change_threshold.max(0.0),
+
+            target: 0.0,
+            value: None,
+            updated: Instant::now(),
+        })
+    }
+
+    fn is_settled(&self) -> bool {
+        self.value.is_none() || self.value == Some(self.target)
+    }
+
+    /// Sets a new raw value, returning the filtered value to send, if it changed.
+    fn set(&mut self, raw: f64, now: Instant) -> Option<f64> {
+        if self.value.is_none() {
+            self.target = raw;
+            self.value = Some(raw);
+            self.updated = now;
+            return Some(raw);
+        }
+
+        // The ends of the range always get through, so that the axis can be moved all the way even
+        // when it's already within the threshold of them.
+        let at_end = raw <= 0.0 || raw >= 1.0;
+        if (raw - self.target).abs() < self.change_threshold && !(at_end && raw != self.target) {
+            return None;
+        }
+
+        // Smoothing up to this point should still be towards the old target.
+        let moved = self.advance(now);
+        self.target = raw;
+
+        if self.smoothing <= 0.0 {
+            self.value = Some(raw);
+            return Some(raw);
+        }
+
+        moved
+    }
+
+    /// Moves the value towards the target for the time elapsed, returning it if it changed.
+    fn advance(&mut self, now: Instant) -> Option<f64> {
+        let value = self.value?;
+        let dt = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.updated = now;
+
+        if value == self.target {
+            return None;
+        }
+
+        let alpha = 1.0 - (-dt / self.smoothing).exp();
+        let mut next = value + alpha * (self.target - value);
+        if (self.target - next).abs() < Self::SETTLED {
+            next = self.target;
+        }
+
+        self.value = Some(next);
+        (next != value).then_some(next)
+    }
+}
+
+fn range_default() -> [f64; 2] {
+    [0.0, 1.0]
+}
+
+fn strength_default() -> f64 {
+    1.0
+}
+
+fn duration_default() -> u32 {
+    200
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    fn filter(smoothing: f64, change_threshold: f64) -> Option<AxisFilter> {
+        let mut config = AxisConfig::default();
+        config.smoothing = smoothing;
+        config.change_threshold = change_threshold;
+        AxisFilter::new(&config)
+    }
+
+    fn close(a: Option<f64>, b: f64) -> bool {
+        a.is_some_and(|a| (a - b).abs() < 1e-9)
+    }
+
+    fn chord(buttons: &[u32], suppress: bool) -> Chord {
+        Chord {
+            id: MappingId::new("chord", 0),
+            buttons: buttons.to_vec(),
+            suppress,
+        }
+    }
+
+    #[test]
+    fn chord_is_held_in_any_order() {
+        let chords = [chord(&[1, 2], false)];
+
+        for (first, second) in [(1, 2), (2, 1)] {
+            let mut state = ChordState::default();
+            assert_eq!(state.update(&chords, first, true).chords, []);
+            assert_eq!(state.update(&chords, second, true).chords, [(0, true)]);
+            assert_eq!(state.update(&chords, first, false).chords, [(0, false)]);
+            assert_eq!(state.update(&chords, second, false).chords, []);
+        }
+    }
+
+    #[test]
+    fn chord_needs_every_button() {
+        let chords = [chord(&[1, 2, 3], false)];
+        let mut state = ChordState::default();
+
+        assert_eq!(state.update(&chords, 3, true).chords, []);
+        assert_eq!(state.update(&chords, 1, true).chords, []);
+        assert_eq!(state.update(&chords, 4, true).chords, []);
+        assert_eq!(state.update(&chords, 2, true).chords, [(0, true)]);
+
+        // Pressing again while it's held (e.g. a repeated event) doesn't press it again.
+        assert_eq!(state.update(&chords, 2, true).chords, []);
+    }
+
+    #[test]
+    fn overlapping_chords_are_separate() {
+        let chords = [chord(&[1, 2], false), chord(&[1, 2, 3], false)];
+        let mut state = ChordState::default();
+
+        state.update(&chords, 1, true);
+        assert_eq!(state.update(&chords, 2, true).chords, [(0, true)]);
+        assert_eq!(state.update(&chords, 3, true).chords, [(1, true)]);
+        assert_eq!(state.update(&chords, 3, false).chords, [(1, false)]);
+        assert_eq!(state.update(&chords, 1, false).chords, [(0, false)]);
+    }
+
+    #[test]
+    fn suppressing_chord_releases_its_buttons() {
+        let chords = [chord(&[1, 2], true)];
+        let mut state = ChordState::default();
+
+        let update = state.update(&chords, 1, true);
+        assert!(!update.suppressed);
+        assert!(update.released.is_empty());
+
+        // Completing the chord releases the button that was already held, and holds back the one
+        // that completed it.
+        let update = state.update(&chords, 2, true);
+        assert!(update.suppressed);
+        assert_eq!(update.released, [1]);
+
+        // Neither is released again when let go, since they were never pressed as far as their own
+        // mappings know.
+        assert!(state.update(&chords, 1, false).suppressed);
+        assert!(state.update(&chords, 2, false).suppressed);
+
+        // Once let go, they're pressed on their own as usual.
+        assert!(!state.update(&chords, 1, true).suppressed);
+    }
+
+    #[test]
+    fn suppressed_button_stays_released_until_let_go() {
+        let chords = [chord(&[1, 2], true)];
+        let mut state = ChordState::default();
+
+        state.update(&chords, 1, true);
+        state.update(&chords, 2, true);
+
+        // Releasing the other button ends the chord, but the one that's still held isn't pressed
+        // again.
+        let update = state.update(&chords, 2, false);
+        assert_eq!(update.chords, [(0, false)]);
+        assert!(update.suppressed);
+        assert!(state.update(&chords, 1, true).suppressed);
+    }
+
+    #[test]
+    fn disconnecting_releases_held_chords() {
+        let chords = [chord(&[1, 2], false), chord(&[3, 4], false)];
+        let mut state = ChordState::default();
+
+        for button in [1, 2, 3] {
+            state.update(&chords, button, true);
+        }
+        assert_eq!(state.release().collect::<Vec<_>>(), [0]);
+
+        // Reconnecting starts over, with nothing held.
+        let mut state = ChordState::default();
+        assert_eq!(state.update(&chords, 2, true).chords, []);
+        assert_eq!(state.update(&chords, 4, true).chords, []);
+    }
+
+    #[test]
+    fn disconnecting_with_nothing_held_releases_nothing() {
+        let chords = [chord(&[1, 2], true)];
+        let mut state = ChordState::default();
+
+        state.update(&chords, 1, true);
+        state.update(&chords, 2, true);
+        state.update(&chords, 2, false);
+        assert_eq!(state.release().count(), 0);
+    }
+
+    #[test]
+    fn axis_scaling_clamps_to_its_range() {
+        let scaling = AxisScaling {
+            range: [-1.0, 1.0],
+            invert: false,
+        };
+
+        assert_eq!(scaling.apply(-1.0), 0.0);
+        assert_eq!(scaling.apply(0.0), 0.5);
+        assert_eq!(scaling.apply(1.0), 1.0);
+        assert_eq!(scaling.apply(-2.0), 0.0);
+        assert_eq!(scaling.apply(2.0), 1.0);
+
+        let inverted = AxisScaling {
+            invert: true,
+            ..scaling
+        };
+        assert_eq!(inverted.apply(-2.0), 1.0);
+        assert_eq!(inverted.apply(2.0), 0.0);
+    }
+
+    #[test]
+    fn no_filter_without_smoothing_or_threshold() {
+        assert!(filter(0.0, 0.0).is_none());
+        assert!(filter(0.1, 0.0).is_some());
+        assert!(filter(0.0, 0.1).is_some());
+    }
+
+    #[test]
+    fn smoothing_follows_its_time_constant() {
+        let start = Instant::now();
+        let mut filter = filter(0.1, 0.0).unwrap();
+
+        // The first value is used as it is, since there's nothing to smooth from.
+        assert_eq!(filter.set(0.0, start), Some(0.0));
+        assert_eq!(filter.set(1.0, start), None);
+        assert!(!filter.is_settled());
+
+        // After one time constant, it has moved 1 - 1/e of the way to the target.
+        let expected = 1.0 - (-1.0f64).exp();
+        assert!(close(
+            filter.advance(start + Duration::from_millis(100)),
+            expected
+        ));
+
+        // Once it's close enough, it settles on the target exactly.
+        assert_eq!(filter.advance(start + Duration::from_secs(2)), Some(1.0));
+        assert!(filter.is_settled());
+        assert_eq!(filter.advance(start + Duration::from_secs(3)), None);
+    }
+
+    #[test]
+    fn smoothing_does_not_depend_on_update_rate() {
+        let start = Instant::now();
+        let mut coarse = filter(0.1, 0.0).unwrap();
+        let mut fine = filter(0.1, 0.0).unwrap();
+        for filter in [&mut coarse, &mut fine] {
+            filter.set(0.0, start);
+            filter.set(1.0, start);
+        }
+
+        let coarse = coarse.advance(start + Duration::from_millis(50));
+        let fine = (1..=50)
+            .filter_map(|ms| fine.advance(start + Duration::from_millis(ms)))
+            .last();
+        assert!(close(fine, coarse.unwrap()), "{fine:?} != {coarse:?}");
+    }
+
+    #[test]
+    fn ignores_changes_below_threshold() {
+        let start = Instant::now();
+        let mut filter = filter(0.0, 0.1).unwrap();
+
+        assert_eq!(filter.set(0.5, start), Some(0.5));
+        assert_eq!(filter.set(0.55, start), None);
+        assert_eq!(filter.set(0.45, start), None);
+        assert_eq!(filter.set(0.65, start), Some(0.65));
+
+        // Changes are measured from the last value that was used, not the last one that was read.
+        assert_eq!(filter.set(0.6, start), None);
+        assert_eq!(filter.set(0.7, start), None);
+        assert_eq!(filter.set(0.5, start), Some(0.5));
+    }
+
+    #[test]
+    fn ends_of_range_pass_threshold() {
+        let start = Instant::now();
+        let mut filter = filter(0.0, 0.1).unwrap();
+
+        assert_eq!(filter.set(0.95, start), Some(0.95));
+        assert_eq!(filter.set(1.0, start), Some(1.0));
+        assert_eq!(filter.set(1.0, start), None);
+        assert_eq!(filter.set(0.95, start), None);
+
+        assert_eq!(filter.set(0.05, start), Some(0.05));
+        assert_eq!(filter.set(0.0, start), Some(0.0));
+        assert_eq!(filter.set(0.0, start), None);
+    }
+}
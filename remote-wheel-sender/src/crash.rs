@@ -0,0 +1,117 @@
+use std::backtrace::Backtrace;
+use std::fmt::Write as _;
+use std::path::Path;
+use std::sync::Mutex;
+
+use time::OffsetDateTime;
+
+use crate::config::AppConfig;
+use crate::version;
+
+/// A summary of the configuration that is running, if one has been loaded.
+static CONFIG_SUMMARY: Mutex<String> = Mutex::new(String::new());
+
+/// Installs a panic hook which writes a crash report next to the log file, in addition to the usual
+/// message. On Windows, the message is also shown in a dialog if there's no console, unless
+/// `headless`.
+#[cfg_attr(not(windows), allow(unused_variables))]
+pub fn install(log_file: &Path, headless: bool) {
+    let dir = log_file.parent().map(Path::to_path_buf).unwrap_or_default();
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        default_hook(info);
+
+        let now = OffsetDateTime::now_local().unwrap_or_else(|_| OffsetDateTime::now_utc());
+        let thread = std::thread::current();
+        let backtrace = Backtrace::force_capture();
+        let report = report(
+            now,
+            thread.name().unwrap_or("<unnamed>"),
+            &info.to_string(),
+            &backtrace,
+        );
+
+        let name = now
+            .format(simplelog::format_description!(
+                "remote-wheel-sender-crash-[year][month][day]-[hour][minute][second].txt"
+            ))
+            .unwrap_or_else(|_| "remote-wheel-sender-crash.txt".into());
+        let path = dir.join(name);
+
+        let message = match std::fs::write(&path, report) {
+            Ok(()) => format!(
+                "The Sender has crashed. A crash report has been written to <{}>. Please include it \
+                when reporting this problem.",
+                path.display()
+            ),
+            Err(e) => format!(
+                "The Sender has crashed, and the crash report could not be written to <{}>: {e}",
+                path.display()
+            ),
+        };
+
+        eprintln!("{message}");
+        #[cfg(windows)]
+        if !headless && !has_console() {
+            rfd::MessageDialog::new()
+                .set_title("Remote Wheel Sender")
+                .set_description(&message)
+                .set_level(rfd::MessageLevel::Error)
+                .show();
+        }
+    }));
+}
+
+/// Notes the configuration that is running, to be included in any crash reports.
+pub fn note_config(path: &Path, config: &AppConfig) {
+    let outputs = config.enabled_outputs();
+    let summary = format!(
+        "File: <{}>\nAxes: {}\nButtons: {}\nEnabled: {}\n",
+        path.display(),
+        config.mappings.axis.len(),
+        config.mappings.button.len(),
+        if outputs.is_empty() {
+            "(none)".to_string()
+        } else {
+            outputs.join(", ")
+        },
+    );
+
+    *CONFIG_SUMMARY.lock().unwrap_or_else(|e| e.into_inner()) = summary;
+}
+
+fn report(now: OffsetDateTime, thread: &str, panic: &str, backtrace: &Backtrace) -> String {
+    let mut report = String::new();
+
+    let _ = writeln!(report, "{} crashed at {now}.", version::describe());
+    let _ = writeln!(report, "OS: {}", version::os());
+    let _ = writeln!(report, "Features: {}", version::features());
+
+    let _ = writeln!(report, "\nThread '{thread}' {panic}");
+
+    let _ = writeln!(report, "\nConfiguration:");
+    match CONFIG_SUMMARY.try_lock() {
+        Ok(summary) if !summary.is_empty() => report.push_str(&summary),
+        _ => report.push_str("(not loaded)\n"),
+    }
+
+    let _ = writeln!(report, "\nBacktrace:\n{backtrace}");
+
+    let _ = writeln!(report, "\nRecent log:");
+    for line in crate::logging::recent_lines() {
+        let _ = writeln!(report, "{line}");
+    }
+
+    report
+}
+
+/// Whether anything written to the console will be seen.
+#[cfg(windows)]
+fn has_console() -> bool {
+    #[cfg(feature = "tray")]
+    if crate::tray::console_hidden() {
+        return false;
+    }
+
+    console::Term::stderr().is_term()
+}
@@ -0,0 +1,123 @@
+mod config;
+
+use std::process::Stdio;
+use std::sync::Arc;
+use std::time::Instant;
+
+use anyhow::Result as AnyResult;
+use async_broadcast::{Receiver as BroadcastRx, RecvError as BroadcastRxErr};
+use hashbrown::HashMap;
+use smol::lock::{Semaphore, SemaphoreGuardArc};
+use smol::process::{Child, Command};
+
+pub use config::{ButtonOutputConfig, Config};
+
+use crate::config::MappingConfig;
+use crate::output::{OutputEvent, Timestamped};
+use crate::route::{MappingId, Routes};
+use crate::stats::OutputStats;
+
+pub async fn run(
+    exec: Arc<smol::Executor<'static>>,
+    config: Config,
+    mappings: Arc<MappingConfig>,
+    mut recv: BroadcastRx<Timestamped<OutputEvent>>,
+    stats: Arc<OutputStats>,
+) -> AnyResult<()> {
+    log::info!("Exec task starting...");
+
+    let pool = Arc::new(Semaphore::new(config.max_processes));
+    let mut last_run = HashMap::<(MappingId, bool), Instant>::new();
+    let outputs = Routes::buttons(&mappings, |_, b| Some(&b.output.exec));
+
+    log::info!(
+        "Exec task started. Up to {} command(s) may run at once.",
+        config.max_processes
+    );
+
+    loop {
+        let msg = recv.recv().await;
+        match msg.map(|e| stats.received(e, recv.len())) {
+            Ok(OutputEvent::UpdateButton(id, pressed)) => {
+                let Some(output) = outputs.get(&id) else {
+                    continue;
+                };
+                let command = if pressed {
+                    &output.on_press
+                } else {
+                    &output.on_release
+                };
+                let Some(command) = command else { continue };
+
+                let now = Instant::now();
+                if let Some(last) = last_run.get(&(id.clone(), pressed)) {
+                    if now.duration_since(*last) < command.cooldown {
+                        log::debug!("Not running command for {id} (cooling down).");
+                        continue;
+                    }
+                }
+
+                let Some(permit) = pool.try_acquire_arc() else {
+                    log::warn!(
+                        "Not running command for {id} (too many commands are already running)."
+                    );
+                    continue;
+                };
+
+                last_run.insert((id.clone(), pressed), now);
+
+                let value = if pressed { "true" } else { "false" };
+                let child = Command::new(&command.command)
+                    .args(command.args.iter().map(|a| a.replace("{value}", value)))
+                    .stdin(Stdio::null())
+                    .stdout(Stdio::null())
+                    .stderr(Stdio::null())
+                    .spawn();
+
+                match child {
+                    Ok(child) => {
+                        log::debug!("Started command {} for {id}.", command.command);
+                        stats.sent();
+
+                        exec.spawn(wait_child(command.command.clone(), child, permit))
+                            .detach();
+                    }
+
+                    Err(e) => {
+                        log::warn!("Failed to run command {} for {id}: {e}", command.command);
+                        stats.send_failed();
+                    }
+                }
+            }
+
+            Ok(_) => {}
+
+            Err(BroadcastRxErr::Overflowed(n)) => {
+                log::warn!("Exec task missed {n} update(s)!");
+                stats.missed(n);
+            }
+
+            Err(BroadcastRxErr::Closed) => {
+                log::info!("Exec task stopping (no inputs remaining)...");
+                break;
+            }
+        }
+    }
+
+    log::info!("Exec task stopped.");
+    Ok(())
+}
+
+pub fn has_commands(mappings: &MappingConfig) -> bool {
+    mappings.button.values().any(|b| !b.output.exec.is_empty())
+}
+
+async fn wait_child(name: String, mut child: Child, permit: SemaphoreGuardArc) {
+    match child.status().await {
+        Ok(status) if status.success() => log::info!("Command {name} finished ({status})."),
+        Ok(status) => log::warn!("Command {name} failed ({status})."),
+        Err(e) => log::warn!("Failed to wait for command {name}: {e}"),
+    }
+
+    drop(permit);
+}
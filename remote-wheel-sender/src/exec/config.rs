@@ -0,0 +1,72 @@
+use std::time::Duration;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::check::Report;
+
+#[derive(Clone, Debug, Deserialize, JsonSchema, Serialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Config {
+    pub(super) max_processes: usize,
+}
+
+#[derive(Debug, Default, Deserialize, JsonSchema, Serialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct ButtonOutputConfig {
+    pub(super) on_press: Option<CommandConfig>,
+    pub(super) on_release: Option<CommandConfig>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema, Serialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct CommandConfig {
+    pub(super) command: String,
+
+    #[serde(default)]
+    pub(super) args: Vec<String>,
+
+    #[serde(
+        default = "cooldown_default",
+        deserialize_with = "parse_seconds",
+        serialize_with = "write_seconds"
+    )]
+    #[schemars(schema_with = "crate::units::seconds_schema")]
+    pub(super) cooldown: Duration,
+}
+
+impl Config {
+    pub fn validate(&self, report: &mut Report) {
+        if self.max_processes == 0 {
+            report.error(
+                "exec.max-processes",
+                "At least one command must be allowed to run at once",
+            );
+        }
+    }
+}
+
+impl ButtonOutputConfig {
+    pub fn is_empty(&self) -> bool {
+        self.on_press.is_none() && self.on_release.is_none()
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config { max_processes: 4 }
+    }
+}
+
+fn cooldown_default() -> Duration {
+    Duration::from_secs(1)
+}
+
+fn parse_seconds<'de, D: Deserializer<'de>>(d: D) -> Result<Duration, D::Error> {
+    let secs = crate::units::seconds(d)?;
+    Duration::try_from_secs_f64(secs).map_err(serde::de::Error::custom)
+}
+
+fn write_seconds<S: Serializer>(duration: &Duration, s: S) -> Result<S::Ok, S::Error> {
+    duration.as_secs_f64().serialize(s)
+}
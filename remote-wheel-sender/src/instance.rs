@@ -0,0 +1,135 @@
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+
+use anyhow::{bail, Context as _, Result as AnyResult};
+use time::OffsetDateTime;
+
+/// The name of the file that describes the running instance, in the temporary directory.
+const INFO_NAME: &str = "remote-wheel-sender.lock";
+
+/// Marks this as the running instance for as long as it's kept.
+pub struct Instance {
+    _lock: sys::Lock,
+    _info: File,
+}
+
+/// Claims this as the only running instance of the Sender. If another instance is already running,
+/// the error describes it.
+pub fn claim() -> AnyResult<Instance> {
+    let path = std::env::temp_dir().join(INFO_NAME);
+    let Some((lock, mut info)) =
+        sys::Lock::try_acquire(&path).context("Failed to check for a running instance")?
+    else {
+        bail!(
+            "Another instance of the Sender is already running{}. Close it before starting another, \
+            or start with --force to run more than one instance at once.",
+            describe(&path)
+        );
+    };
+
+    let now = OffsetDateTime::now_local().unwrap_or_else(|_| OffsetDateTime::now_utc());
+    let started = now
+        .format(simplelog::format_description!(
+            "[year]-[month]-[day] [hour]:[minute]:[second]"
+        ))
+        .unwrap_or_default();
+
+    // The details are only used to describe this instance to others, so failing to write them is
+    // no reason not to run.
+    let _ = info
+        .set_len(0)
+        .and_then(|()| write!(info, "{}\n{started}\n", std::process::id()))
+        .and_then(|()| info.flush());
+
+    Ok(Instance {
+        _lock: lock,
+        _info: info,
+    })
+}
+
+/// Describes the running instance from its details, if they can be read.
+fn describe(path: &Path) -> String {
+    let Ok(text) = std::fs::read_to_string(path) else {
+        return String::new();
+    };
+
+    let mut lines = text.lines();
+    match (lines.next(), lines.next()) {
+        (Some(pid), Some(started)) => format!(" (PID {pid}, started {started})"),
+        (Some(pid), None) => format!(" (PID {pid})"),
+        _ => String::new(),
+    }
+}
+
+#[cfg(windows)]
+mod sys {
+    use std::fs::File;
+    use std::io::Result as IoResult;
+    use std::path::Path;
+
+    use windows_sys::Win32::Foundation::{CloseHandle, GetLastError, ERROR_ALREADY_EXISTS, HANDLE};
+    use windows_sys::Win32::System::Threading::CreateMutexW;
+
+    const MUTEX_NAME: &str = "Local\\RemoteWheelSender";
+
+    /// A named mutex, which exists for as long as any instance has it open.
+    pub struct Lock(HANDLE);
+
+    impl Lock {
+        pub fn try_acquire(info_path: &Path) -> IoResult<Option<(Lock, File)>> {
+            let name = MUTEX_NAME
+                .encode_utf16()
+                .chain(std::iter::once(0))
+                .collect::<Vec<_>>();
+
+            let handle = unsafe { CreateMutexW(std::ptr::null(), 0, name.as_ptr()) };
+            if handle == 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+
+            let lock = Lock(handle);
+            if unsafe { GetLastError() } == ERROR_ALREADY_EXISTS {
+                return Ok(None);
+            }
+
+            Ok(Some((lock, File::create(info_path)?)))
+        }
+    }
+
+    impl Drop for Lock {
+        fn drop(&mut self) {
+            unsafe {
+                CloseHandle(self.0);
+            }
+        }
+    }
+}
+
+#[cfg(not(windows))]
+mod sys {
+    use std::fs::{File, TryLockError};
+    use std::io::Result as IoResult;
+    use std::path::Path;
+
+    /// The lock is held on the file itself, so it is released even if the process dies.
+    pub struct Lock;
+
+    impl Lock {
+        pub fn try_acquire(info_path: &Path) -> IoResult<Option<(Lock, File)>> {
+            // Truncating here would erase the details of a running instance.
+            let file = File::options()
+                .read(true)
+                .write(true)
+                .create(true)
+                .truncate(false)
+                .open(info_path)?;
+
+            match file.try_lock() {
+                Ok(()) => Ok(Some((Lock, file))),
+                Err(TryLockError::WouldBlock) => Ok(None),
+                Err(TryLockError::Error(e)) => Err(e),
+            }
+        }
+    }
+}
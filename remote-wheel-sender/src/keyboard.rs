@@ -0,0 +1,121 @@
+mod config;
+mod sys;
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Result as AnyResult;
+use async_broadcast::Sender as BroadcastTx;
+use futures::prelude::*;
+use smol::channel::Receiver as ChannelRx;
+
+pub use config::ButtonInputConfig;
+
+use crate::button::ButtonStates;
+use crate::config::MappingConfig;
+use crate::keys::KeyCombo;
+use crate::output::{OutputEvent, Timestamped};
+use crate::route::MappingId;
+use crate::stats;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+struct Hotkey {
+    id: MappingId,
+    combo: KeyCombo,
+    pressed: bool,
+}
+
+pub async fn run(
+    mappings: Arc<MappingConfig>,
+    output_tx: BroadcastTx<Timestamped<OutputEvent>>,
+    cancel_rx: ChannelRx<()>,
+) -> AnyResult<()> {
+    log::info!("Keyboard input task starting...");
+
+    let mut hotkeys = Vec::new();
+    for (id, buttons) in mappings.buttons() {
+        for button in &buttons.input {
+            if let crate::config::ButtonInputConfig::Keyboard(c) = button {
+                hotkeys.push(Hotkey {
+                    id: id.clone(),
+                    combo: c.key.clone(),
+                    pressed: false,
+                });
+            }
+        }
+    }
+
+    if hotkeys.is_empty() {
+        log::info!("No hotkeys configured. Keyboard input task not starting.");
+        return Ok(());
+    }
+
+    if !sys::SUPPORTED {
+        log::warn!("Keyboard input task stopped (hotkeys can only be read on Windows).");
+        return Ok(());
+    }
+
+    // Polling rather than registering hotkeys with the system means that releases can be seen too,
+    // and that the keys still reach whichever application has focus.
+    let mut poll_timer = smol::Timer::interval(POLL_INTERVAL);
+    let mut buttons = ButtonStates::new(mappings.clone(), output_tx.clone());
+    let input_stats = stats::InputStats::new("Keyboard");
+    log::info!("Keyboard input task started.");
+
+    'outer: loop {
+        futures::select_biased! {
+            _ = cancel_rx.recv().fuse() => {
+                log::info!("Keyboard input task stopping (shutdown)...");
+                break;
+            },
+
+            _ = poll_timer.next().fuse() => {},
+        }
+
+        let mut flush = false;
+        for hotkey in &mut hotkeys {
+            let pressed = is_held(&hotkey.combo);
+            if pressed == hotkey.pressed {
+                continue;
+            }
+
+            log::debug!(
+                "Hotkey {} {}.",
+                hotkey.combo,
+                if pressed { "pressed" } else { "released" }
+            );
+            hotkey.pressed = pressed;
+
+            let Some(state) = buttons.update(&hotkey.id, pressed) else {
+                continue;
+            };
+
+            let event = OutputEvent::UpdateButton(hotkey.id.clone(), state);
+            input_stats.event(&event);
+            if output_tx.broadcast(event.into()).await.is_err() {
+                log::info!("Keyboard input task stopping (no remaining outputs).");
+                break 'outer;
+            }
+
+            flush = true;
+        }
+
+        if flush
+            && output_tx
+                .broadcast(OutputEvent::Flush.into())
+                .await
+                .is_err()
+        {
+            log::info!("Keyboard input task stopping (no remaining outputs).");
+            break;
+        }
+    }
+
+    log::info!("Keyboard input task stopped.");
+    Ok(())
+}
+
+fn is_held(combo: &KeyCombo) -> bool {
+    combo.modifiers.iter().all(|&k| sys::is_down(k)) && sys::is_down(combo.key)
+}
@@ -0,0 +1,27 @@
+use crate::keys::Key;
+
+#[cfg(windows)]
+pub const SUPPORTED: bool = true;
+
+#[cfg(not(windows))]
+pub const SUPPORTED: bool = false;
+
+/// Returns whether a key is currently held, regardless of which application has focus.
+#[cfg(windows)]
+pub fn is_down(key: Key) -> bool {
+    use windows_sys::Win32::UI::Input::KeyboardAndMouse::GetAsyncKeyState;
+
+    // The most significant bit is set while the key is held.
+    let is_down = |code: u16| unsafe { GetAsyncKeyState(code as i32) } < 0;
+
+    // There is no generic code for the Windows key, so check the right one too.
+    match key {
+        Key::WIN => is_down(key.code()) || is_down(key.code() + 1),
+        _ => is_down(key.code()),
+    }
+}
+
+#[cfg(not(windows))]
+pub fn is_down(_: Key) -> bool {
+    false
+}
@@ -0,0 +1,150 @@
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::str::FromStr;
+
+use anyhow::{bail, ensure, Error as AnyError, Result as AnyResult};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// A keyboard key, identified by its Windows virtual-key code.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct Key(u16);
+
+/// A key with any number of modifiers, written as e.g. `Ctrl+Shift+K`.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct KeyCombo {
+    pub modifiers: Vec<Key>,
+    pub key: Key,
+    name: String,
+}
+
+impl Key {
+    pub const SHIFT: Key = Key(0x10);
+    pub const CTRL: Key = Key(0x11);
+    pub const ALT: Key = Key(0x12);
+    pub const WIN: Key = Key(0x5b);
+
+    #[cfg_attr(not(windows), allow(unused))]
+    pub fn code(self) -> u16 {
+        self.0
+    }
+
+    #[cfg_attr(not(all(windows, feature = "keystroke")), allow(unused))]
+    pub fn is_extended(self) -> bool {
+        matches!(self.0, 0x21..=0x28 | 0x2d | 0x2e | 0x5b..=0x5d | 0x6f | 0x90)
+    }
+
+    pub fn is_modifier(self) -> bool {
+        matches!(self, Key::SHIFT | Key::CTRL | Key::ALT | Key::WIN)
+    }
+}
+
+impl FromStr for Key {
+    type Err = AnyError;
+
+    fn from_str(s: &str) -> AnyResult<Self> {
+        let name = s.trim().to_ascii_lowercase();
+        let bytes = name.as_bytes();
+
+        let code = match name.as_str() {
+            _ if bytes.len() == 1 && bytes[0].is_ascii_alphanumeric() => {
+                bytes[0].to_ascii_uppercase() as u16
+            }
+
+            _ if name.len() > 1 && name.starts_with('f') => match name[1..].parse::<u16>() {
+                Ok(n @ 1..=24) => 0x6f + n,
+                _ => bail!("Unknown key name {s:?}"),
+            },
+
+            _ if name.starts_with("numpad") => match name[6..].parse::<u16>() {
+                Ok(n @ 0..=9) => 0x60 + n,
+                _ => bail!("Unknown key name {s:?}"),
+            },
+
+            "shift" => 0x10,
+            "ctrl" | "control" => 0x11,
+            "alt" => 0x12,
+            "win" | "windows" | "super" => 0x5b,
+
+            "backspace" => 0x08,
+            "tab" => 0x09,
+            "enter" | "return" => 0x0d,
+            "pause" => 0x13,
+            "capslock" => 0x14,
+            "esc" | "escape" => 0x1b,
+            "space" => 0x20,
+            "pageup" => 0x21,
+            "pagedown" => 0x22,
+            "end" => 0x23,
+            "home" => 0x24,
+            "left" => 0x25,
+            "up" => 0x26,
+            "right" => 0x27,
+            "down" => 0x28,
+            "printscreen" => 0x2c,
+            "insert" => 0x2d,
+            "delete" => 0x2e,
+            "numlock" => 0x90,
+            "scrolllock" => 0x91,
+
+            _ => bail!("Unknown key name {s:?}"),
+        };
+
+        Ok(Key(code))
+    }
+}
+
+impl Display for KeyCombo {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        f.write_str(&self.name)
+    }
+}
+
+impl FromStr for KeyCombo {
+    type Err = AnyError;
+
+    fn from_str(s: &str) -> AnyResult<Self> {
+        let mut parts = s
+            .split('+')
+            .map(str::parse::<Key>)
+            .collect::<AnyResult<Vec<_>>>()?;
+        let key = parts.pop().expect("split always yields at least one part");
+
+        for modifier in &parts {
+            ensure!(
+                modifier.is_modifier(),
+                "Only Ctrl, Shift, Alt, and Win may be used as modifiers in {s:?}"
+            );
+        }
+
+        Ok(KeyCombo {
+            modifiers: parts,
+            key,
+            name: s.to_owned(),
+        })
+    }
+}
+
+impl JsonSchema for KeyCombo {
+    fn schema_name() -> String {
+        "KeyCombo".into()
+    }
+
+    fn json_schema(gen: &mut schemars::gen::SchemaGenerator) -> schemars::schema::Schema {
+        String::json_schema(gen)
+    }
+}
+
+impl TryFrom<String> for KeyCombo {
+    type Error = AnyError;
+
+    fn try_from(s: String) -> AnyResult<Self> {
+        s.parse()
+    }
+}
+
+impl From<KeyCombo> for String {
+    fn from(combo: KeyCombo) -> String {
+        combo.name
+    }
+}
@@ -0,0 +1,94 @@
+mod config;
+mod sys;
+
+use std::sync::Arc;
+
+use anyhow::Result as AnyResult;
+use async_broadcast::{Receiver as BroadcastRx, RecvError as BroadcastRxErr};
+
+pub use config::ButtonOutputConfig;
+
+use crate::config::MappingConfig;
+use crate::keys::{Key, KeyCombo};
+use crate::output::{OutputEvent, Timestamped};
+use crate::route::Routes;
+use crate::stats::OutputStats;
+
+use self::config::Mode;
+
+pub async fn run(
+    mappings: Arc<MappingConfig>,
+    mut recv: BroadcastRx<Timestamped<OutputEvent>>,
+    stats: Arc<OutputStats>,
+) -> AnyResult<()> {
+    log::info!("Keystroke task starting...");
+
+    if !sys::SUPPORTED {
+        log::warn!("Keystroke task stopped (keystrokes can only be sent on Windows).");
+        return Ok(());
+    }
+
+    let outputs = Routes::buttons(&mappings, |_, b| {
+        let output = &b.output.keystroke;
+        output.key.as_ref().map(|combo| (combo, output.mode))
+    });
+    log::info!("Keystroke task started.");
+
+    loop {
+        let msg = recv.recv().await;
+        match msg.map(|e| stats.received(e, recv.len())) {
+            Ok(OutputEvent::UpdateButton(id, pressed)) => {
+                let Some(&(combo, mode)) = outputs.get(&id) else {
+                    continue;
+                };
+
+                let keys = match (mode, pressed) {
+                    (Mode::Tap, true) => press(combo).chain(release(combo)).collect(),
+                    (Mode::Tap, false) => continue,
+                    (Mode::Hold, true) => press(combo).collect(),
+                    (Mode::Hold, false) => release(combo).collect::<Vec<_>>(),
+                };
+
+                match sys::send_keys(&keys) {
+                    Ok(()) => stats.sent(),
+                    Err(e) => {
+                        log::warn!("Failed to send keystroke {combo} for {id}: {e:#}");
+                        stats.send_failed();
+                    }
+                }
+            }
+
+            Ok(_) => {}
+
+            Err(BroadcastRxErr::Overflowed(n)) => {
+                log::warn!("Keystroke task missed {n} update(s)!");
+                stats.missed(n);
+            }
+
+            Err(BroadcastRxErr::Closed) => {
+                log::info!("Keystroke task stopping (no inputs remaining)...");
+                break;
+            }
+        }
+    }
+
+    log::info!("Keystroke task stopped.");
+    Ok(())
+}
+
+pub fn has_keystrokes(mappings: &MappingConfig) -> bool {
+    mappings
+        .button
+        .values()
+        .any(|b| b.output.keystroke.key.is_some())
+}
+
+fn press(combo: &KeyCombo) -> impl '_ + Iterator<Item = (Key, bool)> {
+    let keys = combo.modifiers.iter().chain(std::iter::once(&combo.key));
+    keys.map(|&k| (k, true))
+}
+
+fn release(combo: &KeyCombo) -> impl '_ + Iterator<Item = (Key, bool)> {
+    let keys = std::iter::once(&combo.key).chain(combo.modifiers.iter().rev());
+    keys.map(|&k| (k, false))
+}
@@ -0,0 +1,67 @@
+use anyhow::Result as AnyResult;
+
+use crate::keys::Key;
+
+#[cfg(windows)]
+pub const SUPPORTED: bool = true;
+
+#[cfg(not(windows))]
+pub const SUPPORTED: bool = false;
+
+/// Sends a sequence of key presses (`true`) and releases (`false`) as a single batch.
+#[cfg(windows)]
+pub fn send_keys(keys: &[(Key, bool)]) -> AnyResult<()> {
+    use anyhow::ensure;
+    use windows_sys::Win32::UI::Input::KeyboardAndMouse::{
+        SendInput, INPUT, INPUT_0, INPUT_KEYBOARD, KEYBDINPUT, KEYEVENTF_EXTENDEDKEY,
+        KEYEVENTF_KEYUP,
+    };
+
+    let inputs = keys
+        .iter()
+        .map(|&(key, down)| {
+            let mut flags = 0;
+            if !down {
+                flags |= KEYEVENTF_KEYUP;
+            }
+            if key.is_extended() {
+                flags |= KEYEVENTF_EXTENDEDKEY;
+            }
+
+            INPUT {
+                r#type: INPUT_KEYBOARD,
+                Anonymous: INPUT_0 {
+                    ki: KEYBDINPUT {
+                        wVk: key.code(),
+                        wScan: 0,
+                        dwFlags: flags,
+                        time: 0,
+                        dwExtraInfo: 0,
+                    },
+                },
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let sent = unsafe {
+        SendInput(
+            inputs.len() as u32,
+            inputs.as_ptr(),
+            std::mem::size_of::<INPUT>() as i32,
+        )
+    };
+
+    ensure!(
+        sent as usize == inputs.len(),
+        "Only {sent} of {} key event(s) were sent ({})",
+        inputs.len(),
+        std::io::Error::last_os_error(),
+    );
+
+    Ok(())
+}
+
+#[cfg(not(windows))]
+pub fn send_keys(_: &[(Key, bool)]) -> AnyResult<()> {
+    anyhow::bail!("Keystroke output is only supported on Windows")
+}
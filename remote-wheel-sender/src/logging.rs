@@ -0,0 +1,249 @@
+mod recent;
+mod rotate;
+
+use std::sync::RwLock;
+
+use anyhow::{anyhow, bail, Result as AnyResult};
+use hashbrown::HashMap;
+use log::{LevelFilter, Log, Metadata, Record};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use simplelog::SharedLogger;
+
+use crate::check::Report;
+
+pub use recent::{recent_lines, RecentLines};
+pub use rotate::RotatingFile;
+
+/// The modules that a log level may be set for.
+pub const MODULES: &[&str] = &[
+    "advanced",
+    "audio",
+    "awake",
+    "check",
+    "config",
+    "controller",
+    "exec",
+    "keyboard",
+    "keystroke",
+    "midi",
+    "openvr",
+    "osc",
+    "session",
+    "stats",
+    "supervisor",
+    "tray",
+    "vjoy",
+    "vmc",
+];
+
+#[derive(Clone, Debug, Deserialize, JsonSchema, Serialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct LogConfig {
+    level: Option<String>,
+    /// The level for the log file, instead of `level`.
+    file_level: Option<String>,
+    max_size: u64,
+    max_files: usize,
+
+    #[serde(flatten)]
+    #[schemars(with = "std::collections::HashMap<String, String>")]
+    modules: HashMap<String, String>,
+}
+
+/// Wraps a logger so that it follows the levels set by [`LogConfig::apply`], falling back to its own
+/// default level for anything not configured.
+pub struct Filtered {
+    inner: Box<dyn SharedLogger>,
+    default: LevelFilter,
+    /// Whether this writes to the log file, and so follows `file-level`.
+    file: bool,
+}
+
+struct Filters {
+    level: Option<LevelFilter>,
+    file_level: Option<LevelFilter>,
+    modules: Vec<(String, LevelFilter)>,
+}
+
+static FILTERS: RwLock<Filters> = RwLock::new(Filters {
+    level: None,
+    file_level: None,
+    modules: Vec::new(),
+});
+
+impl LogConfig {
+    /// Sets the level for a module, or the global level if `module` is `level` (or the log file's, if
+    /// it's `file-level`).
+    pub fn set(&mut self, module: &str, level: &str) -> AnyResult<()> {
+        parse(module, level)?;
+
+        if module == "level" {
+            self.level = Some(level.to_string());
+        } else if module == "file-level" {
+            self.file_level = Some(level.to_string());
+        } else {
+            self.modules.insert(module.to_string(), level.to_string());
+        }
+
+        Ok(())
+    }
+
+    /// Overrides these levels with any that are set in `other`.
+    pub fn merge(&mut self, other: &LogConfig) {
+        if other.level.is_some() {
+            self.level = other.level.clone();
+        }
+
+        if other.file_level.is_some() {
+            self.file_level = other.file_level.clone();
+        }
+
+        self.modules
+            .extend(other.modules.iter().map(|(m, l)| (m.clone(), l.clone())));
+    }
+
+    pub fn validate(&self, report: &mut Report) {
+        if let Some(ref level) = self.level {
+            if let Err(e) = parse_level(level) {
+                report.error("log.level", e);
+            }
+        }
+
+        if let Some(ref level) = self.file_level {
+            if let Err(e) = parse_level(level) {
+                report.error("log.file-level", e);
+            }
+        }
+
+        for (module, level) in &self.modules {
+            if let Err(e) = parse(module, level) {
+                report.error(format!("log.{module}"), e);
+            }
+        }
+
+        if self.max_files == 0 {
+            report.error("log.max-files", "At least one older log file must be kept");
+        }
+    }
+
+    /// Applies these levels to all [`Filtered`] loggers, and the size limits to the log file.
+    pub fn apply(&self) {
+        self.apply_levels();
+        rotate::set_limits(self.max_size.saturating_mul(1024 * 1024), self.max_files);
+    }
+
+    /// Applies only these levels to all [`Filtered`] loggers, e.g. before the configuration file
+    /// has been read. Invalid entries are skipped, as they should have been reported by validation
+    /// already.
+    pub fn apply_levels(&self) {
+        let level = self.level.as_deref().and_then(|l| parse_level(l).ok());
+        let file_level = self.file_level.as_deref().and_then(|l| parse_level(l).ok());
+        let modules = self
+            .modules
+            .iter()
+            .filter_map(|(m, l)| Some((m.clone(), parse(m, l).ok()?)))
+            .collect();
+
+        *FILTERS.write().unwrap() = Filters {
+            level,
+            file_level,
+            modules,
+        };
+    }
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        LogConfig {
+            level: None,
+            file_level: None,
+            max_size: 10,
+            max_files: 5,
+
+            modules: HashMap::new(),
+        }
+    }
+}
+
+impl Filtered {
+    pub fn new(inner: Box<dyn SharedLogger>, default: LevelFilter) -> Box<Filtered> {
+        Box::new(Filtered {
+            inner,
+            default,
+            file: false,
+        })
+    }
+
+    /// Wraps a logger that writes to the log file, which follows `file-level` over `level`.
+    pub fn file(inner: Box<dyn SharedLogger>, default: LevelFilter) -> Box<Filtered> {
+        Box::new(Filtered {
+            inner,
+            default,
+            file: true,
+        })
+    }
+
+    fn max_level(&self, target: &str) -> LevelFilter {
+        // Targets are module paths, e.g. remote_wheel_sender::vmc::avatar.
+        let module = target.split("::").nth(1);
+        let filters = FILTERS.read().unwrap();
+
+        filters
+            .modules
+            .iter()
+            .find(|(m, _)| Some(m.as_str()) == module)
+            .map(|&(_, l)| l)
+            .or(filters.file_level.filter(|_| self.file))
+            .or(filters.level)
+            .unwrap_or(self.default)
+    }
+}
+
+impl Log for Filtered {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.max_level(metadata.target()) && self.inner.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        if self.enabled(record.metadata()) {
+            self.inner.log(record);
+        }
+    }
+
+    fn flush(&self) {
+        self.inner.flush();
+    }
+}
+
+impl SharedLogger for Filtered {
+    fn level(&self) -> LevelFilter {
+        // Levels may be changed after the logger is installed, so let everything through to here.
+        LevelFilter::Trace
+    }
+
+    fn config(&self) -> Option<&simplelog::Config> {
+        self.inner.config()
+    }
+
+    fn as_log(self: Box<Self>) -> Box<dyn Log> {
+        self
+    }
+}
+
+fn parse(module: &str, level: &str) -> AnyResult<LevelFilter> {
+    if !["level", "file-level"].contains(&module) && !MODULES.contains(&module) {
+        bail!(
+            "Unknown module {module:?} (known modules: {})",
+            MODULES.join(", ")
+        );
+    }
+
+    parse_level(level)
+}
+
+fn parse_level(level: &str) -> AnyResult<LevelFilter> {
+    level.parse().map_err(|_| {
+        anyhow!("Unknown log level {level:?} (expected off, error, warn, info, debug, or trace)")
+    })
+}
@@ -0,0 +1,50 @@
+use std::collections::VecDeque;
+use std::io::{Result as IoResult, Write};
+use std::sync::{Mutex, TryLockError};
+
+/// How many lines to keep.
+const MAX_LINES: usize = 200;
+
+static LINES: Mutex<VecDeque<String>> = Mutex::new(VecDeque::new());
+
+/// A log "file" that keeps only the most recent lines in memory, so that they can be included in
+/// crash reports.
+#[derive(Default)]
+pub struct RecentLines {
+    partial: Vec<u8>,
+}
+
+impl Write for RecentLines {
+    fn write(&mut self, buf: &[u8]) -> IoResult<usize> {
+        self.partial.extend_from_slice(buf);
+
+        while let Some(end) = self.partial.iter().position(|&b| b == b'\n') {
+            let line = String::from_utf8_lossy(&self.partial[..end]).into_owned();
+            self.partial.drain(..=end);
+
+            let mut lines = LINES.lock().unwrap_or_else(|e| e.into_inner());
+            if lines.len() >= MAX_LINES {
+                lines.pop_front();
+            }
+            lines.push_back(line);
+        }
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> IoResult<()> {
+        Ok(())
+    }
+}
+
+/// The most recent lines that have been logged, oldest first. Returns nothing if they're in the
+/// middle of being updated, since this may be called while panicking.
+pub fn recent_lines() -> Vec<String> {
+    let lines = match LINES.try_lock() {
+        Ok(lines) => lines,
+        Err(TryLockError::Poisoned(e)) => e.into_inner(),
+        Err(TryLockError::WouldBlock) => return Vec::new(),
+    };
+
+    lines.iter().cloned().collect()
+}
@@ -0,0 +1,192 @@
+use std::fs::File;
+use std::io::{ErrorKind, Result as IoResult, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+
+/// The size at which the log file is rotated. There's no limit until the configuration is applied,
+/// since the file is opened before it's read, and it shouldn't be rotated by limits that aren't the
+/// configured ones.
+static MAX_SIZE: AtomicU64 = AtomicU64::new(0);
+static MAX_FILES: AtomicUsize = AtomicUsize::new(1);
+
+/// A log file that is rolled over to numbered backups (`.1`, `.2`, ...) once it grows too large.
+pub struct RotatingFile {
+    path: PathBuf,
+    file: File,
+    size: u64,
+    line_start: bool,
+}
+
+impl RotatingFile {
+    /// Opens a log file, appending to it rather than discarding what was logged previously.
+    pub fn open(path: impl Into<PathBuf>) -> IoResult<RotatingFile> {
+        let path = path.into();
+        let file = File::options().create(true).append(true).open(&path)?;
+        let size = file.metadata()?.len();
+
+        let mut rotating = RotatingFile {
+            path,
+            file,
+            size,
+            line_start: true,
+        };
+        let separator = format!(
+            "----- Remote Wheel Sender v{} session started -----\n",
+            env!("CARGO_PKG_VERSION")
+        );
+
+        if rotating.size > 0 {
+            rotating.write_all(b"\n")?;
+        }
+
+        rotating.write_all(separator.as_bytes())?;
+        Ok(rotating)
+    }
+
+    fn rotate(&mut self) -> IoResult<()> {
+        self.file.flush()?;
+
+        // At least one is always kept, so that the log file is never simply discarded.
+        let max_files = MAX_FILES.load(Ordering::Relaxed).max(1);
+        ignore_missing(std::fs::remove_file(backup_path(&self.path, max_files)))?;
+        for i in (1..max_files).rev() {
+            ignore_missing(std::fs::rename(
+                backup_path(&self.path, i),
+                backup_path(&self.path, i + 1),
+            ))?;
+        }
+
+        std::fs::rename(&self.path, backup_path(&self.path, 1))?;
+
+        self.file = File::options()
+            .create(true)
+            .write(true)
+            .truncate(true)
+            .open(&self.path)?;
+        self.size = 0;
+        Ok(())
+    }
+}
+
+impl Write for RotatingFile {
+    fn write(&mut self, buf: &[u8]) -> IoResult<usize> {
+        // Records may be written in several pieces, so only rotate between lines. A size of 0 means
+        // there is no limit.
+        let max_size = MAX_SIZE.load(Ordering::Relaxed);
+        if max_size > 0 && self.line_start && self.size + buf.len() as u64 > max_size {
+            self.rotate()?;
+        }
+
+        let written = self.file.write(buf)?;
+        self.size += written as u64;
+
+        if written > 0 {
+            self.line_start = buf[written - 1] == b'\n';
+        }
+
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> IoResult<()> {
+        self.file.flush()
+    }
+}
+
+pub(super) fn set_limits(max_size: u64, max_files: usize) {
+    MAX_SIZE.store(max_size, Ordering::Relaxed);
+    MAX_FILES.store(max_files, Ordering::Relaxed);
+}
+
+fn backup_path(path: &Path, index: usize) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".{index}"));
+    PathBuf::from(name)
+}
+
+fn ignore_missing(result: IoResult<()>) -> IoResult<()> {
+    match result {
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(()),
+        result => result,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+
+    /// The limits are shared, so tests that set them can't run at the same time.
+    static LIMITS: Mutex<()> = Mutex::new(());
+
+    /// A fresh directory for a test's log files.
+    fn dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("remote-wheel-rotate-{}-{name}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn size(path: &Path) -> Option<u64> {
+        Some(std::fs::metadata(path).ok()?.len())
+    }
+
+    #[test]
+    fn rotates_when_full() {
+        let _limits = LIMITS.lock().unwrap_or_else(|e| e.into_inner());
+        let dir = dir("full");
+        let path = dir.join("test.log");
+
+        // Nothing is rotated until the limits are set.
+        set_limits(0, 1);
+        let mut file = RotatingFile::open(&path).unwrap();
+        let opened = size(&path).unwrap();
+        file.write_all(&[b'a'; 99]).unwrap();
+        file.write_all(b"\n").unwrap();
+        assert_eq!(size(&path), Some(opened + 100));
+        assert_eq!(size(&backup_path(&path, 1)), None);
+
+        // Once the file would be over the limit, it's moved aside before the line is written.
+        set_limits(200, 1);
+        file.write_all(&[b'b'; 99]).unwrap();
+        file.write_all(b"\n").unwrap();
+        assert_eq!(size(&path), Some(100));
+        assert_eq!(size(&backup_path(&path, 1)), Some(opened + 100));
+
+        // A line is never split between files, even if it's written in pieces.
+        file.write_all(&[b'c'; 50]).unwrap();
+        file.write_all(&[b'c'; 99]).unwrap();
+        file.write_all(b"\n").unwrap();
+        assert_eq!(size(&path), Some(250));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn keeps_only_max_files() {
+        let _limits = LIMITS.lock().unwrap_or_else(|e| e.into_inner());
+        let dir = dir("prune");
+        let path = dir.join("test.log");
+
+        set_limits(10, 2);
+        let mut file = RotatingFile::open(&path).unwrap();
+        for line in ["one\n", "two\n", "three\n", "four\n"] {
+            file.write_all(&[b'-'; 9]).unwrap();
+            file.write_all(b"\n").unwrap();
+            file.write_all(line.as_bytes()).unwrap();
+        }
+        file.flush().unwrap();
+
+        let read = |index| match index {
+            0 => std::fs::read_to_string(&path).ok(),
+            i => std::fs::read_to_string(backup_path(&path, i)).ok(),
+        };
+        assert_eq!(read(0).as_deref(), Some("four\n"));
+        assert_eq!(read(1).as_deref(), Some("---------\n"));
+        assert_eq!(read(2).as_deref(), Some("three\n"));
+        assert_eq!(read(3), None);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -1,26 +1,199 @@
+use std::path::{Path, PathBuf};
 use std::process::ExitCode;
 use std::sync::Arc;
 
-use anyhow::{Context as _, Result as AnyResult};
+use anyhow::{bail, Context as _, Result as AnyResult};
 use futures::prelude::*;
-use smol::channel::Receiver as ChannelRx;
+use smol::channel::{Receiver as ChannelRx, Sender as ChannelTx};
 
+mod advanced;
+mod button;
+mod check;
+mod clock;
+mod coalesce;
 mod config;
 mod controller;
+mod crash;
+mod instance;
+mod logging;
 mod output;
+mod paths;
+mod presets;
+mod press;
+mod profile;
+mod route;
+mod session;
+mod state;
+mod stats;
+mod status;
+mod supervisor;
+mod test_signal;
+mod units;
+mod version;
+
+#[cfg(feature = "audio")]
+mod audio;
+
+#[cfg(feature = "keep-awake")]
+mod awake;
+
+#[cfg(feature = "exec")]
+mod exec;
+
+#[cfg(feature = "keyboard")]
+mod keyboard;
+
+#[cfg(any(feature = "keyboard", feature = "keystroke"))]
+mod keys;
+
+#[cfg(feature = "keystroke")]
+mod keystroke;
+
+#[cfg(feature = "midi")]
+mod midi;
+
+#[cfg(feature = "openvr")]
+mod openvr;
 
 #[cfg(feature = "osc")]
 mod osc;
 
+#[cfg(any(feature = "osc", feature = "vmc"))]
+mod reachability;
+
+#[cfg(any(feature = "osc", feature = "vmc"))]
+mod rebind;
+
+#[cfg(any(feature = "osc", feature = "vmc"))]
+mod timetag;
+
+#[cfg(feature = "tray")]
+mod tray;
+
+#[cfg(feature = "update-check")]
+mod update;
+
+#[cfg(feature = "vjoy")]
+mod vjoy;
+
 #[cfg(feature = "vmc")]
 mod vmc;
 
+/// The name of the configuration file, without an extension. See [`config::find_file`].
+const CONFIG_NAME: &str = "remote-wheel-sender";
+
+const USAGE: &str =
+    "Usage: remote-wheel-sender [--check] [--config <path>] [--dump-config] [--dump-schema] [--force] [--headless] [--list-devices] [--log-level <level>] [--log <module>=<level>]... [--portable] [--test-signal <axis>=<waveform>:<frequency>[:<amplitude>]]... [--version]";
+
+/// Requests to stop or restart the tasks, from outside of them.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Control {
+    Quit,
+    #[cfg_attr(not(all(windows, feature = "tray")), allow(dead_code))]
+    Reload,
+}
+
+#[derive(Default)]
+struct Args {
+    check: bool,
+    config: Option<PathBuf>,
+    dump_config: bool,
+    dump_schema: bool,
+    force: bool,
+    headless: bool,
+    list_devices: bool,
+    log: logging::LogConfig,
+    portable: bool,
+    test_signal: test_signal::TestSignalConfig,
+    version: bool,
+}
+
 fn main() -> ExitCode {
-    init_logger().expect("Failed to initialize logging");
+    status::record_start();
+
+    let args = match parse_args() {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("{e:#}");
+            eprintln!("{USAGE}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    if args.version {
+        version::print();
+        return ExitCode::SUCCESS;
+    }
 
-    match run_sync() {
+    if args.dump_schema {
+        println!("{}", config::schema());
+        return ExitCode::SUCCESS;
+    }
+
+    // This doesn't need a configuration, since it's for finding out what to put in one.
+    if args.list_devices {
+        return match controller::list_devices() {
+            Ok(()) => ExitCode::SUCCESS,
+            Err(e) => {
+                eprintln!("error: {e:#}");
+                ExitCode::FAILURE
+            }
+        };
+    }
+
+    if let Err(e) = paths::init(args.portable, CONFIG_NAME) {
+        eprintln!("{e:#}");
+        if !args.check && !args.dump_config {
+            wait_for_key(args.headless);
+        }
+        return ExitCode::FAILURE;
+    }
+
+    if args.check {
+        return run_check(args.config.as_deref(), &args.test_signal);
+    }
+
+    if args.dump_config {
+        return run_dump_config(args.config.as_deref(), &args.log, &args.test_signal);
+    }
+
+    // This must come before anything that might conflict with another instance, such as the log
+    // file or any sockets.
+    let _instance = if args.force {
+        None
+    } else {
+        match instance::claim() {
+            Ok(i) => Some(i),
+            Err(e) => {
+                eprintln!("{e:#}");
+                wait_for_key(args.headless);
+                return ExitCode::FAILURE;
+            }
+        }
+    };
+
+    // Instances that were forced to run alongside others each need their own log file.
+    let log_file = paths::file(if args.force {
+        format!("remote-wheel-sender-{}.log", std::process::id())
+    } else {
+        "remote-wheel-sender.log".to_string()
+    });
+
+    init_logger(&args.log, &log_file, args.headless).expect("Failed to initialize logging");
+    crash::install(&log_file, args.headless);
+    version::log_startup();
+    paths::log_startup();
+    if args.force {
+        log::info!("Skipped the check for other running instances (--force).");
+    }
+    if args.headless {
+        log::info!("Running headless (--headless). Nothing will be asked on the console.");
+    }
+
+    match run_sync(args.config, args.log, args.test_signal, args.headless) {
         Ok(()) => {
             log::debug!("Clean exit.");
+            log::logger().flush();
             ExitCode::SUCCESS
         }
 
@@ -28,20 +201,281 @@ fn main() -> ExitCode {
             log::error!("An error has occurred: {:#}", e);
             log::logger().flush();
 
-            eprintln!("Press any key to exit.");
-            let _ = console::Term::stdout().read_key();
+            #[cfg(all(windows, feature = "tray"))]
+            if !args.headless && tray::console_hidden() {
+                rfd::MessageDialog::new()
+                    .set_title("Remote Wheel Sender")
+                    .set_description(&format!("An error has occurred: {e:#}"))
+                    .set_level(rfd::MessageLevel::Error)
+                    .show();
+                return ExitCode::FAILURE;
+            }
+
+            wait_for_key(args.headless);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Waits for a key to be pressed before exiting, so that an error can be read before the console
+/// closes. This is skipped if nobody could press a key.
+fn wait_for_key(headless: bool) {
+    let term = console::Term::stdout();
+    if headless || !term.is_term() || !console::Term::stderr().is_term() {
+        return;
+    }
+
+    eprintln!("Press any key to exit.");
+    let _ = term.read_key();
+}
+
+fn parse_args() -> AnyResult<Args> {
+    let mut args = Args::default();
+    let mut raw = std::env::args().skip(1);
+
+    while let Some(arg) = raw.next() {
+        match arg.as_str() {
+            "--check" => args.check = true,
+            "--dump-config" => args.dump_config = true,
+            "--dump-schema" => args.dump_schema = true,
+            "--force" => args.force = true,
+            "--headless" => args.headless = true,
+            "--list-devices" => args.list_devices = true,
+            "--portable" => args.portable = true,
+            "--version" => args.version = true,
+
+            "--config" => {
+                let path = raw.next().context("--config requires a path")?;
+                args.config = Some(PathBuf::from(path));
+            }
+
+            "--log-level" => {
+                let level = raw.next().context("--log-level requires a level")?;
+                args.log.set("level", &level)?;
+            }
+
+            "--log" => {
+                let filter = raw.next().context("--log requires <module>=<level>")?;
+                let (module, level) = filter
+                    .split_once('=')
+                    .context("--log requires <module>=<level>")?;
+                args.log.set(module, level)?;
+            }
+
+            "--test-signal" => {
+                let spec = raw.next().context(
+                    "--test-signal requires <axis>=<waveform>:<frequency>[:<amplitude>]",
+                )?;
+                args.test_signal.set(&spec)?;
+            }
+
+            _ => bail!("Unrecognized argument: {arg}"),
+        }
+    }
+
+    Ok(args)
+}
+
+/// The configuration file to use: the one given with `--config`, or else the usual one.
+fn config_file(custom: Option<&Path>) -> PathBuf {
+    custom.map_or_else(|| config::find_file(CONFIG_NAME), Path::to_path_buf)
+}
+
+fn run_check(config_path: Option<&Path>, test_signal: &test_signal::TestSignalConfig) -> ExitCode {
+    let mut config =
+        match smol::block_on(config::AppConfig::read_existing(config_file(config_path))) {
+            Ok(c) => c,
+            Err(e) => {
+                println!("error: {e:#}");
+                return ExitCode::FAILURE;
+            }
+        };
+
+    config.test_signal.merge(test_signal);
+    let problems = config.validate();
+    for problem in &problems {
+        println!("{}: {problem}", problem.severity);
+    }
+
+    let errors = problems
+        .iter()
+        .filter(|p| p.severity == check::Severity::Error)
+        .count();
+    let warnings = problems.len() - errors;
+    println!("{errors} error(s), {warnings} warning(s).");
+
+    if errors > 0 {
+        ExitCode::FAILURE
+    } else if warnings > 0 {
+        ExitCode::from(2)
+    } else {
+        ExitCode::SUCCESS
+    }
+}
 
+/// Prints the configuration as it was read, with every default filled in.
+fn run_dump_config(
+    config_path: Option<&Path>,
+    log: &logging::LogConfig,
+    test_signal: &test_signal::TestSignalConfig,
+) -> ExitCode {
+    let path = config_file(config_path);
+    let mut config = match smol::block_on(config::AppConfig::read_existing(&path)) {
+        Ok(c) => c,
+        Err(e) => {
+            eprintln!("error: {e:#}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    config.log.merge(log);
+    config.test_signal.merge(test_signal);
+    match config.dump() {
+        Ok(dump) => {
+            println!(
+                "# The effective configuration read from <{}>.",
+                path.display()
+            );
+            print!("{dump}");
+            ExitCode::SUCCESS
+        }
+
+        Err(e) => {
+            eprintln!("error: {e:#}");
             ExitCode::FAILURE
         }
     }
 }
 
-fn run_sync() -> AnyResult<()> {
-    smol::block_on(run_async())
+fn run_sync(
+    config_path: Option<PathBuf>,
+    log: logging::LogConfig,
+    test_signal: test_signal::TestSignalConfig,
+    headless: bool,
+) -> AnyResult<()> {
+    smol::block_on(async {
+        let (control_tx, control_rx) = smol::channel::unbounded();
+        install_ctrlc(control_tx.clone());
+
+        // A sample configuration is only written where it would be looked for by default.
+        let missing = if config_path.is_some() {
+            config::Missing::Fail
+        } else if headless {
+            config::Missing::WriteReference
+        } else {
+            config::Missing::Prompt
+        };
+        let mut config = load_config(config_path.as_deref(), &log, &test_signal, missing).await?;
+
+        #[cfg(feature = "tray")]
+        let mut tray = None;
+
+        #[cfg(feature = "update-check")]
+        let mut update_check = Some(config.update_check.clone());
+
+        loop {
+            // The tray icon's menu would be another way to quit, and there's nobody to use it.
+            #[cfg(feature = "tray")]
+            if !headless {
+                tray::update(&mut tray, &config.tray, &control_tx);
+            } else if config.tray.enabled() {
+                log::info!("Not showing the tray icon, since running headless.");
+            }
+
+            // This is only done once, but after the tray icon is added so that it can show the
+            // notice. Nothing waits for it.
+            #[cfg(feature = "update-check")]
+            if let Some(update_config) = update_check.take() {
+                smol::spawn(update::run(update_config)).detach();
+            }
+
+            match run_async(
+                config,
+                config_path.clone(),
+                log.clone(),
+                test_signal.clone(),
+                control_rx.clone(),
+            )
+            .await?
+            {
+                Some(next) => config = next,
+                None => {
+                    session::finish();
+                    break Ok(());
+                }
+            }
+        }
+    })
 }
 
-async fn run_async() -> AnyResult<()> {
-    let config = config::AppConfig::read_from("remote-wheel-sender.toml").await?;
+/// Reads and validates the configuration, handling it being missing as `missing` says.
+async fn load_config(
+    config_path: Option<&Path>,
+    log: &logging::LogConfig,
+    test_signal: &test_signal::TestSignalConfig,
+    missing: config::Missing,
+) -> AnyResult<config::AppConfig> {
+    let path = config_file(config_path);
+    let mut config = config::AppConfig::read_from(&path, missing).await?;
+
+    // Signals given on the command line are checked along with any in the configuration file.
+    config.test_signal.merge(test_signal);
+
+    // Every error is reported together, so that they can all be fixed at once.
+    let mut errors = Vec::new();
+    for problem in config.validate() {
+        match problem.severity {
+            check::Severity::Error => errors.push(problem.to_string()),
+            check::Severity::Warning => log::warn!("Configuration warning: {problem}"),
+        }
+    }
+
+    if !errors.is_empty() {
+        bail!(
+            "Configuration has {} error(s):\n{}",
+            errors.len(),
+            errors.join("\n")
+        );
+    }
+
+    // Levels given on the command line take priority over the configuration file.
+    config.log.merge(log);
+
+    let outputs = config.enabled_outputs();
+    // The path may be relative to the working directory, which is usually the question.
+    let full_path = std::env::current_dir()
+        .map(|dir| dir.join(&path))
+        .unwrap_or_else(|_| path.clone());
+    if config_path.is_some() {
+        log::info!("Configuration: <{}> (from --config)", full_path.display());
+    } else {
+        log::info!("Configuration: <{}>", full_path.display());
+    }
+    log::info!(
+        "Outputs enabled: {}",
+        if outputs.is_empty() {
+            "(none)".to_string()
+        } else {
+            outputs.join(", ")
+        }
+    );
+
+    crash::note_config(&path, &config);
+    Ok(config)
+}
+
+/// Runs the tasks until they stop. Returns the configuration to restart them with, if they were
+/// stopped to reload it.
+async fn run_async(
+    config: config::AppConfig,
+    config_path: Option<PathBuf>,
+    log: logging::LogConfig,
+    test_signal: test_signal::TestSignalConfig,
+    control_rx: ChannelRx<Control>,
+) -> AnyResult<Option<config::AppConfig>> {
+    config.log.apply();
+
+    let _tuning = config.advanced.apply();
 
     let (_cancel_tx, cancel_rx) = smol::channel::unbounded();
     let (value_tx, value_rx) = async_broadcast::broadcast(16);
@@ -49,47 +483,353 @@ async fn run_async() -> AnyResult<()> {
     let exec = Arc::new(smol::Executor::new());
     let mut tasks = Vec::new();
 
-    let _cancel_task = exec.spawn(run_cancel(cancel_rx.clone()));
+    // vJoy failing (e.g. because its driver isn't installed) only stops the other tasks if there's
+    // no other output for them to carry on with. This is checked before the outputs' configurations
+    // are moved into their tasks.
+    #[cfg(feature = "vjoy")]
+    let vjoy_only_output = config.enabled_outputs() == ["vJoy"];
+
+    // The wheels are also worked out before the VMC configuration is moved into its task.
+    let wheels = session::Wheels::new(&config);
+
+    // The outputs that follow profiles are given every profile, rather than just the mappings.
+    #[cfg(any(feature = "osc", feature = "vmc"))]
+    let profiles = profile::Profiles::new(&config);
+
+    let control_task = exec.spawn(run_control(
+        cancel_rx.clone(),
+        control_rx,
+        config_path,
+        log,
+        test_signal,
+    ));
+
+    // With a fixed output rate, inputs send to the output clock, which sends on to the outputs.
+    // Otherwise, they may send to a task that combines their flushes.
+    let mut input_tx = match (
+        config.advanced.output_rate(),
+        config.advanced.flush_window(),
+    ) {
+        (Some(rate), _) => {
+            let (input_tx, input_rx) = async_broadcast::broadcast(16);
+            let clock_task = exec.spawn(clock::run(rate, input_rx, value_tx.clone()));
+            tasks.push(("Output clock", clock_task));
+            input_tx
+        }
+
+        (None, Some(window)) => {
+            let (input_tx, input_rx) = async_broadcast::broadcast(16);
+            let coalesce_task = exec.spawn(coalesce::run(window, input_rx, value_tx.clone()));
+            tasks.push(("Flush coalescing", coalesce_task));
+            input_tx
+        }
+
+        (None, None) => value_tx.clone(),
+    };
+
+    // With state persistence, inputs send to the state task first. It's only started once the
+    // outputs have been, so that they receive the restored values.
+    let state_channel = if config.state.enabled() {
+        let (state_tx, state_rx) = async_broadcast::broadcast(16);
+        Some((state_rx, std::mem::replace(&mut input_tx, state_tx)))
+    } else {
+        None
+    };
+
+    // Long and double presses are recognized before anything else, so that every output (and the
+    // saved state) sees the same presses.
+    if press::needed(&config.mappings) {
+        let (press_tx, press_rx) = async_broadcast::broadcast(16);
+        let output_tx = std::mem::replace(&mut input_tx, press_tx);
+        let press_task = exec.spawn(press::run(config.mappings.clone(), press_rx, output_tx));
+        tasks.push(("Button presses", press_task));
+    }
 
     let controller_task = exec.spawn(controller::run(
         exec.clone(),
         config.mappings.clone(),
-        value_tx.clone(),
+        input_tx.clone(),
+        value_rx.clone().deactivate(),
         cancel_rx.clone(),
     ));
-    tasks.push(controller_task);
+    tasks.push(("Controller", controller_task));
+
+    #[cfg(feature = "keyboard")]
+    {
+        let keyboard_task = exec.spawn(keyboard::run(
+            config.mappings.clone(),
+            input_tx.clone(),
+            cancel_rx.clone(),
+        ));
+        tasks.push(("Keyboard", keyboard_task));
+    }
+
+    #[cfg(feature = "midi")]
+    {
+        let midi_task = exec.spawn(midi::run(
+            config.mappings.clone(),
+            input_tx.clone(),
+            cancel_rx.clone(),
+        ));
+        tasks.push(("MIDI", midi_task));
+    }
+
+    if config.test_signal.enabled() {
+        let test_signal_task = exec.spawn(test_signal::run(
+            config.test_signal.clone(),
+            config.mappings.clone(),
+            input_tx.clone(),
+            cancel_rx.clone(),
+        ));
+        tasks.push(("Test signal", test_signal_task));
+    }
+
+    #[cfg(feature = "keep-awake")]
+    if config.keep_awake.enabled() {
+        let awake_task = exec.spawn(awake::run(config.keep_awake.clone(), value_rx.clone()));
+        tasks.push(("Keep-awake", awake_task));
+    }
 
     #[cfg(feature = "osc")]
     if config.osc.enabled() {
-        let osc_task = exec.spawn(osc::run(
-            exec.clone(),
-            config.osc,
-            config.mappings.clone(),
+        let exec_inner = exec.clone();
+        let profiles = profiles.clone();
+        let osc_cancel_rx = cancel_rx.clone();
+        let osc_value_rx = value_rx.clone().deactivate();
+        let osc_input_tx = input_tx.clone();
+
+        let stats = stats::OutputStats::new("OSC");
+
+        let osc_task = exec.spawn(supervisor::supervise(
+            "OSC",
+            config.restart.clone(),
             cancel_rx.clone(),
-            value_tx.clone(),
-            value_rx.clone(),
+            move || {
+                let value_rx = osc_value_rx.activate_cloned();
+                osc::run(
+                    exec_inner.clone(),
+                    config.osc.clone(),
+                    profiles.clone(),
+                    osc_cancel_rx.clone(),
+                    osc_input_tx.clone(),
+                    value_rx,
+                    stats.clone(),
+                )
+            },
         ));
-        tasks.push(osc_task);
+        tasks.push(("OSC", osc_task));
     }
 
     #[cfg(feature = "vmc")]
     if config.vmc.enabled() {
-        let vmc_task = exec.spawn(vmc::run(
-            config.vmc,
+        let profiles = profiles.clone();
+        let vmc_value_rx = value_rx.clone().deactivate();
+
+        let stats = stats::OutputStats::new("VMC");
+
+        let vmc_task = exec.spawn(supervisor::supervise(
+            "VMC",
+            config.restart.clone(),
+            cancel_rx.clone(),
+            move || {
+                vmc::run(
+                    config.vmc.clone(),
+                    profiles.clone(),
+                    vmc_value_rx.activate_cloned(),
+                    stats.clone(),
+                )
+            },
+        ));
+        tasks.push(("VMC", vmc_task));
+    }
+
+    #[cfg(feature = "openvr")]
+    if config.openvr.enabled() {
+        let mappings = config.mappings.clone();
+        let openvr_value_rx = value_rx.clone().deactivate();
+
+        let stats = stats::OutputStats::new("OpenVR");
+
+        let openvr_task = exec.spawn(supervisor::supervise(
+            "OpenVR",
+            config.restart.clone(),
+            cancel_rx.clone(),
+            move || {
+                openvr::run(
+                    config.openvr.clone(),
+                    mappings.clone(),
+                    openvr_value_rx.activate_cloned(),
+                    stats.clone(),
+                )
+            },
+        ));
+        tasks.push(("OpenVR", openvr_task));
+    }
+
+    #[cfg(feature = "audio")]
+    if config.audio.enabled() {
+        let mappings = config.mappings.clone();
+        let audio_value_rx = value_rx.clone().deactivate();
+
+        let stats = stats::OutputStats::new("Audio");
+
+        let audio_task = exec.spawn(supervisor::supervise(
+            "Audio",
+            config.restart.clone(),
+            cancel_rx.clone(),
+            move || {
+                audio::run(
+                    config.audio.clone(),
+                    mappings.clone(),
+                    audio_value_rx.activate_cloned(),
+                    stats.clone(),
+                )
+            },
+        ));
+        tasks.push(("Audio", audio_task));
+    }
+
+    #[cfg(feature = "exec")]
+    if config.allow_exec && exec::has_commands(&config.mappings) {
+        let exec_inner = exec.clone();
+        let mappings = config.mappings.clone();
+        let exec_value_rx = value_rx.clone().deactivate();
+
+        let stats = stats::OutputStats::new("Exec");
+
+        let exec_task = exec.spawn(supervisor::supervise(
+            "Exec",
+            config.restart.clone(),
+            cancel_rx.clone(),
+            move || {
+                exec::run(
+                    exec_inner.clone(),
+                    config.exec.clone(),
+                    mappings.clone(),
+                    exec_value_rx.activate_cloned(),
+                    stats.clone(),
+                )
+            },
+        ));
+        tasks.push(("Exec", exec_task));
+    }
+
+    #[cfg(feature = "keystroke")]
+    if keystroke::has_keystrokes(&config.mappings) {
+        let mappings = config.mappings.clone();
+        let keystroke_value_rx = value_rx.clone().deactivate();
+
+        let stats = stats::OutputStats::new("Keystroke");
+
+        let keystroke_task = exec.spawn(supervisor::supervise(
+            "Keystroke",
+            config.restart.clone(),
+            cancel_rx.clone(),
+            move || {
+                keystroke::run(
+                    mappings.clone(),
+                    keystroke_value_rx.activate_cloned(),
+                    stats.clone(),
+                )
+            },
+        ));
+        tasks.push(("Keystroke", keystroke_task));
+    }
+
+    #[cfg(feature = "vjoy")]
+    if config.vjoy.enabled() {
+        let mappings = config.mappings.clone();
+        let vjoy_value_rx = value_rx.clone().deactivate();
+
+        let stats = stats::OutputStats::new("vJoy");
+
+        let supervised = supervisor::supervise(
+            "vJoy",
+            config.restart.clone(),
+            cancel_rx.clone(),
+            move || {
+                vjoy::run(
+                    config.vjoy.clone(),
+                    mappings.clone(),
+                    vjoy_value_rx.activate_cloned(),
+                    stats.clone(),
+                )
+            },
+        );
+
+        let vjoy_task = exec.spawn(async move {
+            match supervised.await {
+                Err(e) if !vjoy_only_output => {
+                    log::error!(
+                        "vJoy output has stopped: {e:#}. Other outputs will carry on without it."
+                    );
+                    Ok(())
+                }
+
+                result => result,
+            }
+        });
+        tasks.push(("vJoy", vjoy_task));
+    }
+
+    // Spawned after every output, so that each has started listening by the time this runs.
+    if let Some((state_rx, state_tx)) = state_channel {
+        let state_task = exec.spawn(state::run(
+            config.state.clone(),
             config.mappings.clone(),
-            value_rx.clone(),
+            state_rx,
+            state_tx,
         ));
-        tasks.push(vmc_task);
+        tasks.push(("State", state_task));
     }
 
+    let _status_task = exec.spawn(status::run(
+        config.status,
+        value_rx.clone(),
+        cancel_rx.clone(),
+    ));
+
+    let _session_task = exec.spawn(session::run(
+        config.session,
+        config.mappings.clone(),
+        wheels,
+        value_rx.clone(),
+        cancel_rx.clone(),
+    ));
+
     drop(value_rx);
     drop(value_tx);
+    drop(input_tx);
+
+    let _stats_task = exec.spawn(stats::run(
+        config.stats,
+        config.mappings.clone(),
+        cancel_rx.clone(),
+    ));
+
+    let mut shutdown = Box::pin(supervisor::shutdown_deadline(
+        config.shutdown,
+        cancel_rx.clone(),
+    ));
 
     exec.run(async move {
         let mut result = Ok(());
+        let (mut names, mut tasks): (Vec<_>, Vec<_>) = tasks.into_iter().unzip();
 
         while !tasks.is_empty() {
-            let (task_result, _, rest) = futures::future::select_all(tasks).await;
+            let finished = futures::future::select_all(tasks);
+            let (task_result, index, rest) = match future::select(finished, &mut shutdown).await {
+                future::Either::Left((finished, _)) => finished,
+                future::Either::Right((grace_period, _)) => {
+                    log::error!(
+                        "Task(s) still running {:.1} second(s) after stopping: {}. Exiting anyway.",
+                        grace_period.as_secs_f64(),
+                        names.join(", "),
+                    );
+                    log::logger().flush();
+                    std::process::exit(1);
+                }
+            };
 
             if let Err(ref e) = task_result {
                 log::error!("Task failed: {e}");
@@ -98,32 +838,40 @@ async fn run_async() -> AnyResult<()> {
 
             result = result.and(task_result);
             tasks = rest;
+            // `select_all` removes the finished task the same way.
+            names.swap_remove(index);
         }
 
-        result
+        // The tasks may have all stopped on their own, in which case there's nothing to wait for.
+        cancel_rx.close();
+        let next = control_task.await;
+        result.map(|()| next)
     })
     .await
 }
 
-fn init_logger() -> AnyResult<()> {
+fn init_logger(log: &logging::LogConfig, log_file: &Path, headless: bool) -> AnyResult<()> {
+    // The log file's limits come from the configuration file, which hasn't been read yet.
+    log.apply_levels();
+
     let term_config = simplelog::ConfigBuilder::new()
         .set_time_offset_to_local()
         .unwrap_or_else(|e| e)
         .build();
 
     let term_logger = simplelog::TermLogger::new(
-        log::LevelFilter::Info,
+        log::LevelFilter::Trace,
         term_config,
         simplelog::TerminalMode::Mixed,
-        simplelog::ColorChoice::Auto,
+        // The output is probably being redirected somewhere that doesn't understand colors.
+        if headless {
+            simplelog::ColorChoice::Never
+        } else {
+            simplelog::ColorChoice::Auto
+        },
     );
 
-    let file = std::fs::File::options()
-        .create(true)
-        .truncate(true)
-        .write(true)
-        .open("remote-wheel-sender.log")
-        .context("Failed to open log file")?;
+    let file = logging::RotatingFile::open(log_file).context("Failed to open log file")?;
 
     let file_config = simplelog::ConfigBuilder::new()
         .set_time_format_custom(simplelog::format_description!(
@@ -133,10 +881,34 @@ fn init_logger() -> AnyResult<()> {
         .unwrap_or_else(|e| e)
         .build();
 
-    let file_logger = simplelog::WriteLogger::new(log::LevelFilter::Trace, file_config, file);
+    let file_logger =
+        simplelog::WriteLogger::new(log::LevelFilter::Trace, file_config.clone(), file);
+
+    // Recent lines are kept for crash reports, with the same detail as the log file.
+    let recent_logger = simplelog::WriteLogger::new(
+        log::LevelFilter::Trace,
+        file_config,
+        logging::RecentLines::default(),
+    );
+
+    // Release builds only log up to Info to the file by default, to keep it from growing too large.
+    let file_level = if cfg!(debug_assertions) {
+        log::LevelFilter::Trace
+    } else {
+        log::LevelFilter::Info
+    };
+
+    #[allow(unused_mut)]
+    let mut loggers: Vec<Box<dyn simplelog::SharedLogger>> = vec![
+        logging::Filtered::new(term_logger, log::LevelFilter::Info),
+        logging::Filtered::file(file_logger, file_level),
+        logging::Filtered::file(recent_logger, file_level),
+    ];
+
+    #[cfg(feature = "tray")]
+    loggers.push(tray::Notifier::new());
 
-    simplelog::CombinedLogger::init(vec![term_logger, file_logger])
-        .context("Failed to install logger")?;
+    simplelog::CombinedLogger::init(loggers).context("Failed to install logger")?;
 
     let version = env!("CARGO_PKG_VERSION");
     log::info!("Remote Wheel Sender v{version} starting.");
@@ -144,26 +916,60 @@ fn init_logger() -> AnyResult<()> {
     Ok(())
 }
 
-async fn run_cancel(cancel_rx: ChannelRx<()>) -> AnyResult<()> {
-    let (signal_tx, signal_rx) = smol::channel::bounded(1);
+/// Asks the tasks to stop on Ctrl-C, and also on SIGTERM and SIGHUP on Unix (e.g. when stopped by
+/// systemd) or when the console window is closed on Windows.
+fn install_ctrlc(control_tx: ChannelTx<Control>) {
     match ctrlc::set_handler(move || {
-        let _ = signal_tx.send_blocking(());
+        let _ = control_tx.send_blocking(Control::Quit);
     }) {
-        Ok(()) => {
-            log::debug!("Ctrl-C handler is active.");
-            futures::select_biased! {
-                _ = cancel_rx.recv().fuse() => {},
-                _ = signal_rx.recv().fuse() => {
-                    cancel_rx.close();
-                },
-            };
-            Ok(())
-        }
+        Ok(()) => log::debug!("Stop signal handler is active."),
+        Err(e) => log::warn!("Failed to install stop signal handler: {}", e),
+    }
+}
 
-        Err(e) => {
-            log::warn!("Failed to install Ctrl-C handler: {}", e);
-            let _ = cancel_rx.recv().await;
-            Ok(())
+/// Stops the tasks when asked to quit, or once a new configuration has been loaded to replace the
+/// current one.
+async fn run_control(
+    cancel_rx: ChannelRx<()>,
+    control_rx: ChannelRx<Control>,
+    config_path: Option<PathBuf>,
+    log: logging::LogConfig,
+    test_signal: test_signal::TestSignalConfig,
+) -> Option<config::AppConfig> {
+    loop {
+        let control = futures::select_biased! {
+            _ = cancel_rx.recv().fuse() => return None,
+            control = control_rx.recv().fuse() => control,
+        };
+
+        match control {
+            Ok(Control::Quit) => {
+                cancel_rx.close();
+                return None;
+            }
+
+            Ok(Control::Reload) => match load_config(
+                config_path.as_deref(),
+                &log,
+                &test_signal,
+                config::Missing::Fail,
+            )
+            .await
+            {
+                Ok(config) => {
+                    log::info!("Configuration reloaded. Restarting...");
+                    cancel_rx.close();
+                    return Some(config);
+                }
+                Err(e) => {
+                    log::error!("Failed to reload configuration (keeping the current one): {e:#}")
+                }
+            },
+
+            Err(_) => {
+                let _ = cancel_rx.recv().await;
+                return None;
+            }
         }
     }
 }
@@ -0,0 +1,274 @@
+mod config;
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, Result as AnyResult};
+use async_broadcast::Sender as BroadcastTx;
+use futures::prelude::*;
+use midir::{Ignore, MidiInput, MidiInputConnection};
+use smol::channel::{Receiver as ChannelRx, Sender as ChannelTx};
+
+pub use config::{validate, AxisInputConfig, ButtonInputConfig};
+
+use crate::button::ButtonStates;
+use crate::config::MappingConfig;
+use crate::output::{OutputEvent, Timestamped};
+use crate::route::MappingId;
+use crate::stats;
+
+const CLIENT_NAME: &str = "Remote Wheel Sender";
+
+/// How often to look for ports that weren't found.
+const RETRY_INTERVAL: Duration = Duration::from_secs(5);
+
+/// How often the connection thread checks whether it should stop.
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+struct Binding {
+    port: usize,
+    channel: Option<u8>,
+    message: Message,
+    id: MappingId,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Message {
+    Cc(u8),
+    Note(u8),
+}
+
+pub async fn run(
+    mappings: Arc<MappingConfig>,
+    output_tx: BroadcastTx<Timestamped<OutputEvent>>,
+    cancel_rx: ChannelRx<()>,
+) -> AnyResult<()> {
+    log::info!("MIDI input task starting...");
+
+    let mut ports = Vec::new();
+    let mut port_index = |name: &str| match ports.iter().position(|p| p == name) {
+        Some(i) => i,
+        None => {
+            ports.push(name.to_string());
+            ports.len() - 1
+        }
+    };
+
+    let mut bindings = Vec::new();
+    for (id, axis) in mappings.axes() {
+        for input in &axis.input {
+            if let crate::config::AxisInputConfig::Midi(c) = input {
+                bindings.push(Binding {
+                    port: port_index(&c.port),
+                    channel: c.channel,
+                    message: Message::Cc(c.cc),
+                    id: id.clone(),
+                });
+            }
+        }
+    }
+
+    for (id, button) in mappings.buttons() {
+        for input in &button.input {
+            if let crate::config::ButtonInputConfig::Midi(c) = input {
+                bindings.push(Binding {
+                    port: port_index(&c.port),
+                    channel: c.channel,
+                    message: Message::Note(c.note),
+                    id: id.clone(),
+                });
+            }
+        }
+    }
+
+    if bindings.is_empty() {
+        log::info!("No MIDI inputs configured. MIDI input task not starting.");
+        return Ok(());
+    }
+
+    // MIDI messages arrive on threads owned by the MIDI backend, which pass them on to this task.
+    let (message_tx, message_rx) = smol::channel::unbounded();
+    std::thread::Builder::new()
+        .name("MIDI".into())
+        .spawn(move || connect_ports(ports, message_tx))
+        .map_err(|e| anyhow!("Failed to start MIDI connection thread: {e}"))?;
+
+    let mut buttons = ButtonStates::new(mappings.clone(), output_tx.clone());
+    let input_stats = stats::InputStats::new("MIDI");
+    log::info!("MIDI input task started.");
+
+    loop {
+        let (port, data) = futures::select_biased! {
+            _ = cancel_rx.recv().fuse() => {
+                log::info!("MIDI input task stopping (shutdown)...");
+                break;
+            },
+
+            msg = message_rx.recv().fuse() => match msg {
+                Ok(msg) => msg,
+                Err(_) => {
+                    log::info!("MIDI input task stopping (MIDI connections have stopped)...");
+                    break;
+                },
+            },
+        };
+
+        let Some((channel, message, value)) = decode(&data) else {
+            continue;
+        };
+
+        let mut flush = false;
+        for binding in &bindings {
+            if binding.port != port
+                || binding.message != message
+                || binding.channel.is_some_and(|c| c != channel)
+            {
+                continue;
+            }
+
+            let event = match message {
+                Message::Cc(_) => OutputEvent::UpdateAxis(binding.id.clone(), value as f64 / 127.0),
+                Message::Note(_) => {
+                    let Some(state) = buttons.update(&binding.id, value > 0) else {
+                        continue;
+                    };
+                    OutputEvent::UpdateButton(binding.id.clone(), state)
+                }
+            };
+
+            input_stats.event(&event);
+            if output_tx.broadcast(event.into()).await.is_err() {
+                log::info!("MIDI input task stopping (no remaining outputs).");
+                return Ok(());
+            }
+
+            flush = true;
+        }
+
+        if flush
+            && output_tx
+                .broadcast(OutputEvent::Flush.into())
+                .await
+                .is_err()
+        {
+            log::info!("MIDI input task stopping (no remaining outputs).");
+            break;
+        }
+    }
+
+    log::info!("MIDI input task stopped.");
+    Ok(())
+}
+
+/// Connects to each port whose name contains the given text, retrying periodically for any that
+/// aren't found, and keeps the connections open until the MIDI input task stops.
+fn connect_ports(ports: Vec<String>, message_tx: ChannelTx<(usize, Vec<u8>)>) {
+    let mut connections: Vec<Option<MidiInputConnection<()>>> =
+        ports.iter().map(|_| None).collect();
+    let mut listed = false;
+    let mut warned = vec![false; ports.len()];
+    let mut next_attempt = Instant::now();
+
+    while !message_tx.is_closed() {
+        if Instant::now() >= next_attempt && connections.iter().any(Option::is_none) {
+            next_attempt = Instant::now() + RETRY_INTERVAL;
+
+            for (index, pattern) in ports.iter().enumerate() {
+                if connections[index].is_some() {
+                    continue;
+                }
+
+                match connect(index, pattern, &message_tx, !listed) {
+                    Ok(Some((name, connection))) => {
+                        log::info!("Connected to MIDI port {name:?}.");
+                        connections[index] = Some(connection);
+                    }
+
+                    Ok(None) if !warned[index] => {
+                        log::warn!(
+                            "No MIDI port matching {pattern:?} was found. Retrying every \
+                            {RETRY_INTERVAL:?}."
+                        );
+                        warned[index] = true;
+                    }
+
+                    Ok(None) => {}
+
+                    Err(e) => {
+                        log::warn!("Failed to connect to MIDI port matching {pattern:?}: {e:#}");
+                    }
+                }
+
+                listed = true;
+            }
+        }
+
+        std::thread::sleep(POLL_INTERVAL);
+    }
+
+    // Dropping the connections closes them.
+    log::debug!(
+        "Closing {} MIDI connection(s).",
+        connections.iter().flatten().count()
+    );
+}
+
+fn connect(
+    index: usize,
+    pattern: &str,
+    message_tx: &ChannelTx<(usize, Vec<u8>)>,
+    list: bool,
+) -> AnyResult<Option<(String, MidiInputConnection<()>)>> {
+    let mut input = MidiInput::new(CLIENT_NAME).map_err(|e| anyhow!("{e}"))?;
+    input.ignore(Ignore::All);
+
+    let pattern_lower = pattern.to_lowercase();
+    let mut found = None;
+    for port in input.ports() {
+        let Ok(name) = input.port_name(&port) else {
+            continue;
+        };
+
+        if list {
+            log::info!("MIDI input port available: {name:?}");
+        }
+
+        if found.is_none() && name.to_lowercase().contains(&pattern_lower) {
+            found = Some((port, name));
+        }
+    }
+
+    let Some((port, name)) = found else {
+        return Ok(None);
+    };
+
+    let message_tx = message_tx.clone();
+    let connection = input
+        .connect(
+            &port,
+            CLIENT_NAME,
+            move |_, data, _| {
+                let _ = message_tx.try_send((index, data.to_vec()));
+            },
+            (),
+        )
+        .map_err(|e| anyhow!("{e}"))?;
+
+    Ok(Some((name, connection)))
+}
+
+/// Decodes a channel voice message into its channel (from 1), message, and value. Note on messages
+/// with a velocity of 0 are treated as note off messages, which have a value of 0.
+fn decode(data: &[u8]) -> Option<(u8, Message, u8)> {
+    let &[status, number, value] = data else {
+        return None;
+    };
+
+    let channel = (status & 0x0F) + 1;
+    match status & 0xF0 {
+        0x80 => Some((channel, Message::Note(number), 0)),
+        0x90 => Some((channel, Message::Note(number), value)),
+        0xB0 => Some((channel, Message::Cc(number), value)),
+        _ => None,
+    }
+}
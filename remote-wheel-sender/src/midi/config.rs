@@ -0,0 +1,60 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::check::Report;
+use crate::config::{
+    AxisInputConfig as AnyAxisInput, ButtonInputConfig as AnyButtonInput, MappingConfig,
+};
+
+#[derive(Debug, Deserialize, JsonSchema, Serialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct AxisInputConfig {
+    pub(super) port: String,
+    pub(super) channel: Option<u8>,
+    pub(super) cc: u8,
+}
+
+#[derive(Debug, Deserialize, JsonSchema, Serialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct ButtonInputConfig {
+    pub(super) port: String,
+    pub(super) channel: Option<u8>,
+    pub(super) note: u8,
+}
+
+pub fn validate(mappings: &MappingConfig, report: &mut Report) {
+    for (id, axis) in &mappings.axis {
+        for (i, input) in axis.input.iter().enumerate() {
+            if let AnyAxisInput::Midi(c) = input {
+                let path = format!("axis.{id}.input[{i}].midi");
+                validate_channel(&path, c.channel, report);
+
+                if c.cc > 127 {
+                    report.error(format!("{path}.cc"), "CC number must be from 0 to 127");
+                }
+            }
+        }
+    }
+
+    for (id, button) in &mappings.button {
+        for (i, input) in button.input.iter().enumerate() {
+            if let AnyButtonInput::Midi(c) = input {
+                let path = format!("button.{id}.input[{i}].midi");
+                validate_channel(&path, c.channel, report);
+
+                if c.note > 127 {
+                    report.error(format!("{path}.note"), "Note number must be from 0 to 127");
+                }
+            }
+        }
+    }
+}
+
+fn validate_channel(path: &str, channel: Option<u8>, report: &mut Report) {
+    if channel.is_some_and(|c| !(1..=16).contains(&c)) {
+        report.error(
+            format!("{path}.channel"),
+            "MIDI channel must be from 1 to 16",
+        );
+    }
+}
@@ -0,0 +1,295 @@
+mod config;
+mod ffi;
+
+use std::ffi::CString;
+use std::io::Cursor;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{bail, Context as _, Result as AnyResult};
+use async_broadcast::{Receiver as BroadcastRx, RecvError as BroadcastRxErr};
+use futures::prelude::*;
+use image::{ImageFormat, RgbaImage};
+
+pub use config::{AxisOutputConfig, Config};
+
+use crate::config::MappingConfig;
+use crate::output::{OutputEvent, Timestamped};
+use crate::route::Routes;
+use crate::stats::OutputStats;
+
+use self::config::Anchor;
+
+pub async fn run(
+    config: Config,
+    mappings: Arc<MappingConfig>,
+    mut recv: BroadcastRx<Timestamped<OutputEvent>>,
+    stats: Arc<OutputStats>,
+) -> AnyResult<()> {
+    log::info!("OpenVR task starting...");
+
+    let image = load_image(&config.image)?;
+    let transform = overlay_transform(config.position, config.rotation);
+
+    let mut overlay = None;
+    let mut pixels = Vec::new();
+    let mut angle = 0.0f32;
+    let mut dirty = true;
+
+    let mut retry_timer = smol::Timer::interval(Duration::from_secs_f64(config.retry_interval));
+    let mut update_timer = smol::Timer::interval(Duration::from_secs_f64(1.0 / config.update_rate));
+
+    match Overlay::new(&config, &transform) {
+        Ok(o) => overlay = Some(o),
+        Err(e) => log::warn!(
+            "OpenVR overlay unavailable ({e:#}). Retrying every {} second(s).",
+            config.retry_interval
+        ),
+    }
+
+    let rotations = Routes::axes(&mappings, |_, a| a.output.openvr.rotation);
+    log::info!("OpenVR task started.");
+
+    loop {
+        futures::select_biased! {
+            msg = recv.recv().fuse() => match msg.map(|e| stats.received(e, recv.len())) {
+                Ok(OutputEvent::UpdateAxis(id, value)) => {
+                    if let Some(range) = rotations.get(&id) {
+                        angle = range[0] + value as f32 * (range[1] - range[0]);
+                        dirty = true;
+                    }
+                },
+
+                Ok(_) => {},
+
+                Err(BroadcastRxErr::Overflowed(n)) => {
+                    log::warn!("OpenVR task missed {n} update(s)!");
+                    stats.missed(n);
+                },
+
+                Err(BroadcastRxErr::Closed) => {
+                    log::info!("OpenVR task stopping (no inputs remaining)...");
+                    break;
+                },
+            },
+
+            _ = update_timer.next().fuse() => {
+                let Some(ref o) = overlay else { continue };
+                if !dirty {
+                    continue;
+                }
+
+                render_rotated(&image, angle, config.resolution, &mut pixels);
+                if let Err(e) = o.upload(&mut pixels, config.resolution) {
+                    log::warn!("Failed to update OpenVR overlay ({e:#}). Retrying every {} second(s).", config.retry_interval);
+                    stats.send_failed();
+                    overlay = None;
+                } else {
+                    stats.sent();
+                }
+
+                dirty = false;
+            },
+
+            _ = retry_timer.next().fuse() => {
+                if overlay.is_none() {
+                    match Overlay::new(&config, &transform) {
+                        Ok(o) => {
+                            log::info!("OpenVR overlay created.");
+                            overlay = Some(o);
+                            dirty = true;
+                        },
+
+                        Err(e) => log::debug!("OpenVR overlay still unavailable: {e:#}"),
+                    }
+                }
+            },
+        }
+    }
+
+    drop(overlay);
+    log::info!("OpenVR task stopped.");
+    Ok(())
+}
+
+struct Overlay {
+    handle: ffi::VROverlayHandle,
+    table: *const ffi::IVROverlayFnTable,
+}
+
+// The OpenVR interfaces may be used from any thread.
+unsafe impl Send for Overlay {}
+
+impl Overlay {
+    fn new(config: &Config, transform: &ffi::HmdMatrix34) -> AnyResult<Overlay> {
+        let mut error = ffi::VR_INIT_ERROR_NONE;
+        unsafe { ffi::VR_InitInternal(&mut error, ffi::VR_APPLICATION_OVERLAY) };
+        if error != ffi::VR_INIT_ERROR_NONE {
+            bail!("Failed to initialize OpenVR (error {error})");
+        }
+
+        let table = unsafe {
+            ffi::VR_GetGenericInterface(ffi::IVROVERLAY_FNTABLE.as_ptr().cast(), &mut error)
+        } as *const ffi::IVROverlayFnTable;
+
+        if error != ffi::VR_INIT_ERROR_NONE || table.is_null() {
+            unsafe { ffi::VR_ShutdownInternal() };
+            bail!("Failed to get OpenVR overlay interface (error {error})");
+        }
+
+        let key = CString::new("remote-wheel.wheel").unwrap();
+        let name = CString::new("Remote Wheel").unwrap();
+
+        let mut handle = 0;
+        let result = unsafe { ((*table).CreateOverlay)(key.as_ptr(), name.as_ptr(), &mut handle) };
+        if result != ffi::VR_OVERLAY_ERROR_NONE {
+            unsafe { ffi::VR_ShutdownInternal() };
+            bail!("Failed to create OpenVR overlay (error {result})");
+        }
+
+        let overlay = Overlay { handle, table };
+
+        overlay.check(
+            unsafe { ((*table).SetOverlayWidthInMeters)(handle, config.width) },
+            "set overlay width",
+        )?;
+
+        let result = unsafe {
+            match config.anchor {
+                Anchor::World => ((*table).SetOverlayTransformAbsolute)(
+                    handle,
+                    ffi::TRACKING_UNIVERSE_STANDING,
+                    transform,
+                ),
+                Anchor::Seated => ((*table).SetOverlayTransformAbsolute)(
+                    handle,
+                    ffi::TRACKING_UNIVERSE_SEATED,
+                    transform,
+                ),
+                Anchor::Hmd => ((*table).SetOverlayTransformTrackedDeviceRelative)(
+                    handle,
+                    ffi::TRACKED_DEVICE_INDEX_HMD,
+                    transform,
+                ),
+                Anchor::Device(index) => {
+                    ((*table).SetOverlayTransformTrackedDeviceRelative)(handle, index, transform)
+                }
+            }
+        };
+        overlay.check(result, "set overlay transform")?;
+
+        overlay.check(unsafe { ((*table).ShowOverlay)(handle) }, "show overlay")?;
+
+        Ok(overlay)
+    }
+
+    fn check(&self, result: ffi::EVROverlayError, action: &str) -> AnyResult<()> {
+        if result != ffi::VR_OVERLAY_ERROR_NONE {
+            bail!("Failed to {action} (error {result})");
+        }
+
+        Ok(())
+    }
+
+    fn upload(&self, pixels: &mut [u8], resolution: u32) -> AnyResult<()> {
+        let result = unsafe {
+            ((*self.table).SetOverlayRaw)(
+                self.handle,
+                pixels.as_mut_ptr().cast(),
+                resolution,
+                resolution,
+                4,
+            )
+        };
+
+        self.check(result, "upload overlay image")
+    }
+}
+
+impl Drop for Overlay {
+    fn drop(&mut self) {
+        unsafe {
+            ((*self.table).DestroyOverlay)(self.handle);
+            ffi::VR_ShutdownInternal();
+        }
+
+        log::debug!("OpenVR overlay released.");
+    }
+}
+
+fn load_image(path: &Path) -> AnyResult<RgbaImage> {
+    let image = if path == Path::new("default") {
+        image::io::Reader::with_format(
+            Cursor::new(include_bytes!(
+                "../../remote-wheel-viewer/src/default-wheel.png"
+            )),
+            ImageFormat::Png,
+        )
+        .decode()
+        .context("Failed to decode wheel image")?
+    } else {
+        image::io::Reader::open(path)
+            .with_context(|| format!("Failed to open wheel image <{}>", path.display()))?
+            .decode()
+            .with_context(|| format!("Failed to decode wheel image <{}>", path.display()))?
+    };
+
+    Ok(image.to_rgba8())
+}
+
+fn render_rotated(image: &RgbaImage, angle: f32, resolution: u32, out: &mut Vec<u8>) {
+    let (width, height) = image.dimensions();
+    let scale = u32::max(width, height) as f32 / resolution as f32;
+    let (sin, cos) = (-angle.to_radians()).sin_cos();
+
+    out.clear();
+    out.reserve(4 * (resolution * resolution) as usize);
+
+    let center = 0.5 * resolution as f32;
+    for y in 0..resolution {
+        for x in 0..resolution {
+            let dx = x as f32 + 0.5 - center;
+            let dy = y as f32 + 0.5 - center;
+
+            let sx = scale * (cos * dx - sin * dy) + 0.5 * width as f32;
+            let sy = scale * (sin * dx + cos * dy) + 0.5 * height as f32;
+
+            if sx >= 0.0 && sy >= 0.0 && (sx as u32) < width && (sy as u32) < height {
+                out.extend_from_slice(&image.get_pixel(sx as u32, sy as u32).0);
+            } else {
+                out.extend_from_slice(&[0, 0, 0, 0]);
+            }
+        }
+    }
+}
+
+fn overlay_transform(position: [f32; 3], rotation: [f32; 3]) -> ffi::HmdMatrix34 {
+    // Same convention as VMC devices: X = pitch, Y = yaw, Z = roll, applied in YXZ order.
+    let (sx, cx) = rotation[0].to_radians().sin_cos();
+    let (sy, cy) = rotation[1].to_radians().sin_cos();
+    let (sz, cz) = rotation[2].to_radians().sin_cos();
+
+    let yaw = [[cy, 0.0, sy], [0.0, 1.0, 0.0], [-sy, 0.0, cy]];
+    let pitch = [[1.0, 0.0, 0.0], [0.0, cx, -sx], [0.0, sx, cx]];
+    let roll = [[cz, -sz, 0.0], [sz, cz, 0.0], [0.0, 0.0, 1.0]];
+    let rot = mul3(mul3(yaw, pitch), roll);
+
+    let mut result = ffi::HmdMatrix34::default();
+    for (row, (r, p)) in result.m.iter_mut().zip(std::iter::zip(rot, position)) {
+        *row = [r[0], r[1], r[2], p];
+    }
+
+    result
+}
+
+fn mul3(a: [[f32; 3]; 3], b: [[f32; 3]; 3]) -> [[f32; 3]; 3] {
+    let mut out = [[0.0; 3]; 3];
+    for (i, row) in out.iter_mut().enumerate() {
+        for (j, v) in row.iter_mut().enumerate() {
+            *v = (0..3).map(|k| a[i][k] * b[k][j]).sum();
+        }
+    }
+
+    out
+}
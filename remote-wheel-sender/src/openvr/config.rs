@@ -0,0 +1,99 @@
+use std::path::{Path, PathBuf};
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Deserializer, Serialize};
+
+use crate::check::Report;
+
+/// The largest overlay texture that may be asked for. Each update renders and uploads every pixel,
+/// so anything larger would be slow for no visible benefit.
+const MAX_RESOLUTION: u32 = 4096;
+
+#[derive(Clone, Debug, Deserialize, JsonSchema, Serialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Config {
+    enabled: bool,
+
+    #[serde(deserialize_with = "image")]
+    pub(super) image: PathBuf,
+    pub(super) resolution: u32,
+    pub(super) width: f32,
+
+    pub(super) anchor: Anchor,
+    pub(super) position: [f32; 3],
+    pub(super) rotation: [f32; 3],
+
+    pub(super) update_rate: f64,
+
+    #[serde(deserialize_with = "crate::units::seconds")]
+    #[schemars(schema_with = "crate::units::seconds_schema")]
+    pub(super) retry_interval: f64,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, JsonSchema, Serialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub(super) enum Anchor {
+    World,
+    Seated,
+    Hmd,
+    Device(u32),
+}
+
+#[derive(Debug, Default, Deserialize, JsonSchema, Serialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct AxisOutputConfig {
+    pub(super) rotation: Option<[f32; 2]>,
+}
+
+impl Config {
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn validate(&self, report: &mut Report) {
+        if !(1..=MAX_RESOLUTION).contains(&self.resolution) {
+            report.error(
+                "openvr.resolution",
+                format!("Resolution must be between 1 and {MAX_RESOLUTION} pixels"),
+            );
+        }
+
+        if !(self.update_rate > 0.0 && self.update_rate.is_finite()) {
+            report.error("openvr.update-rate", "Update rate must be positive");
+        }
+
+        if !(self.retry_interval > 0.0 && self.retry_interval.is_finite()) {
+            report.error("openvr.retry-interval", "Retry interval must be positive");
+        }
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            enabled: false,
+
+            image: PathBuf::from("default"),
+            resolution: 256,
+            width: 0.25,
+
+            anchor: Anchor::World,
+            position: [0.0, 1.0, -0.5],
+            rotation: [0.0, 0.0, 0.0],
+
+            update_rate: 30.0,
+            retry_interval: 10.0,
+        }
+    }
+}
+
+fn image<'de, D: Deserializer<'de>>(de: D) -> Result<PathBuf, D::Error> {
+    let image = PathBuf::deserialize(de)?;
+
+    // "default" is the embedded image, not a file.
+    if image == Path::new("default") {
+        Ok(image)
+    } else {
+        Ok(crate::config::resolve_path(&image))
+    }
+}
@@ -0,0 +1,78 @@
+//! Minimal bindings to the parts of the OpenVR C API used by the overlay.
+//!
+//! The function table layout follows `VR_IVROverlay_FnTable` from `openvr_capi.h` (OpenVR SDK
+//! 1.16), and must match the interface version requested in [`IVROVERLAY_FNTABLE`].
+
+#![allow(non_snake_case)]
+
+use std::ffi::{c_char, c_void};
+
+pub type EVRInitError = i32;
+pub type EVROverlayError = i32;
+pub type VROverlayHandle = u64;
+
+pub const VR_APPLICATION_OVERLAY: i32 = 2;
+pub const VR_INIT_ERROR_NONE: EVRInitError = 0;
+pub const VR_OVERLAY_ERROR_NONE: EVROverlayError = 0;
+
+pub const TRACKING_UNIVERSE_SEATED: i32 = 0;
+pub const TRACKING_UNIVERSE_STANDING: i32 = 1;
+pub const TRACKED_DEVICE_INDEX_HMD: u32 = 0;
+
+pub const IVROVERLAY_FNTABLE: &[u8] = b"FnTable:IVROverlay_024\0";
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct HmdMatrix34 {
+    pub m: [[f32; 4]; 3],
+}
+
+/// The start of `VR_IVROverlay_FnTable` for `IVROverlay_024` (OpenVR SDK 1.16), up to the last
+/// function that's used. Each padding field is named after the first and last functions it skips.
+#[repr(C)]
+pub struct IVROverlayFnTable {
+    _find_overlay: usize,
+    pub CreateOverlay: unsafe extern "system" fn(
+        key: *const c_char,
+        name: *const c_char,
+        handle: *mut VROverlayHandle,
+    ) -> EVROverlayError,
+    pub DestroyOverlay: unsafe extern "system" fn(handle: VROverlayHandle) -> EVROverlayError,
+
+    _get_key_to_get_sort_order: [usize; 18],
+    pub SetOverlayWidthInMeters:
+        unsafe extern "system" fn(handle: VROverlayHandle, width: f32) -> EVROverlayError,
+
+    _get_width_to_get_transform_type: [usize; 8],
+    pub SetOverlayTransformAbsolute: unsafe extern "system" fn(
+        handle: VROverlayHandle,
+        origin: i32,
+        transform: *const HmdMatrix34,
+    ) -> EVROverlayError,
+
+    _get_transform_absolute: usize,
+    pub SetOverlayTransformTrackedDeviceRelative: unsafe extern "system" fn(
+        handle: VROverlayHandle,
+        device: u32,
+        transform: *const HmdMatrix34,
+    ) -> EVROverlayError,
+
+    _get_device_relative_to_set_transform_projection: [usize; 8],
+    pub ShowOverlay: unsafe extern "system" fn(handle: VROverlayHandle) -> EVROverlayError,
+
+    _hide_overlay_to_clear_texture: [usize; 17],
+    pub SetOverlayRaw: unsafe extern "system" fn(
+        handle: VROverlayHandle,
+        buffer: *mut c_void,
+        width: u32,
+        height: u32,
+        bytes_per_pixel: u32,
+    ) -> EVROverlayError,
+}
+
+#[link(name = "openvr_api")]
+extern "C" {
+    pub fn VR_InitInternal(error: *mut EVRInitError, application_type: i32) -> isize;
+    pub fn VR_ShutdownInternal();
+    pub fn VR_GetGenericInterface(version: *const c_char, error: *mut EVRInitError) -> isize;
+}
@@ -1,57 +1,127 @@
 mod config;
 mod input;
 mod output;
+mod tcp;
 
-use std::net::SocketAddr;
 use std::sync::Arc;
+use std::time::Duration;
 
-use anyhow::{Context as _, Result as AnyResult};
+use anyhow::Result as AnyResult;
 use async_broadcast::{Receiver as BroadcastRx, Sender as BroadcastTx};
 use futures::prelude::*;
 use smol::channel::{Receiver as ChannelRx, Sender as ChannelTx};
-use smol::net::UdpSocket;
 
-use crate::config::MappingConfig;
-use crate::output::OutputEvent;
+use crate::output::{OutputEvent, Timestamped};
+use crate::profile::Profiles;
+use crate::reachability::{self, Destination};
+use crate::rebind::{RebindingSocket, SocketOptions};
+use crate::stats::OutputStats;
 pub use config::{
-    AxisInputConfig, AxisOutputConfig, ButtonInputConfig, ButtonOutputConfig, Config,
+    AxisButtonInputConfig, AxisInputConfig, AxisOutputConfig, ButtonInputConfig,
+    ButtonOutputConfig, Config, RelativeAxisInputConfig,
 };
 
+use self::config::Transport;
+use self::output::{Delivery, Outbound};
+
 pub async fn run(
     exec: Arc<smol::Executor<'static>>,
     config: Config,
-    mappings: Arc<MappingConfig>,
+    profiles: Arc<Profiles>,
     cancel_rx: ChannelRx<()>,
-    value_tx: BroadcastTx<OutputEvent>,
-    value_rx: BroadcastRx<OutputEvent>,
+    value_tx: BroadcastTx<Timestamped<OutputEvent>>,
+    value_rx: BroadcastRx<Timestamped<OutputEvent>>,
+    stats: Arc<OutputStats>,
 ) -> AnyResult<()> {
     log::info!("OSC task starting...");
 
-    let socket = UdpSocket::bind(config.input.address)
-        .await
-        .with_context(|| {
-            format!(
-                "Failed to bind to UDP input address {}",
-                config.input.address
-            )
-        })?;
+    let transport = config.output.transport;
+
+    // Output is sent from the input socket, unless it's given its own address to send from. Any
+    // multicast options are set on whichever one that is.
+    let bind_address = config
+        .output
+        .bind_address
+        .filter(|_| transport == Transport::Udp);
+    let (input_options, output_options) = match bind_address {
+        Some(_) => (SocketOptions::default(), config.output.socket_options()),
+        None => (config.output.socket_options(), SocketOptions::default()),
+    };
+    let input_options = SocketOptions {
+        stall_timeout: config.input.stall_timeout.map(Duration::from_secs_f64),
+        ..input_options
+    };
+
+    let socket =
+        RebindingSocket::bind_with("OSC input", config.input.address, input_options).await?;
 
     let local_addr = socket
         .local_addr()
         .expect("Failed to get local address of UdpSocket");
-    let remote_addr = config.output.address;
+    let remote_addrs = config.output.address.clone();
+    let remote_desc = remote_addrs
+        .iter()
+        .map(|a| a.to_string())
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    // Whether destinations are reachable is only kept track of over UDP, since TCP connections
+    // have their own errors.
+    let destinations = match transport {
+        Transport::Udp => remote_addrs
+            .iter()
+            .map(|&addr| Destination::get("OSC", addr))
+            .collect(),
+        Transport::Tcp => Vec::new(),
+    };
+
+    // Errors that come back from a destination are reported on the socket that sent to it, on
+    // some OSes. They don't say where they came from, though, so they're only of use if there's
+    // just one destination.
+    let icmp_destination = match (config.output.bind_address, &destinations[..]) {
+        (None, [destination]) => Some(destination.clone()),
+        _ => None,
+    };
+
+    let send_socket = match bind_address {
+        Some(address) => {
+            let send_socket =
+                RebindingSocket::bind_with("OSC output", address, output_options).await?;
+
+            let local_addr = send_socket
+                .local_addr()
+                .expect("Failed to get local address of UdpSocket");
+            log::info!("OSC output will be sent from {local_addr}.");
+
+            send_socket
+        }
+
+        None => socket.clone(),
+    };
 
-    let socket = Arc::new(socket);
     let (inbound_tx, inbound_rx) = smol::channel::bounded(16);
     let (outbound_tx, outbound_rx) = smol::channel::bounded(16);
+    let (recycle_tx, recycle_rx) = smol::channel::bounded(16);
+    let delivery = Arc::new(Delivery::default());
+
+    let _probe_task = config
+        .output
+        .probe_interval
+        .filter(|_| !destinations.is_empty())
+        .map(|interval| exec.spawn(reachability::probe(destinations.clone(), interval)));
 
     let mut recv_task = exec
-        .spawn(run_recv(socket.clone(), inbound_tx, cancel_rx))
+        .spawn(run_recv(
+            socket.clone(),
+            inbound_tx,
+            icmp_destination,
+            cancel_rx,
+        ))
         .fuse();
     let mut input_task = exec
         .spawn(input::run(
             config.input,
-            mappings.clone(),
+            profiles.clone(),
             inbound_rx,
             value_tx,
         ))
@@ -59,69 +129,100 @@ pub async fn run(
     let mut output_task = exec
         .spawn(output::run(
             config.output,
-            mappings.clone(),
+            profiles,
             value_rx,
             outbound_tx,
+            recycle_rx,
+            delivery.clone(),
+            stats.clone(),
         ))
         .fuse();
-    let mut send_task = exec
-        .spawn(run_send(socket, remote_addr, outbound_rx))
-        .fuse();
+    let mut send_task = match transport {
+        Transport::Udp => exec.spawn(run_send(
+            send_socket,
+            destinations,
+            outbound_rx,
+            recycle_tx,
+            delivery,
+            stats,
+        )),
+        Transport::Tcp => exec.spawn(tcp::run_send(
+            remote_addrs,
+            outbound_rx,
+            recycle_tx,
+            delivery,
+            stats,
+        )),
+    }
+    .fuse();
 
-    log::info!("OSC task has started. Listening for input on {local_addr}, sending output to {remote_addr}.");
+    log::info!("OSC task has started. Listening for input on {local_addr}, sending output to {remote_desc} via {transport}.");
 
-    let mut result = Ok(());
     loop {
         futures::select! {
             task_result = recv_task => {
                 if let Err(e) = task_result {
                     log::error!("OSC receive task has stopped with an error: {e}");
-                    result = result.and(Err(e));
+                    break Err(e);
                 }
             },
 
             task_result = input_task => {
                 if let Err(e) = task_result {
                     log::error!("OSC input processing task has stopped with an error: {e}");
-                    result = result.and(Err(e));
+                    break Err(e);
                 }
             },
 
             task_result = output_task => {
                 if let Err(e) = task_result {
                     log::error!("OSC output processing task has stopped with an error: {e}");
-                    result = result.and(Err(e));
+                    break Err(e);
                 }
             },
 
             task_result = send_task => {
                 if let Err(e) = task_result {
                     log::error!("OSC send task has stopped with an error: {e}");
-                    result = result.and(Err(e));
+                    break Err(e);
                 }
             },
 
-            complete => break result,
+            complete => break Ok(()),
         }
     }
 }
 
 async fn run_recv(
-    socket: Arc<UdpSocket>,
+    socket: Arc<RebindingSocket>,
     inbound_tx: ChannelTx<rosc::OscPacket>,
+    icmp_destination: Option<Arc<Destination>>,
     cancel_rx: ChannelRx<()>,
 ) -> AnyResult<()> {
     let mut buffer = vec![0u8; 16384];
     log::info!("OSC receive task started.");
 
     loop {
-        let Ok((len, addr)) = futures::select_biased!{
+        let result = futures::select_biased!{
 			_ = cancel_rx.recv().fuse() => {
 				log::info!("OSC receive task stopping (shutdown).");
 				break Ok(());
 			},
 			result = socket.recv_from(&mut buffer).fuse() => result,
-		} else { continue };
+		};
+
+        let (len, addr) = match result {
+            Ok(r) => r,
+            Err(e) => {
+                if let Some(destination) = icmp_destination
+                    .as_ref()
+                    .filter(|_| reachability::is_icmp_error(&e))
+                {
+                    destination.failed(&e);
+                }
+                continue;
+            }
+        };
 
         log::debug!("Received {len} bytes of data from {addr}.");
 
@@ -139,21 +240,53 @@ async fn run_recv(
 }
 
 async fn run_send(
-    socket: Arc<UdpSocket>,
-    addr: SocketAddr,
-    outbound_rx: ChannelRx<Vec<u8>>,
+    socket: Arc<RebindingSocket>,
+    destinations: Vec<Arc<Destination>>,
+    outbound_rx: ChannelRx<Outbound>,
+    recycle_tx: ChannelTx<Outbound>,
+    delivery: Arc<Delivery>,
+    stats: Arc<OutputStats>,
 ) -> AnyResult<()> {
     log::info!("OSC send task started.");
     loop {
-        let Ok(data) = outbound_rx.recv().await else {
+        let Ok(outbound) = outbound_rx.recv().await else {
 			log::info!("OSC send task stopping (output processing task has stopped).");
 			break Ok(());
 		};
 
-        log::debug!("Sending {} bytes of data to {}.", data.len(), addr);
+        for data in outbound.packets() {
+            let mut any_sent = false;
+            for destination in &destinations {
+                let addr = destination.addr();
+                log::debug!("Sending {} bytes of data to {}.", data.len(), addr);
+
+                match socket.send_to(data, addr).await {
+                    Ok(_) => {
+                        destination.sent();
+                        any_sent = true;
 
-        if let Err(e) = socket.send_to(&data, addr).await {
-            log::error!("Failed to send OSC data to {addr}: {e}");
+                        // Data can be sent without an error even if it isn't arriving.
+                        if destination.is_reachable() {
+                            delivery.sent(addr);
+                        } else {
+                            delivery.failed(addr);
+                        }
+                    }
+                    Err(e) => {
+                        log::error!("Failed to send OSC data to {addr}: {e}");
+                        stats.send_failed();
+                        destination.failed(&e);
+                        delivery.failed(addr);
+                    }
+                }
+            }
+
+            if any_sent {
+                stats.sent();
+            }
         }
+
+        // If the output task already has enough buffers, this one is dropped.
+        let _ = recycle_tx.try_send(outbound);
     }
 }
@@ -1,11 +1,22 @@
 use std::borrow::Cow;
+use std::fmt::Display;
 use std::marker::PhantomData;
-use std::net::{Ipv4Addr, SocketAddr};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
 
 use linear_map::LinearMap;
-use serde::{de::Error as _, Deserialize, Deserializer};
+use schemars::JsonSchema;
+use serde::ser::SerializeMap as _;
+use serde::{de::Error as _, Deserialize, Deserializer, Serialize, Serializer};
 
-#[derive(Clone, Debug, Default, Deserialize)]
+use crate::button::Threshold;
+use crate::check::Report;
+use crate::config::{
+    AxisInputConfig as AnyAxisInput, ButtonInputConfig as AnyButtonInput, MappingConfig,
+};
+use crate::rebind::SocketOptions;
+use crate::timetag::Timetag;
+
+#[derive(Clone, Debug, Default, Deserialize, JsonSchema, Serialize)]
 #[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
 pub struct Config {
     enabled: bool,
@@ -13,22 +24,66 @@ pub struct Config {
     pub(super) output: OutputConfig,
 }
 
-#[derive(Clone, Debug, Deserialize)]
+#[derive(Clone, Debug, Deserialize, JsonSchema, Serialize)]
 #[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
 pub(super) struct InputConfig {
     pub(super) address: SocketAddr,
+
+    /// A message to this address with a profile's name switches to that profile.
+    pub(super) profile_address: Option<String>,
+
+    #[serde(deserialize_with = "crate::units::optional_seconds")]
+    #[schemars(schema_with = "crate::units::optional_seconds_schema")]
+    pub(super) stall_timeout: Option<f64>,
 }
 
-#[derive(Clone, Debug, Deserialize)]
+#[derive(Clone, Debug, Deserialize, JsonSchema, Serialize)]
 #[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
 pub(super) struct OutputConfig {
-    pub(super) address: SocketAddr,
+    #[serde(deserialize_with = "parse_addresses")]
+    #[schemars(with = "Addresses")]
+    pub(super) address: Vec<SocketAddr>,
+    pub(super) bind_address: Option<SocketAddr>,
+    pub(super) transport: Transport,
+
+    /// How many hops messages sent to a multicast group may travel.
+    pub(super) multicast_ttl: Option<u32>,
+    pub(super) multicast_interface: Option<MulticastInterface>,
+    pub(super) bundle: bool,
+    pub(super) timetag: Timetag,
+
+    #[serde(deserialize_with = "crate::units::optional_seconds")]
+    #[schemars(schema_with = "crate::units::optional_seconds_schema")]
+    pub(super) probe_interval: Option<f64>,
+
+    #[serde(deserialize_with = "crate::units::optional_seconds")]
+    #[schemars(schema_with = "crate::units::optional_seconds_schema")]
+    pub(super) retry_interval: Option<f64>,
 
     pub(super) pre_bundle: BundleConfig<NullInput>,
     pub(super) post_bundle: BundleConfig<NullInput>,
 }
 
-#[derive(Clone, Debug, Default, Deserialize)]
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, JsonSchema, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub(super) enum Transport {
+    #[default]
+    Udp,
+
+    /// OSC 1.1 stream transport, with packets framed using SLIP.
+    Tcp,
+}
+
+/// The network interface that multicast is sent from: the address of an IPv4 interface, or the
+/// index of an IPv6 one.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, JsonSchema, PartialEq, Serialize)]
+#[serde(untagged)]
+pub(super) enum MulticastInterface {
+    Address(Ipv4Addr),
+    Index(u32),
+}
+
+#[derive(Clone, Debug, Default, Deserialize, JsonSchema, Serialize)]
 #[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
 pub struct AxisInputConfig {
     pub(super) address: String,
@@ -37,29 +92,71 @@ pub struct AxisInputConfig {
     pub(super) range: [f64; 2],
 }
 
-#[derive(Clone, Debug, Default, Deserialize)]
+/// An axis that's moved in steps by each OSC message, e.g. from a rotary encoder that sends +1 and
+/// -1, rather than set to the value in it.
+#[derive(Clone, Debug, Deserialize, JsonSchema, Serialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct RelativeAxisInputConfig {
+    pub(super) address: String,
+
+    /// How far the axis moves for a value of 1, as a part of its whole range.
+    pub(super) step: f64,
+    /// Whether moving past one end of the range comes back around from the other.
+    pub(super) wrap: bool,
+    pub(super) initial: f64,
+
+    /// A message to this address moves the axis back to `initial`.
+    pub(super) reset_address: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, JsonSchema, Serialize)]
 #[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
 pub struct AxisOutputConfig {
     pub(super) on_update: BundleConfig<FloatRangeInput>,
+    pub(super) viewer: Option<ViewerOutputConfig>,
 }
 
-#[derive(Clone, Debug, Default, Deserialize)]
+/// A shorthand for the message that the Viewer turns its wheel by: the axis' value as a single
+/// float, remapped to the wheel's rotation in degrees.
+#[derive(Clone, Debug, Deserialize, JsonSchema, Serialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct ViewerOutputConfig {
+    pub(super) address: String,
+    pub(super) range: [f32; 2],
+}
+
+#[derive(Clone, Debug, Default, Deserialize, JsonSchema, Serialize)]
 #[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
 pub struct ButtonInputConfig {
     pub(super) address: String,
 }
 
-#[derive(Clone, Debug, Default, Deserialize)]
+/// A button that's pressed by an OSC value moving past a threshold.
+#[derive(Clone, Debug, Deserialize, JsonSchema, Serialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct AxisButtonInputConfig {
+    pub(super) address: String,
+    pub(super) range: [f64; 2],
+    pub(super) threshold: f64,
+    pub(super) hysteresis: f64,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, JsonSchema, Serialize)]
 #[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
 pub struct ButtonOutputConfig {
     pub(super) on_press: BundleConfig<BoolInput>,
     pub(super) on_release: BundleConfig<BoolInput>,
     pub(super) on_update: BundleConfig<BoolInput>,
+    /// Sent when the button is held for its long-press-ms.
+    pub(super) on_long_press: BundleConfig<BoolInput>,
+    /// Sent when the button is pressed again within its double-press-ms.
+    pub(super) on_double_press: BundleConfig<BoolInput>,
 }
 
-#[derive(Clone, Debug, Deserialize)]
+#[derive(Clone, Debug, Deserialize, JsonSchema, Serialize)]
 #[serde(transparent)]
 pub(super) struct BundleConfig<I> {
+    #[schemars(with = "std::collections::HashMap<String, Vec<OscParameter<I>>>")]
     messages: LinearMap<String, Vec<OscParameter<I>>>,
 }
 
@@ -94,6 +191,41 @@ impl<I: OscInput> OscParameter<I> {
     }
 }
 
+/// The forms that an [`OscParameter`] may be written in, for the configuration schema.
+#[allow(unused)]
+#[derive(JsonSchema)]
+#[serde(untagged)]
+enum OscParameterSchema<I> {
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    String(String),
+    Tagged(TaggedOscParameterSchema<I>),
+}
+
+#[allow(unused)]
+#[derive(JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+enum TaggedOscParameterSchema<I> {
+    Bool(bool),
+    Int(i32),
+    Long(i64),
+    Float(f32),
+    Double(f64),
+    String(String),
+    Input(I),
+}
+
+impl<I: JsonSchema> JsonSchema for OscParameter<I> {
+    fn schema_name() -> String {
+        format!("OscParameter_for_{}", I::schema_name())
+    }
+
+    fn json_schema(gen: &mut schemars::gen::SchemaGenerator) -> schemars::schema::Schema {
+        OscParameterSchema::<I>::json_schema(gen)
+    }
+}
+
 struct OscParameterVisitor<'de, I: Deserialize<'de>>(PhantomData<(&'de (), I)>);
 
 impl<'de, I: Deserialize<'de>> Deserialize<'de> for OscParameter<I> {
@@ -102,6 +234,34 @@ impl<'de, I: Deserialize<'de>> Deserialize<'de> for OscParameter<I> {
     }
 }
 
+// Only those that would be read back as something else are written as tagged values.
+impl<I: Serialize> Serialize for OscParameter<I> {
+    fn serialize<S: Serializer>(&self, ser: S) -> Result<S::Ok, S::Error> {
+        fn tagged<S: Serializer, T: Serialize>(
+            ser: S,
+            tag: &str,
+            value: &T,
+        ) -> Result<S::Ok, S::Error> {
+            let mut map = ser.serialize_map(Some(1))?;
+            map.serialize_entry(tag, value)?;
+            map.end()
+        }
+
+        match *self {
+            OscParameter::Int(i) => i.serialize(ser),
+            OscParameter::Long(i) => tagged(ser, "long", &i),
+
+            OscParameter::Float(f) => f.serialize(ser),
+            OscParameter::Double(f) => tagged(ser, "double", &f),
+
+            OscParameter::Bool(b) => b.serialize(ser),
+            OscParameter::String(ref s) => s.serialize(ser),
+
+            OscParameter::Input(ref i) => tagged(ser, "input", i),
+        }
+    }
+}
+
 impl<'de, I: Deserialize<'de>> serde::de::Visitor<'de> for OscParameterVisitor<'de, I> {
     type Value = OscParameter<I>;
 
@@ -219,11 +379,20 @@ pub trait OscInput {
     fn to_rosc(&self, raw: &Self::Param) -> rosc::OscType;
 }
 
-impl<I: OscInput> BundleConfig<I> {
-    pub fn len(&self) -> usize {
-        self.messages.len()
+impl<I> BundleConfig<I> {
+    fn validate(&self, path: &str, report: &mut Report) {
+        for address in self.messages.keys() {
+            if let Err(e) = rosc::address::verify_address(address) {
+                report.warning(
+                    format!("{path}.\"{address}\""),
+                    format!("Invalid OSC address ({e})"),
+                );
+            }
+        }
     }
+}
 
+impl<I: OscInput> BundleConfig<I> {
     pub fn to_messages<'m>(
         &'m self,
         raw: &'m I::Param,
@@ -235,11 +404,41 @@ impl<I: OscInput> BundleConfig<I> {
                 args: params.iter().map(|p| p.to_rosc(raw)).collect(),
             })
     }
+
+    /// Updates messages built by [`BundleConfig::to_messages`] for a new input value. Only the
+    /// arguments that depend on the input are replaced, so that nothing needs to be allocated.
+    pub fn update_messages(&self, raw: &I::Param, packets: &mut [rosc::OscPacket]) {
+        for ((_, params), packet) in std::iter::zip(self.messages.iter(), packets) {
+            let rosc::OscPacket::Message(message) = packet else { unreachable!() };
+            for (param, arg) in std::iter::zip(params, &mut message.args) {
+                if let OscParameter::Input(ref input) = *param {
+                    *arg = input.to_rosc(raw);
+                }
+            }
+        }
+    }
 }
 
 #[derive(Clone, Debug, Default, Deserialize)]
 pub struct BoolInput;
 
+impl JsonSchema for BoolInput {
+    fn schema_name() -> String {
+        "BoolInput".into()
+    }
+
+    fn json_schema(gen: &mut schemars::gen::SchemaGenerator) -> schemars::schema::Schema {
+        empty_input_schema(gen)
+    }
+}
+
+// This is written as `[]` rather than as a unit, which TOML can't represent.
+impl Serialize for BoolInput {
+    fn serialize<S: Serializer>(&self, ser: S) -> Result<S::Ok, S::Error> {
+        [(); 0].serialize(ser)
+    }
+}
+
 impl OscInput for BoolInput {
     type Param = bool;
     fn to_rosc(&self, raw: &Self::Param) -> rosc::OscType {
@@ -247,8 +446,8 @@ impl OscInput for BoolInput {
     }
 }
 
-#[derive(Clone, Debug, Deserialize)]
-#[serde(from = "(f32, f32)")]
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(from = "(f32, f32)", into = "(f32, f32)")]
 pub struct FloatRangeInput(f32, f32);
 
 impl From<(f32, f32)> for FloatRangeInput {
@@ -257,6 +456,22 @@ impl From<(f32, f32)> for FloatRangeInput {
     }
 }
 
+impl From<FloatRangeInput> for (f32, f32) {
+    fn from(FloatRangeInput(a, range): FloatRangeInput) -> Self {
+        (a, a + range)
+    }
+}
+
+impl JsonSchema for FloatRangeInput {
+    fn schema_name() -> String {
+        "FloatRangeInput".into()
+    }
+
+    fn json_schema(gen: &mut schemars::gen::SchemaGenerator) -> schemars::schema::Schema {
+        <(f32, f32)>::json_schema(gen)
+    }
+}
+
 impl OscInput for FloatRangeInput {
     type Param = f32;
     fn to_rosc(&self, raw: &Self::Param) -> rosc::OscType {
@@ -267,6 +482,22 @@ impl OscInput for FloatRangeInput {
 #[derive(Clone, Debug, Deserialize)]
 pub struct NullInput;
 
+impl JsonSchema for NullInput {
+    fn schema_name() -> String {
+        "NullInput".into()
+    }
+
+    fn json_schema(gen: &mut schemars::gen::SchemaGenerator) -> schemars::schema::Schema {
+        empty_input_schema(gen)
+    }
+}
+
+impl Serialize for NullInput {
+    fn serialize<S: Serializer>(&self, ser: S) -> Result<S::Ok, S::Error> {
+        [(); 0].serialize(ser)
+    }
+}
+
 impl OscInput for NullInput {
     type Param = ();
     fn to_rosc(&self, _: &Self::Param) -> rosc::OscType {
@@ -274,16 +505,294 @@ impl OscInput for NullInput {
     }
 }
 
+/// The schema for an input that has no settings, which is written as `[]` (since TOML can't
+/// represent a unit), but may also be `null` in formats that can.
+fn empty_input_schema(gen: &mut schemars::gen::SchemaGenerator) -> schemars::schema::Schema {
+    schemars::schema::SchemaObject {
+        subschemas: Some(Box::new(schemars::schema::SubschemaValidation {
+            any_of: Some(vec![
+                gen.subschema_for::<[(); 0]>(),
+                gen.subschema_for::<()>(),
+            ]),
+            ..Default::default()
+        })),
+        ..Default::default()
+    }
+    .into()
+}
+
 impl Config {
     pub fn enabled(&self) -> bool {
         self.enabled
     }
+
+    pub fn validate(&self, mappings: &MappingConfig, report: &mut Report) {
+        if self.enabled {
+            report.bind("osc.input.address", self.input.address);
+            crate::rebind::validate_stall_timeout(
+                "osc.input.stall-timeout",
+                self.input.address,
+                self.input.stall_timeout,
+                report,
+            );
+
+            if let Some(ref address) = self.input.profile_address {
+                if let Err(e) = rosc::address::verify_address(address) {
+                    report.error(
+                        "osc.input.profile-address",
+                        format!("Invalid OSC address ({e})"),
+                    );
+                }
+            }
+
+            if self.output.address.is_empty() {
+                report.error(
+                    "osc.output.address",
+                    "At least one address must be given to send output to",
+                );
+            }
+            for &address in &self.output.address {
+                report.target("osc.output.address", address);
+            }
+
+            if let Some(address) = self.output.bind_address {
+                if self.output.transport == Transport::Udp {
+                    report.bind("osc.output.bind-address", address);
+                } else {
+                    report.warning(
+                        "osc.output.bind-address",
+                        "The bind address is only used when sending over UDP, so it will be ignored",
+                    );
+                }
+            }
+
+            self.output.validate_multicast(report);
+
+            if !self.output.bundle && !self.output.timetag.is_immediate() {
+                report.warning(
+                    "osc.output.timetag",
+                    "Timetags are only sent on bundles, so this will be ignored",
+                );
+            }
+
+            if let Some(interval) = self.output.probe_interval {
+                if self.output.transport != Transport::Udp {
+                    report.warning(
+                        "osc.output.probe-interval",
+                        "Probing is only done when sending over UDP, so it will be ignored",
+                    );
+                } else if interval <= 0.0 {
+                    report.error(
+                        "osc.output.probe-interval",
+                        "Probe interval must be positive",
+                    );
+                }
+            }
+
+            if self.output.retry_interval.is_some_and(|i| i <= 0.0) {
+                report.error(
+                    "osc.output.retry-interval",
+                    "Retry interval must be positive",
+                );
+            }
+        }
+
+        self.output
+            .pre_bundle
+            .validate("osc.output.pre-bundle", report);
+        self.output
+            .post_bundle
+            .validate("osc.output.post-bundle", report);
+
+        for (id, axis) in &mappings.axis {
+            for (i, input) in axis.input.iter().enumerate() {
+                if let AnyAxisInput::Osc(c) = input {
+                    let path = format!("axis.{id}.input[{i}].osc");
+                    self.validate_input(&path, &c.address, report);
+                } else if let AnyAxisInput::OscRelative(c) = input {
+                    let path = format!("axis.{id}.input[{i}].osc-relative");
+                    self.validate_input(&path, &c.address, report);
+                    c.validate(&path, report);
+
+                    if let Some(ref address) = c.reset_address {
+                        self.validate_address(&format!("{path}.reset-address"), address, report);
+                    }
+                }
+            }
+
+            let path = format!("axis.{id}.output.osc");
+            let output = &axis.output.osc;
+            output
+                .on_update
+                .validate(&format!("{path}.on-update"), report);
+
+            if let Some(ref viewer) = output.viewer {
+                let path = format!("{path}.viewer");
+                self.validate_address(&format!("{path}.address"), &viewer.address, report);
+
+                if output.on_update.messages.contains_key(&viewer.address) {
+                    report.warning(
+                        &path,
+                        "This address is also in on-update, whose message it replaces",
+                    );
+                }
+            }
+        }
+
+        for (id, button) in &mappings.button {
+            for (i, input) in button.input.iter().enumerate() {
+                if let AnyButtonInput::Osc(c) = input {
+                    let path = format!("button.{id}.input[{i}].osc");
+                    self.validate_input(&path, &c.address, report);
+                } else if let AnyButtonInput::OscAxis(c) = input {
+                    let path = format!("button.{id}.input[{i}].osc-axis");
+                    self.validate_input(&path, &c.address, report);
+                    c.threshold().validate(&path, report);
+                }
+            }
+
+            let path = format!("button.{id}.output.osc");
+            let output = &button.output.osc;
+            output
+                .on_press
+                .validate(&format!("{path}.on-press"), report);
+            output
+                .on_release
+                .validate(&format!("{path}.on-release"), report);
+            output
+                .on_update
+                .validate(&format!("{path}.on-update"), report);
+            output
+                .on_long_press
+                .validate(&format!("{path}.on-long-press"), report);
+            output
+                .on_double_press
+                .validate(&format!("{path}.on-double-press"), report);
+
+            if button.long_press_ms.is_none() && !output.on_long_press.messages.is_empty() {
+                report.warning(
+                    format!("{path}.on-long-press"),
+                    "The button has no long-press-ms, so this will never be sent",
+                );
+            }
+
+            if button.double_press_ms.is_none() && !output.on_double_press.messages.is_empty() {
+                report.warning(
+                    format!("{path}.on-double-press"),
+                    "The button has no double-press-ms, so this will never be sent",
+                );
+            }
+        }
+    }
+
+    fn validate_input(&self, path: &str, address: &str, report: &mut Report) {
+        if !self.enabled {
+            report.warning(
+                path,
+                "OSC is not enabled, so this input will never be received",
+            );
+        }
+
+        self.validate_address(&format!("{path}.address"), address, report);
+    }
+
+    fn validate_address(&self, path: &str, address: &str, report: &mut Report) {
+        if let Err(e) = rosc::address::verify_address(address) {
+            let message = format!("Invalid OSC address ({e})");
+
+            // The address is only parsed when the OSC task starts.
+            if self.enabled {
+                report.error(path, message);
+            } else {
+                report.warning(path, message);
+            }
+        }
+    }
 }
 
 impl Default for InputConfig {
     fn default() -> Self {
         Self {
             address: SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), 0),
+            profile_address: None,
+            stall_timeout: None,
+        }
+    }
+}
+
+impl OutputConfig {
+    fn is_multicast(&self) -> bool {
+        self.address.iter().any(|a| a.ip().is_multicast())
+    }
+
+    /// The options for the socket that output is sent from.
+    pub(super) fn socket_options(&self) -> SocketOptions {
+        if !self.is_multicast() {
+            return SocketOptions::default();
+        }
+
+        SocketOptions {
+            stall_timeout: None,
+            multicast_ttl: self.multicast_ttl,
+            multicast_interface_v4: match self.multicast_interface {
+                Some(MulticastInterface::Address(address)) => Some(address),
+                _ => None,
+            },
+            multicast_interface_v6: match self.multicast_interface {
+                Some(MulticastInterface::Index(index)) => Some(index),
+                _ => None,
+            },
+        }
+    }
+
+    fn validate_multicast(&self, report: &mut Report) {
+        if !self.is_multicast() {
+            let keys = [
+                ("multicast-ttl", self.multicast_ttl.is_some()),
+                ("multicast-interface", self.multicast_interface.is_some()),
+            ];
+
+            for (key, set) in keys {
+                if set {
+                    report.warning(
+                        format!("osc.output.{key}"),
+                        "None of the output addresses is a multicast group, so this will be ignored",
+                    );
+                }
+            }
+
+            return;
+        }
+
+        if self.transport != Transport::Udp {
+            report.error(
+                "osc.output.address",
+                "Multicast groups can only be sent to over UDP",
+            );
+        }
+
+        if matches!(self.multicast_ttl, Some(0 | 256..)) {
+            report.error(
+                "osc.output.multicast-ttl",
+                "Multicast TTL must be from 1 to 255",
+            );
+        }
+
+        for address in self.address.iter().filter(|a| a.ip().is_multicast()) {
+            let message = match (address.ip(), self.multicast_interface) {
+                (IpAddr::V4(_), Some(MulticastInterface::Index(_))) => {
+                    "is an IPv4 group, so the interface must be given as its IPv4 address"
+                }
+                (IpAddr::V6(_), Some(MulticastInterface::Address(_))) => {
+                    "is an IPv6 group, so the interface must be given as its index"
+                }
+                _ => continue,
+            };
+
+            report.error(
+                "osc.output.multicast-interface",
+                format!("Multicast group {address} {message}"),
+            );
         }
     }
 }
@@ -291,7 +800,15 @@ impl Default for InputConfig {
 impl Default for OutputConfig {
     fn default() -> Self {
         Self {
-            address: SocketAddr::new(Ipv4Addr::LOCALHOST.into(), 19794),
+            address: vec![SocketAddr::new(Ipv4Addr::LOCALHOST.into(), 19794)],
+            bind_address: None,
+            transport: Transport::default(),
+            multicast_ttl: None,
+            multicast_interface: None,
+            bundle: true,
+            timetag: Timetag::default(),
+            probe_interval: None,
+            retry_interval: None,
 
             pre_bundle: BundleConfig::default(),
             post_bundle: BundleConfig::default(),
@@ -299,12 +816,109 @@ impl Default for OutputConfig {
     }
 }
 
+impl Display for Transport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Transport::Udp => write!(f, "UDP"),
+            Transport::Tcp => write!(f, "TCP"),
+        }
+    }
+}
+
+#[derive(Deserialize, JsonSchema)]
+#[serde(untagged, expecting = "a socket address or a list of socket addresses")]
+enum Addresses {
+    One(SocketAddr),
+    Many(Vec<SocketAddr>),
+}
+
+fn parse_addresses<'de, D: Deserializer<'de>>(de: D) -> Result<Vec<SocketAddr>, D::Error> {
+    match Addresses::deserialize(de)? {
+        Addresses::One(addr) => Ok(vec![addr]),
+        Addresses::Many(addrs) => Ok(addrs),
+    }
+}
+
 impl AxisInputConfig {
     fn default_range() -> [f64; 2] {
         [0.0, 1.0]
     }
 }
 
+impl RelativeAxisInputConfig {
+    fn validate(&self, path: &str, report: &mut Report) {
+        if self.step == 0.0 || !self.step.is_finite() {
+            report.error(format!("{path}.step"), "Step must be a non-zero number");
+        }
+
+        if !(0.0..=1.0).contains(&self.initial) {
+            report.error(
+                format!("{path}.initial"),
+                "Initial value must be from 0 to 1",
+            );
+        }
+    }
+}
+
+impl Default for RelativeAxisInputConfig {
+    fn default() -> Self {
+        RelativeAxisInputConfig {
+            address: String::new(),
+            step: 0.01,
+            wrap: false,
+            initial: 0.5,
+            reset_address: None,
+        }
+    }
+}
+
+impl AxisOutputConfig {
+    /// The on-update messages, followed by the Viewer's message if there is one.
+    pub(super) fn on_update(&self) -> Cow<'_, BundleConfig<FloatRangeInput>> {
+        let Some(ref viewer) = self.viewer else {
+            return Cow::Borrowed(&self.on_update);
+        };
+
+        let mut bundle = self.on_update.clone();
+        let [min, max] = viewer.range;
+        bundle.messages.insert(
+            viewer.address.clone(),
+            vec![OscParameter::Input((min, max).into())],
+        );
+
+        Cow::Owned(bundle)
+    }
+}
+
+impl Default for ViewerOutputConfig {
+    fn default() -> Self {
+        ViewerOutputConfig {
+            address: String::from("/wheel/rotation"),
+            range: [-450.0, 450.0],
+        }
+    }
+}
+
+impl AxisButtonInputConfig {
+    pub(super) fn threshold(&self) -> Threshold {
+        Threshold {
+            threshold: self.threshold,
+            hysteresis: self.hysteresis,
+        }
+    }
+}
+
+impl Default for AxisButtonInputConfig {
+    fn default() -> Self {
+        AxisButtonInputConfig {
+            address: String::new(),
+            range: AxisInputConfig::default_range(),
+            threshold: crate::button::threshold_default(),
+            hysteresis: crate::button::hysteresis_default(),
+        }
+    }
+}
+
 impl<I> Default for BundleConfig<I> {
     fn default() -> Self {
         Self {
@@ -312,3 +926,105 @@ impl<I> Default for BundleConfig<I> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn axis_output(toml: &str) -> AxisOutputConfig {
+        toml::from_str(toml).unwrap()
+    }
+
+    /// The packets sent for an axis' value, encoded as they would be without a bundle.
+    fn packets(config: &AxisOutputConfig, value: f32) -> Vec<Vec<u8>> {
+        (config.on_update().to_messages(&value))
+            .map(|m| rosc::encoder::encode(&rosc::OscPacket::Message(m)).unwrap())
+            .collect()
+    }
+
+    /// A message with a single float, encoded by hand.
+    fn float_message(address: &str, value: f32) -> Vec<u8> {
+        let mut packet = address.as_bytes().to_vec();
+        packet.resize((address.len() + 4) & !3, 0);
+        packet.extend_from_slice(b",f\0\0");
+        packet.extend_from_slice(&value.to_be_bytes());
+        packet
+    }
+
+    fn rotation(value: f32) -> Vec<u8> {
+        float_message("/wheel/rotation", value)
+    }
+
+    #[test]
+    fn rejects_empty_output_address() {
+        let errors = |toml: &str| {
+            let config: Config = toml::from_str(toml).unwrap();
+            let mut report = Report::default();
+            config.validate(&MappingConfig::default(), &mut report);
+            (report.finish().into_iter())
+                .filter(|p| p.path == "osc.output.address")
+                .count()
+        };
+
+        assert_eq!(errors("enabled = true"), 0);
+        assert_eq!(
+            errors("enabled = true\noutput.address = ['127.0.0.1:19794']"),
+            0
+        );
+        assert_eq!(errors("enabled = true\noutput.address = []"), 1);
+        assert_eq!(errors("enabled = false\noutput.address = []"), 0);
+    }
+
+    #[test]
+    fn viewer_message_at_boundaries() {
+        let config =
+            axis_output(r#"viewer = { address = "/wheel/rotation", range = [-450, 450] }"#);
+
+        assert_eq!(packets(&config, 0.0), [rotation(-450.0)]);
+        assert_eq!(packets(&config, 0.5), [rotation(0.0)]);
+        assert_eq!(packets(&config, 1.0), [rotation(450.0)]);
+    }
+
+    #[test]
+    fn viewer_message_with_default_range() {
+        let config = axis_output("viewer = {}");
+
+        assert_eq!(packets(&config, 0.0), [rotation(-450.0)]);
+        assert_eq!(packets(&config, 1.0), [rotation(450.0)]);
+    }
+
+    #[test]
+    fn viewer_message_with_reversed_range() {
+        let config = axis_output(r#"viewer = { address = "/wheel", range = [90, -270] }"#);
+
+        assert_eq!(packets(&config, 0.0), [float_message("/wheel", 90.0)]);
+        assert_eq!(packets(&config, 0.25), [float_message("/wheel", 0.0)]);
+        assert_eq!(packets(&config, 1.0), [float_message("/wheel", -270.0)]);
+    }
+
+    #[test]
+    fn viewer_message_is_sent_after_on_update() {
+        let config = axis_output(
+            r#"
+                viewer = { address = "/wheel/rotation", range = [-450, 450] }
+                on-update = { "/wheel/raw" = [{ input = [0, 1] }] }
+            "#,
+        );
+
+        assert_eq!(
+            packets(&config, 1.0),
+            [float_message("/wheel/raw", 1.0), rotation(450.0),]
+        );
+    }
+
+    /// Values outside of 0 to 1 are clamped by inputs (see `AxisScaling`), not here, so the range
+    /// is extended rather than clamped.
+    #[test]
+    fn viewer_message_is_not_clamped() {
+        let config =
+            axis_output(r#"viewer = { address = "/wheel/rotation", range = [-450, 450] }"#);
+
+        assert_eq!(packets(&config, -0.5), [rotation(-900.0)]);
+        assert_eq!(packets(&config, 1.5), [rotation(900.0)]);
+    }
+}
@@ -5,22 +5,36 @@ use async_broadcast::Sender as BroadcastTx;
 use smol::channel::Receiver as ChannelRx;
 use string_cache::DefaultAtom;
 
-use super::config::InputConfig;
-use crate::config::{AxisInputConfig, ButtonInputConfig, MappingConfig};
-use crate::output::OutputEvent;
+use super::config::{InputConfig, RelativeAxisInputConfig};
+use crate::button::{ButtonStates, Threshold};
+use crate::config::{AxisInputConfig, ButtonInputConfig};
+use crate::output::{OutputEvent, Timestamped};
+use crate::profile::Profiles;
+use crate::route::MappingId;
+use crate::stats;
 
 pub(super) async fn run(
-    _config: InputConfig,
-    mappings: Arc<MappingConfig>,
+    config: InputConfig,
+    profiles: Arc<Profiles>,
     inbound_rx: ChannelRx<rosc::OscPacket>,
-    value_tx: BroadcastTx<OutputEvent>,
+    value_tx: BroadcastTx<Timestamped<OutputEvent>>,
 ) -> AnyResult<()> {
     log::info!("OSC input processing task starting...");
 
+    // Inputs don't follow the profile, so they're always those of the default one.
+    let mappings = profiles.default().mappings.clone();
+    let profile_address = config
+        .profile_address
+        .map(rosc::address::OscAddress::new)
+        .transpose()
+        .context("Invalid OSC profile address in configuration")?;
+
     let mut axis_mappings = Vec::new();
     let mut button_mappings = Vec::new();
+    let mut axis_button_mappings = Vec::new();
+    let mut relative_axes = Vec::new();
 
-    for (id, axes) in &mappings.axis {
+    for (id, axes) in mappings.axes() {
         for axis in &axes.input {
             if let AxisInputConfig::Osc(c) = axis {
                 let address = rosc::address::OscAddress::new(c.address.to_string())
@@ -28,27 +42,46 @@ pub(super) async fn run(
                 let range = c.range;
 
                 axis_mappings.push((id.clone(), address, range));
+            } else if let AxisInputConfig::OscRelative(c) = axis {
+                relative_axes.push(RelativeAxis::new(id.clone(), c)?);
             }
         }
     }
 
-    for (id, buttons) in &mappings.button {
+    for (id, buttons) in mappings.buttons() {
         for button in &buttons.input {
             if let ButtonInputConfig::Osc(c) = button {
                 let address = rosc::address::OscAddress::new(c.address.to_string())
                     .context("Invalid OSC address in configuration")?;
                 button_mappings.push((id.clone(), address));
+            } else if let ButtonInputConfig::OscAxis(c) = button {
+                let address = rosc::address::OscAddress::new(c.address.to_string())
+                    .context("Invalid OSC address in configuration")?;
+                axis_button_mappings.push(AxisButton {
+                    id: id.clone(),
+                    address,
+                    range: c.range,
+                    threshold: c.threshold(),
+                    pressed: false,
+                });
             }
         }
     }
 
-    if axis_mappings.is_empty() && button_mappings.is_empty() {
+    if axis_mappings.is_empty()
+        && button_mappings.is_empty()
+        && axis_button_mappings.is_empty()
+        && relative_axes.is_empty()
+        && profile_address.is_none()
+    {
         log::info!("OSC input processing task stopped (no OSC inputs configured).");
         return Ok(());
     }
 
     log::info!("OSC input processing task started.");
+    let mut buttons = ButtonStates::new(mappings.clone(), value_tx.clone());
     let mut events = Vec::new();
+    let input_stats = stats::InputStats::new("OSC");
 
     loop {
         let Ok(packet) = inbound_rx.recv().await else {
@@ -56,14 +89,28 @@ pub(super) async fn run(
 			break;
 		};
 
-        collect_values(&packet, &axis_mappings, &button_mappings, &mut events);
+        // Switching profile comes first, so that the values that come with it are for the new one.
+        if let Some(ref address) = profile_address {
+            collect_profiles(&packet, address, &profiles, &mut events);
+        }
+
+        collect_values(
+            &packet,
+            &axis_mappings,
+            &button_mappings,
+            &mut axis_button_mappings,
+            &mut buttons,
+            &mut events,
+        );
+        collect_relative(&packet, &mut relative_axes, &mut events);
 
         if !events.is_empty() {
             events.push(OutputEvent::Flush);
         }
 
         for event in events.drain(..) {
-            if value_tx.broadcast(event).await.is_err() {
+            input_stats.event(&event);
+            if value_tx.broadcast(event.into()).await.is_err() {
                 log::info!("OSC input processing stopping (no remaining outputs).");
                 break;
             }
@@ -74,27 +121,102 @@ pub(super) async fn run(
     Ok(())
 }
 
+/// A button that's pressed by an OSC value moving past a threshold.
+struct AxisButton {
+    id: MappingId,
+    address: rosc::address::OscAddress,
+    range: [f64; 2],
+    threshold: Threshold,
+    pressed: bool,
+}
+
+/// An axis that's moved in steps by each message, rather than set to the value in it.
+struct RelativeAxis {
+    id: MappingId,
+    address: rosc::address::OscAddress,
+    reset_address: Option<rosc::address::OscAddress>,
+    step: f64,
+    wrap: bool,
+    initial: f64,
+    value: f64,
+}
+
+impl RelativeAxis {
+    fn new(id: MappingId, config: &RelativeAxisInputConfig) -> AnyResult<Self> {
+        let address = rosc::address::OscAddress::new(config.address.to_string())
+            .context("Invalid OSC address in configuration")?;
+        let reset_address = config
+            .reset_address
+            .as_ref()
+            .map(|a| rosc::address::OscAddress::new(a.to_string()))
+            .transpose()
+            .context("Invalid OSC reset address in configuration")?;
+
+        Ok(RelativeAxis {
+            id,
+            address,
+            reset_address,
+            step: config.step,
+            wrap: config.wrap,
+            initial: config.initial,
+            value: config.initial,
+        })
+    }
+
+    /// Moves the axis for a message, returning its new value if the message was for it.
+    fn handle(
+        &mut self,
+        matcher: &rosc::address::Matcher,
+        message: &rosc::OscMessage,
+    ) -> Option<f64> {
+        if self
+            .reset_address
+            .as_ref()
+            .is_some_and(|a| matcher.match_address(a))
+        {
+            self.value = self.initial;
+            return Some(self.value);
+        }
+
+        if !matcher.match_address(&self.address) {
+            return None;
+        }
+
+        let value = self.value + number(message.args.first())? * self.step;
+        self.value = if self.wrap {
+            value.rem_euclid(1.0)
+        } else {
+            value.clamp(0.0, 1.0)
+        };
+
+        Some(self.value)
+    }
+}
+
 fn collect_values(
     packet: &rosc::OscPacket,
-    axis_mappings: &[(DefaultAtom, rosc::address::OscAddress, [f64; 2])],
-    button_mappings: &[(DefaultAtom, rosc::address::OscAddress)],
+    axis_mappings: &[(MappingId, rosc::address::OscAddress, [f64; 2])],
+    button_mappings: &[(MappingId, rosc::address::OscAddress)],
+    axis_button_mappings: &mut [AxisButton],
+    buttons: &mut ButtonStates,
     into: &mut Vec<OutputEvent>,
 ) {
     match packet {
         rosc::OscPacket::Bundle(b) => {
             for subpacket in &b.content {
-                collect_values(subpacket, axis_mappings, button_mappings, into);
+                collect_values(
+                    subpacket,
+                    axis_mappings,
+                    button_mappings,
+                    axis_button_mappings,
+                    buttons,
+                    into,
+                );
             }
         }
 
         rosc::OscPacket::Message(m) => {
-            let axis_value = m.args.get(0).and_then(|a| match a {
-                rosc::OscType::Double(f) => Some(*f),
-                rosc::OscType::Float(f) => Some(*f as f64),
-                rosc::OscType::Int(i) => Some(*i as f64),
-                rosc::OscType::Long(i) => Some(*i as f64),
-                _ => None,
-            });
+            let axis_value = number(m.args.first());
 
             let button_value = m.args.get(0).and_then(|a| match a {
                 rosc::OscType::Bool(b) => Some(*b),
@@ -107,22 +229,37 @@ fn collect_values(
                 if let Some(axis_value) = axis_value {
                     for (id, address, range) in axis_mappings {
                         if matcher.match_address(address) {
-                            let span = range[1] - range[0];
-                            let mapped_value = if span != 0.0 {
-                                (axis_value - range[0]) / span
-                            } else {
-                                0.0
-                            };
-
+                            let mapped_value = map_range(axis_value, *range);
                             into.push(OutputEvent::UpdateAxis(id.clone(), mapped_value));
                         }
                     }
+
+                    for button in axis_button_mappings.iter_mut() {
+                        if !matcher.match_address(&button.address) {
+                            continue;
+                        }
+
+                        let mapped_value = map_range(axis_value, button.range);
+                        let pressed = button.threshold.pressed(mapped_value, button.pressed);
+                        if pressed == button.pressed {
+                            continue;
+                        }
+
+                        button.pressed = pressed;
+                        if let Some(state) = buttons.update(&button.id, pressed) {
+                            into.push(OutputEvent::UpdateButton(button.id.clone(), state));
+                        }
+                    }
                 }
 
                 if let Some(button_value) = button_value {
                     for (id, address) in button_mappings {
-                        if matcher.match_address(address) {
-                            into.push(OutputEvent::UpdateButton(id.clone(), button_value));
+                        if !matcher.match_address(address) {
+                            continue;
+                        }
+
+                        if let Some(state) = buttons.update(id, button_value) {
+                            into.push(OutputEvent::UpdateButton(id.clone(), state));
                         }
                     }
                 }
@@ -130,3 +267,96 @@ fn collect_values(
         }
     }
 }
+
+fn collect_relative(
+    packet: &rosc::OscPacket,
+    axes: &mut [RelativeAxis],
+    into: &mut Vec<OutputEvent>,
+) {
+    match packet {
+        rosc::OscPacket::Bundle(b) => {
+            for subpacket in &b.content {
+                collect_relative(subpacket, axes, into);
+            }
+        }
+
+        rosc::OscPacket::Message(m) => {
+            if axes.is_empty() {
+                return;
+            }
+
+            let Ok(matcher) = rosc::address::Matcher::new(&m.addr) else {
+                return;
+            };
+
+            for axis in axes.iter_mut() {
+                if let Some(value) = axis.handle(&matcher, m) {
+                    into.push(OutputEvent::UpdateAxis(axis.id.clone(), value));
+                }
+            }
+        }
+    }
+}
+
+fn collect_profiles(
+    packet: &rosc::OscPacket,
+    address: &rosc::address::OscAddress,
+    profiles: &Profiles,
+    into: &mut Vec<OutputEvent>,
+) {
+    match packet {
+        rosc::OscPacket::Bundle(b) => {
+            for subpacket in &b.content {
+                collect_profiles(subpacket, address, profiles, into);
+            }
+        }
+
+        rosc::OscPacket::Message(m) => {
+            let Ok(matcher) = rosc::address::Matcher::new(&m.addr) else {
+                return;
+            };
+
+            if !matcher.match_address(address) {
+                return;
+            }
+
+            let Some(rosc::OscType::String(name)) = m.args.first() else {
+                log::warn!(
+                    "OSC profile switch to {} has no profile name, so it will be ignored.",
+                    m.addr
+                );
+                return;
+            };
+
+            let name = DefaultAtom::from(name.as_str());
+            if profiles.get(&name).is_some() {
+                log::info!("Switching to profile \"{name}\".");
+                into.push(OutputEvent::SwitchProfile(name));
+            } else {
+                log::warn!(
+                    "There is no profile named \"{name}\", so the current profile will be kept."
+                );
+            }
+        }
+    }
+}
+
+/// The value of a numeric argument.
+fn number(arg: Option<&rosc::OscType>) -> Option<f64> {
+    match *arg? {
+        rosc::OscType::Double(f) => Some(f),
+        rosc::OscType::Float(f) => Some(f as f64),
+        rosc::OscType::Int(i) => Some(i as f64),
+        rosc::OscType::Long(i) => Some(i as f64),
+        _ => None,
+    }
+}
+
+fn map_range(value: f64, range: [f64; 2]) -> f64 {
+    let span = range[1] - range[0];
+    if span != 0.0 {
+        (value - range[0]) / span
+    } else {
+        0.0
+    }
+}
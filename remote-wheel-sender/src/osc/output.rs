@@ -1,105 +1,472 @@
-use std::sync::Arc;
+use std::borrow::Cow;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
 
-use anyhow::{Context as _, Result as AnyResult};
+use anyhow::Result as AnyResult;
 use async_broadcast::{Receiver as BroadcastRx, RecvError as BroadcastRxErr};
-use smol::channel::Sender as ChannelTx;
+use futures::prelude::*;
+use hashbrown::HashSet;
+use smol::channel::{Receiver as ChannelRx, Sender as ChannelTx};
 
-use crate::{config::MappingConfig, output::OutputEvent};
+use crate::config::MappingConfig;
+use crate::output::{OutputEvent, Timestamped};
+use crate::press::Gesture;
+use crate::profile::{ActiveProfile, Profiles};
+use crate::route::Routes;
+use crate::stats::OutputStats;
+use crate::timetag::Timetag;
 
-use super::config::OutputConfig;
+use super::config::{BoolInput, BundleConfig, FloatRangeInput, OscInput, OutputConfig};
+
+/// Encoded packets, ready to be sent. Once they have been, the buffer is handed back so that it can
+/// be reused for later packets.
+#[derive(Clone, Debug, Default)]
+pub(super) struct Outbound {
+    data: Vec<u8>,
+
+    /// Where each packet in `data` ends.
+    ends: Vec<usize>,
+}
+
+impl Outbound {
+    pub(super) fn packets(&self) -> impl Iterator<Item = &[u8]> {
+        let starts = std::iter::once(0).chain(self.ends.iter().copied());
+        std::iter::zip(starts, &self.ends).map(|(start, &end)| &self.data[start..end])
+    }
+}
+
+/// Which destinations output isn't getting through to. The send task keeps this up to date, so that
+/// the output processing task can send everything again once it does.
+#[derive(Debug, Default)]
+pub(super) struct Delivery {
+    failing: Mutex<HashSet<SocketAddr>>,
+}
+
+impl Delivery {
+    pub(super) fn failed(&self, addr: SocketAddr) {
+        self.failing.lock().unwrap().insert(addr);
+    }
+
+    pub(super) fn sent(&self, addr: SocketAddr) {
+        self.failing.lock().unwrap().remove(&addr);
+    }
+
+    fn is_failing(&self) -> bool {
+        !self.failing.lock().unwrap().is_empty()
+    }
+}
+
+/// Whether output is currently failing to get through.
+struct Outage {
+    delivery: Arc<Delivery>,
+    active: bool,
+}
+
+impl Outage {
+    /// Checks whether output is getting through, and returns whether the latest value of every
+    /// mapping should be sent again: when it has just started getting through again, or every time
+    /// `retry` is set while it isn't.
+    fn check(&mut self, retry: bool) -> bool {
+        match (self.active, self.delivery.is_failing()) {
+            (false, true) => {
+                log::warn!(
+                    "OSC output is not getting through. The latest values will be sent again \
+                    until it does."
+                );
+                self.active = true;
+                retry
+            }
+
+            (true, true) => retry,
+
+            (true, false) => {
+                log::info!("OSC output is getting through again. Sending the latest values.");
+                self.active = false;
+                true
+            }
+
+            (false, false) => false,
+        }
+    }
+}
+
+/// The messages for one of a mapping's events. These are built once, then updated in place with
+/// each new value.
+struct Messages<'c, I: Clone + OscInput> {
+    config: Cow<'c, BundleConfig<I>>,
+    packets: Vec<rosc::OscPacket>,
+
+    /// The latest value that the messages were given, if they've been given one yet.
+    latest: Option<I::Param>,
+}
+
+impl<'c, I: Clone + OscInput> Messages<'c, I>
+where
+    I::Param: Clone,
+{
+    fn new(config: Cow<'c, BundleConfig<I>>, initial: &I::Param) -> Self {
+        let packets = config
+            .to_messages(initial)
+            .map(rosc::OscPacket::Message)
+            .collect();
+
+        Messages {
+            config,
+            packets,
+            latest: None,
+        }
+    }
+
+    fn update(&mut self, raw: &I::Param) -> &[rosc::OscPacket] {
+        self.config.update_messages(raw, &mut self.packets);
+        self.latest = Some(raw.clone());
+        &self.packets
+    }
+}
+
+struct ButtonMessages<'c> {
+    on_press: Messages<'c, BoolInput>,
+    on_release: Messages<'c, BoolInput>,
+    on_update: Messages<'c, BoolInput>,
+    on_long_press: Messages<'c, BoolInput>,
+    on_double_press: Messages<'c, BoolInput>,
+}
+
+/// Encodes messages as they're produced, into a buffer that starts with the bundle header and the
+/// pre-bundle messages, which are only encoded once.
+struct Encoder {
+    bundle: bool,
+    timetag: Timetag,
+    current: Outbound,
+    template: Outbound,
+    post_packets: Vec<rosc::OscPacket>,
+}
+
+impl Encoder {
+    fn new(config: &OutputConfig) -> Encoder {
+        let mut current = Outbound::default();
+        if config.bundle {
+            let header = rosc::OscPacket::Bundle(rosc::OscBundle {
+                timetag: crate::timetag::IMMEDIATE.into(),
+                content: Vec::new(),
+            });
+            encode(&header, &mut current.data);
+        }
+
+        let mut encoder = Encoder {
+            bundle: config.bundle,
+            timetag: config.timetag,
+            current,
+            template: Outbound::default(),
+            post_packets: config
+                .post_bundle
+                .to_messages(&())
+                .map(rosc::OscPacket::Message)
+                .collect(),
+        };
+
+        for message in config.pre_bundle.to_messages(&()) {
+            encoder.push(&rosc::OscPacket::Message(message));
+        }
+
+        encoder.template = encoder.current.clone();
+        encoder
+    }
+
+    fn is_empty(&self) -> bool {
+        self.current.data.len() == self.template.data.len()
+    }
+
+    fn push(&mut self, packet: &rosc::OscPacket) {
+        let data = &mut self.current.data;
+
+        // Some receivers don't understand bundles, so each message can be sent in a packet of its
+        // own instead, in the same order.
+        if self.bundle {
+            let start = data.len();
+            data.extend_from_slice(&[0; 4]);
+
+            let len = encode(packet, data) as u32;
+            data[start..start + 4].copy_from_slice(&len.to_be_bytes());
+        } else {
+            encode(packet, data);
+            self.current.ends.push(data.len());
+        }
+    }
+
+    /// Adds the post-bundle messages and returns the finished packets, starting over in `next` (a
+    /// buffer that has already been sent, if there is one).
+    fn finish(&mut self, next: Option<Outbound>) -> Outbound {
+        let post_packets = std::mem::take(&mut self.post_packets);
+        for packet in &post_packets {
+            self.push(packet);
+        }
+        self.post_packets = post_packets;
+
+        if self.bundle {
+            self.current.ends.push(self.current.data.len());
+
+            // The header was encoded with an immediate timetag, which comes after "#bundle\0".
+            if !self.timetag.is_immediate() {
+                let (seconds, fraction) = self.timetag.at(SystemTime::now());
+                self.current.data[8..12].copy_from_slice(&seconds.to_be_bytes());
+                self.current.data[12..16].copy_from_slice(&fraction.to_be_bytes());
+            }
+        }
+
+        // Every buffer starts out the same, so only what comes after that needs to be removed.
+        let next = match next {
+            Some(mut next) => {
+                next.data.truncate(self.template.data.len());
+                next.ends.truncate(self.template.ends.len());
+                next
+            }
+            None => self.template.clone(),
+        };
+
+        std::mem::replace(&mut self.current, next)
+    }
+}
+
+fn encode(packet: &rosc::OscPacket, out: &mut Vec<u8>) -> usize {
+    match rosc::encoder::encode_into(packet, out) {
+        Ok(len) => len,
+        Err(e) => match e {},
+    }
+}
+
+/// Pushes the latest messages of every mapping that has been given a value. Buttons only have their
+/// on-update messages sent again, since they haven't been pressed or released again.
+fn push_latest(
+    encoder: &mut Encoder,
+    axes: &Routes<Messages<FloatRangeInput>>,
+    buttons: &Routes<ButtonMessages>,
+) {
+    let axes = axes.values().filter(|m| m.latest.is_some());
+    let buttons = buttons
+        .values()
+        .map(|m| &m.on_update)
+        .filter(|m| m.latest.is_some());
+
+    for packet in axes.flat_map(|m| &m.packets) {
+        encoder.push(packet);
+    }
+
+    for packet in buttons.flat_map(|m| &m.packets) {
+        encoder.push(packet);
+    }
+}
+
+async fn flush(
+    encoder: &mut Encoder,
+    outbound_tx: &ChannelTx<Outbound>,
+    recycle_rx: &ChannelRx<Outbound>,
+    stats: &OutputStats,
+) {
+    if !encoder.is_empty() {
+        let outbound = encoder.finish(recycle_rx.try_recv().ok());
+        if let Err(e) = outbound_tx.send(outbound).await {
+            log::warn!("Failed to transfer OSC packet data for sending: {e}");
+            stats.send_failed();
+        }
+    }
+}
+
+/// Builds the messages for every mapping that has any.
+fn routes(
+    mappings: &MappingConfig,
+) -> (
+    Routes<Messages<'_, FloatRangeInput>>,
+    Routes<ButtonMessages<'_>>,
+) {
+    let axes = Routes::axes(mappings, |_, mapping| {
+        let messages = Messages::<FloatRangeInput>::new(mapping.output.osc.on_update(), &0.0);
+        (!messages.packets.is_empty()).then_some(messages)
+    });
+
+    let buttons = Routes::buttons(mappings, |_, mapping| {
+        let osc = &mapping.output.osc;
+        let messages = ButtonMessages {
+            on_press: Messages::new(Cow::Borrowed(&osc.on_press), &true),
+            on_release: Messages::new(Cow::Borrowed(&osc.on_release), &false),
+            on_update: Messages::new(Cow::Borrowed(&osc.on_update), &false),
+            on_long_press: Messages::new(Cow::Borrowed(&osc.on_long_press), &true),
+            on_double_press: Messages::new(Cow::Borrowed(&osc.on_double_press), &true),
+        };
+
+        let empty = messages.on_press.packets.is_empty()
+            && messages.on_release.packets.is_empty()
+            && messages.on_update.packets.is_empty()
+            && messages.on_long_press.packets.is_empty()
+            && messages.on_double_press.packets.is_empty();
+        (!empty).then_some(messages)
+    });
+
+    (axes, buttons)
+}
+
+/// Builds the messages for a profile that was switched to, and gives them the latest value of each
+/// mapping from the previous profile's messages. Those are pushed so that they're sent once, and
+/// buttons only have their on-update messages sent, like in [`push_latest`].
+fn switch_routes<'c>(
+    encoder: &mut Encoder,
+    mappings: &'c MappingConfig,
+    axes: &Routes<Messages<FloatRangeInput>>,
+    buttons: &Routes<ButtonMessages>,
+) -> (
+    Routes<Messages<'c, FloatRangeInput>>,
+    Routes<ButtonMessages<'c>>,
+) {
+    let (mut new_axes, mut new_buttons) = routes(mappings);
+
+    for (id, _) in mappings.axes() {
+        let latest = axes.get(&id).and_then(|m| m.latest);
+        if let (Some(value), Some(messages)) = (latest, new_axes.get_mut(&id)) {
+            for packet in messages.update(&value) {
+                encoder.push(packet);
+            }
+        }
+    }
+
+    for (id, _) in mappings.buttons() {
+        let latest = buttons.get(&id).and_then(|m| m.on_update.latest);
+        if let (Some(pressed), Some(messages)) = (latest, new_buttons.get_mut(&id)) {
+            for packet in messages.on_update.update(&pressed) {
+                encoder.push(packet);
+            }
+        }
+    }
+
+    (new_axes, new_buttons)
+}
 
 pub(super) async fn run(
     config: OutputConfig,
-    mappings: Arc<MappingConfig>,
-    mut output_rx: BroadcastRx<OutputEvent>,
-    outbound_tx: ChannelTx<Vec<u8>>,
+    profiles: Arc<Profiles>,
+    mut output_rx: BroadcastRx<Timestamped<OutputEvent>>,
+    outbound_tx: ChannelTx<Outbound>,
+    recycle_rx: ChannelRx<Outbound>,
+    delivery: Arc<Delivery>,
+    stats: Arc<OutputStats>,
 ) -> AnyResult<()> {
     log::info!("OSC output processing task starting...");
 
-    let mut packet = rosc::OscPacket::Bundle(rosc::OscBundle {
-        timetag: (0, 0).into(),
-        content: config
-            .pre_bundle
-            .to_messages(&())
-            .map(rosc::OscPacket::Message)
-            .collect(),
-    });
+    let mut profile = ActiveProfile::new(&profiles);
+    let (mut axes, mut buttons) = routes(&profile.current().mappings);
 
-    let mut post_packets = config
-        .post_bundle
-        .to_messages(&())
-        .map(rosc::OscPacket::Message)
-        .collect::<Vec<_>>();
+    let mut encoder = Encoder::new(&config);
 
-    let num_pre_packets = config.pre_bundle.len();
+    // Output only has its outages handled if it's set to be sent again.
+    let mut outage = config.retry_interval.map(|_| Outage {
+        delivery,
+        active: false,
+    });
+    let mut retry_timer = config
+        .retry_interval
+        .map(|i| smol::Timer::interval(Duration::from_secs_f64(i)))
+        .unwrap_or_else(smol::Timer::never);
 
     log::info!("OSC output processing task started.");
 
     loop {
-        match output_rx.recv().await {
+        let msg = futures::select_biased! {
+            msg = output_rx.recv().fuse() => msg,
+
+            _ = retry_timer.next().fuse() => {
+                if outage.as_mut().is_some_and(|o| o.check(true)) {
+                    push_latest(&mut encoder, &axes, &buttons);
+                    flush(&mut encoder, &outbound_tx, &recycle_rx, &stats).await;
+                }
+
+                continue;
+            },
+        };
+
+        let msg = msg.map(|e| stats.received(e, output_rx.len()));
+        if let Ok(ref event) = msg {
+            // The new profile's messages are sent with the latest values right away, rather than
+            // waiting for each mapping to change.
+            if let Some(switched) = profile.handle(event) {
+                log::info!("OSC output switched to profile \"{}\".", switched.name);
+                (axes, buttons) = switch_routes(&mut encoder, &switched.mappings, &axes, &buttons);
+            }
+        }
+
+        match msg {
             Ok(OutputEvent::UpdateAxis(id, value)) => {
-                if let Some(mapping) = mappings.axis.get(&id) {
-                    let rosc::OscPacket::Bundle(ref mut bundle) = packet else { unreachable!() };
-
-                    bundle.content.extend(
-                        mapping
-                            .output
-                            .osc
-                            .on_update
-                            .to_messages(&(value as f32))
-                            .map(rosc::OscPacket::Message),
-                    );
+                if let Some(messages) = axes.get_mut(&id) {
+                    for packet in messages.update(&(value as f32)) {
+                        encoder.push(packet);
+                    }
+
+                    stats.sent_mapping(&id);
                 }
             }
 
-            Ok(OutputEvent::UpdateButton(id, pressed)) => {
-                if let Some(mapping) = mappings.button.get(&id) {
-                    let rosc::OscPacket::Bundle(ref mut bundle) = packet else { unreachable!() };
+            Ok(
+                ref event @ (OutputEvent::UpdateButton(ref id, pressed)
+                | OutputEvent::RestoreButton(ref id, pressed)),
+            ) => {
+                if let Some(messages) = buttons.get_mut(id) {
+                    let mut any = false;
 
-                    let specific_messages = if pressed {
-                        &mapping.output.osc.on_press
-                    } else {
-                        &mapping.output.osc.on_release
-                    };
+                    if let OutputEvent::UpdateButton(..) = event {
+                        let specific_messages = if pressed {
+                            &mut messages.on_press
+                        } else {
+                            &mut messages.on_release
+                        };
+
+                        for packet in specific_messages.update(&pressed) {
+                            encoder.push(packet);
+                            any = true;
+                        }
+                    }
 
-                    bundle.content.extend(
-                        specific_messages
-                            .to_messages(&pressed)
-                            .map(rosc::OscPacket::Message),
-                    );
-
-                    bundle.content.extend(
-                        mapping
-                            .output
-                            .osc
-                            .on_update
-                            .to_messages(&pressed)
-                            .map(rosc::OscPacket::Message),
-                    );
+                    for packet in messages.on_update.update(&pressed) {
+                        encoder.push(packet);
+                        any = true;
+                    }
+
+                    if any {
+                        stats.sent_mapping(id);
+                    }
                 }
             }
 
-            Ok(OutputEvent::Flush) => {
-                let rosc::OscPacket::Bundle(ref mut bundle) = packet else { unreachable!() };
-                if bundle.content.len() > num_pre_packets {
-                    let post_start = bundle.content.len();
-                    bundle.content.append(&mut post_packets);
+            Ok(OutputEvent::ButtonGesture(id, gesture)) => {
+                if let Some(messages) = buttons.get_mut(&id) {
+                    let gesture_messages = match gesture {
+                        Gesture::LongPress => &mut messages.on_long_press,
+                        Gesture::DoublePress => &mut messages.on_double_press,
+                    };
 
-                    let bytes =
-                        rosc::encoder::encode(&packet).context("Failed to encode OSC packet")?;
+                    let mut any = false;
+                    for packet in gesture_messages.update(&true) {
+                        encoder.push(packet);
+                        any = true;
+                    }
 
-                    if let Err(e) = outbound_tx.send(bytes).await {
-                        log::warn!("Failed to transfer OSC packet data for sending: {e}");
+                    if any {
+                        stats.sent_mapping(&id);
                     }
+                }
+            }
 
-                    let rosc::OscPacket::Bundle(ref mut bundle) = packet else { unreachable!() };
-                    post_packets.extend(bundle.content.drain(post_start..));
-                    bundle.content.truncate(num_pre_packets);
+            Ok(OutputEvent::SwitchProfile(_)) => {}
+
+            Ok(OutputEvent::Flush) => {
+                if outage.as_mut().is_some_and(|o| o.check(false)) {
+                    push_latest(&mut encoder, &axes, &buttons);
                 }
+
+                flush(&mut encoder, &outbound_tx, &recycle_rx, &stats).await;
             }
 
             Err(BroadcastRxErr::Overflowed(n)) => {
                 log::warn!("OSC output processing task missed {} update(s)!", n);
+                stats.missed(n);
             }
 
             Err(BroadcastRxErr::Closed) => {
@@ -112,3 +479,197 @@ pub(super) async fn run(
     log::info!("OSC output processing task stopped.");
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use string_cache::DefaultAtom;
+
+    use super::*;
+    use crate::config::AppConfig;
+
+    const MESSAGES: &str = r#"
+        "/wheel/rotation" = [{ input = [-450, 900] }]
+        "/wheel/both" = [{ input = [0, 1] }, 2, "text", { input = [1, -1] }]
+    "#;
+
+    fn output_config(bundle: bool) -> OutputConfig {
+        toml::from_str(&format!(
+            r#"
+                bundle = {bundle}
+                pre-bundle = {{ "/pre" = [1] }}
+                post-bundle = {{ "/post" = ["done", 2.5] }}
+            "#
+        ))
+        .unwrap()
+    }
+
+    fn encode_all(packets: &[rosc::OscPacket]) -> Vec<Vec<u8>> {
+        packets
+            .iter()
+            .map(|p| rosc::encoder::encode(p).unwrap())
+            .collect()
+    }
+
+    /// The packets for `messages`, encoded from scratch.
+    fn expected(config: &OutputConfig, messages: &[rosc::OscPacket]) -> Vec<Vec<u8>> {
+        let content: Vec<_> = (config.pre_bundle.to_messages(&()))
+            .map(rosc::OscPacket::Message)
+            .chain(messages.iter().cloned())
+            .chain(
+                config
+                    .post_bundle
+                    .to_messages(&())
+                    .map(rosc::OscPacket::Message),
+            )
+            .collect();
+
+        if config.bundle {
+            encode_all(&[rosc::OscPacket::Bundle(rosc::OscBundle {
+                timetag: config.timetag.at(SystemTime::now()).into(),
+                content,
+            })])
+        } else {
+            encode_all(&content)
+        }
+    }
+
+    #[test]
+    fn updated_messages_match_new_ones() {
+        let config: BundleConfig<FloatRangeInput> = toml::from_str(MESSAGES).unwrap();
+        let mut messages = Messages::new(Cow::Borrowed(&config), &0.0);
+
+        for value in [0.25, -1.0, 1.0, 0.0, 0.5] {
+            let new: Vec<_> = config
+                .to_messages(&value)
+                .map(rosc::OscPacket::Message)
+                .collect();
+            assert_eq!(encode_all(messages.update(&value)), encode_all(&new));
+        }
+    }
+
+    #[test]
+    fn reused_buffers_match_new_ones() {
+        let messages: BundleConfig<FloatRangeInput> = toml::from_str(MESSAGES).unwrap();
+        let packets = |value: f32, count: usize| {
+            (messages.to_messages(&value))
+                .take(count)
+                .map(rosc::OscPacket::Message)
+                .collect::<Vec<_>>()
+        };
+
+        for bundle in [true, false] {
+            let config = output_config(bundle);
+            let mut encoder = Encoder::new(&config);
+            let mut previous = None;
+
+            // Each buffer is reused for packets that are shorter, longer, and empty.
+            for (value, count) in [(0.5, 2), (-1.0, 1), (0.0, 2), (1.0, 0), (0.75, 2)] {
+                let packets = packets(value, count);
+                for packet in &packets {
+                    encoder.push(packet);
+                }
+
+                let outbound = encoder.finish(previous.take());
+                let sent: Vec<_> = outbound.packets().map(<[u8]>::to_vec).collect();
+                assert_eq!(sent, expected(&config, &packets), "bundle = {bundle}");
+                previous = Some(outbound);
+            }
+        }
+    }
+
+    #[test]
+    fn switching_routes_sends_latest_values() {
+        // Read as part of a whole configuration, since that's what lets bool inputs be `[]`.
+        let mappings = |address: &str| -> Arc<MappingConfig> {
+            toml::from_str::<AppConfig>(&format!(
+                r#"
+                    [axis.wheel.output.osc.on-update]
+                    "{address}" = [{{ input = [0, 1] }}]
+
+                    [axis.idle.output.osc.on-update]
+                    "/idle" = [{{ input = [0, 1] }}]
+
+                    [button.horn.output.osc.on-update]
+                    "{address}/horn" = [{{ input = [] }}]
+                "#
+            ))
+            .unwrap()
+            .mappings
+        };
+        let (old, new) = (mappings("/old"), mappings("/new"));
+        let (wheel, horn) = (DefaultAtom::from("wheel"), DefaultAtom::from("horn"));
+
+        let config = output_config(false);
+        let mut encoder = Encoder::new(&config);
+
+        let (mut axes, mut buttons) = routes(&old);
+        axes.get_mut(&old.axis_id(&wheel).unwrap())
+            .unwrap()
+            .update(&0.5);
+        buttons
+            .get_mut(&old.button_id(&horn).unwrap())
+            .unwrap()
+            .on_update
+            .update(&true);
+
+        // Only the mappings that had been given a value are sent, with the new profile's messages.
+        let (axes, _) = switch_routes(&mut encoder, &new, &axes, &buttons);
+        let sent: Vec<_> = encoder.finish(None).packets().map(<[u8]>::to_vec).collect();
+
+        let wheel_messages = new.axis[&wheel].output.osc.on_update();
+        let horn_messages = &new.button[&horn].output.osc.on_update;
+        let packets: Vec<_> = (wheel_messages.to_messages(&0.5))
+            .chain(horn_messages.to_messages(&true))
+            .map(rosc::OscPacket::Message)
+            .collect();
+        assert_eq!(sent, expected(&config, &packets));
+
+        let wheel = new.axis_id(&wheel).unwrap();
+        assert_eq!(axes.get(&wheel).unwrap().latest, Some(0.5));
+    }
+
+    /// Compares building and encoding every packet from scratch for each flush to updating and
+    /// encoding them into reused buffers. Run with `cargo test --release -- --ignored --nocapture`.
+    #[test]
+    #[ignore]
+    fn bench_encoding() {
+        const ITERATIONS: u32 = 200_000;
+
+        let config = output_config(true);
+        let bundle: BundleConfig<FloatRangeInput> = toml::from_str(MESSAGES).unwrap();
+
+        let start = Instant::now();
+        for i in 0..ITERATIONS {
+            let value = i as f32 / ITERATIONS as f32;
+            let content = (config.pre_bundle.to_messages(&()))
+                .chain(bundle.to_messages(&value))
+                .chain(config.post_bundle.to_messages(&()))
+                .map(rosc::OscPacket::Message)
+                .collect();
+            let packet = rosc::OscPacket::Bundle(rosc::OscBundle {
+                timetag: crate::timetag::IMMEDIATE.into(),
+                content,
+            });
+            std::hint::black_box(rosc::encoder::encode(&packet).unwrap());
+        }
+        let fresh = start.elapsed() / ITERATIONS;
+
+        let mut encoder = Encoder::new(&config);
+        let mut messages = Messages::new(Cow::Borrowed(&bundle), &0.0);
+        let mut previous = None;
+
+        let start = Instant::now();
+        for i in 0..ITERATIONS {
+            let value = i as f32 / ITERATIONS as f32;
+            for packet in messages.update(&value) {
+                encoder.push(packet);
+            }
+            previous = Some(std::hint::black_box(encoder.finish(previous.take())));
+        }
+        let reused = start.elapsed() / ITERATIONS;
+
+        println!("OSC encoding: {fresh:?}/flush from scratch, {reused:?}/flush reused");
+    }
+}
@@ -0,0 +1,143 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Result as AnyResult;
+use futures::prelude::*;
+use smol::channel::{Receiver as ChannelRx, Sender as ChannelTx, TrySendError};
+use smol::net::TcpStream;
+
+use crate::stats::OutputStats;
+
+use super::output::{Delivery, Outbound};
+
+const SLIP_END: u8 = 0xC0;
+const SLIP_ESC: u8 = 0xDB;
+const SLIP_ESC_END: u8 = 0xDC;
+const SLIP_ESC_ESC: u8 = 0xDD;
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+const RECONNECT_DELAY_MIN: Duration = Duration::from_millis(500);
+const RECONNECT_DELAY_MAX: Duration = Duration::from_secs(30);
+
+/// Sends OSC packets over TCP to each address, framed with SLIP as described by OSC 1.1.
+///
+/// Each address has its own connection, which is re-established with increasing delays whenever it
+/// drops. Packets for an address that isn't connected (or can't keep up) are dropped rather than
+/// queued, since they would be out of date by the time they could be sent.
+pub(super) async fn run_send(
+    addrs: Vec<SocketAddr>,
+    outbound_rx: ChannelRx<Outbound>,
+    recycle_tx: ChannelTx<Outbound>,
+    delivery: Arc<Delivery>,
+    stats: Arc<OutputStats>,
+) -> AnyResult<()> {
+    let links = addrs
+        .iter()
+        .map(|&addr| {
+            let (tx, rx) = smol::channel::bounded(4);
+            smol::spawn(run_link(addr, rx, delivery.clone())).detach();
+            (addr, tx)
+        })
+        .collect::<Vec<(SocketAddr, ChannelTx<Arc<[u8]>>)>>();
+
+    log::info!("OSC send task started.");
+    loop {
+        let Ok(outbound) = outbound_rx.recv().await else {
+            log::info!("OSC send task stopping (output processing task has stopped).");
+            break Ok(());
+        };
+
+        for data in outbound.packets() {
+            let mut framed = Vec::with_capacity(data.len() + 2);
+            slip_encode(data, &mut framed);
+            let framed: Arc<[u8]> = framed.into();
+
+            let mut any_sent = false;
+            for (addr, tx) in &links {
+                match tx.try_send(framed.clone()) {
+                    Ok(()) => any_sent = true,
+                    Err(TrySendError::Full(_)) => {
+                        log::debug!("Dropping OSC data for {addr} (connection is not ready).");
+                        stats.send_failed();
+                        delivery.failed(*addr);
+                    }
+                    Err(TrySendError::Closed(_)) => {
+                        log::error!("OSC connection task for {addr} has stopped.");
+                        stats.send_failed();
+                        delivery.failed(*addr);
+                    }
+                }
+            }
+
+            if any_sent {
+                stats.sent();
+            }
+        }
+
+        let _ = recycle_tx.try_send(outbound);
+    }
+}
+
+async fn run_link(addr: SocketAddr, rx: ChannelRx<Arc<[u8]>>, delivery: Arc<Delivery>) {
+    let mut delay = RECONNECT_DELAY_MIN;
+
+    loop {
+        if rx.is_closed() {
+            return;
+        }
+
+        let timeout = async {
+            smol::Timer::after(CONNECT_TIMEOUT).await;
+            Err(std::io::ErrorKind::TimedOut.into())
+        };
+
+        let mut stream = match smol::future::or(TcpStream::connect(addr), timeout).await {
+            Ok(s) => s,
+            Err(e) => {
+                log::debug!("Failed to connect to {addr} for OSC: {e}. Retrying in {delay:?}.");
+                delivery.failed(addr);
+                smol::Timer::after(delay).await;
+                delay = (delay * 2).min(RECONNECT_DELAY_MAX);
+                continue;
+            }
+        };
+
+        log::info!("Connected to {addr} for OSC.");
+        delay = RECONNECT_DELAY_MIN;
+        let _ = stream.set_nodelay(true);
+
+        // Anything queued before the connection was made is stale by now.
+        while rx.try_recv().is_ok() {}
+
+        loop {
+            let Ok(data) = rx.recv().await else {
+                return;
+            };
+
+            log::debug!("Sending {} bytes of data to {addr}.", data.len());
+            if let Err(e) = stream.write_all(&data).await {
+                log::warn!("OSC connection to {addr} was lost: {e}");
+                delivery.failed(addr);
+                break;
+            }
+
+            // Only data that was actually written counts, since anything queued while the
+            // connection was down is thrown away.
+            delivery.sent(addr);
+        }
+    }
+}
+
+fn slip_encode(data: &[u8], out: &mut Vec<u8>) {
+    // A leading END flushes out any noise that may have been received before the packet.
+    out.push(SLIP_END);
+    for &byte in data {
+        match byte {
+            SLIP_END => out.extend([SLIP_ESC, SLIP_ESC_END]),
+            SLIP_ESC => out.extend([SLIP_ESC, SLIP_ESC_ESC]),
+            _ => out.push(byte),
+        }
+    }
+    out.push(SLIP_END);
+}
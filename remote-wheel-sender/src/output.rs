@@ -1,8 +1,50 @@
+use std::ops::Deref;
+use std::time::Instant;
+
 use string_cache::DefaultAtom;
 
-#[derive(Clone, Debug)]
+use crate::press::Gesture;
+use crate::route::MappingId;
+
+#[derive(Clone, Debug, PartialEq)]
 pub enum OutputEvent {
-    UpdateAxis(DefaultAtom, f64),
-    UpdateButton(DefaultAtom, bool),
+    UpdateAxis(MappingId, f64),
+    UpdateButton(MappingId, bool),
+    /// A button's state as it was before the Sender was last stopped. Only what's mapped on update
+    /// applies to this, since the button wasn't actually pressed or released.
+    RestoreButton(MappingId, bool),
+    /// A long or double press of a button, which is sent in addition to its updates.
+    ButtonGesture(MappingId, Gesture),
+    /// Switches the outputs that follow profiles to the profile with this name.
+    SwitchProfile(DefaultAtom),
     Flush,
 }
+
+#[derive(Clone, Debug)]
+pub struct Timestamped<T> {
+    pub time: Instant,
+    pub event: T,
+}
+
+impl<T> Timestamped<T> {
+    pub fn now(event: T) -> Self {
+        Timestamped {
+            time: Instant::now(),
+            event,
+        }
+    }
+}
+
+impl<T> Deref for Timestamped<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.event
+    }
+}
+
+impl From<OutputEvent> for Timestamped<OutputEvent> {
+    fn from(event: OutputEvent) -> Self {
+        Timestamped::now(event)
+    }
+}
@@ -0,0 +1,123 @@
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use anyhow::{Context as _, Result as AnyResult};
+
+/// The name of the file which, next to the executable, makes the Sender portable.
+const PORTABLE_MARKER: &str = "portable.txt";
+
+/// The name of the Sender's directory within the per-user configuration directory.
+const DIR_NAME: &str = "remote-wheel";
+
+static LOCATION: OnceLock<Location> = OnceLock::new();
+
+#[derive(Debug)]
+struct Location {
+    /// The directory that files are kept in. This is empty (i.e. the working directory) when
+    /// portable.
+    dir: PathBuf,
+    reason: &'static str,
+}
+
+/// Decides where the Sender's files (configuration, log, crash reports) are kept. When portable,
+/// they're kept in the working directory, as they always have been; otherwise they're kept in a
+/// per-user directory, which is created if needed.
+///
+/// `config` is the name of the configuration file, without an extension. If one already exists in
+/// the working directory, it keeps being used there.
+pub fn init(portable: bool, config: &str) -> AnyResult<()> {
+    let location = if portable {
+        Location::portable("--portable was given")
+    } else if marker_exists() {
+        Location::portable("portable.txt is next to the executable")
+    } else if crate::config::find_existing(Path::new(config)).is_some() {
+        Location::portable("a configuration file is in the working directory")
+    } else {
+        let dir = sys::config_dir()
+            .context(
+                "Could not find the per-user configuration directory. Start with --portable to \
+                keep files in the working directory instead.",
+            )?
+            .join(DIR_NAME);
+
+        std::fs::create_dir_all(&dir)
+            .with_context(|| format!("Failed to create directory <{}>", dir.display()))?;
+
+        Location {
+            dir,
+            reason: "per-user",
+        }
+    };
+
+    let _ = LOCATION.set(location);
+    Ok(())
+}
+
+/// Resolves where the file named `name` is kept. Every file the Sender keeps should be found
+/// through this.
+pub fn file(name: impl AsRef<Path>) -> PathBuf {
+    match LOCATION.get() {
+        Some(location) => location.dir.join(name),
+        None => name.as_ref().to_path_buf(),
+    }
+}
+
+/// Logs where files are being kept, and why.
+pub fn log_startup() {
+    let Some(location) = LOCATION.get() else {
+        return;
+    };
+
+    let dir = std::env::current_dir()
+        .map(|cwd| cwd.join(&location.dir))
+        .unwrap_or_else(|_| location.dir.clone());
+    log::info!("Files: <{}> ({})", dir.display(), location.reason);
+}
+
+impl Location {
+    fn portable(reason: &'static str) -> Location {
+        Location {
+            dir: PathBuf::new(),
+            reason,
+        }
+    }
+}
+
+fn marker_exists() -> bool {
+    std::env::current_exe()
+        .ok()
+        .and_then(|exe| Some(exe.parent()?.join(PORTABLE_MARKER)))
+        .is_some_and(|marker| marker.is_file())
+}
+
+#[cfg(windows)]
+mod sys {
+    use std::path::PathBuf;
+
+    pub fn config_dir() -> Option<PathBuf> {
+        absolute_var("APPDATA")
+    }
+
+    fn absolute_var(name: &str) -> Option<PathBuf> {
+        Some(PathBuf::from(std::env::var_os(name)?)).filter(|p| p.is_absolute())
+    }
+}
+
+#[cfg(not(windows))]
+mod sys {
+    use std::path::PathBuf;
+
+    #[cfg(target_os = "macos")]
+    pub fn config_dir() -> Option<PathBuf> {
+        Some(absolute_var("HOME")?.join("Library/Application Support"))
+    }
+
+    #[cfg(not(target_os = "macos"))]
+    pub fn config_dir() -> Option<PathBuf> {
+        absolute_var("XDG_CONFIG_HOME").or_else(|| Some(absolute_var("HOME")?.join(".config")))
+    }
+
+    fn absolute_var(name: &str) -> Option<PathBuf> {
+        Some(PathBuf::from(std::env::var_os(name)?)).filter(|p| p.is_absolute())
+    }
+}
@@ -0,0 +1,183 @@
+use std::fmt::Display;
+
+/// The controller name, axes, and buttons of a known device.
+///
+/// These are what the device reports with its usual Windows drivers. Other drivers or compatibility
+/// modes may report different names or numbering. Axis and button numbers start at 1, as in the
+/// configuration.
+#[derive(Debug)]
+pub struct Preset {
+    pub desc: &'static str,
+    pub controller: &'static str,
+
+    pub wheel: u8,
+    pub gas: Option<u8>,
+    pub brake: Option<u8>,
+    pub clutch: Option<u8>,
+
+    pub shift_up: Option<u32>,
+    pub shift_down: Option<u32>,
+}
+
+pub static PRESETS: &[Preset] = &[
+    Preset {
+        desc: "Logitech G29",
+        controller: "Logitech G29 Driving Force Racing Wheel USB",
+        wheel: 1,
+        gas: Some(2),
+        brake: Some(6),
+        clutch: Some(7),
+        shift_up: Some(5),
+        shift_down: Some(6),
+    },
+    Preset {
+        desc: "Logitech G920",
+        controller: "Logitech G920 Driving Force Racing Wheel USB",
+        wheel: 1,
+        gas: Some(2),
+        brake: Some(3),
+        clutch: Some(6),
+        shift_up: Some(5),
+        shift_down: Some(6),
+    },
+    Preset {
+        desc: "Thrustmaster T300",
+        controller: "Thrustmaster T300RS Racing wheel",
+        wheel: 1,
+        gas: Some(2),
+        brake: Some(6),
+        clutch: Some(7),
+        shift_up: Some(1),
+        shift_down: Some(2),
+    },
+    Preset {
+        desc: "Thrustmaster T150",
+        controller: "Thrustmaster T150RS",
+        wheel: 1,
+        gas: Some(2),
+        brake: Some(6),
+        clutch: Some(7),
+        shift_up: Some(1),
+        shift_down: Some(2),
+    },
+    Preset {
+        desc: "Fanatec CSL DD",
+        controller: "FANATEC CSL DD",
+        wheel: 1,
+        gas: Some(3),
+        brake: Some(2),
+        clutch: Some(4),
+        shift_up: Some(2),
+        shift_down: Some(1),
+    },
+    Preset {
+        desc: "Generic gamepad (Xbox layout)",
+        controller: "Controller (Xbox One For Windows)",
+        wheel: 1,
+        gas: Some(3),
+        brake: None,
+        clutch: None,
+        shift_up: Some(6),
+        shift_down: Some(5),
+    },
+];
+
+impl Preset {
+    /// Finds the preset for a connected controller, by its name.
+    pub fn find(controller: &str) -> Option<&'static Preset> {
+        PRESETS.iter().find(|p| p.controller == controller)
+    }
+
+    fn axis(&self, name: &str) -> Option<u8> {
+        match name {
+            "wheel" => Some(self.wheel),
+            "gas" => self.gas,
+            "brake" => self.brake,
+            "clutch" => self.clutch,
+            _ => None,
+        }
+    }
+
+    fn button(&self, name: &str) -> Option<u32> {
+        match name {
+            "shift-up" => self.shift_up,
+            "shift-down" => self.shift_down,
+            _ => None,
+        }
+    }
+
+    /// Fills in the controller inputs of a sample configuration with this device's name and numbers.
+    ///
+    /// Inputs that this device doesn't have are left as they are, and their sections are returned so
+    /// that they can be reported.
+    pub fn apply(&self, contents: &str) -> (String, Vec<String>) {
+        let mut out = String::with_capacity(contents.len());
+        let mut skipped = Vec::new();
+        let mut section: Option<(&str, Option<String>)> = None;
+
+        for line in contents.split_inclusive('\n') {
+            let trimmed = line.trim();
+
+            if trimmed.starts_with('[') {
+                section = input_section(trimmed).map(|(kind, name)| {
+                    let value = match kind {
+                        "axis" => self.axis(name).map(|a| a.to_string()),
+                        _ => self.button(name).map(|b| b.to_string()),
+                    };
+
+                    if value.is_none() {
+                        skipped.push(trimmed.to_string());
+                    }
+
+                    (kind, value)
+                });
+
+                out.push_str(line);
+                continue;
+            }
+
+            let replacement = match section {
+                Some((kind, Some(ref value))) => {
+                    if trimmed.starts_with("controller.name") {
+                        Some(format!("controller.name = {:?}", self.controller))
+                    } else if trimmed.starts_with(&format!("controller.{kind}")) {
+                        Some(format!("controller.{kind} = {value}"))
+                    } else {
+                        None
+                    }
+                }
+
+                _ => None,
+            };
+
+            match replacement {
+                Some(replacement) => {
+                    let indent = &line[..line.len() - line.trim_start().len()];
+                    let ending = &line[line.trim_end().len()..];
+                    out.push_str(indent);
+                    out.push_str(&replacement);
+                    out.push_str(ending);
+                }
+
+                None => out.push_str(line),
+            }
+        }
+
+        (out, skipped)
+    }
+}
+
+impl Display for Preset {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.desc)
+    }
+}
+
+/// Parses a `[[axis.NAME.input]]` or `[[button.NAME.input]]` section header.
+fn input_section(header: &str) -> Option<(&str, &str)> {
+    let inner = header.strip_prefix("[[")?.strip_suffix("]]")?;
+    let (kind, rest) = inner.split_once('.')?;
+    let name = rest.strip_suffix(".input")?;
+
+    matches!(kind, "axis" | "button").then_some((kind, name))
+}
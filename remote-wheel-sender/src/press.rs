@@ -0,0 +1,485 @@
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use anyhow::Result as AnyResult;
+use async_broadcast::{
+    Receiver as BroadcastRx, RecvError as BroadcastRxErr, Sender as BroadcastTx,
+};
+use futures::prelude::*;
+use hashbrown::HashMap;
+
+use crate::config::MappingConfig;
+use crate::output::{OutputEvent, Timestamped};
+use crate::route::{MappingId, Routes};
+use crate::stats;
+
+/// A way of pressing a button that's recognized from the timing of its presses and releases, which
+/// outputs may act on separately from an ordinary press.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Gesture {
+    /// The button was held for at least its long-press-ms. This is sent while it's still held.
+    LongPress,
+    /// The button was pressed again within its double-press-ms of the first press.
+    DoublePress,
+}
+
+/// How a button's presses are told apart.
+#[derive(Clone, Copy, Debug)]
+struct Timing {
+    long: Option<Duration>,
+    double: Option<Duration>,
+    /// Whether a long or double press is sent only as that, rather than also as an ordinary press.
+    suppress: bool,
+}
+
+/// What's known about a button's current press.
+#[derive(Debug, Default)]
+struct PressState {
+    /// When the button was pressed, while it's held.
+    held: Option<Instant>,
+    /// Whether the current press has already been sent as a gesture.
+    classified: bool,
+    /// When the first press of a possible double press was made, until it can no longer be one.
+    first: Option<Instant>,
+    /// Whether a short press is being held back until it's known not to start a double press.
+    pending: bool,
+}
+
+/// Whether any button has long or double presses configured, which needs the task to run.
+pub fn needed(mappings: &MappingConfig) -> bool {
+    mappings
+        .button
+        .values()
+        .any(|b| b.long_press_ms.is_some() || b.double_press_ms.is_some())
+}
+
+/// Passes events on to the outputs, adding long and double presses of the buttons that have them
+/// configured. Every output receives the same gestures, since they're recognized once, here.
+///
+/// When a button suppresses its ordinary presses, its press and release are held back until it's
+/// known not to be a long or double press, and then sent together.
+pub async fn run(
+    mappings: Arc<MappingConfig>,
+    mut input_rx: BroadcastRx<Timestamped<OutputEvent>>,
+    output_tx: BroadcastTx<Timestamped<OutputEvent>>,
+) -> AnyResult<()> {
+    log::info!("Button press task starting...");
+
+    let timings = Routes::buttons(&mappings, |_, b| {
+        let timing = Timing {
+            long: b.long_press_ms.map(Duration::from_millis),
+            double: b.double_press_ms.map(Duration::from_millis),
+            suppress: b.suppress_press,
+        };
+        (timing.long.is_some() || timing.double.is_some()).then_some(timing)
+    });
+
+    let mut states = HashMap::<MappingId, PressState>::new();
+    let mut timer = smol::Timer::never();
+    let mut events = Vec::new();
+
+    log::info!("Button press task started.");
+
+    loop {
+        let deadline = states
+            .iter()
+            .filter_map(|(id, state)| state.deadline(timings.get(id)?))
+            .min();
+        match deadline {
+            Some(deadline) => timer.set_at(deadline),
+            None => timer = smol::Timer::never(),
+        }
+
+        futures::select_biased! {
+            _ = future::FutureExt::fuse(&mut timer) => {
+                let now = Instant::now();
+                for (id, state) in &mut states {
+                    if let Some(timing) = timings.get(id) {
+                        state.expire(id, timing, now, &mut events);
+                    }
+                }
+
+                if !events.is_empty() {
+                    events.push(OutputEvent::Flush.into());
+                }
+            },
+
+            event = input_rx.recv().fuse() => match event {
+                Ok(event) => match event.event {
+                    OutputEvent::UpdateButton(ref id, pressed) => match timings.get(id) {
+                        Some(timing) => {
+                            let state = states.entry(id.clone()).or_default();
+                            state.update(id, timing, pressed, event.time, &mut events);
+                        }
+
+                        None => events.push(event),
+                    },
+
+                    _ => events.push(event),
+                },
+
+                Err(BroadcastRxErr::Overflowed(n)) => {
+                    log::warn!("Button press task missed {n} update(s)!");
+                    stats::counter("Button presses", "missed events").add(n);
+                }
+
+                Err(BroadcastRxErr::Closed) => {
+                    log::info!("Button press task stopping (no remaining inputs).");
+                    break;
+                }
+            },
+        }
+
+        for event in events.drain(..) {
+            if output_tx.broadcast(event).await.is_err() {
+                log::info!("Button press task stopping (no remaining outputs).");
+                return Ok(());
+            }
+        }
+    }
+
+    log::info!("Button press task stopped.");
+    Ok(())
+}
+
+impl PressState {
+    /// When the press will next need to be looked at, if it's waiting on anything.
+    fn deadline(&self, timing: &Timing) -> Option<Instant> {
+        let long = timing
+            .long
+            .zip(self.held)
+            .filter(|_| !self.classified)
+            .map(|(long, held)| held + long);
+        let double = timing
+            .double
+            .zip(self.first)
+            .map(|(double, first)| first + double);
+
+        long.into_iter().chain(double).min()
+    }
+
+    fn update(
+        &mut self,
+        id: &MappingId,
+        timing: &Timing,
+        pressed: bool,
+        time: Instant,
+        events: &mut Vec<Timestamped<OutputEvent>>,
+    ) {
+        let event = || Timestamped {
+            time,
+            event: OutputEvent::UpdateButton(id.clone(), pressed),
+        };
+
+        // Inputs may repeat their state (e.g. OSC), which isn't a new press or release.
+        if pressed == self.held.is_some() {
+            if !timing.suppress {
+                events.push(event());
+            }
+            return;
+        }
+
+        if pressed {
+            self.held = Some(time);
+            self.classified = false;
+
+            if !timing.suppress {
+                events.push(event());
+            }
+
+            let is_double = timing
+                .double
+                .zip(self.first)
+                .is_some_and(|(double, first)| time.saturating_duration_since(first) <= double);
+            if is_double {
+                self.first = None;
+                self.pending = false;
+                self.classified = true;
+                gesture(id, Gesture::DoublePress, events);
+            } else {
+                // A press held back from before can't be part of this one.
+                if std::mem::take(&mut self.pending) {
+                    short_press(id, events);
+                }
+
+                self.first = timing.double.map(|_| time);
+            }
+        } else {
+            self.held = None;
+
+            if !timing.suppress {
+                events.push(event());
+            } else if self.classified {
+                // The press was sent as a gesture, so neither it nor its release are.
+            } else if self.first.is_some() {
+                self.pending = true;
+            } else {
+                short_press(id, events);
+            }
+        }
+    }
+
+    /// Acts on anything that has been waited on until `now`.
+    fn expire(
+        &mut self,
+        id: &MappingId,
+        timing: &Timing,
+        now: Instant,
+        events: &mut Vec<Timestamped<OutputEvent>>,
+    ) {
+        if let (Some(long), Some(held)) = (timing.long, self.held) {
+            if !self.classified && now >= held + long {
+                self.classified = true;
+                // A long press can't be the first of a double press.
+                self.first = None;
+                gesture(id, Gesture::LongPress, events);
+            }
+        }
+
+        if let (Some(double), Some(first)) = (timing.double, self.first) {
+            if now >= first + double {
+                self.first = None;
+
+                if std::mem::take(&mut self.pending) {
+                    short_press(id, events);
+                }
+            }
+        }
+    }
+}
+
+fn gesture(id: &MappingId, gesture: Gesture, events: &mut Vec<Timestamped<OutputEvent>>) {
+    log::debug!("Button {id} was given a {gesture:?}.");
+    events.push(OutputEvent::ButtonGesture(id.clone(), gesture).into());
+}
+
+/// Sends a press that was held back, now that it's known to be an ordinary one.
+fn short_press(id: &MappingId, events: &mut Vec<Timestamped<OutputEvent>>) {
+    events.push(OutputEvent::UpdateButton(id.clone(), true).into());
+    events.push(OutputEvent::UpdateButton(id.clone(), false).into());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LONG: Duration = Duration::from_millis(500);
+    const DOUBLE: Duration = Duration::from_millis(300);
+
+    fn timing(long: bool, double: bool, suppress: bool) -> Timing {
+        Timing {
+            long: long.then_some(LONG),
+            double: double.then_some(DOUBLE),
+            suppress,
+        }
+    }
+
+    fn id() -> MappingId {
+        MappingId::new("button", 0)
+    }
+
+    fn button(pressed: bool) -> OutputEvent {
+        OutputEvent::UpdateButton(id(), pressed)
+    }
+
+    fn gesture(gesture: Gesture) -> OutputEvent {
+        OutputEvent::ButtonGesture(id(), gesture)
+    }
+
+    /// A button's presses and releases at given times, relative to when it was first pressed.
+    struct Button {
+        timing: Timing,
+        state: PressState,
+        start: Instant,
+    }
+
+    impl Button {
+        fn new(timing: Timing) -> Self {
+            Button {
+                timing,
+                state: PressState::default(),
+                start: Instant::now(),
+            }
+        }
+
+        fn at(&self, ms: u64) -> Instant {
+            self.start + Duration::from_millis(ms)
+        }
+
+        fn update(&mut self, pressed: bool, ms: u64) -> Vec<OutputEvent> {
+            let mut events = Vec::new();
+            let time = self.at(ms);
+            self.state
+                .update(&id(), &self.timing, pressed, time, &mut events);
+            events.into_iter().map(|e| e.event).collect()
+        }
+
+        fn expire(&mut self, ms: u64) -> Vec<OutputEvent> {
+            let mut events = Vec::new();
+            let now = self.at(ms);
+            self.state.expire(&id(), &self.timing, now, &mut events);
+            events.into_iter().map(|e| e.event).collect()
+        }
+
+        fn deadline(&self) -> Option<Instant> {
+            self.state.deadline(&self.timing)
+        }
+    }
+
+    #[test]
+    fn long_press_is_sent_while_held() {
+        let mut b = Button::new(timing(true, false, false));
+
+        assert_eq!(b.update(true, 0), [button(true)]);
+        assert_eq!(b.deadline(), Some(b.at(500)));
+        assert_eq!(b.expire(499), []);
+        assert_eq!(b.expire(500), [gesture(Gesture::LongPress)]);
+
+        // It's only sent once per press.
+        assert_eq!(b.deadline(), None);
+        assert_eq!(b.expire(1000), []);
+        assert_eq!(b.update(false, 1200), [button(false)]);
+    }
+
+    #[test]
+    fn suppressed_long_press_is_only_a_gesture() {
+        let mut b = Button::new(timing(true, false, true));
+
+        assert_eq!(b.update(true, 0), []);
+        assert_eq!(b.expire(500), [gesture(Gesture::LongPress)]);
+        assert_eq!(b.update(false, 700), []);
+    }
+
+    #[test]
+    fn release_during_long_press_is_a_short_press() {
+        let mut b = Button::new(timing(true, false, true));
+
+        assert_eq!(b.update(true, 0), []);
+        assert_eq!(b.update(false, 100), [button(true), button(false)]);
+
+        // Nothing is waited on once it's released.
+        assert_eq!(b.deadline(), None);
+        assert_eq!(b.expire(500), []);
+    }
+
+    #[test]
+    fn repeated_state_is_not_a_new_press() {
+        let mut b = Button::new(timing(true, false, true));
+
+        assert_eq!(b.update(true, 0), []);
+        assert_eq!(b.update(true, 200), []);
+        assert_eq!(b.deadline(), Some(b.at(500)));
+        assert_eq!(b.update(false, 300), [button(true), button(false)]);
+        assert_eq!(b.update(false, 400), []);
+    }
+
+    #[test]
+    fn double_press_is_sent_with_the_second_press() {
+        let mut b = Button::new(timing(false, true, false));
+
+        assert_eq!(b.update(true, 0), [button(true)]);
+        assert_eq!(b.update(false, 50), [button(false)]);
+        assert_eq!(
+            b.update(true, 300),
+            [button(true), gesture(Gesture::DoublePress)]
+        );
+        assert_eq!(b.update(false, 350), [button(false)]);
+
+        // A third press starts over, rather than being another double press.
+        assert_eq!(b.update(true, 400), [button(true)]);
+    }
+
+    #[test]
+    fn suppressed_double_press_is_only_a_gesture() {
+        let mut b = Button::new(timing(false, true, true));
+
+        assert_eq!(b.update(true, 0), []);
+        assert_eq!(b.update(false, 50), []);
+        assert_eq!(b.update(true, 200), [gesture(Gesture::DoublePress)]);
+        assert_eq!(b.update(false, 250), []);
+        assert_eq!(b.deadline(), None);
+    }
+
+    #[test]
+    fn suppressed_short_press_waits_for_double_press_window() {
+        let mut b = Button::new(timing(false, true, true));
+
+        assert_eq!(b.update(true, 0), []);
+        assert_eq!(b.update(false, 50), []);
+        assert_eq!(b.deadline(), Some(b.at(300)));
+        assert_eq!(b.expire(299), []);
+        assert_eq!(b.expire(300), [button(true), button(false)]);
+
+        // The window has expired, so the next press can't complete a double press.
+        assert_eq!(b.deadline(), None);
+        assert_eq!(b.update(true, 400), []);
+        assert_eq!(b.deadline(), Some(b.at(700)));
+    }
+
+    #[test]
+    fn held_back_press_is_sent_before_a_late_press() {
+        let mut b = Button::new(timing(false, true, true));
+
+        assert_eq!(b.update(true, 0), []);
+        assert_eq!(b.update(false, 50), []);
+
+        // The window expired without the timer noticing, so the second press isn't a double press,
+        // but the first one is still sent.
+        assert_eq!(b.update(true, 301), [button(true), button(false)]);
+        assert_eq!(b.update(false, 350), []);
+        assert_eq!(b.expire(601), [button(true), button(false)]);
+    }
+
+    #[test]
+    fn long_press_cannot_start_a_double_press() {
+        let mut b = Button::new(timing(true, true, true));
+
+        assert_eq!(b.update(true, 0), []);
+        assert_eq!(b.deadline(), Some(b.at(300)));
+        assert_eq!(b.expire(300), []);
+        assert_eq!(b.deadline(), Some(b.at(500)));
+        assert_eq!(b.expire(500), [gesture(Gesture::LongPress)]);
+        assert_eq!(b.update(false, 550), []);
+
+        // Pressing again straight away is an ordinary press, held back in case it's the first of a
+        // double press.
+        assert_eq!(b.update(true, 600), []);
+        assert_eq!(b.update(false, 650), []);
+        assert_eq!(b.expire(900), [button(true), button(false)]);
+    }
+
+    #[test]
+    fn long_press_is_sent_by_the_timer() {
+        let mappings: MappingConfig = toml::from_str(
+            "[button.horn]
+            long-press-ms = 50
+            suppress-press = true",
+        )
+        .unwrap();
+        let id = mappings.buttons().next().unwrap().0;
+
+        let (input_tx, input_rx) = async_broadcast::broadcast(64);
+        let (output_tx, mut output_rx) = async_broadcast::broadcast(64);
+        let task = smol::spawn(run(Arc::new(mappings), input_rx, output_tx));
+
+        smol::block_on(async {
+            let start = Instant::now();
+            let press = OutputEvent::UpdateButton(id.clone(), true);
+            input_tx.broadcast(press.into()).await.unwrap();
+
+            assert_eq!(
+                output_rx.recv().await.unwrap().event,
+                OutputEvent::ButtonGesture(id.clone(), Gesture::LongPress)
+            );
+            assert!(start.elapsed() >= Duration::from_millis(50));
+            assert_eq!(output_rx.recv().await.unwrap().event, OutputEvent::Flush);
+
+            // Its release isn't sent, since the press was sent as a long press.
+            let release = OutputEvent::UpdateButton(id.clone(), false);
+            input_tx.broadcast(release.into()).await.unwrap();
+            drop(input_tx);
+            task.await.unwrap();
+            assert!(output_rx.recv().await.is_err());
+        });
+    }
+}
@@ -0,0 +1,108 @@
+use std::sync::Arc;
+
+use hashbrown::HashMap;
+use string_cache::DefaultAtom;
+
+use crate::config::{AppConfig, MappingConfig};
+use crate::output::OutputEvent;
+
+/// The name of the profile made up of the configuration outside of any `[profile]` table.
+pub const DEFAULT: &str = "default";
+
+/// The settings that change along with the profile. Only the OSC and VMC outputs follow the
+/// profile; everything else uses the default one.
+#[derive(Debug)]
+pub struct Profile {
+    pub name: DefaultAtom,
+    pub mappings: Arc<MappingConfig>,
+    #[cfg(feature = "vmc")]
+    pub vmc: crate::vmc::Config,
+}
+
+/// Every profile, including the default one.
+#[derive(Debug)]
+pub struct Profiles {
+    profiles: HashMap<DefaultAtom, Profile>,
+}
+
+/// Which profile an output is using, switched by [`OutputEvent::SwitchProfile`] or by pressing a
+/// button that switches profile.
+#[derive(Debug)]
+pub struct ActiveProfile<'p> {
+    profiles: &'p Profiles,
+    current: &'p Profile,
+}
+
+impl Profile {
+    fn new(name: DefaultAtom, config: &AppConfig) -> Self {
+        Profile {
+            name,
+            mappings: config.mappings.clone(),
+            #[cfg(feature = "vmc")]
+            vmc: config.vmc.clone(),
+        }
+    }
+}
+
+impl Profiles {
+    pub fn new(config: &AppConfig) -> Arc<Self> {
+        let default = DefaultAtom::from(DEFAULT);
+        let mut profiles = HashMap::with_capacity(1 + config.profiles.len());
+        profiles.insert(default.clone(), Profile::new(default, config));
+
+        for (name, profile) in &config.profiles {
+            profiles.insert(name.clone(), Profile::new(name.clone(), profile));
+        }
+
+        Arc::new(Profiles { profiles })
+    }
+
+    pub fn default(&self) -> &Profile {
+        &self.profiles[&DefaultAtom::from(DEFAULT)]
+    }
+
+    pub fn get(&self, name: &DefaultAtom) -> Option<&Profile> {
+        self.profiles.get(name)
+    }
+}
+
+impl<'p> ActiveProfile<'p> {
+    pub fn new(profiles: &'p Profiles) -> Self {
+        ActiveProfile {
+            profiles,
+            current: profiles.default(),
+        }
+    }
+
+    pub fn current(&self) -> &'p Profile {
+        self.current
+    }
+
+    /// Switches profile if `event` calls for it, returning the new profile if it did. Switching to
+    /// an unknown profile keeps the current one.
+    pub fn handle(&mut self, event: &OutputEvent) -> Option<&'p Profile> {
+        let name = match *event {
+            OutputEvent::SwitchProfile(ref name) => name,
+            OutputEvent::UpdateButton(ref id, true) => {
+                let button = self.current.mappings.button.get(id.name())?;
+                button.output.profile.as_ref()?
+            }
+            _ => return None,
+        };
+
+        if *name == self.current.name {
+            return None;
+        }
+
+        let Some(profile) = self.profiles.get(name) else {
+            log::warn!(
+                "There is no profile named \"{name}\", so profile \"{}\" will be kept.",
+                self.current.name
+            );
+            return None;
+        };
+
+        self.current = profile;
+        Some(profile)
+    }
+}
@@ -0,0 +1,188 @@
+use std::io;
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use futures::prelude::*;
+use smol::net::UdpSocket;
+
+use crate::stats::{self, Counter, Gauge};
+
+/// How many errors a destination can have, without a recovery in between, before it's considered
+/// unreachable.
+const UNREACHABLE_AFTER: u32 = 3;
+
+/// How long data must be sent to a destination without any errors before it's considered reachable
+/// again.
+const RECOVERY_TIME: Duration = Duration::from_secs(5);
+
+/// How long a probe waits for an error to come back from its destination.
+const PROBE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// An empty OSC bundle, which does nothing if it arrives.
+const PROBE_PACKET: &[u8] = b"#bundle\0\0\0\0\0\0\0\0\x01";
+
+/// Every destination that output has been sent to, so that their state carries on if an output is
+/// restarted or the configuration is reloaded.
+static DESTINATIONS: Mutex<Vec<Arc<Destination>>> = Mutex::new(Vec::new());
+
+/// Somewhere that an output sends data to, and whether it seems to be receiving it.
+#[derive(Debug)]
+pub struct Destination {
+    name: String,
+    addr: SocketAddr,
+    state: Mutex<State>,
+    reachable: &'static Gauge,
+    errors: &'static Counter,
+}
+
+#[derive(Debug)]
+struct State {
+    reachable: bool,
+    /// Errors since the destination last recovered.
+    errors: u32,
+    last_error: Option<Instant>,
+}
+
+impl Destination {
+    /// Finds the destination for `output` sending to `addr`, adding it if there isn't one yet.
+    pub fn get(output: &str, addr: SocketAddr) -> Arc<Destination> {
+        let name = format!("{output} output to {addr}");
+
+        let mut destinations = DESTINATIONS.lock().unwrap();
+        if let Some(destination) = destinations.iter().find(|d| d.name == name) {
+            return destination.clone();
+        }
+
+        let reachable = stats::gauge(&name, "reachable");
+        reachable.set(1);
+
+        let destination = Arc::new(Destination {
+            errors: stats::counter(&name, "send errors"),
+            reachable,
+            state: Mutex::new(State {
+                reachable: true,
+                errors: 0,
+                last_error: None,
+            }),
+            addr,
+            name,
+        });
+
+        destinations.push(destination.clone());
+        destination
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Whether data sent to the destination seems to be arriving.
+    pub fn is_reachable(&self) -> bool {
+        self.state.lock().unwrap().reachable
+    }
+
+    /// Records an error from sending to the destination, or one that it sent back.
+    pub fn failed(&self, error: &io::Error) {
+        self.errors.increment();
+
+        let mut state = self.state.lock().unwrap();
+        state.errors += 1;
+        state.last_error = Some(Instant::now());
+
+        if state.reachable && state.errors >= UNREACHABLE_AFTER {
+            state.reachable = false;
+            self.reachable.set(0);
+            log::warn!(
+                "{} is UNREACHABLE ({error}). Nothing sent to it will arrive; check that the receiving application is running and listening on that address.",
+                self.name
+            );
+        }
+    }
+
+    /// Records that data was sent to the destination without an error.
+    pub fn sent(&self) {
+        let mut state = self.state.lock().unwrap();
+        match state.last_error {
+            Some(time) if time.elapsed() >= RECOVERY_TIME => {}
+            _ => return,
+        }
+
+        state.errors = 0;
+        state.last_error = None;
+
+        if !state.reachable {
+            state.reachable = true;
+            self.reachable.set(1);
+            log::info!("{} is reachable again.", self.name);
+        }
+    }
+}
+
+/// Whether `error` is one that the OS gives when a destination sent back an ICMP error (e.g. port
+/// unreachable), which it only reports on a later send or receive.
+pub fn is_icmp_error(error: &io::Error) -> bool {
+    matches!(
+        error.kind(),
+        io::ErrorKind::ConnectionRefused | io::ErrorKind::ConnectionReset
+    )
+}
+
+/// Periodically sends a tiny packet to each destination, from a socket of its own, to see whether
+/// an error comes back. This finds unreachable destinations even where the OS doesn't report errors
+/// on the socket that output is sent from, and finds when they're reachable again even when nothing
+/// else is being sent to them. Runs until it's dropped.
+pub async fn probe(destinations: Vec<Arc<Destination>>, interval: f64) {
+    let interval = Duration::from_secs_f64(interval);
+    future::join_all(destinations.iter().map(|d| probe_one(d, interval))).await;
+}
+
+async fn probe_one(destination: &Destination, interval: Duration) {
+    let addr = destination.addr;
+    let local_addr = match addr {
+        SocketAddr::V4(_) => SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), 0),
+        SocketAddr::V6(_) => SocketAddr::new(Ipv6Addr::UNSPECIFIED.into(), 0),
+    };
+
+    // Errors that come back are only reported on a connected socket.
+    let socket = async {
+        let socket = UdpSocket::bind(local_addr).await?;
+        socket.connect(addr).await?;
+        io::Result::Ok(socket)
+    };
+
+    let socket = match socket.await {
+        Ok(socket) => socket,
+        Err(e) => {
+            log::warn!("Failed to set up probing of {}: {e}", destination.name);
+            return;
+        }
+    };
+
+    let mut buffer = [0; 64];
+    let mut timer = smol::Timer::interval(interval);
+
+    while timer.next().await.is_some() {
+        let result = match socket.send(PROBE_PACKET).await {
+            Ok(_) => {
+                let reply = socket.recv(&mut buffer).map_ok(|_| ());
+                let timeout = async {
+                    smol::Timer::after(PROBE_TIMEOUT).await;
+                    Ok(())
+                };
+
+                smol::future::or(reply, timeout).await
+            }
+
+            Err(e) => Err(e),
+        };
+
+        match result {
+            Ok(()) => destination.sent(),
+            Err(e) => {
+                log::debug!("Probe of {} failed: {e}", destination.name);
+                destination.failed(&e);
+            }
+        }
+    }
+}
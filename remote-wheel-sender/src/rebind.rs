@@ -0,0 +1,255 @@
+use std::io;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::{Context as _, Result as AnyResult};
+use futures::prelude::*;
+use smol::channel::{Receiver as ChannelRx, Sender as ChannelTx};
+use smol::net::UdpSocket;
+
+use crate::check::Report;
+use crate::reachability;
+
+/// How many errors in a row a socket can have before it's bound again.
+const REBIND_AFTER: u32 = 5;
+
+/// How long to wait after failing to bind a socket again, at first and at most. The delay doubles
+/// after each failure.
+const MIN_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+/// A UDP socket bound to a configured address, which is bound to it again if it stops working, e.g.
+/// when the network interface changes. Errors are still returned, so that they can be reported.
+///
+/// A socket that's received on is bound again by whatever is receiving on it, since the old one has
+/// to be closed first to free up its address.
+#[derive(Debug)]
+pub struct RebindingSocket {
+    name: String,
+    address: SocketAddr,
+    options: SocketOptions,
+    socket: Mutex<Option<Arc<UdpSocket>>>,
+
+    /// Errors since data was last sent or received successfully.
+    errors: AtomicU32,
+    receiving: AtomicBool,
+    /// Whether data has been received since the socket was last bound.
+    flowing: AtomicBool,
+    rebind_tx: ChannelTx<()>,
+    rebind_rx: ChannelRx<()>,
+}
+
+/// Options for a socket, which are applied each time that it's bound.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SocketOptions {
+    /// How long a socket that was receiving data can go without any before it's bound again, in
+    /// case the network interface that it was on has gone away without an error. Sockets bound to
+    /// port 0 are never bound again for this, since they would end up on a different port.
+    pub stall_timeout: Option<Duration>,
+
+    /// How many hops multicast that's sent may travel.
+    pub multicast_ttl: Option<u32>,
+    /// The address of the interface that IPv4 multicast is sent from.
+    pub multicast_interface_v4: Option<Ipv4Addr>,
+    /// The index of the interface that IPv6 multicast is sent from.
+    pub multicast_interface_v6: Option<u32>,
+}
+
+impl SocketOptions {
+    fn apply(&self, socket: &UdpSocket) -> io::Result<()> {
+        let ipv4 = socket.local_addr()?.is_ipv4();
+        let socket = socket2::SockRef::from(socket);
+
+        match self.multicast_ttl {
+            Some(ttl) if ipv4 => socket.set_multicast_ttl_v4(ttl)?,
+            Some(hops) => socket.set_multicast_hops_v6(hops)?,
+            None => {}
+        }
+
+        if let Some(ref interface) = self.multicast_interface_v4.filter(|_| ipv4) {
+            socket.set_multicast_if_v4(interface)?;
+        }
+
+        if let Some(interface) = self.multicast_interface_v6.filter(|_| !ipv4) {
+            socket.set_multicast_if_v6(interface)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl RebindingSocket {
+    /// Binds a socket with `options` set on it, which are set again whenever it's bound again.
+    pub async fn bind_with(
+        name: impl Into<String>,
+        address: SocketAddr,
+        options: SocketOptions,
+    ) -> AnyResult<Arc<Self>> {
+        let name = name.into();
+        let socket = open(address, options)
+            .await
+            .with_context(|| format!("Failed to bind {name} to UDP socket {address}"))?;
+        let (rebind_tx, rebind_rx) = smol::channel::bounded(1);
+
+        Ok(Arc::new(RebindingSocket {
+            name,
+            address,
+            options,
+            socket: Mutex::new(Some(Arc::new(socket))),
+
+            errors: AtomicU32::new(0),
+            receiving: AtomicBool::new(false),
+            flowing: AtomicBool::new(false),
+            rebind_tx,
+            rebind_rx,
+        }))
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.get()?.local_addr()
+    }
+
+    fn get(&self) -> io::Result<Arc<UdpSocket>> {
+        self.socket.lock().unwrap().clone().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::NotConnected, "socket is being bound again")
+        })
+    }
+
+    pub async fn recv_from(&self, buffer: &mut [u8]) -> io::Result<(usize, SocketAddr)> {
+        self.receiving.store(true, Ordering::Relaxed);
+        let stall_timeout = match self.address.port() {
+            0 => None,
+            _ => self.options.stall_timeout,
+        };
+
+        loop {
+            let socket = self.get()?;
+            let stall = match stall_timeout.filter(|_| self.flowing.load(Ordering::Relaxed)) {
+                Some(timeout) => smol::Timer::after(timeout),
+                None => smol::Timer::never(),
+            };
+
+            let result = futures::select_biased! {
+                _ = self.rebind_rx.recv().fuse() => None,
+                _ = futures::FutureExt::fuse(stall) => {
+                    let secs = stall_timeout.unwrap_or_default().as_secs_f64();
+                    log::warn!("{} hasn't received any data for {secs} seconds.", self.name);
+                    None
+                },
+                result = socket.recv_from(buffer).fuse() => Some(result),
+            };
+
+            match result {
+                Some(Ok(received)) => {
+                    self.errors.store(0, Ordering::Relaxed);
+                    self.flowing.store(true, Ordering::Relaxed);
+                    return Ok(received);
+                }
+
+                Some(Err(e)) => {
+                    self.failed(&e);
+                    return Err(e);
+                }
+
+                None => {
+                    drop(socket);
+                    self.rebind().await;
+                }
+            }
+        }
+    }
+
+    pub async fn send_to(&self, data: &[u8], addr: SocketAddr) -> io::Result<usize> {
+        let result = self.get()?.send_to(data, addr).await;
+        match result {
+            Ok(_) => self.errors.store(0, Ordering::Relaxed),
+            Err(ref e) => {
+                // Nothing else will bind a socket again if nothing is receiving on it.
+                if self.failed(e) && !self.receiving.load(Ordering::Relaxed) {
+                    self.rebind().await;
+                }
+            }
+        }
+
+        result
+    }
+
+    /// Counts an error, returning whether the socket should be bound again. Errors that come back
+    /// from a destination say nothing about the socket itself, so they aren't counted.
+    fn failed(&self, error: &io::Error) -> bool {
+        if reachability::is_icmp_error(error) {
+            return false;
+        }
+
+        let errors = self.errors.fetch_add(1, Ordering::Relaxed) + 1;
+        if errors < REBIND_AFTER {
+            return false;
+        }
+
+        let _ = self.rebind_tx.try_send(());
+        true
+    }
+
+    async fn rebind(&self) {
+        // The old socket is closed first, so that its address is free.
+        drop(self.socket.lock().unwrap().take());
+
+        let mut backoff = MIN_BACKOFF;
+        for attempt in 1.. {
+            log::warn!(
+                "Binding {} to {} again (attempt {attempt})...",
+                self.name,
+                self.address
+            );
+
+            match open(self.address, self.options).await {
+                Ok(socket) => {
+                    *self.socket.lock().unwrap() = Some(Arc::new(socket));
+                    break;
+                }
+
+                Err(e) => {
+                    log::warn!(
+                        "Failed to bind {} to {}: {e}. Trying again in {} second(s).",
+                        self.name,
+                        self.address,
+                        backoff.as_secs()
+                    );
+                    smol::Timer::after(backoff).await;
+                    backoff = (backoff * 2).min(MAX_BACKOFF);
+                }
+            }
+        }
+
+        self.errors.store(0, Ordering::Relaxed);
+        self.flowing.store(false, Ordering::Relaxed);
+        while self.rebind_rx.try_recv().is_ok() {}
+        log::info!("{} has been bound to {} again.", self.name, self.address);
+    }
+}
+
+/// Checks a stall timeout (see [`SocketOptions::stall_timeout`]) for a socket bound to `address`.
+pub fn validate_stall_timeout(
+    path: &str,
+    address: SocketAddr,
+    timeout: Option<f64>,
+    report: &mut Report,
+) {
+    match timeout {
+        Some(timeout) if timeout <= 0.0 => report.error(path, "Stall timeout must be positive"),
+        Some(_) if address.port() == 0 => report.warning(
+            path,
+            "The port is chosen automatically, so the socket will not be bound again when it \
+            stalls",
+        ),
+        _ => {}
+    }
+}
+
+async fn open(address: SocketAddr, options: SocketOptions) -> io::Result<UdpSocket> {
+    let socket = UdpSocket::bind(address).await?;
+    options.apply(&socket)?;
+    Ok(socket)
+}
@@ -0,0 +1,179 @@
+use std::fmt::{Display, Formatter, Result as FmtResult};
+
+use hashbrown::HashMap;
+use string_cache::DefaultAtom;
+
+use crate::config::{AxisConfig, ButtonConfig, MappingConfig};
+
+/// A mapping, as it's carried by events. Along with its name, it has an index which is unique among
+/// the mappings of its kind, so that outputs can find what it's routed to without looking up its
+/// name.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct MappingId {
+    name: DefaultAtom,
+    index: u32,
+}
+
+/// The IDs of every mapping, by name. Mappings are numbered in order of their names, separately for
+/// axes and buttons.
+#[derive(Debug)]
+pub struct MappingIds {
+    axis: HashMap<DefaultAtom, MappingId>,
+    button: HashMap<DefaultAtom, MappingId>,
+}
+
+/// What each mapping of one kind is routed to by an output. This is resolved from the
+/// configuration once, when the output starts, so that each event only needs to be indexed.
+#[derive(Debug)]
+pub struct Routes<T> {
+    routes: Vec<Option<T>>,
+}
+
+impl MappingId {
+    /// An ID for tests, which don't have a configuration to number mappings from.
+    #[cfg(test)]
+    pub fn new(name: &str, index: u32) -> MappingId {
+        MappingId {
+            name: name.into(),
+            index,
+        }
+    }
+
+    pub fn name(&self) -> &DefaultAtom {
+        &self.name
+    }
+
+    pub fn index(&self) -> usize {
+        self.index as usize
+    }
+}
+
+impl Display for MappingId {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        Display::fmt(&self.name, f)
+    }
+}
+
+impl MappingIds {
+    pub fn new(mappings: &MappingConfig) -> Self {
+        MappingIds {
+            axis: number(mappings.axis.keys()),
+            button: number(mappings.button.keys()),
+        }
+    }
+
+    pub fn axis(&self, name: &DefaultAtom) -> Option<&MappingId> {
+        self.axis.get(name)
+    }
+
+    pub fn button(&self, name: &DefaultAtom) -> Option<&MappingId> {
+        self.button.get(name)
+    }
+}
+
+fn number<'a>(names: impl Iterator<Item = &'a DefaultAtom>) -> HashMap<DefaultAtom, MappingId> {
+    let mut names = names.collect::<Vec<_>>();
+    names.sort_unstable_by(|a, b| a.as_ref().cmp(b.as_ref()));
+
+    names
+        .into_iter()
+        .zip(0..)
+        .map(|(name, index)| {
+            let id = MappingId {
+                name: name.clone(),
+                index,
+            };
+            (name.clone(), id)
+        })
+        .collect()
+}
+
+impl<T> Routes<T> {
+    /// Resolves what each axis is routed to. Axes that `resolve` gives nothing for aren't routed.
+    pub fn axes<'a>(
+        mappings: &'a MappingConfig,
+        resolve: impl FnMut(&MappingId, &'a AxisConfig) -> Option<T>,
+    ) -> Self {
+        Self::resolve(mappings.axes(), mappings.axis.len(), resolve)
+    }
+
+    /// Resolves what each button is routed to. Buttons that `resolve` gives nothing for aren't
+    /// routed.
+    pub fn buttons<'a>(
+        mappings: &'a MappingConfig,
+        resolve: impl FnMut(&MappingId, &'a ButtonConfig) -> Option<T>,
+    ) -> Self {
+        Self::resolve(mappings.buttons(), mappings.button.len(), resolve)
+    }
+
+    fn resolve<C>(
+        mappings: impl Iterator<Item = (MappingId, C)>,
+        len: usize,
+        mut resolve: impl FnMut(&MappingId, C) -> Option<T>,
+    ) -> Self {
+        let mut routes = Vec::new();
+        routes.resize_with(len, || None);
+
+        for (id, config) in mappings {
+            routes[id.index()] = resolve(&id, config);
+        }
+
+        Routes { routes }
+    }
+
+    /// What a mapping is routed to, if anything.
+    pub fn get(&self, id: &MappingId) -> Option<&T> {
+        self.routes.get(id.index())?.as_ref()
+    }
+
+    pub fn get_mut(&mut self, id: &MappingId) -> Option<&mut T> {
+        self.routes.get_mut(id.index())?.as_mut()
+    }
+
+    /// Everything that mappings are routed to.
+    pub fn values(&self) -> impl Iterator<Item = &T> {
+        self.routes.iter().flatten()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use super::*;
+
+    /// Compares finding what each mapping is routed to by its index to looking it up by name, as
+    /// outputs did before mappings had IDs. Run with `cargo test --release -- --ignored --nocapture`.
+    #[test]
+    #[ignore]
+    fn bench_lookup() {
+        const ITERATIONS: u32 = 1_000_000;
+        const MAPPINGS: usize = 32;
+
+        let config = (0..MAPPINGS)
+            .map(|i| format!("[axis.axis{i}]\n"))
+            .collect::<String>();
+        let mappings: MappingConfig = toml::from_str(&config).unwrap();
+
+        let routes = Routes::axes(&mappings, |id, _| Some(id.index()));
+        let by_name = mappings
+            .axes()
+            .map(|(id, _)| (id.name().clone(), id.index()))
+            .collect::<HashMap<_, _>>();
+        let ids = mappings.axes().map(|(id, _)| id).collect::<Vec<_>>();
+
+        let start = Instant::now();
+        for id in ids.iter().cycle().take(ITERATIONS as usize) {
+            std::hint::black_box(routes.get(std::hint::black_box(id)));
+        }
+        let indexed = start.elapsed() / ITERATIONS;
+
+        let start = Instant::now();
+        for id in ids.iter().cycle().take(ITERATIONS as usize) {
+            std::hint::black_box(by_name.get(std::hint::black_box(id).name()));
+        }
+        let named = start.elapsed() / ITERATIONS;
+
+        println!("Route lookup: {indexed:?}/event by index, {named:?}/event by name");
+    }
+}
@@ -0,0 +1,289 @@
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::{Context as _, Result as AnyResult};
+use async_broadcast::{Receiver as BroadcastRx, RecvError as BroadcastRxErr};
+use futures::prelude::*;
+use hashbrown::HashMap;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use smol::channel::Receiver as ChannelRx;
+use string_cache::DefaultAtom;
+
+use crate::config::{AppConfig, AxisConfig, MappingConfig};
+use crate::output::{OutputEvent, Timestamped};
+use crate::paths;
+use crate::route::Routes;
+use crate::stats::{self, Metric};
+use crate::status;
+
+#[derive(Clone, Debug, Deserialize, JsonSchema, Serialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct SessionConfig {
+    summary: bool,
+    file: Option<PathBuf>,
+}
+
+/// Works out the angle that an axis turns a wheel to, for axes whose mappings turn a VMC wheel
+/// device. Angles are integrated from these, rather than from the axes' values, so that the
+/// rotations counted are those of the wheel.
+pub struct Wheels {
+    #[cfg(feature = "vmc")]
+    vmc: Option<crate::vmc::Config>,
+}
+
+/// What has happened so far. This is kept for as long as the Sender runs, so that reloading the
+/// configuration carries on with the same session.
+#[derive(Debug, Default)]
+struct Session {
+    /// The configuration of the latest session task, which says what to do with the summary.
+    config: SessionConfig,
+    wheels: HashMap<DefaultAtom, WheelStats>,
+    buttons: HashMap<DefaultAtom, ButtonStats>,
+}
+
+#[derive(Debug, Default)]
+struct WheelStats {
+    angle: Option<f32>,
+    /// How far the wheel has turned, in degrees, in either direction.
+    turned: f64,
+    max_angle: f32,
+}
+
+#[derive(Debug, Default)]
+struct ButtonStats {
+    pressed: bool,
+    presses: u64,
+}
+
+/// The summary of a session, as it's written to the file.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct Summary {
+    duration_seconds: f64,
+    wheels: BTreeMap<DefaultAtom, WheelSummary>,
+    button_presses: BTreeMap<DefaultAtom, u64>,
+    /// How many times each output has sent data.
+    sends: BTreeMap<&'static str, u64>,
+    /// How many events were missed by anything falling too far behind the inputs.
+    missed_events: u64,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct WheelSummary {
+    rotations: f64,
+    max_angle: f32,
+}
+
+static SESSION: Mutex<Option<Session>> = Mutex::new(None);
+
+impl Default for SessionConfig {
+    fn default() -> Self {
+        SessionConfig {
+            summary: true,
+            file: None,
+        }
+    }
+}
+
+impl Wheels {
+    #[cfg_attr(not(feature = "vmc"), allow(unused_variables))]
+    pub fn new(config: &AppConfig) -> Self {
+        Wheels {
+            #[cfg(feature = "vmc")]
+            vmc: config.vmc.enabled().then(|| config.vmc.clone()),
+        }
+    }
+
+    #[cfg_attr(not(feature = "vmc"), allow(unused_variables))]
+    fn angle(&self, axis: &AxisConfig, value: f64) -> Option<f32> {
+        #[cfg(feature = "vmc")]
+        if let Some(vmc) = &self.vmc {
+            return vmc.wheel_angle(&axis.output.vmc, value);
+        }
+
+        None
+    }
+}
+
+impl Session {
+    fn turn(&mut self, id: DefaultAtom, angle: f32) {
+        let wheel = self.wheels.entry(id).or_default();
+        if let Some(previous) = wheel.angle.replace(angle) {
+            wheel.turned += f64::from((angle - previous).abs());
+        }
+        wheel.max_angle = wheel.max_angle.max(angle.abs());
+    }
+
+    fn press(&mut self, id: DefaultAtom, pressed: bool) {
+        let button = self.buttons.entry(id).or_default();
+        if pressed && !button.pressed {
+            button.presses += 1;
+        }
+        button.pressed = pressed;
+    }
+
+    fn summarize(&self) -> Summary {
+        let mut sends = BTreeMap::new();
+        let mut missed_events = 0;
+        for entry in stats::registry() {
+            if let Metric::Counter(counter) = entry.metric {
+                match entry.name {
+                    "sends" => {
+                        sends.insert(entry.source, counter.get());
+                    }
+                    "missed events" => missed_events += counter.get(),
+                    _ => {}
+                }
+            }
+        }
+
+        Summary {
+            duration_seconds: status::uptime().as_secs_f64(),
+            wheels: self
+                .wheels
+                .iter()
+                .map(|(id, wheel)| {
+                    let summary = WheelSummary {
+                        rotations: wheel.turned / 360.0,
+                        max_angle: wheel.max_angle,
+                    };
+                    (id.clone(), summary)
+                })
+                .collect(),
+            button_presses: self
+                .buttons
+                .iter()
+                .map(|(id, button)| (id.clone(), button.presses))
+                .collect(),
+            sends,
+            missed_events,
+        }
+    }
+}
+
+impl Summary {
+    fn describe(&self) -> String {
+        let duration = Duration::from_secs_f64(self.duration_seconds).as_secs();
+        let mut out = format!(
+            "Session summary: ran for {}h {:02}m {:02}s.",
+            duration / 3600,
+            duration / 60 % 60,
+            duration % 60,
+        );
+
+        for (id, wheel) in &self.wheels {
+            let _ = write!(
+                out,
+                "\nMapping {id}: turned {:.1} full rotation(s), at most {:.1} degrees from center",
+                wheel.rotations, wheel.max_angle,
+            );
+        }
+
+        let presses = self
+            .button_presses
+            .iter()
+            .filter(|&(_, &count)| count > 0)
+            .map(|(id, count)| format!("{id} {count}"))
+            .collect::<Vec<_>>();
+        if !presses.is_empty() {
+            let _ = write!(out, "\nButton presses: {}", presses.join(", "));
+        }
+
+        for (source, count) in &self.sends {
+            let _ = write!(out, "\n{source}: {count} send(s)");
+        }
+
+        let _ = write!(out, "\nMissed events: {}", self.missed_events);
+        out
+    }
+}
+
+/// Keeps track of what the inputs do over the session, for the summary that's given when the
+/// Sender stops.
+pub async fn run(
+    config: SessionConfig,
+    mappings: Arc<MappingConfig>,
+    wheels: Wheels,
+    mut value_rx: BroadcastRx<Timestamped<OutputEvent>>,
+    cancel_rx: ChannelRx<()>,
+) {
+    let enabled = config.summary || config.file.is_some();
+    SESSION
+        .lock()
+        .unwrap()
+        .get_or_insert_with(Session::default)
+        .config = config;
+
+    if !enabled {
+        return;
+    }
+
+    let axes = Routes::axes(&mappings, |_, a| Some(a));
+
+    loop {
+        let event = futures::select_biased! {
+            _ = cancel_rx.recv().fuse() => break,
+            event = value_rx.recv().fuse() => event,
+        };
+
+        match event.map(|e| e.event) {
+            Ok(OutputEvent::UpdateAxis(id, value)) => {
+                let Some(angle) = axes.get(&id).and_then(|a| wheels.angle(a, value)) else {
+                    continue;
+                };
+
+                if let Some(session) = SESSION.lock().unwrap().as_mut() {
+                    session.turn(id.name().clone(), angle);
+                }
+            }
+
+            // A restored button wasn't pressed during this session.
+            Ok(OutputEvent::UpdateButton(id, pressed)) => {
+                if let Some(session) = SESSION.lock().unwrap().as_mut() {
+                    session.press(id.name().clone(), pressed);
+                }
+            }
+
+            Ok(_) => {}
+
+            Err(BroadcastRxErr::Overflowed(n)) => {
+                log::warn!("Session task missed {n} update(s)!");
+                stats::counter("Session", "missed events").add(n);
+            }
+
+            Err(BroadcastRxErr::Closed) => break,
+        }
+    }
+}
+
+/// Logs the summary of the session and writes it to its file, as configured. This is done once,
+/// when the Sender stops for good.
+pub fn finish() {
+    let Some(session) = SESSION.lock().unwrap().take() else {
+        return;
+    };
+
+    let summary = session.summarize();
+    if session.config.summary {
+        log::info!("{}", summary.describe());
+    }
+
+    if let Some(file) = session.config.file {
+        let path = paths::file(file);
+        match write(&path, &summary) {
+            Ok(()) => log::info!("Session summary written to <{}>.", path.display()),
+            Err(e) => log::warn!("{e:#}"),
+        }
+    }
+}
+
+fn write(path: &Path, summary: &Summary) -> AnyResult<()> {
+    let data = serde_json::to_vec_pretty(summary)?;
+    std::fs::write(path, data)
+        .with_context(|| format!("Failed to write session summary to <{}>", path.display()))
+}
@@ -0,0 +1,267 @@
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{Context as _, Result as AnyResult};
+use async_broadcast::{
+    Receiver as BroadcastRx, RecvError as BroadcastRxErr, Sender as BroadcastTx,
+};
+use futures::prelude::*;
+use hashbrown::HashMap;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use string_cache::DefaultAtom;
+
+use crate::button::ButtonMode;
+use crate::check::Report;
+use crate::config::MappingConfig;
+use crate::output::{OutputEvent, Timestamped};
+use crate::paths;
+use crate::stats;
+
+/// The name of the file that the latest value of each mapping is kept in.
+const STATE_NAME: &str = "remote-wheel-state.json";
+
+/// The version of the state file's format. A file with any other version is ignored.
+const STATE_VERSION: u32 = 1;
+
+#[derive(Clone, Debug, Deserialize, JsonSchema, Serialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct StateConfig {
+    enabled: bool,
+    buttons: bool,
+
+    #[serde(deserialize_with = "crate::units::seconds")]
+    #[schemars(schema_with = "crate::units::seconds_schema")]
+    save_delay: f64,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct State {
+    version: u32,
+    axis: HashMap<DefaultAtom, f64>,
+    button: HashMap<DefaultAtom, bool>,
+}
+
+impl StateConfig {
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn validate(&self, report: &mut Report) {
+        if !(self.save_delay >= 0.0 && self.save_delay.is_finite()) {
+            report.error(
+                "state.save-delay",
+                "Save delay must be a non-negative number of seconds",
+            );
+        }
+    }
+}
+
+impl Default for StateConfig {
+    fn default() -> Self {
+        StateConfig {
+            enabled: false,
+            buttons: true,
+            save_delay: 1.0,
+        }
+    }
+}
+
+impl Default for State {
+    fn default() -> Self {
+        State {
+            version: STATE_VERSION,
+            axis: HashMap::new(),
+            button: HashMap::new(),
+        }
+    }
+}
+
+/// Passes input events on to the outputs, keeping the latest value of each mapping in a file. When
+/// started, the values from that file are sent first, so that outputs don't fall back to their
+/// defaults until each input is used again.
+pub async fn run(
+    config: StateConfig,
+    mappings: Arc<MappingConfig>,
+    mut input_rx: BroadcastRx<Timestamped<OutputEvent>>,
+    output_tx: BroadcastTx<Timestamped<OutputEvent>>,
+) -> AnyResult<()> {
+    log::info!("State task starting...");
+
+    let path = paths::file(STATE_NAME);
+    let mut state = load(&path).await;
+    restrict(&mut state, &config, &mappings);
+
+    // Live inputs wait in the channel until these have been sent, so they can't be overwritten by
+    // older values.
+    let restored = state.axis.len() + state.button.len();
+    let events = state
+        .axis
+        .iter()
+        .filter_map(|(id, &value)| Some(OutputEvent::UpdateAxis(mappings.axis_id(id)?, value)))
+        .chain(state.button.iter().filter_map(|(id, &pressed)| {
+            Some(OutputEvent::RestoreButton(mappings.button_id(id)?, pressed))
+        }))
+        .chain((restored > 0).then_some(OutputEvent::Flush))
+        .collect::<Vec<_>>();
+
+    for event in events {
+        if output_tx.broadcast(event.into()).await.is_err() {
+            log::info!("State task stopping (no remaining outputs).");
+            return Ok(());
+        }
+    }
+
+    if restored > 0 {
+        log::info!("Restored the last values of {restored} mapping(s).");
+    }
+
+    let save_delay = Duration::from_secs_f64(config.save_delay);
+    let mut save_timer = smol::Timer::never();
+    let mut dirty = false;
+
+    log::info!("State task started.");
+
+    loop {
+        futures::select_biased! {
+            _ = future::FutureExt::fuse(&mut save_timer) => {
+                // A timer that has fired stays ready, so it's replaced until the next change.
+                save_timer = smol::Timer::never();
+                save(&path, &state).await;
+                dirty = false;
+            },
+
+            event = input_rx.recv().fuse() => {
+                let event = match event {
+                    Ok(event) => event,
+
+                    Err(BroadcastRxErr::Overflowed(n)) => {
+                        log::warn!("State task missed {n} update(s)!");
+                        stats::counter("State", "missed events").add(n);
+                        continue;
+                    }
+
+                    Err(BroadcastRxErr::Closed) => {
+                        log::info!("State task stopping (no remaining inputs).");
+                        break;
+                    }
+                };
+
+                let changed = match event.event {
+                    OutputEvent::UpdateAxis(ref id, value) => {
+                        state.axis.insert(id.name().clone(), value) != Some(value)
+                    }
+
+                    OutputEvent::UpdateButton(ref id, pressed) if restores_button(&config, &mappings, id.name()) => {
+                        state.button.insert(id.name().clone(), pressed) != Some(pressed)
+                    }
+
+                    _ => false,
+                };
+
+                // The file is saved a while after the first change since it was last saved, rather
+                // than after changes stop, so that an axis that's always moving is still saved.
+                if changed && !dirty {
+                    dirty = true;
+                    save_timer.set_after(save_delay);
+                }
+
+                if output_tx.broadcast(event).await.is_err() {
+                    log::info!("State task stopping (no remaining outputs).");
+                    break;
+                }
+            },
+        }
+    }
+
+    if dirty {
+        save(&path, &state).await;
+    }
+
+    log::info!("State task stopped.");
+    Ok(())
+}
+
+/// Reads the saved state. If it can't be used, it's ignored, and the state starts out empty.
+async fn load(path: &Path) -> State {
+    let data = match smol::fs::read(path).await {
+        Ok(data) => data,
+        Err(e) if e.kind() == ErrorKind::NotFound => return State::default(),
+        Err(e) => {
+            log::warn!(
+                "Ignoring saved state (failed to read <{}>: {e}).",
+                path.display()
+            );
+            return State::default();
+        }
+    };
+
+    match serde_json::from_slice::<State>(&data) {
+        Ok(state) if state.version == STATE_VERSION => state,
+
+        Ok(state) => {
+            log::warn!(
+                "Ignoring saved state (<{}> is from another version of the Sender, format {}).",
+                path.display(),
+                state.version,
+            );
+            State::default()
+        }
+
+        Err(e) => {
+            log::warn!(
+                "Ignoring saved state (<{}> is corrupt: {e}).",
+                path.display()
+            );
+            State::default()
+        }
+    }
+}
+
+/// Removes anything from the state which shouldn't be restored with the current configuration.
+fn restrict(state: &mut State, config: &StateConfig, mappings: &MappingConfig) {
+    let before = state.axis.len() + state.button.len();
+    state.axis.retain(|id, _| mappings.axis.contains_key(id));
+    state
+        .button
+        .retain(|id, _| restores_button(config, mappings, id));
+
+    let stale = before - state.axis.len() - state.button.len();
+    if stale > 0 {
+        log::warn!("Ignoring the saved state of {stale} mapping(s) which are no longer configured to be restored.");
+    }
+}
+
+/// Whether a button's state is restored. Only held buttons are, since the inputs of any others keep
+/// track of their state themselves and would disagree with it.
+fn restores_button(config: &StateConfig, mappings: &MappingConfig, id: &DefaultAtom) -> bool {
+    config.buttons
+        && mappings
+            .button
+            .get(id)
+            .is_some_and(|b| b.mode == ButtonMode::Hold)
+}
+
+async fn save(path: &Path, state: &State) {
+    if let Err(e) = write(path, state).await {
+        log::warn!("Failed to save state: {e:#}");
+    }
+}
+
+/// Writes the state to a temporary file first, so that the existing one isn't left half-written if
+/// the Sender stops partway through.
+async fn write(path: &Path, state: &State) -> AnyResult<()> {
+    let data = serde_json::to_vec(state)?;
+
+    let mut temp = PathBuf::from(path);
+    temp.set_extension("json.tmp");
+
+    smol::fs::write(&temp, data)
+        .await
+        .with_context(|| format!("Failed to write <{}>", temp.display()))?;
+    smol::fs::rename(&temp, path)
+        .await
+        .with_context(|| format!("Failed to replace <{}>", path.display()))
+}
@@ -0,0 +1,657 @@
+use std::fmt::Write as _;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use futures::prelude::*;
+use hashbrown::{HashMap, HashSet};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use smol::channel::Receiver as ChannelRx;
+use string_cache::DefaultAtom;
+
+use crate::check::Report;
+use crate::config::MappingConfig;
+use crate::output::{OutputEvent, Timestamped};
+use crate::route::MappingId;
+
+#[derive(Debug, Default, Deserialize, JsonSchema, Serialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct StatsConfig {
+    #[serde(deserialize_with = "crate::units::optional_seconds")]
+    #[schemars(schema_with = "crate::units::optional_seconds_schema")]
+    report_interval: Option<f64>,
+    mappings: bool,
+}
+
+impl StatsConfig {
+    pub fn validate(&self, report: &mut Report) {
+        if self
+            .report_interval
+            .is_some_and(|i| !(i > 0.0 && i.is_finite()))
+        {
+            report.error("stats.report-interval", "Report interval must be positive");
+        }
+    }
+}
+
+/// A statistic which a task updates as it runs, and which the reports read.
+#[derive(Debug)]
+pub enum Metric {
+    /// How many times something has happened since the Sender started.
+    Counter(Counter),
+    /// A value that may go up or down.
+    Gauge(Gauge),
+    /// The highest a value has been since the last report.
+    Peak(Peak),
+    /// How long something took, each time it happened.
+    Timing(Timing),
+}
+
+#[derive(Debug, Default)]
+pub struct Counter(AtomicU64);
+
+#[derive(Debug, Default)]
+pub struct Gauge(AtomicU64);
+
+#[derive(Debug, Default)]
+pub struct Peak(AtomicU64);
+
+#[derive(Debug)]
+pub struct Timing {
+    count: AtomicU64,
+    total_nanos: AtomicU64,
+    /// The shortest and longest times since the last report.
+    min_nanos: AtomicU64,
+    max_nanos: AtomicU64,
+    /// Each time, for percentiles. Only the first [`MAX_TIMING_SAMPLES`] since the last report are
+    /// kept, and only while reports are being logged.
+    samples: Mutex<Vec<Duration>>,
+}
+
+/// A registered statistic, along with what it's for.
+#[derive(Clone, Copy, Debug)]
+pub struct Entry {
+    /// What the statistic belongs to, e.g. "OSC output".
+    pub source: &'static str,
+    pub name: &'static str,
+    pub metric: &'static Metric,
+}
+
+#[derive(Debug)]
+pub struct InputStats {
+    source: String,
+    events: &'static Counter,
+}
+
+#[derive(Debug)]
+pub struct OutputStats {
+    name: &'static str,
+    source: String,
+    events: &'static Counter,
+    sends: &'static Counter,
+    send_failures: &'static Counter,
+    missed: &'static Counter,
+    queue_depth: &'static Peak,
+    latency: &'static Timing,
+    /// When the earliest event that hasn't been sent yet was received.
+    pending: Mutex<Option<Instant>>,
+}
+
+/// How many times are kept for percentiles in each report interval, so that a long interval can't
+/// use up memory.
+const MAX_TIMING_SAMPLES: usize = 100_000;
+
+/// Every statistic that has been registered, in the order they were. Each one is kept for as long
+/// as the Sender runs, so that a task that's restarted (or started again when the configuration is
+/// reloaded) carries on from where it was.
+static REGISTRY: Mutex<Vec<Entry>> = Mutex::new(Vec::new());
+
+/// Whether reports are being logged, so that samples aren't kept when they aren't.
+static REPORTING: AtomicBool = AtomicBool::new(false);
+
+/// Whether per-mapping statistics are being counted, so that nothing is locked when they aren't.
+static MAPPINGS_ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Per-mapping counts of how many events the inputs produced and how many times each output
+/// produced something for them. These are shared by every input and output, so they're kept here
+/// rather than being passed to each of them.
+static MAPPINGS: Mutex<Option<MappingCounters>> = Mutex::new(None);
+
+#[derive(Debug, Default)]
+struct MappingCounters {
+    events: HashMap<DefaultAtom, u64>,
+    sends: HashMap<DefaultAtom, Vec<(&'static str, u64)>>,
+    /// Mappings which have had at least one event since the statistics started.
+    seen: HashSet<DefaultAtom>,
+}
+
+impl Counter {
+    pub fn add(&self, count: u64) {
+        self.0.fetch_add(count, Ordering::Relaxed);
+    }
+
+    pub fn increment(&self) {
+        self.add(1);
+    }
+
+    pub fn get(&self) -> u64 {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+impl Gauge {
+    pub fn set(&self, value: u64) {
+        self.0.store(value, Ordering::Relaxed);
+    }
+
+    pub fn get(&self) -> u64 {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+impl Peak {
+    pub fn raise(&self, value: u64) {
+        self.0.fetch_max(value, Ordering::Relaxed);
+    }
+
+    pub fn get(&self) -> u64 {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    fn take(&self) -> u64 {
+        self.0.swap(0, Ordering::Relaxed)
+    }
+}
+
+impl Timing {
+    fn new() -> Self {
+        Timing {
+            count: AtomicU64::new(0),
+            total_nanos: AtomicU64::new(0),
+            min_nanos: AtomicU64::new(u64::MAX),
+            max_nanos: AtomicU64::new(0),
+            samples: Mutex::default(),
+        }
+    }
+
+    pub fn record(&self, time: Duration) {
+        let nanos = u64::try_from(time.as_nanos()).unwrap_or(u64::MAX);
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.total_nanos.fetch_add(nanos, Ordering::Relaxed);
+        self.min_nanos.fetch_min(nanos, Ordering::Relaxed);
+        self.max_nanos.fetch_max(nanos, Ordering::Relaxed);
+
+        if REPORTING.load(Ordering::Relaxed) {
+            let mut samples = self.samples.lock().unwrap();
+            if samples.len() < MAX_TIMING_SAMPLES {
+                samples.push(time);
+            }
+        }
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count.load(Ordering::Relaxed)
+    }
+
+    pub fn total(&self) -> Duration {
+        Duration::from_nanos(self.total_nanos.load(Ordering::Relaxed))
+    }
+
+    /// Takes the shortest and longest times and the samples since the last report.
+    fn take_window(&self) -> (Duration, Duration, Vec<Duration>) {
+        let min = self.min_nanos.swap(u64::MAX, Ordering::Relaxed);
+        let max = self.max_nanos.swap(0, Ordering::Relaxed);
+        let samples = std::mem::take(&mut *self.samples.lock().unwrap());
+        (
+            Duration::from_nanos(min),
+            Duration::from_nanos(max),
+            samples,
+        )
+    }
+}
+
+/// Finds the statistic with the given source and name, registering it if there isn't one yet.
+fn register(source: &str, name: &'static str, new: fn() -> Metric) -> &'static Metric {
+    let mut registry = REGISTRY.lock().unwrap();
+    if let Some(entry) = registry
+        .iter()
+        .find(|e| e.source == source && e.name == name)
+    {
+        return entry.metric;
+    }
+
+    let entry = Entry {
+        source: Box::leak(source.into()),
+        name,
+        metric: Box::leak(Box::new(new())),
+    };
+    registry.push(entry);
+    entry.metric
+}
+
+pub fn counter(source: &str, name: &'static str) -> &'static Counter {
+    match register(source, name, || Metric::Counter(Counter::default())) {
+        Metric::Counter(counter) => counter,
+        _ => panic!("Statistic {name} of {source} is not a counter"),
+    }
+}
+
+pub fn gauge(source: &str, name: &'static str) -> &'static Gauge {
+    match register(source, name, || Metric::Gauge(Gauge::default())) {
+        Metric::Gauge(gauge) => gauge,
+        _ => panic!("Statistic {name} of {source} is not a gauge"),
+    }
+}
+
+pub fn peak(source: &str, name: &'static str) -> &'static Peak {
+    match register(source, name, || Metric::Peak(Peak::default())) {
+        Metric::Peak(peak) => peak,
+        _ => panic!("Statistic {name} of {source} is not a peak"),
+    }
+}
+
+pub fn timing(source: &str, name: &'static str) -> &'static Timing {
+    match register(source, name, || Metric::Timing(Timing::new())) {
+        Metric::Timing(timing) => timing,
+        _ => panic!("Statistic {name} of {source} is not a timing"),
+    }
+}
+
+/// Every statistic that has been registered so far.
+pub fn registry() -> Vec<Entry> {
+    REGISTRY.lock().unwrap().clone()
+}
+
+impl InputStats {
+    pub fn new(name: &str) -> Self {
+        let source = format!("{name} input");
+        InputStats {
+            events: counter(&source, "events"),
+            source,
+        }
+    }
+
+    /// Records an event that the input is about to send.
+    pub fn event(&self, event: &OutputEvent) {
+        if let OutputEvent::UpdateAxis(..) | OutputEvent::UpdateButton(..) = event {
+            self.events.increment();
+            mapping_event(event);
+        }
+    }
+
+    pub fn gauge(&self, name: &'static str) -> &'static Gauge {
+        gauge(&self.source, name)
+    }
+}
+
+impl OutputStats {
+    pub fn new(name: &'static str) -> Arc<Self> {
+        let source = format!("{name} output");
+        Arc::new(OutputStats {
+            name,
+            events: counter(&source, "events"),
+            sends: counter(&source, "sends"),
+            send_failures: counter(&source, "failed sends"),
+            missed: counter(&source, "missed events"),
+            queue_depth: peak(&source, "queue depth"),
+            latency: timing(&source, "latency"),
+            pending: Mutex::default(),
+            source,
+        })
+    }
+
+    /// Records an event taken from the output channel, along with how many events were still
+    /// queued behind it, and unwraps it.
+    pub fn received<T>(&self, event: Timestamped<T>, queued: usize) -> T {
+        self.events.increment();
+        self.queue_depth.raise(queued as u64);
+        self.pending.lock().unwrap().get_or_insert(event.time);
+        event.event
+    }
+
+    /// Records that the output has sent everything received so far.
+    pub fn sent(&self) {
+        self.sends.increment();
+
+        if let Some(time) = self.pending.lock().unwrap().take() {
+            self.latency.record(time.elapsed());
+        }
+    }
+
+    pub fn send_failed(&self) {
+        self.send_failures.increment();
+    }
+
+    /// Records that events were dropped because the output fell too far behind.
+    pub fn missed(&self, count: u64) {
+        self.missed.add(count);
+    }
+
+    /// Records that the output has produced something for a mapping.
+    pub fn sent_mapping(&self, id: &MappingId) {
+        if !MAPPINGS_ENABLED.load(Ordering::Relaxed) {
+            return;
+        }
+
+        let mut counters = MAPPINGS.lock().unwrap();
+        let sends = counters
+            .get_or_insert_with(MappingCounters::default)
+            .sends
+            .entry(id.name().clone())
+            .or_default();
+
+        match sends.iter_mut().find(|(name, _)| *name == self.name) {
+            Some((_, count)) => *count += 1,
+            None => sends.push((self.name, 1)),
+        }
+    }
+
+    /// A further statistic for this output.
+    #[cfg_attr(not(feature = "vmc"), allow(dead_code))]
+    pub fn counter(&self, name: &'static str) -> &'static Counter {
+        counter(&self.source, name)
+    }
+
+    /// A further statistic for this output.
+    #[cfg_attr(not(feature = "vmc"), allow(dead_code))]
+    pub fn timing(&self, name: &'static str) -> &'static Timing {
+        timing(&self.source, name)
+    }
+}
+
+/// Records an event for the per-mapping statistics.
+fn mapping_event(event: &OutputEvent) {
+    if !MAPPINGS_ENABLED.load(Ordering::Relaxed) {
+        return;
+    }
+
+    if let OutputEvent::UpdateAxis(id, _) | OutputEvent::UpdateButton(id, _) = event {
+        let mut counters = MAPPINGS.lock().unwrap();
+        *counters
+            .get_or_insert_with(MappingCounters::default)
+            .events
+            .entry(id.name().clone())
+            .or_default() += 1;
+    }
+}
+
+/// Adds a line for each source's statistics to `out`, describing what's changed since `previous`
+/// (the cumulative values at the last report), and updates it.
+fn report_registry(
+    interval: f64,
+    previous: &mut HashMap<(&'static str, &'static str), (u64, Duration)>,
+    out: &mut String,
+) {
+    let registry = registry();
+    let mut sources = Vec::new();
+    for entry in &registry {
+        if !sources.contains(&entry.source) {
+            sources.push(entry.source);
+        }
+    }
+
+    for source in sources {
+        let parts = registry
+            .iter()
+            .filter(|e| e.source == source)
+            .filter_map(|e| describe(e, interval, previous.entry((e.source, e.name)).or_default()))
+            .collect::<Vec<_>>();
+
+        let _ = write!(out, "\n{source}: {}", parts.join(", "));
+    }
+}
+
+fn describe(entry: &Entry, interval: f64, previous: &mut (u64, Duration)) -> Option<String> {
+    let name = entry.name;
+    match entry.metric {
+        Metric::Counter(counter) => {
+            let value = counter.get();
+            let count = value.saturating_sub(previous.0);
+            previous.0 = value;
+
+            Some(match count {
+                0 => format!("0 {name}"),
+                _ => format!("{count} {name} ({:.1}/sec)", count as f64 / interval),
+            })
+        }
+
+        Metric::Gauge(gauge) => Some(format!("{} {name}", gauge.get())),
+        Metric::Peak(peak) => Some(format!("max {name} {}", peak.take())),
+
+        Metric::Timing(timing) => {
+            let (count, total) = (timing.count(), timing.total());
+            let (new_count, new_total) = (
+                count.saturating_sub(previous.0),
+                total.saturating_sub(previous.1),
+            );
+            *previous = (count, total);
+
+            let (min, max, mut samples) = timing.take_window();
+            if new_count == 0 {
+                return None;
+            }
+
+            let us = |time: Duration| 1e6 * time.as_secs_f64();
+            let mut text = format!("{name} avg {:.1} us", us(new_total) / new_count as f64);
+
+            if !samples.is_empty() {
+                samples.sort_unstable();
+                let percentile = |p: usize| {
+                    let index = (p * samples.len()).div_ceil(100).saturating_sub(1);
+                    us(samples[index])
+                };
+
+                let _ = write!(
+                    text,
+                    ", p50 {:.1} us, p95 {:.1} us, p99 {:.1} us",
+                    percentile(50),
+                    percentile(95),
+                    percentile(99),
+                );
+            }
+
+            let _ = write!(text, ", range {:.1}-{:.1} us", us(min), us(max));
+            Some(text)
+        }
+    }
+}
+
+fn report_mappings(mappings: &MappingConfig, out: &mut String) {
+    let (events, sends, mut silent) = {
+        let mut counters = MAPPINGS.lock().unwrap();
+        let counters = counters.get_or_insert_with(MappingCounters::default);
+        counters.seen.extend(counters.events.keys().cloned());
+
+        // A mapping that has never had an event is almost always a misconfigured input.
+        let silent = mappings
+            .axis
+            .keys()
+            .chain(mappings.button.keys())
+            .filter(|id| !counters.seen.contains(*id))
+            .cloned()
+            .collect::<Vec<_>>();
+
+        (
+            std::mem::take(&mut counters.events),
+            std::mem::take(&mut counters.sends),
+            silent,
+        )
+    };
+
+    let mut ids = events.keys().chain(sends.keys()).collect::<Vec<_>>();
+    ids.sort_unstable();
+    ids.dedup();
+
+    for id in ids {
+        let sent = match sends.get(id) {
+            Some(sends) => sends
+                .iter()
+                .map(|(name, count)| format!("{name} {count}"))
+                .collect::<Vec<_>>()
+                .join(", "),
+            None => "nothing".into(),
+        };
+
+        let _ = write!(
+            out,
+            "\nMapping {id}: {} event(s), sent by {sent}",
+            events.get(id).copied().unwrap_or(0)
+        );
+    }
+
+    if !silent.is_empty() {
+        silent.sort_unstable();
+        let _ = write!(
+            out,
+            "\nMappings without any events yet: {}",
+            silent
+                .iter()
+                .map(|id| id.as_ref())
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+    }
+}
+
+pub async fn run(config: StatsConfig, mappings: Arc<MappingConfig>, cancel_rx: ChannelRx<()>) {
+    // Counts from before the configuration was reloaded would be misleading.
+    *MAPPINGS.lock().unwrap() = None;
+    MAPPINGS_ENABLED.store(
+        config.mappings && config.report_interval.is_some(),
+        Ordering::Relaxed,
+    );
+    REPORTING.store(config.report_interval.is_some(), Ordering::Relaxed);
+
+    let Some(interval) = config.report_interval else {
+        return;
+    };
+
+    // The first report only covers what happens after this, so anything from before is skipped.
+    let mut previous = HashMap::new();
+    report_registry(interval, &mut previous, &mut String::new());
+
+    let mut report_timer = smol::Timer::interval(Duration::from_secs_f64(interval));
+
+    loop {
+        futures::select_biased! {
+            _ = cancel_rx.recv().fuse() => break,
+            _ = report_timer.next().fuse() => {
+                let mut report = format!("Statistics for the last {interval} second(s):");
+                report_registry(interval, &mut previous, &mut report);
+
+                if config.mappings {
+                    report_mappings(&mappings, &mut report);
+                }
+
+                log::info!("{report}");
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Compares passing events through an output with and without its statistics being recorded.
+    /// Run with `cargo test --release -- --ignored --nocapture`.
+    #[test]
+    #[ignore]
+    fn bench_output_instrumentation() {
+        const ITERATIONS: u32 = 1_000_000;
+
+        let stats = OutputStats::new("Benchmark");
+        let event = Timestamped::now(OutputEvent::UpdateAxis(MappingId::new("a", 0), 0.5));
+
+        let start = Instant::now();
+        for _ in 0..ITERATIONS {
+            std::hint::black_box(std::hint::black_box(event.clone()).event);
+        }
+        let bare = start.elapsed() / ITERATIONS;
+
+        for reporting in [false, true] {
+            REPORTING.store(reporting, Ordering::Relaxed);
+
+            let start = Instant::now();
+            for _ in 0..ITERATIONS {
+                std::hint::black_box(stats.received(std::hint::black_box(event.clone()), 1));
+                stats.sent();
+            }
+            let recorded = start.elapsed() / ITERATIONS;
+
+            // Once there are MAX_TIMING_SAMPLES, later times aren't kept, as in a long interval.
+            stats.latency.take_window();
+            println!(
+                "Output statistics (reporting = {reporting}): {bare:?}/event bare, {recorded:?}/event recorded"
+            );
+        }
+
+        REPORTING.store(false, Ordering::Relaxed);
+    }
+
+    #[test]
+    fn timing_samples_are_bounded() {
+        let timing = timing("Test", "bounded");
+
+        REPORTING.store(false, Ordering::Relaxed);
+        timing.record(Duration::from_micros(1));
+        assert_eq!(timing.take_window().2.len(), 0);
+
+        REPORTING.store(true, Ordering::Relaxed);
+        for i in 0..MAX_TIMING_SAMPLES + 10 {
+            timing.record(Duration::from_nanos(i as u64));
+        }
+        REPORTING.store(false, Ordering::Relaxed);
+
+        let (min, max, samples) = timing.take_window();
+        assert_eq!(samples.len(), MAX_TIMING_SAMPLES);
+        assert_eq!(min, Duration::ZERO);
+        assert_eq!(max, Duration::from_nanos(MAX_TIMING_SAMPLES as u64 + 9));
+        assert_eq!(timing.count(), MAX_TIMING_SAMPLES as u64 + 11);
+    }
+
+    /// Measures cloning an event (which is done for every output), and reporting a timing with as
+    /// many samples as are kept. Run with `cargo test --release -- --ignored --nocapture`.
+    #[test]
+    #[ignore]
+    fn bench_timing_report() {
+        const ITERATIONS: u32 = 1_000_000;
+        const REPORTS: u32 = 20;
+
+        let event = Timestamped::now(OutputEvent::UpdateAxis(MappingId::new("a", 0), 0.5));
+        let start = Instant::now();
+        for _ in 0..ITERATIONS {
+            std::hint::black_box(std::hint::black_box(&event).clone());
+        }
+        let clone = start.elapsed() / ITERATIONS;
+
+        let timing = timing("Benchmark", "report");
+        let entry = registry()
+            .into_iter()
+            .find(|e| e.source == "Benchmark" && e.name == "report")
+            .unwrap();
+        let mut previous = Default::default();
+        let mut report = Duration::ZERO;
+
+        REPORTING.store(true, Ordering::Relaxed);
+        for _ in 0..REPORTS {
+            // Times in a shuffled order, so that sorting them isn't trivial.
+            for i in 0..MAX_TIMING_SAMPLES as u64 {
+                timing.record(Duration::from_nanos(
+                    i.wrapping_mul(0x9E37_79B9) % 1_000_000,
+                ));
+            }
+
+            let start = Instant::now();
+            std::hint::black_box(describe(&entry, 1.0, &mut previous));
+            report += start.elapsed();
+        }
+        REPORTING.store(false, Ordering::Relaxed);
+
+        println!(
+            "Event clone: {clone:?}. Timing report with {MAX_TIMING_SAMPLES} samples: {:?}",
+            report / REPORTS
+        );
+    }
+}
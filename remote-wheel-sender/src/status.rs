@@ -0,0 +1,259 @@
+use std::fmt::Write as _;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+use anyhow::{Context as _, Result as AnyResult};
+use async_broadcast::{Receiver as BroadcastRx, RecvError as BroadcastRxErr};
+use futures::prelude::*;
+use hashbrown::HashMap;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use smol::channel::Receiver as ChannelRx;
+use smol::net::{TcpListener, TcpStream};
+use string_cache::DefaultAtom;
+
+use crate::check::Report;
+use crate::output::{OutputEvent, Timestamped};
+use crate::stats::{self, Metric};
+
+/// How long a client has to send its request before the connection is dropped.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The most that's read of a request. Only the fact that one was made matters.
+const MAX_REQUEST_LEN: usize = 8192;
+
+#[derive(Clone, Debug, Deserialize, JsonSchema, Serialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct StatusConfig {
+    enabled: bool,
+    address: SocketAddr,
+}
+
+/// When the Sender started, for its uptime.
+static STARTED: OnceLock<Instant> = OnceLock::new();
+
+impl StatusConfig {
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn validate(&self, report: &mut Report) {
+        if self.enabled {
+            report.bind("status.address", self.address);
+        }
+    }
+}
+
+impl Default for StatusConfig {
+    fn default() -> Self {
+        StatusConfig {
+            enabled: false,
+            address: SocketAddr::new(Ipv4Addr::LOCALHOST.into(), 19797),
+        }
+    }
+}
+
+/// Notes when the Sender started. Called once, as early as possible.
+pub fn record_start() {
+    STARTED.get_or_init(Instant::now);
+}
+
+/// How long the Sender has been running.
+pub fn uptime() -> Duration {
+    STARTED.get().map_or(Duration::ZERO, Instant::elapsed)
+}
+
+/// Serves the Sender's status over HTTP, in the Prometheus text format, for monitoring.
+pub async fn run(
+    config: StatusConfig,
+    mut value_rx: BroadcastRx<Timestamped<OutputEvent>>,
+    cancel_rx: ChannelRx<()>,
+) {
+    if !config.enabled() {
+        return;
+    }
+
+    log::info!("Status task starting...");
+
+    let listener = match TcpListener::bind(config.address).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            log::error!(
+                "Status task stopped (failed to listen on {}: {e}).",
+                config.address
+            );
+            return;
+        }
+    };
+
+    let mut axes = HashMap::new();
+    let mut buttons = HashMap::new();
+    let mut inputs_open = true;
+
+    log::info!(
+        "Status task started. Serving status at http://{}/metrics.",
+        config.address
+    );
+
+    loop {
+        let event = async {
+            if inputs_open {
+                value_rx.recv().await
+            } else {
+                future::pending().await
+            }
+        };
+
+        futures::select_biased! {
+            _ = cancel_rx.recv().fuse() => break,
+
+            event = event.fuse() => match event.map(|e| e.event) {
+                Ok(OutputEvent::UpdateAxis(id, value)) => {
+                    axes.insert(id.name().clone(), value);
+                }
+
+                Ok(OutputEvent::UpdateButton(id, pressed) | OutputEvent::RestoreButton(id, pressed)) => {
+                    buttons.insert(id.name().clone(), pressed);
+                }
+
+                Ok(OutputEvent::ButtonGesture(..) | OutputEvent::SwitchProfile(_) | OutputEvent::Flush)
+                | Err(BroadcastRxErr::Overflowed(_)) => {}
+
+                // The status is still served, but no longer changes.
+                Err(BroadcastRxErr::Closed) => inputs_open = false,
+            },
+
+            conn = listener.accept().fuse() => match conn {
+                Ok((stream, _)) => {
+                    let body = render(&axes, &buttons);
+                    smol::spawn(async move {
+                        if let Err(e) = respond(stream, body).await {
+                            log::debug!("Failed to serve status: {e:#}");
+                        }
+                    })
+                    .detach();
+                }
+
+                Err(e) => log::warn!("Failed to accept status connection: {e}"),
+            },
+        }
+    }
+
+    log::info!("Status task stopped.");
+}
+
+/// Waits for the client's request, then sends the status in response, whatever was requested.
+async fn respond(mut stream: TcpStream, body: String) -> AnyResult<()> {
+    let read_request = async {
+        let mut request = Vec::new();
+        let mut buffer = [0; 1024];
+
+        while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+            let len = stream.read(&mut buffer).await?;
+            if len == 0 || request.len() + len > MAX_REQUEST_LEN {
+                break;
+            }
+            request.extend_from_slice(&buffer[..len]);
+        }
+
+        AnyResult::<()>::Ok(())
+    };
+
+    let timeout = async {
+        smol::Timer::after(REQUEST_TIMEOUT).await;
+        anyhow::bail!("timed out waiting for the request")
+    };
+
+    smol::future::or(read_request, timeout).await?;
+
+    let response = format!(
+        "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    );
+
+    stream
+        .write_all(response.as_bytes())
+        .await
+        .context("Failed to send response")?;
+    stream.close().await.context("Failed to close connection")
+}
+
+fn render(axes: &HashMap<DefaultAtom, f64>, buttons: &HashMap<DefaultAtom, bool>) -> String {
+    let mut out = String::new();
+    let uptime = uptime().as_secs_f64();
+
+    metric(&mut out, "uptime_seconds", "gauge");
+    let _ = writeln!(out, "remote_wheel_uptime_seconds {uptime}");
+
+    let mut axes = axes.iter().collect::<Vec<_>>();
+    axes.sort_unstable_by_key(|&(id, _)| id);
+    metric(&mut out, "axis_value", "gauge");
+    for (id, value) in axes {
+        let id = escape(id);
+        let _ = writeln!(out, "remote_wheel_axis_value{{mapping=\"{id}\"}} {value}");
+    }
+
+    let mut buttons = buttons.iter().collect::<Vec<_>>();
+    buttons.sort_unstable_by_key(|&(id, _)| id);
+    metric(&mut out, "button_pressed", "gauge");
+    for (id, &pressed) in buttons {
+        let id = escape(id);
+        let pressed = u8::from(pressed);
+        let _ = writeln!(
+            out,
+            "remote_wheel_button_pressed{{mapping=\"{id}\"}} {pressed}"
+        );
+    }
+
+    // Each metric's samples have to be together, so every source's statistic of the same name is
+    // listed at once.
+    let mut entries = stats::registry();
+    entries.sort_by_key(|e| e.name);
+    for group in entries.chunk_by(|a, b| a.name == b.name) {
+        let name = group[0].name.replace(' ', "_");
+        let (suffix, kind) = match group[0].metric {
+            Metric::Counter(_) => ("_total", "counter"),
+            Metric::Gauge(_) => ("", "gauge"),
+            Metric::Peak(_) => ("_max", "gauge"),
+            Metric::Timing(_) => ("_seconds", "summary"),
+        };
+
+        let name = format!("{name}{suffix}");
+        metric(&mut out, &name, kind);
+
+        for entry in group {
+            let source = escape(entry.source);
+            let labels = format!("{{source=\"{source}\"}}");
+            let _ = match entry.metric {
+                Metric::Counter(counter) => {
+                    writeln!(out, "remote_wheel_{name}{labels} {}", counter.get())
+                }
+                Metric::Gauge(gauge) => {
+                    writeln!(out, "remote_wheel_{name}{labels} {}", gauge.get())
+                }
+                Metric::Peak(peak) => writeln!(out, "remote_wheel_{name}{labels} {}", peak.get()),
+                Metric::Timing(timing) => writeln!(
+                    out,
+                    "remote_wheel_{name}_sum{labels} {}\nremote_wheel_{name}_count{labels} {}",
+                    timing.total().as_secs_f64(),
+                    timing.count(),
+                ),
+            };
+        }
+    }
+
+    out
+}
+
+fn metric(out: &mut String, name: &str, kind: &str) {
+    let _ = writeln!(out, "# TYPE remote_wheel_{name} {kind}");
+}
+
+/// Escapes a label value, as the Prometheus text format requires.
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
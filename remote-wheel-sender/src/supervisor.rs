@@ -0,0 +1,255 @@
+use std::future::Future;
+use std::time::{Duration, Instant};
+
+use anyhow::Result as AnyResult;
+use futures::prelude::*;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use smol::channel::Receiver as ChannelRx;
+
+use crate::check::Report;
+
+#[derive(Clone, Debug, Deserialize, JsonSchema, Serialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct RestartConfig {
+    max_restarts: u32,
+
+    #[serde(deserialize_with = "crate::units::seconds")]
+    #[schemars(schema_with = "crate::units::seconds_schema")]
+    initial_delay: f64,
+
+    #[serde(deserialize_with = "crate::units::seconds")]
+    #[schemars(schema_with = "crate::units::seconds_schema")]
+    max_delay: f64,
+}
+
+#[derive(Clone, Debug, Deserialize, JsonSchema, Serialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct ShutdownConfig {
+    #[serde(deserialize_with = "crate::units::seconds")]
+    #[schemars(schema_with = "crate::units::seconds_schema")]
+    grace_period: f64,
+}
+
+impl Default for RestartConfig {
+    fn default() -> Self {
+        RestartConfig {
+            max_restarts: 5,
+            initial_delay: 1.0,
+            max_delay: 30.0,
+        }
+    }
+}
+
+impl RestartConfig {
+    pub fn validate(&self, report: &mut Report) {
+        if !(self.initial_delay >= 0.0 && self.initial_delay.is_finite()) {
+            report.error(
+                "restart.initial-delay",
+                "Delay must be a non-negative number of seconds",
+            );
+        }
+
+        // This is also how long a task has to run to be considered to have recovered, so a task
+        // that fails straight away would otherwise be restarted forever without a delay.
+        if !(self.max_delay > 0.0 && self.max_delay.is_finite()) {
+            report.error(
+                "restart.max-delay",
+                "Delay must be a positive number of seconds",
+            );
+        }
+
+        if self.initial_delay > self.max_delay {
+            report.error(
+                "restart.initial-delay",
+                "Initial delay must not be longer than the maximum delay",
+            );
+        }
+    }
+}
+
+impl ShutdownConfig {
+    pub fn validate(&self, report: &mut Report) {
+        if !(self.grace_period >= 0.0 && self.grace_period.is_finite()) {
+            report.error(
+                "shutdown.grace-period",
+                "Grace period must be a non-negative number of seconds",
+            );
+        }
+    }
+}
+
+impl Default for ShutdownConfig {
+    fn default() -> Self {
+        ShutdownConfig { grace_period: 5.0 }
+    }
+}
+
+pub async fn supervise<F, Fut>(
+    name: &'static str,
+    config: RestartConfig,
+    cancel_rx: ChannelRx<()>,
+    mut start: F,
+) -> AnyResult<()>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = AnyResult<()>>,
+{
+    let max_delay = Duration::from_secs_f64(config.max_delay);
+    let mut delay = Duration::from_secs_f64(config.initial_delay);
+    let mut restarts = 0;
+
+    loop {
+        let start_time = Instant::now();
+        let Err(e) = start().await else {
+            break Ok(());
+        };
+
+        if cancel_rx.is_closed() {
+            break Err(e);
+        }
+
+        // A task that ran for a while before failing is considered to have recovered.
+        if start_time.elapsed() >= max_delay {
+            delay = Duration::from_secs_f64(config.initial_delay);
+            restarts = 0;
+        }
+
+        if restarts >= config.max_restarts {
+            log::error!(
+                "{name} task has failed too many times ({restarts} restart(s)); giving up."
+            );
+            break Err(e);
+        }
+
+        restarts += 1;
+        log::warn!(
+            "{name} task failed ({e:#}). Restarting in {:.1} second(s) (attempt {restarts} of {}).",
+            delay.as_secs_f64(),
+            config.max_restarts,
+        );
+
+        let mut timer = smol::Timer::after(delay);
+        futures::select_biased! {
+            _ = cancel_rx.recv().fuse() => break Ok(()),
+            _ = timer.next().fuse() => {},
+        }
+
+        delay = Duration::min(2 * delay, max_delay);
+        log::info!("Restarting {name} task...");
+    }
+}
+
+/// Completes once the tasks have been cancelled for longer than the grace period, at which point
+/// whatever is still running should be given up on. Returns the grace period.
+pub async fn shutdown_deadline(config: ShutdownConfig, cancel_rx: ChannelRx<()>) -> Duration {
+    // Nothing is ever sent, so this only returns once the channel is closed.
+    let _ = cancel_rx.recv().await;
+
+    let grace_period = Duration::from_secs_f64(config.grace_period);
+    smol::Timer::after(grace_period).await;
+    grace_period
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+
+    use anyhow::anyhow;
+
+    use super::*;
+
+    fn config(max_restarts: u32) -> RestartConfig {
+        RestartConfig {
+            max_restarts,
+            initial_delay: 0.02,
+            max_delay: 0.05,
+        }
+    }
+
+    /// Supervises a task that fails `failures` times before succeeding, returning the result and
+    /// when each attempt started.
+    fn run(config: RestartConfig, failures: usize) -> (AnyResult<()>, Vec<Instant>) {
+        let (_cancel_tx, cancel_rx) = smol::channel::unbounded();
+        let starts = RefCell::new(Vec::new());
+
+        let result = smol::block_on(supervise("Test", config, cancel_rx, || {
+            starts.borrow_mut().push(Instant::now());
+            let attempt = starts.borrow().len();
+            async move {
+                if attempt <= failures {
+                    Err(anyhow!("failure {attempt}"))
+                } else {
+                    Ok(())
+                }
+            }
+        }));
+
+        (result, starts.into_inner())
+    }
+
+    #[test]
+    fn restarts_until_success() {
+        let (result, starts) = run(config(5), 2);
+        assert!(result.is_ok());
+        assert_eq!(starts.len(), 3);
+    }
+
+    #[test]
+    fn backs_off_up_to_max_delay() {
+        let config = RestartConfig {
+            max_restarts: 4,
+            initial_delay: 0.1,
+            max_delay: 0.25,
+        };
+        let (_, starts) = run(config, 4);
+        assert!(starts.windows(2).all(|w| w[0] <= w[1]), "starts are out of order");
+        let gaps = starts.windows(2).map(|w| w[1] - w[0]).collect::<Vec<_>>();
+
+        // 100 ms, doubling to 200 ms, then held at the 250 ms maximum. Only the least each delay
+        // could be is checked, since a loaded machine may wake up from any of them late.
+        let expected = [100, 200, 250, 250].map(Duration::from_millis);
+        assert_eq!(gaps.len(), expected.len());
+        for (gap, expected) in std::iter::zip(gaps, expected) {
+            assert!(gap >= expected, "{gap:?} is shorter than {expected:?}");
+        }
+    }
+
+    #[test]
+    fn gives_up_after_max_restarts() {
+        let (result, starts) = run(config(3), usize::MAX);
+        let error = result.expect_err("supervisor should have given up");
+        assert_eq!(error.to_string(), "failure 4");
+        assert_eq!(starts.len(), 4);
+    }
+
+    #[test]
+    fn no_restarts() {
+        let (result, starts) = run(config(0), usize::MAX);
+        assert!(result.is_err());
+        assert_eq!(starts.len(), 1);
+    }
+
+    #[test]
+    fn rejects_invalid_delays() {
+        let errors = |initial_delay, max_delay| {
+            let mut report = Report::default();
+            RestartConfig {
+                max_restarts: 1,
+                initial_delay,
+                max_delay,
+            }
+            .validate(&mut report);
+            report.finish().len()
+        };
+
+        assert_eq!(errors(1.0, 30.0), 0);
+        assert_eq!(errors(0.0, 1.0), 0);
+        assert_eq!(errors(0.0, 0.0), 1);
+        assert_eq!(errors(-1.0, 30.0), 1);
+        assert_eq!(errors(1.0, -5.0), 2);
+        assert_eq!(errors(f64::NAN, 30.0), 1);
+        assert_eq!(errors(1.0, f64::INFINITY), 1);
+        assert_eq!(errors(10.0, 5.0), 1);
+    }
+}
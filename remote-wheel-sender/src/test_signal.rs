@@ -0,0 +1,256 @@
+use std::f64::consts::TAU;
+use std::fmt::Display;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use anyhow::{bail, Context as _, Result as AnyResult};
+use async_broadcast::Sender as BroadcastTx;
+use futures::prelude::*;
+use hashbrown::HashMap;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use smol::channel::Receiver as ChannelRx;
+use string_cache::DefaultAtom;
+
+use crate::check::Report;
+use crate::config::MappingConfig;
+use crate::output::{OutputEvent, Timestamped};
+use crate::stats;
+
+/// How often to remind that test signals are still being sent.
+const REMINDER_INTERVAL: Duration = Duration::from_secs(60);
+
+#[derive(Clone, Debug, Deserialize, JsonSchema, Serialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct TestSignalConfig {
+    #[serde(deserialize_with = "crate::units::optional_seconds")]
+    #[schemars(schema_with = "crate::units::optional_seconds_schema")]
+    duration: Option<f64>,
+    rate: f64,
+
+    #[schemars(with = "std::collections::HashMap<String, SignalConfig>")]
+    axis: HashMap<DefaultAtom, SignalConfig>,
+}
+
+#[derive(Clone, Debug, Deserialize, JsonSchema, Serialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct SignalConfig {
+    waveform: Waveform,
+
+    #[serde(deserialize_with = "crate::units::hertz")]
+    #[schemars(schema_with = "crate::units::hertz_schema")]
+    frequency: f64,
+
+    #[serde(default = "amplitude_default")]
+    amplitude: f64,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, JsonSchema, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Waveform {
+    Sine,
+    Triangle,
+    Step,
+}
+
+impl TestSignalConfig {
+    pub fn enabled(&self) -> bool {
+        !self.axis.is_empty()
+    }
+
+    /// Adds a signal given on the command line, as `<axis>=<waveform>:<frequency>[:<amplitude>]`.
+    pub fn set(&mut self, spec: &str) -> AnyResult<()> {
+        let (id, signal) = spec
+            .split_once('=')
+            .context("--test-signal requires <axis>=<waveform>:<frequency>[:<amplitude>]")?;
+
+        let mut parts = signal.split(':');
+        let waveform = match parts.next().unwrap_or_default() {
+            "sine" => Waveform::Sine,
+            "triangle" => Waveform::Triangle,
+            "step" => Waveform::Step,
+            w => bail!("Unrecognized test signal waveform: {w} (expected sine, triangle, or step)"),
+        };
+
+        let frequency = parts
+            .next()
+            .context("A test signal requires a frequency, e.g. sine:0.2hz")?;
+        let frequency = crate::units::parse_hertz(frequency)
+            .with_context(|| format!("Invalid test signal frequency: {frequency}"))?;
+
+        let amplitude = match parts.next() {
+            Some(a) => a
+                .parse()
+                .ok()
+                .with_context(|| format!("Invalid test signal amplitude: {a}"))?,
+            None => amplitude_default(),
+        };
+
+        if parts.next().is_some() {
+            bail!("Too many parts in test signal: {spec}");
+        }
+
+        self.axis.insert(
+            DefaultAtom::from(id),
+            SignalConfig {
+                waveform,
+                frequency,
+                amplitude,
+            },
+        );
+        Ok(())
+    }
+
+    /// Adds any signals that are set in `other`, replacing those for the same axes.
+    pub fn merge(&mut self, other: &TestSignalConfig) {
+        self.axis
+            .extend(other.axis.iter().map(|(id, s)| (id.clone(), s.clone())));
+    }
+
+    pub fn validate(&self, mappings: &MappingConfig, report: &mut Report) {
+        if !self.enabled() {
+            return;
+        }
+
+        if !(self.rate > 0.0 && self.rate.is_finite()) {
+            report.error("test-signal.rate", "Rate must be positive");
+        }
+
+        if self.duration.is_some_and(|d| d <= 0.0) {
+            report.error("test-signal.duration", "Duration must be positive");
+        }
+
+        for (id, signal) in &self.axis {
+            let path = format!("test-signal.axis.{id}");
+
+            if !mappings.axis.contains_key(id) {
+                report.error(&path, "No axis with this name is configured");
+            }
+
+            if !(signal.frequency > 0.0 && signal.frequency.is_finite()) {
+                report.error(format!("{path}.frequency"), "Frequency must be positive");
+            }
+
+            if !(0.0..=1.0).contains(&signal.amplitude) {
+                report.error(
+                    format!("{path}.amplitude"),
+                    "Amplitude must be between 0 and 1",
+                );
+            }
+        }
+    }
+}
+
+impl Default for TestSignalConfig {
+    fn default() -> Self {
+        TestSignalConfig {
+            duration: None,
+            rate: 60.0,
+            axis: HashMap::new(),
+        }
+    }
+}
+
+impl SignalConfig {
+    /// The value of the signal `t` seconds after it started, from 0 to 1. It starts from the middle.
+    fn value(&self, t: f64) -> f64 {
+        let phase = (t * self.frequency).rem_euclid(1.0);
+        let wave = match self.waveform {
+            Waveform::Sine => (TAU * phase).sin(),
+            Waveform::Triangle => 4.0 * ((phase + 0.75).rem_euclid(1.0) - 0.5).abs() - 1.0,
+            Waveform::Step if phase < 0.5 => 1.0,
+            Waveform::Step => -1.0,
+        };
+
+        0.5 + 0.5 * self.amplitude * wave
+    }
+}
+
+impl Display for SignalConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let waveform = match self.waveform {
+            Waveform::Sine => "sine",
+            Waveform::Triangle => "triangle",
+            Waveform::Step => "step",
+        };
+
+        write!(
+            f,
+            "{waveform} wave at {} Hz, amplitude {}",
+            self.frequency, self.amplitude
+        )
+    }
+}
+
+fn amplitude_default() -> f64 {
+    1.0
+}
+
+/// Drives axes with generated signals, as if their inputs were being moved, so that everything
+/// after the inputs can be checked without any hardware.
+pub async fn run(
+    config: TestSignalConfig,
+    mappings: Arc<MappingConfig>,
+    output_tx: BroadcastTx<Timestamped<OutputEvent>>,
+    cancel_rx: ChannelRx<()>,
+) -> AnyResult<()> {
+    // This is a warning so that it stands out, since it overrides real inputs.
+    for (id, signal) in &config.axis {
+        log::warn!("TEST SIGNAL ACTIVE: axis {id} is being driven by a {signal}.");
+    }
+
+    match config.duration {
+        Some(duration) => log::warn!("Test signals will stop after {duration} second(s)."),
+        None => log::warn!("Test signals will run until the Sender is stopped."),
+    }
+
+    // Every signal's axis is configured, since that's checked along with the configuration.
+    let signals = config
+        .axis
+        .iter()
+        .filter_map(|(id, signal)| Some((mappings.axis_id(id)?, signal)))
+        .collect::<Vec<_>>();
+
+    let start = Instant::now();
+    let stop_at = config.duration.map(|d| start + Duration::from_secs_f64(d));
+
+    let mut ticks = smol::Timer::interval(Duration::from_secs_f64(config.rate.recip()));
+    let mut reminders = smol::Timer::interval(REMINDER_INTERVAL);
+    let input_stats = stats::InputStats::new("Test signal");
+
+    loop {
+        futures::select_biased! {
+            _ = cancel_rx.recv().fuse() => break,
+
+            _ = reminders.next().fuse() => {
+                let ids = config.axis.keys().map(|id| id.to_string()).collect::<Vec<_>>();
+                log::warn!("Test signals are still active for: {}", ids.join(", "));
+            },
+
+            now = ticks.next().fuse() => {
+                let Some(now) = now else { break };
+                if stop_at.is_some_and(|s| now >= s) {
+                    log::warn!("Test signals stopped (duration reached).");
+                    break;
+                }
+
+                let t = now.saturating_duration_since(start).as_secs_f64();
+                let events = signals
+                    .iter()
+                    .map(|(id, signal)| OutputEvent::UpdateAxis(id.clone(), signal.value(t)))
+                    .chain(std::iter::once(OutputEvent::Flush));
+
+                for event in events {
+                    input_stats.event(&event);
+
+                    if output_tx.broadcast(event.into()).await.is_err() {
+                        log::info!("Test signal task stopping (no remaining outputs).");
+                        return Ok(());
+                    }
+                }
+            },
+        }
+    }
+
+    Ok(())
+}
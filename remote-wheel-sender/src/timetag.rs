@@ -0,0 +1,153 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// The special timetag that tells receivers to act on a bundle as soon as they get it (a 1 in the
+/// least significant bit, as the OSC specification gives it).
+pub const IMMEDIATE: (u32, u32) = (0, 1);
+
+/// The seconds from the NTP epoch (1900) to the Unix epoch (1970).
+const NTP_UNIX_OFFSET: u64 = 2_208_988_800;
+
+/// When a receiver should act on an OSC bundle, which is given to it as the bundle's timetag.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, JsonSchema, PartialEq, Serialize)]
+#[serde(
+    deny_unknown_fields,
+    untagged,
+    expecting = "\"immediate\", \"now\", or { offset-ms = <milliseconds> }"
+)]
+pub enum Timetag {
+    Mode(TimetagMode),
+
+    /// A fixed delay after the bundle is sent, e.g. so that a receiver can compensate for latency.
+    Offset {
+        #[serde(rename = "offset-ms")]
+        offset_ms: u32,
+    },
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, JsonSchema, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TimetagMode {
+    Immediate,
+    /// When the bundle is sent.
+    Now,
+}
+
+impl Default for Timetag {
+    fn default() -> Self {
+        Timetag::Mode(TimetagMode::Immediate)
+    }
+}
+
+impl Timetag {
+    pub fn is_immediate(self) -> bool {
+        self == Timetag::Mode(TimetagMode::Immediate)
+    }
+
+    /// The timetag for a bundle sent at `now`, as NTP seconds and fractions of a second.
+    pub fn at(self, now: SystemTime) -> (u32, u32) {
+        let offset = match self {
+            Timetag::Mode(TimetagMode::Immediate) => return IMMEDIATE,
+            Timetag::Mode(TimetagMode::Now) => Duration::ZERO,
+            Timetag::Offset { offset_ms } => Duration::from_millis(offset_ms.into()),
+        };
+
+        // A time before 1970 can't be given here, and is acted on immediately instead. NTP's seconds
+        // wrap around in February 2036, after which they count up from 0 again (era 1, as RFC 5905
+        // calls it), which receivers are expected to tell apart from era 0 by how close it is.
+        let Ok(since_unix) = (now + offset).duration_since(UNIX_EPOCH) else {
+            return IMMEDIATE;
+        };
+
+        let seconds = (since_unix.as_secs() + NTP_UNIX_OFFSET) % (1 << 32);
+        let fraction = f64::from(since_unix.subsec_nanos()) / 1e9 * (1u64 << 32) as f64;
+        (seconds as u32, fraction.round() as u32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::UNIX_EPOCH;
+
+    use super::*;
+
+    /// The seconds from the NTP epoch (1900) to the Unix epoch (1970).
+    const UNIX_OFFSET: u32 = 2_208_988_800;
+
+    const NOW: Timetag = Timetag::Mode(TimetagMode::Now);
+
+    #[test]
+    fn immediate_is_one() {
+        let now = SystemTime::now();
+
+        assert!(Timetag::default().is_immediate());
+        assert_eq!(Timetag::default().at(now), (0, 1));
+        assert_eq!(Timetag::Mode(TimetagMode::Immediate).at(now), (0, 1));
+        assert!(!NOW.is_immediate());
+    }
+
+    #[test]
+    fn converts_unix_epoch() {
+        assert_eq!(NOW.at(UNIX_EPOCH), (UNIX_OFFSET, 0));
+        assert_eq!(
+            NOW.at(UNIX_EPOCH + Duration::from_secs(1_000_000_000)),
+            (UNIX_OFFSET + 1_000_000_000, 0)
+        );
+    }
+
+    #[test]
+    fn converts_fractional_seconds() {
+        let at = |nanos| NOW.at(UNIX_EPOCH + Duration::new(10, nanos));
+
+        assert_eq!(at(500_000_000), (UNIX_OFFSET + 10, 1 << 31));
+        assert_eq!(at(250_000_000), (UNIX_OFFSET + 10, 1 << 30));
+        assert_eq!(at(1), (UNIX_OFFSET + 10, 4));
+        assert_eq!(at(999_999_999).1, u32::MAX - 3);
+    }
+
+    #[test]
+    fn adds_offset() {
+        let offset = Timetag::Offset { offset_ms: 1500 };
+        assert_eq!(offset.at(UNIX_EPOCH), (UNIX_OFFSET + 1, 1 << 31));
+        assert_eq!(
+            offset.at(UNIX_EPOCH + Duration::from_millis(250)),
+            (UNIX_OFFSET + 1, 3 << 30)
+        );
+    }
+
+    #[test]
+    fn times_before_unix_epoch_are_immediate() {
+        assert_eq!(NOW.at(UNIX_EPOCH - Duration::from_secs(1)), IMMEDIATE);
+    }
+
+    #[test]
+    fn wraps_around_in_2036() {
+        let wrap = UNIX_EPOCH + Duration::from_secs(u64::from(u32::MAX - UNIX_OFFSET) + 1);
+        assert_eq!(NOW.at(wrap - Duration::from_secs(1)), (u32::MAX, 0));
+        assert_eq!(NOW.at(wrap + Duration::from_millis(500)), (0, 1 << 31));
+        assert_eq!(NOW.at(wrap + Duration::from_secs(10)), (10, 0));
+    }
+
+    #[test]
+    fn parses_each_form() {
+        #[derive(Deserialize)]
+        struct Config {
+            timetag: Timetag,
+        }
+
+        let parse = |s: &str| toml::from_str::<Config>(s).map(|c| c.timetag);
+        assert_eq!(
+            parse(r#"timetag = "immediate""#).unwrap(),
+            Timetag::default()
+        );
+        assert_eq!(parse(r#"timetag = "now""#).unwrap(), NOW);
+        assert_eq!(
+            parse("timetag = { offset-ms = 50 }").unwrap(),
+            Timetag::Offset { offset_ms: 50 }
+        );
+        assert!(parse(r#"timetag = "later""#).is_err());
+        assert!(parse("timetag = { offset-ms = -1 }").is_err());
+    }
+}
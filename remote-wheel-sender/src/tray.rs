@@ -0,0 +1,116 @@
+mod config;
+mod sys;
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use anyhow::Result as AnyResult;
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use simplelog::SharedLogger;
+use smol::channel::Sender as ChannelTx;
+
+pub use config::Config;
+
+use crate::Control;
+
+/// Whether warnings and errors should be shown as notifications.
+static NOTIFICATIONS: AtomicBool = AtomicBool::new(false);
+
+/// The tray icon, which is removed when this is dropped.
+pub struct Tray {
+    _icon: sys::Icon,
+}
+
+/// Shows warnings and errors that are logged as notifications from the tray icon, if there is one.
+pub struct Notifier;
+
+impl Tray {
+    fn start(config: &Config, control_tx: ChannelTx<Control>) -> AnyResult<Tray> {
+        let icon = sys::Icon::create(control_tx)?;
+        if config.hide_console {
+            sys::set_console_visible(false);
+        }
+
+        NOTIFICATIONS.store(config.notifications, Ordering::Relaxed);
+        Ok(Tray { _icon: icon })
+    }
+}
+
+impl Drop for Tray {
+    fn drop(&mut self) {
+        NOTIFICATIONS.store(false, Ordering::Relaxed);
+    }
+}
+
+/// Starts, updates, or removes the tray icon to match the configuration.
+pub fn update(tray: &mut Option<Tray>, config: &Config, control_tx: &ChannelTx<Control>) {
+    match tray {
+        Some(_) if config.enabled => {
+            NOTIFICATIONS.store(config.notifications, Ordering::Relaxed);
+        }
+
+        Some(_) => {
+            *tray = None;
+            sys::set_console_visible(true);
+            log::info!("Tray icon removed.");
+        }
+
+        None if config.enabled => match Tray::start(config, control_tx.clone()) {
+            Ok(t) => {
+                log::info!("Tray icon added.");
+                *tray = Some(t);
+            }
+            Err(e) => log::warn!("Failed to add the tray icon: {e:#}"),
+        },
+
+        None => {}
+    }
+}
+
+/// Shows `text` as a notification from the tray icon, if there is one and notifications are enabled.
+#[cfg_attr(not(feature = "update-check"), allow(dead_code))]
+pub fn notify(text: String) {
+    if NOTIFICATIONS.load(Ordering::Relaxed) {
+        sys::notify(false, text);
+    }
+}
+
+/// Whether the console has been hidden, in which case nothing written to it will be seen. This is
+/// only needed to decide whether to show a dialog, which is only done on Windows.
+#[cfg_attr(not(windows), allow(dead_code))]
+pub fn console_hidden() -> bool {
+    sys::SUPPORTED && !sys::console_visible()
+}
+
+impl Notifier {
+    pub fn new() -> Box<Notifier> {
+        Box::new(Notifier)
+    }
+}
+
+impl Log for Notifier {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= Level::Warn && NOTIFICATIONS.load(Ordering::Relaxed)
+    }
+
+    fn log(&self, record: &Record) {
+        if self.enabled(record.metadata()) {
+            sys::notify(record.level() == Level::Error, record.args().to_string());
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+impl SharedLogger for Notifier {
+    fn level(&self) -> LevelFilter {
+        LevelFilter::Warn
+    }
+
+    fn config(&self) -> Option<&simplelog::Config> {
+        None
+    }
+
+    fn as_log(self: Box<Self>) -> Box<dyn Log> {
+        self
+    }
+}
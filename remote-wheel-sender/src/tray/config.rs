@@ -0,0 +1,37 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::check::Report;
+
+#[derive(Clone, Debug, Deserialize, JsonSchema, Serialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Config {
+    pub(super) enabled: bool,
+    pub(super) hide_console: bool,
+    pub(super) notifications: bool,
+}
+
+impl Config {
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn validate(&self, report: &mut Report) {
+        if self.enabled && !super::sys::SUPPORTED {
+            report.warning(
+                "tray.enabled",
+                "The tray icon is only supported on Windows, so it will not be shown",
+            );
+        }
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            enabled: false,
+            hide_console: true,
+            notifications: true,
+        }
+    }
+}
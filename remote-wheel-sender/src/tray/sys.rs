@@ -0,0 +1,374 @@
+#[cfg(windows)]
+pub use self::windows::*;
+
+#[cfg(not(windows))]
+pub use self::other::*;
+
+#[cfg(windows)]
+mod windows {
+    use std::sync::atomic::{AtomicIsize, AtomicU32, Ordering};
+    use std::sync::Mutex;
+    use std::thread::JoinHandle;
+
+    use anyhow::{anyhow, bail, Context as _, Result as AnyResult};
+    use smol::channel::Sender as ChannelTx;
+    use windows_sys::Win32::Foundation::{
+        GetLastError, ERROR_CLASS_ALREADY_EXISTS, HWND, LPARAM, LRESULT, POINT, WPARAM,
+    };
+    use windows_sys::Win32::System::Console::GetConsoleWindow;
+    use windows_sys::Win32::System::LibraryLoader::GetModuleHandleW;
+    use windows_sys::Win32::UI::Shell::{
+        Shell_NotifyIconW, NIF_ICON, NIF_INFO, NIF_MESSAGE, NIF_TIP, NIIF_ERROR, NIIF_WARNING,
+        NIM_ADD, NIM_DELETE, NIM_MODIFY, NOTIFYICONDATAW,
+    };
+    use windows_sys::Win32::UI::WindowsAndMessaging::{
+        AppendMenuW, CreatePopupMenu, CreateWindowExW, DefWindowProcW, DestroyMenu, DestroyWindow,
+        DispatchMessageW, GetCursorPos, GetMessageW, IsWindowVisible, LoadIconW, PostMessageW,
+        PostQuitMessage, RegisterClassW, RegisterWindowMessageW, SetForegroundWindow, ShowWindow,
+        TrackPopupMenu, TranslateMessage, IDI_APPLICATION, MF_CHECKED, MF_SEPARATOR, MF_STRING,
+        MSG, SW_HIDE, SW_SHOW, TPM_RIGHTBUTTON, WM_APP, WM_CLOSE, WM_COMMAND, WM_CONTEXTMENU,
+        WM_DESTROY, WM_LBUTTONDBLCLK, WM_RBUTTONUP, WNDCLASSW,
+    };
+
+    use crate::Control;
+
+    pub const SUPPORTED: bool = true;
+
+    const CLASS_NAME: &str = "RemoteWheelSenderTray";
+    const TOOLTIP: &str = "Remote Wheel Sender";
+
+    /// Sent by the shell when something happens to the icon.
+    const WM_TRAY: u32 = WM_APP;
+    /// Sent by [`notify`] when there is a notification to show.
+    const WM_TRAY_NOTIFY: u32 = WM_APP + 1;
+
+    const ID_CONSOLE: usize = 1;
+    const ID_RELOAD: usize = 2;
+    const ID_QUIT: usize = 3;
+
+    /// The window that owns the icon, or 0 if there isn't one.
+    static WINDOW: AtomicIsize = AtomicIsize::new(0);
+    /// The message that the shell sends when the taskbar is (re)created, e.g. after Explorer
+    /// restarts, since the icon must be added again.
+    static TASKBAR_CREATED: AtomicU32 = AtomicU32::new(0);
+    /// Where menu choices are sent.
+    static CONTROL_TX: Mutex<Option<ChannelTx<Control>>> = Mutex::new(None);
+    /// The latest notification that hasn't been shown yet, and whether it's an error. Only the
+    /// latest is kept, since each one would replace the last anyway.
+    static PENDING: Mutex<Option<(bool, String)>> = Mutex::new(None);
+
+    /// A tray icon, with a thread to handle its messages.
+    pub struct Icon {
+        thread: Option<JoinHandle<()>>,
+    }
+
+    impl Icon {
+        pub fn create(control_tx: ChannelTx<Control>) -> AnyResult<Icon> {
+            *CONTROL_TX.lock().unwrap() = Some(control_tx);
+
+            let (ready_tx, ready_rx) = std::sync::mpsc::channel();
+            let thread = std::thread::Builder::new()
+                .name("Tray".into())
+                .spawn(move || match create_window() {
+                    Ok(hwnd) => {
+                        WINDOW.store(hwnd, Ordering::Release);
+                        let _ = ready_tx.send(Ok(()));
+                        run_messages();
+                        WINDOW.store(0, Ordering::Release);
+                    }
+                    Err(e) => {
+                        let _ = ready_tx.send(Err(e));
+                    }
+                })
+                .context("Failed to start tray thread")?;
+
+            let result = ready_rx
+                .recv()
+                .map_err(|_| anyhow!("Tray thread stopped unexpectedly"))
+                .and_then(|r| r);
+
+            let icon = Icon {
+                thread: Some(thread),
+            };
+            result.map(|()| icon)
+        }
+    }
+
+    impl Drop for Icon {
+        fn drop(&mut self) {
+            let hwnd = WINDOW.load(Ordering::Acquire);
+            if hwnd != 0 {
+                unsafe {
+                    PostMessageW(hwnd, WM_CLOSE, 0, 0);
+                }
+            }
+
+            if let Some(thread) = self.thread.take() {
+                let _ = thread.join();
+            }
+
+            *CONTROL_TX.lock().unwrap() = None;
+        }
+    }
+
+    pub fn set_console_visible(visible: bool) {
+        unsafe {
+            let console = GetConsoleWindow();
+            if console != 0 {
+                ShowWindow(console, if visible { SW_SHOW } else { SW_HIDE });
+            }
+        }
+    }
+
+    pub fn console_visible() -> bool {
+        unsafe {
+            let console = GetConsoleWindow();
+            console != 0 && IsWindowVisible(console) != 0
+        }
+    }
+
+    pub fn notify(error: bool, text: String) {
+        let hwnd = WINDOW.load(Ordering::Acquire);
+        if hwnd == 0 {
+            return;
+        }
+
+        *PENDING.lock().unwrap() = Some((error, text));
+        unsafe {
+            PostMessageW(hwnd, WM_TRAY_NOTIFY, 0, 0);
+        }
+    }
+
+    fn create_window() -> AnyResult<HWND> {
+        let class_name = wide(CLASS_NAME);
+
+        unsafe {
+            let instance = GetModuleHandleW(std::ptr::null());
+
+            let mut class: WNDCLASSW = std::mem::zeroed();
+            class.lpfnWndProc = Some(window_proc);
+            class.hInstance = instance;
+            class.lpszClassName = class_name.as_ptr();
+
+            // The class is still registered if the icon was removed and added again.
+            if RegisterClassW(&class) == 0 && GetLastError() != ERROR_CLASS_ALREADY_EXISTS {
+                bail!(
+                    "Failed to register tray window class: {}",
+                    std::io::Error::last_os_error()
+                );
+            }
+
+            TASKBAR_CREATED.store(
+                RegisterWindowMessageW(wide("TaskbarCreated").as_ptr()),
+                Ordering::Relaxed,
+            );
+
+            // This is a hidden top-level window rather than a message-only window, since those
+            // don't receive TaskbarCreated.
+            let hwnd = CreateWindowExW(
+                0,
+                class_name.as_ptr(),
+                class_name.as_ptr(),
+                0,
+                0,
+                0,
+                0,
+                0,
+                0,
+                0,
+                instance,
+                std::ptr::null(),
+            );
+            if hwnd == 0 {
+                bail!(
+                    "Failed to create tray window: {}",
+                    std::io::Error::last_os_error()
+                );
+            }
+
+            if !add_icon(hwnd) {
+                DestroyWindow(hwnd);
+                bail!("Failed to add tray icon");
+            }
+
+            Ok(hwnd)
+        }
+    }
+
+    fn run_messages() {
+        unsafe {
+            let mut msg: MSG = std::mem::zeroed();
+            while GetMessageW(&mut msg, 0, 0, 0) > 0 {
+                TranslateMessage(&msg);
+                DispatchMessageW(&msg);
+            }
+        }
+    }
+
+    unsafe extern "system" fn window_proc(
+        hwnd: HWND,
+        msg: u32,
+        wparam: WPARAM,
+        lparam: LPARAM,
+    ) -> LRESULT {
+        match msg {
+            WM_TRAY => match lparam as u32 {
+                WM_RBUTTONUP | WM_CONTEXTMENU => show_menu(hwnd),
+                WM_LBUTTONDBLCLK => set_console_visible(!console_visible()),
+                _ => {}
+            },
+
+            WM_COMMAND => match wparam & 0xFFFF {
+                ID_CONSOLE => set_console_visible(!console_visible()),
+                ID_RELOAD => send(Control::Reload),
+                ID_QUIT => send(Control::Quit),
+                _ => {}
+            },
+
+            WM_TRAY_NOTIFY => show_pending(hwnd),
+
+            WM_CLOSE => {
+                DestroyWindow(hwnd);
+            }
+
+            WM_DESTROY => {
+                Shell_NotifyIconW(NIM_DELETE, &icon_data(hwnd));
+                PostQuitMessage(0);
+            }
+
+            _ if msg == TASKBAR_CREATED.load(Ordering::Relaxed) && msg != 0 => {
+                add_icon(hwnd);
+            }
+
+            _ => return DefWindowProcW(hwnd, msg, wparam, lparam),
+        }
+
+        0
+    }
+
+    fn send(control: Control) {
+        if let Some(control_tx) = CONTROL_TX.lock().unwrap().as_ref() {
+            let _ = control_tx.try_send(control);
+        }
+    }
+
+    unsafe fn show_menu(hwnd: HWND) {
+        let menu = CreatePopupMenu();
+        if menu == 0 {
+            return;
+        }
+
+        let console_flags = if console_visible() {
+            MF_STRING | MF_CHECKED
+        } else {
+            MF_STRING
+        };
+
+        AppendMenuW(
+            menu,
+            console_flags,
+            ID_CONSOLE,
+            wide("Show Console").as_ptr(),
+        );
+        AppendMenuW(menu, MF_STRING, ID_RELOAD, wide("Reload Config").as_ptr());
+        AppendMenuW(menu, MF_SEPARATOR, 0, std::ptr::null());
+        AppendMenuW(menu, MF_STRING, ID_QUIT, wide("Quit").as_ptr());
+
+        let mut point = POINT { x: 0, y: 0 };
+        GetCursorPos(&mut point);
+
+        // Otherwise, the menu won't close when clicking elsewhere.
+        SetForegroundWindow(hwnd);
+        TrackPopupMenu(
+            menu,
+            TPM_RIGHTBUTTON,
+            point.x,
+            point.y,
+            0,
+            hwnd,
+            std::ptr::null(),
+        );
+        DestroyMenu(menu);
+    }
+
+    unsafe fn show_pending(hwnd: HWND) {
+        let Some((error, text)) = PENDING.lock().unwrap().take() else {
+            return;
+        };
+
+        let mut data = icon_data(hwnd);
+        data.uFlags = NIF_INFO;
+        data.dwInfoFlags = if error { NIIF_ERROR } else { NIIF_WARNING };
+        copy_wide(
+            &mut data.szInfoTitle,
+            if error {
+                "Remote Wheel Sender error"
+            } else {
+                "Remote Wheel Sender warning"
+            },
+        );
+        copy_wide(&mut data.szInfo, &text);
+
+        Shell_NotifyIconW(NIM_MODIFY, &data);
+    }
+
+    unsafe fn add_icon(hwnd: HWND) -> bool {
+        let mut data = icon_data(hwnd);
+        data.uFlags = NIF_ICON | NIF_MESSAGE | NIF_TIP;
+        data.uCallbackMessage = WM_TRAY;
+        data.hIcon = LoadIconW(0, IDI_APPLICATION);
+        copy_wide(&mut data.szTip, TOOLTIP);
+
+        Shell_NotifyIconW(NIM_ADD, &data) != 0
+    }
+
+    fn icon_data(hwnd: HWND) -> NOTIFYICONDATAW {
+        let mut data: NOTIFYICONDATAW = unsafe { std::mem::zeroed() };
+        data.cbSize = std::mem::size_of::<NOTIFYICONDATAW>() as u32;
+        data.hWnd = hwnd;
+        data.uID = 1;
+        data
+    }
+
+    fn wide(s: &str) -> Vec<u16> {
+        s.encode_utf16().chain(std::iter::once(0)).collect()
+    }
+
+    /// Copies a string into a fixed-size buffer, truncating it if needed.
+    fn copy_wide(buf: &mut [u16], s: &str) {
+        let len = buf.len() - 1;
+        let mut end = 0;
+        for (dst, src) in buf[..len].iter_mut().zip(s.encode_utf16()) {
+            *dst = src;
+            end += 1;
+        }
+        buf[end] = 0;
+    }
+}
+
+#[cfg(not(windows))]
+mod other {
+    use anyhow::{bail, Result as AnyResult};
+    use smol::channel::Sender as ChannelTx;
+
+    use crate::Control;
+
+    pub const SUPPORTED: bool = false;
+
+    pub struct Icon;
+
+    impl Icon {
+        pub fn create(_: ChannelTx<Control>) -> AnyResult<Icon> {
+            bail!("this is only supported on Windows")
+        }
+    }
+
+    pub fn set_console_visible(_: bool) {}
+
+    #[allow(dead_code)]
+    pub fn console_visible() -> bool {
+        true
+    }
+
+    pub fn notify(_: bool, _: String) {}
+}
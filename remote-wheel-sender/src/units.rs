@@ -0,0 +1,354 @@
+//! Deserializers for configuration values with units.
+//!
+//! Each accepts either a bare number, which is in the same unit that the setting has always used,
+//! or a string with a unit suffix, such as `"90deg"` or `"250ms"`.
+
+use std::f64::consts::PI;
+
+use schemars::gen::SchemaGenerator;
+use schemars::schema::{InstanceType, Schema, SchemaObject, StringValidation, SubschemaValidation};
+use schemars::JsonSchema;
+use serde::de::{Deserializer, Error, Unexpected, Visitor};
+use serde::Deserialize;
+
+/// Angle units, and how many degrees are in each.
+const ANGLE_UNITS: &[(&str, f64)] = &[("deg", 1.0), ("°", 1.0), ("rad", 180.0 / PI)];
+
+/// Frequency units, and how many hertz are in each.
+const FREQUENCY_UNITS: &[(&str, f64)] = &[("Hz", 1.0), ("hz", 1.0)];
+
+/// Time units, and how many seconds are in each. Units are matched in order, so any unit must come
+/// before those that it ends with (e.g. "ms" before "s").
+const TIME_UNITS: &[(&str, f64)] = &[
+    ("ms", 0.001),
+    ("min", 60.0),
+    ("s", 1.0),
+    ("m", 60.0),
+    ("h", 3600.0),
+];
+
+/// The same time units, and how many milliseconds are in each, for settings that have always been
+/// in milliseconds.
+const MILLISECOND_UNITS: &[(&str, f64)] = &[
+    ("ms", 1.0),
+    ("min", 60_000.0),
+    ("s", 1000.0),
+    ("m", 60_000.0),
+    ("h", 3_600_000.0),
+];
+
+/// An angle, in degrees.
+pub fn degrees<'de, D: Deserializer<'de>>(de: D) -> Result<f64, D::Error> {
+    de.deserialize_any(UnitVisitor {
+        units: ANGLE_UNITS,
+        negative: true,
+        expecting: "an angle in degrees, or a string such as \"90deg\" or \"1.57rad\"",
+    })
+}
+
+/// A duration, in seconds.
+pub fn seconds<'de, D: Deserializer<'de>>(de: D) -> Result<f64, D::Error> {
+    de.deserialize_any(UnitVisitor {
+        units: TIME_UNITS,
+        negative: false,
+        expecting: "a non-negative duration in seconds, or a string such as \"250ms\", \"1.5s\", or \"2m\"",
+    })
+}
+
+/// A duration, in milliseconds.
+fn milliseconds<'de, D: Deserializer<'de>>(de: D) -> Result<f64, D::Error> {
+    de.deserialize_any(UnitVisitor {
+        units: MILLISECOND_UNITS,
+        negative: false,
+        expecting: "a non-negative duration in milliseconds, or a string such as \"250ms\" or \"1.5s\"",
+    })
+}
+
+/// A frequency, in hertz.
+pub fn hertz<'de, D: Deserializer<'de>>(de: D) -> Result<f64, D::Error> {
+    de.deserialize_any(UnitVisitor {
+        units: FREQUENCY_UNITS,
+        negative: false,
+        expecting: "a non-negative frequency in hertz, or a string such as \"0.2hz\"",
+    })
+}
+
+/// Parses a frequency, in hertz, written the same way as for [`hertz`].
+pub fn parse_hertz(s: &str) -> Option<f64> {
+    UnitVisitor {
+        units: FREQUENCY_UNITS,
+        negative: false,
+        expecting: "",
+    }
+    .parse(s)
+}
+
+/// An optional duration, in seconds.
+pub fn optional_seconds<'de, D: Deserializer<'de>>(de: D) -> Result<Option<f64>, D::Error> {
+    #[derive(Deserialize)]
+    struct Seconds(#[serde(deserialize_with = "seconds")] f64);
+
+    Ok(Option::<Seconds>::deserialize(de)?.map(|s| s.0))
+}
+
+/// An optional duration, in whole milliseconds.
+pub fn optional_milliseconds<'de, D: Deserializer<'de>>(de: D) -> Result<Option<u64>, D::Error> {
+    #[derive(Deserialize)]
+    struct Milliseconds(#[serde(deserialize_with = "milliseconds")] f64);
+
+    Ok(Option::<Milliseconds>::deserialize(de)?.map(|ms| ms.0.round() as u64))
+}
+
+struct UnitVisitor {
+    units: &'static [(&'static str, f64)],
+    /// Whether negative values are allowed (e.g. angles, but not durations).
+    negative: bool,
+    expecting: &'static str,
+}
+
+impl UnitVisitor {
+    fn parse(&self, s: &str) -> Option<f64> {
+        let s = s.trim();
+
+        let value = match self
+            .units
+            .iter()
+            .find_map(|&(u, scale)| Some((s.strip_suffix(u)?, scale)))
+        {
+            Some((value, scale)) => value.trim_end().parse::<f64>().ok()? * scale,
+            None => s.parse().ok()?,
+        };
+        self.check(value)
+    }
+
+    /// Rejects values that aren't numbers at all (e.g. "inf" and "NaN", which Rust accepts when
+    /// parsing), and negative values if they aren't allowed.
+    fn check(&self, v: f64) -> Option<f64> {
+        (v.is_finite() && (self.negative || v >= 0.0)).then_some(v)
+    }
+}
+
+impl<'de> Visitor<'de> for UnitVisitor {
+    type Value = f64;
+
+    fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(self.expecting)
+    }
+
+    fn visit_f64<E: Error>(self, v: f64) -> Result<f64, E> {
+        self.check(v)
+            .ok_or_else(|| E::invalid_value(Unexpected::Float(v), &self))
+    }
+
+    fn visit_i64<E: Error>(self, v: i64) -> Result<f64, E> {
+        self.check(v as f64)
+            .ok_or_else(|| E::invalid_value(Unexpected::Signed(v), &self))
+    }
+
+    fn visit_u64<E: Error>(self, v: u64) -> Result<f64, E> {
+        Ok(v as f64)
+    }
+
+    fn visit_str<E: Error>(self, v: &str) -> Result<f64, E> {
+        self.parse(v)
+            .ok_or_else(|| E::invalid_value(Unexpected::Str(v), &self))
+    }
+}
+
+/// The schema for [`degrees`].
+pub fn degrees_schema(gen: &mut SchemaGenerator) -> Schema {
+    unit_schema(gen, ANGLE_UNITS)
+}
+
+/// The schema for [`seconds`].
+pub fn seconds_schema(gen: &mut SchemaGenerator) -> Schema {
+    unit_schema(gen, TIME_UNITS)
+}
+
+/// The schema for [`hertz`].
+pub fn hertz_schema(gen: &mut SchemaGenerator) -> Schema {
+    unit_schema(gen, FREQUENCY_UNITS)
+}
+
+/// The schema for [`optional_milliseconds`].
+pub fn optional_milliseconds_schema(gen: &mut SchemaGenerator) -> Schema {
+    SchemaObject {
+        subschemas: Some(Box::new(SubschemaValidation {
+            any_of: Some(vec![
+                unit_schema(gen, MILLISECOND_UNITS),
+                gen.subschema_for::<()>(),
+            ]),
+            ..Default::default()
+        })),
+        ..Default::default()
+    }
+    .into()
+}
+
+/// A duration, as described by [`seconds_schema`]. This is for fields that need the schema of an
+/// optional duration to be an `Option` (e.g. ones that aren't serialized, which would otherwise be
+/// required).
+pub struct Seconds;
+
+impl JsonSchema for Seconds {
+    fn schema_name() -> String {
+        "Seconds".to_owned()
+    }
+
+    fn json_schema(gen: &mut SchemaGenerator) -> Schema {
+        seconds_schema(gen)
+    }
+}
+
+/// The schema for [`optional_seconds`].
+pub fn optional_seconds_schema(gen: &mut SchemaGenerator) -> Schema {
+    SchemaObject {
+        subschemas: Some(Box::new(SubschemaValidation {
+            any_of: Some(vec![seconds_schema(gen), gen.subschema_for::<()>()]),
+            ..Default::default()
+        })),
+        ..Default::default()
+    }
+    .into()
+}
+
+fn unit_schema(gen: &mut SchemaGenerator, units: &[(&str, f64)]) -> Schema {
+    let units = units.iter().map(|&(u, _)| u).collect::<Vec<_>>().join("|");
+    let string = SchemaObject {
+        instance_type: Some(InstanceType::String.into()),
+        string: Some(Box::new(StringValidation {
+            pattern: Some(format!(
+                r"^\s*[-+]?[0-9]*\.?[0-9]+([eE][-+]?[0-9]+)?\s*({units})?\s*$"
+            )),
+            ..Default::default()
+        })),
+        ..Default::default()
+    };
+
+    SchemaObject {
+        subschemas: Some(Box::new(SubschemaValidation {
+            any_of: Some(vec![gen.subschema_for::<f64>(), string.into()]),
+            ..Default::default()
+        })),
+        ..Default::default()
+    }
+    .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn seconds(s: &str) -> Option<f64> {
+        UnitVisitor {
+            units: TIME_UNITS,
+            negative: false,
+            expecting: "",
+        }
+        .parse(s)
+    }
+
+    fn degrees(s: &str) -> Option<f64> {
+        UnitVisitor {
+            units: ANGLE_UNITS,
+            negative: true,
+            expecting: "",
+        }
+        .parse(s)
+    }
+
+    #[test]
+    fn parses_time_units() {
+        assert_eq!(seconds("250ms"), Some(0.25));
+        assert_eq!(seconds("1.5s"), Some(1.5));
+        assert_eq!(seconds("2m"), Some(120.0));
+        assert_eq!(seconds("2min"), Some(120.0));
+        assert_eq!(seconds("1h"), Some(3600.0));
+    }
+
+    #[test]
+    fn parses_angle_units() {
+        assert_eq!(degrees("90deg"), Some(90.0));
+        assert_eq!(degrees("90°"), Some(90.0));
+        assert_eq!(degrees("-450deg"), Some(-450.0));
+        assert!((degrees("1rad").unwrap() - 180.0 / PI).abs() < 1e-9);
+    }
+
+    #[test]
+    fn parses_frequency_units() {
+        assert_eq!(parse_hertz("0.2hz"), Some(0.2));
+        assert_eq!(parse_hertz("60Hz"), Some(60.0));
+    }
+
+    #[test]
+    fn parses_bare_numbers() {
+        assert_eq!(seconds("3"), Some(3.0));
+        assert_eq!(seconds("0.5"), Some(0.5));
+        assert_eq!(seconds("1e-3"), Some(0.001));
+        assert_eq!(degrees("-90"), Some(-90.0));
+    }
+
+    #[test]
+    fn ignores_whitespace() {
+        assert_eq!(seconds("  250ms  "), Some(0.25));
+        assert_eq!(seconds("250 ms"), Some(0.25));
+        assert_eq!(seconds("\t2\n"), Some(2.0));
+    }
+
+    #[test]
+    fn rejects_bad_input() {
+        for bad in ["", "ms", "fast", "250 ms s", "250xs", "1,5s", "s250"] {
+            assert_eq!(seconds(bad), None, "{bad:?}");
+        }
+    }
+
+    #[test]
+    fn rejects_non_finite_values() {
+        for bad in ["inf", "infs", "-inf", "NaN", "nanms", "infinityh", "1e400s"] {
+            assert_eq!(seconds(bad), None, "{bad:?}");
+            assert_eq!(degrees(bad), None, "{bad:?}");
+        }
+    }
+
+    #[test]
+    fn rejects_negative_durations() {
+        assert_eq!(seconds("-1"), None);
+        assert_eq!(seconds("-5s"), None);
+        assert_eq!(parse_hertz("-1hz"), None);
+    }
+
+    #[test]
+    fn deserializes_numbers_and_strings() {
+        #[derive(Deserialize)]
+        struct Config {
+            #[serde(deserialize_with = "super::seconds")]
+            delay: f64,
+        }
+
+        let parse = |s: &str| toml::from_str::<Config>(s).map(|c| c.delay);
+        assert_eq!(parse("delay = 2").unwrap(), 2.0);
+        assert_eq!(parse("delay = 0.25").unwrap(), 0.25);
+        assert_eq!(parse("delay = \"250ms\"").unwrap(), 0.25);
+        assert!(parse("delay = -1").is_err());
+        assert!(parse("delay = -0.5").is_err());
+        assert!(parse("delay = nan").is_err());
+        assert!(parse("delay = \"infs\"").is_err());
+    }
+
+    #[test]
+    fn deserializes_bare_numbers_as_milliseconds() {
+        #[derive(Deserialize)]
+        struct Config {
+            #[serde(default, deserialize_with = "super::optional_milliseconds")]
+            hold: Option<u64>,
+        }
+
+        let parse = |s: &str| toml::from_str::<Config>(s).map(|c| c.hold);
+        assert_eq!(parse("").unwrap(), None);
+        assert_eq!(parse("hold = 500").unwrap(), Some(500));
+        assert_eq!(parse("hold = \"250ms\"").unwrap(), Some(250));
+        assert_eq!(parse("hold = \"1.5s\"").unwrap(), Some(1500));
+        assert!(parse("hold = -1").is_err());
+        assert!(parse("hold = \"fast\"").is_err());
+    }
+}
@@ -0,0 +1,166 @@
+mod config;
+
+use std::cmp::Ordering;
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context as _, Result as AnyResult};
+use serde::{Deserialize, Serialize};
+
+pub use config::Config;
+
+use crate::{paths, version};
+
+/// Where the latest release is asked for. Drafts and pre-releases aren't included.
+const LATEST_URL: &str = "https://api.github.com/repos/Barinzaya/remote-wheel/releases/latest";
+
+/// How long to wait for GitHub before giving up. Nothing waits on the check, but there's no point
+/// in keeping a connection around for long.
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long a checked release is remembered, so that GitHub isn't asked on every start.
+const CACHE_DURATION: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// The name of the file that the last checked release is remembered in.
+const CACHE_NAME: &str = "update-check.json";
+
+#[derive(Debug, Deserialize, Serialize)]
+struct Release {
+    tag_name: String,
+    html_url: String,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct Cache {
+    /// When the release was checked, in seconds since the Unix epoch.
+    checked: u64,
+    release: Release,
+}
+
+/// Checks whether a newer release is available, and says so if it is. This never fails; if the
+/// check can't be done, that's only logged.
+pub async fn run(config: Config) {
+    if !config.enabled() {
+        return;
+    }
+
+    let release = match smol::unblock(latest_release).await {
+        Ok(r) => r,
+        Err(e) => {
+            log::info!("Could not check for a newer release: {e:#}");
+            return;
+        }
+    };
+
+    let latest = release.tag_name.trim_start_matches('v');
+    if compare_versions(latest, version::VERSION) == Ordering::Greater {
+        let notice = format!(
+            "Remote Wheel Sender v{latest} is available (running v{}): {}",
+            version::VERSION,
+            release.html_url
+        );
+
+        log::info!("{notice}");
+        #[cfg(feature = "tray")]
+        crate::tray::notify(notice);
+    } else {
+        log::debug!("No newer release is available (latest is v{latest}).");
+    }
+}
+
+/// Gets the latest release, from the cache if it was checked recently enough.
+fn latest_release() -> AnyResult<Release> {
+    let path = paths::file(CACHE_NAME);
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+
+    // An unreadable cache is no different from a stale one.
+    let cached = std::fs::read(&path)
+        .ok()
+        .and_then(|data| serde_json::from_slice::<Cache>(&data).ok());
+    if let Some(cache) = cached {
+        if now.saturating_sub(Duration::from_secs(cache.checked)) < CACHE_DURATION {
+            log::debug!("Using the release that was checked recently.");
+            return Ok(cache.release);
+        }
+    }
+
+    let agent = ureq::AgentBuilder::new()
+        .timeout(TIMEOUT)
+        .user_agent(&format!("remote-wheel-sender/{}", version::VERSION))
+        .build();
+
+    let response = agent
+        .get(LATEST_URL)
+        .set("Accept", "application/vnd.github+json")
+        .call()
+        .context("Failed to request the latest release")?;
+    let release: Release = serde_json::from_reader(response.into_reader())
+        .context("Failed to read the latest release")?;
+
+    let cache = Cache {
+        checked: now.as_secs(),
+        release,
+    };
+    if let Err(e) = write_cache(&path, &cache) {
+        log::debug!("Could not remember the latest release: {e:#}");
+    }
+
+    Ok(cache.release)
+}
+
+fn write_cache(path: &Path, cache: &Cache) -> AnyResult<()> {
+    let data = serde_json::to_vec(cache)?;
+    std::fs::write(path, data).with_context(|| format!("Failed to write <{}>", path.display()))
+}
+
+/// Compares two versions by semver precedence. Anything that isn't a number where one is expected
+/// is treated as 0, so a malformed version won't be considered newer than it is.
+fn compare_versions(a: &str, b: &str) -> Ordering {
+    let split = |v: &str| {
+        let v = v.split_once('+').map_or(v, |(v, _)| v);
+        let (core, pre) = v.split_once('-').unwrap_or((v, ""));
+        let mut parts = core.split('.').map(|p| p.parse::<u64>().unwrap_or(0));
+        let core = [(); 3].map(|_| parts.next().unwrap_or(0));
+        (core, pre.to_owned())
+    };
+
+    let (a_core, a_pre) = split(a);
+    let (b_core, b_pre) = split(b);
+
+    a_core
+        .cmp(&b_core)
+        .then_with(|| match (a_pre.is_empty(), b_pre.is_empty()) {
+            // A pre-release comes before the release it leads up to.
+            (true, true) => Ordering::Equal,
+            (true, false) => Ordering::Greater,
+            (false, true) => Ordering::Less,
+            (false, false) => compare_pre_release(&a_pre, &b_pre),
+        })
+}
+
+fn compare_pre_release(a: &str, b: &str) -> Ordering {
+    let mut a = a.split('.');
+    let mut b = b.split('.');
+
+    loop {
+        let ordering = match (a.next(), b.next()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+
+            // Numeric identifiers are compared as numbers, and come before any others.
+            (Some(a), Some(b)) => match (a.parse::<u64>(), b.parse::<u64>()) {
+                (Ok(a), Ok(b)) => a.cmp(&b),
+                (Ok(_), Err(_)) => Ordering::Less,
+                (Err(_), Ok(_)) => Ordering::Greater,
+                (Err(_), Err(_)) => a.cmp(b),
+            },
+        };
+
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+}
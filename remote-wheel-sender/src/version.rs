@@ -0,0 +1,99 @@
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+pub const GIT_HASH: &str = env!("REMOTE_WHEEL_GIT_HASH");
+pub const BUILD_DATE: &str = env!("REMOTE_WHEEL_BUILD_DATE");
+
+/// The features that the Sender may have been built with.
+const FEATURES: &[(&str, bool)] = &[
+    ("audio", cfg!(feature = "audio")),
+    ("exec", cfg!(feature = "exec")),
+    ("keep-awake", cfg!(feature = "keep-awake")),
+    ("keyboard", cfg!(feature = "keyboard")),
+    ("keystroke", cfg!(feature = "keystroke")),
+    ("midi", cfg!(feature = "midi")),
+    ("openvr", cfg!(feature = "openvr")),
+    ("osc", cfg!(feature = "osc")),
+    ("tray", cfg!(feature = "tray")),
+    ("update-check", cfg!(feature = "update-check")),
+    ("vjoy", cfg!(feature = "vjoy")),
+    ("vmc", cfg!(feature = "vmc")),
+];
+
+/// The name and version of this build, e.g. `Remote Wheel Sender v0.3.3 (1a2b3c4, built 2023-09-01)`.
+pub fn describe() -> String {
+    format!("Remote Wheel Sender v{VERSION} ({GIT_HASH}, built {BUILD_DATE})")
+}
+
+/// The features that this build has enabled, separated by commas.
+pub fn features() -> String {
+    let features = FEATURES
+        .iter()
+        .filter(|&&(_, enabled)| enabled)
+        .map(|&(name, _)| name)
+        .collect::<Vec<_>>();
+
+    if features.is_empty() {
+        "(none)".into()
+    } else {
+        features.join(", ")
+    }
+}
+
+/// The name and version of the OS that's running, and its architecture.
+pub fn os() -> String {
+    format!("{} ({})", sys::os_version(), std::env::consts::ARCH)
+}
+
+/// Prints the version and features, for `--version`.
+pub fn print() {
+    println!("{}", describe());
+    println!("Features: {}", features());
+}
+
+/// Logs what's running and where, so that it's in every log file.
+pub fn log_startup() {
+    log::info!("{}", describe());
+    log::info!("Features: {}", features());
+    log::info!("OS: {}", os());
+    log::info!("SDL: {}", sdl2::version::version());
+}
+
+#[cfg(windows)]
+mod sys {
+    use windows_sys::Win32::System::SystemInformation::OSVERSIONINFOW;
+
+    // GetVersionExW reports Windows 8 to programs without a manifest saying otherwise, so this asks
+    // ntdll directly instead.
+    #[link(name = "ntdll")]
+    extern "system" {
+        fn RtlGetVersion(info: *mut OSVERSIONINFOW) -> i32;
+    }
+
+    pub fn os_version() -> String {
+        let mut info: OSVERSIONINFOW = unsafe { std::mem::zeroed() };
+        info.dwOSVersionInfoSize = std::mem::size_of::<OSVERSIONINFOW>() as u32;
+
+        if unsafe { RtlGetVersion(&mut info) } != 0 {
+            return "Windows (unknown version)".into();
+        }
+
+        format!(
+            "Windows {}.{} build {}",
+            info.dwMajorVersion, info.dwMinorVersion, info.dwBuildNumber
+        )
+    }
+}
+
+#[cfg(not(windows))]
+mod sys {
+    pub fn os_version() -> String {
+        std::fs::read_to_string("/etc/os-release")
+            .ok()
+            .and_then(|release| {
+                release.lines().find_map(|line| {
+                    let name = line.strip_prefix("PRETTY_NAME=")?;
+                    Some(name.trim_matches('"').to_string())
+                })
+            })
+            .unwrap_or_else(|| std::env::consts::OS.to_string())
+    }
+}
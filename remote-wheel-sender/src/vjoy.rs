@@ -0,0 +1,106 @@
+mod config;
+mod sys;
+
+use std::sync::Arc;
+
+use anyhow::{Context as _, Result as AnyResult};
+use async_broadcast::{Receiver as BroadcastRx, RecvError as BroadcastRxErr};
+
+pub use config::{AxisOutputConfig, ButtonOutputConfig, Config};
+
+use crate::config::MappingConfig;
+use crate::output::{OutputEvent, Timestamped};
+use crate::route::Routes;
+use crate::stats::OutputStats;
+
+use self::sys::Device;
+
+pub async fn run(
+    config: Config,
+    mappings: Arc<MappingConfig>,
+    mut recv: BroadcastRx<Timestamped<OutputEvent>>,
+    stats: Arc<OutputStats>,
+) -> AnyResult<()> {
+    log::info!("vJoy task starting...");
+
+    let mut device = Device::acquire(config.device).context("Failed to start vJoy output")?;
+
+    // Axes and buttons that the device doesn't have are left out, after a warning.
+    let axes = Routes::axes(&mappings, |id, axis| {
+        let number = axis.output.vjoy.axis?;
+        if !device.has_axis(number) {
+            log::warn!(
+                "vJoy device {} has no axis {number}, so it won't be set by {id}.",
+                config.device
+            );
+            return None;
+        }
+
+        Some(number)
+    });
+
+    let buttons = Routes::buttons(&mappings, |id, button| {
+        let number = button.output.vjoy.button?;
+        if !device.has_button(number) {
+            log::warn!(
+                "vJoy device {} has no button {number}, so it won't be set by {id}.",
+                config.device
+            );
+            return None;
+        }
+
+        Some(number)
+    });
+
+    log::info!("vJoy task started, using device {}.", config.device);
+
+    loop {
+        let msg = recv.recv().await;
+        let result = match msg.map(|e| stats.received(e, recv.len())) {
+            Ok(OutputEvent::UpdateAxis(id, value)) => {
+                let Some(&number) = axes.get(&id) else {
+                    continue;
+                };
+
+                stats.sent_mapping(&id);
+                device.set_axis(number, value)
+            }
+
+            // A button's state is all that's set, so restoring it is the same as updating it.
+            Ok(
+                OutputEvent::UpdateButton(id, pressed) | OutputEvent::RestoreButton(id, pressed),
+            ) => {
+                let Some(&number) = buttons.get(&id) else {
+                    continue;
+                };
+
+                stats.sent_mapping(&id);
+                device.set_button(number, pressed)
+            }
+
+            Ok(_) => continue,
+
+            Err(BroadcastRxErr::Overflowed(n)) => {
+                log::warn!("vJoy task missed {n} update(s)!");
+                stats.missed(n);
+                continue;
+            }
+
+            Err(BroadcastRxErr::Closed) => {
+                log::info!("vJoy task stopping (no inputs remaining)...");
+                break;
+            }
+        };
+
+        match result {
+            Ok(()) => stats.sent(),
+            Err(e) => {
+                log::warn!("{e:#}");
+                stats.send_failed();
+            }
+        }
+    }
+
+    log::info!("vJoy task stopped.");
+    Ok(())
+}
@@ -0,0 +1,92 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::check::Report;
+use crate::config::MappingConfig;
+
+use super::sys;
+
+#[derive(Clone, Debug, Deserialize, JsonSchema, Serialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Config {
+    enabled: bool,
+    pub(super) device: u32,
+}
+
+#[derive(Debug, Default, Deserialize, JsonSchema, Serialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct AxisOutputConfig {
+    pub(super) axis: Option<u8>,
+}
+
+#[derive(Debug, Default, Deserialize, JsonSchema, Serialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct ButtonOutputConfig {
+    pub(super) button: Option<u8>,
+}
+
+impl Config {
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn validate(&self, mappings: &MappingConfig, report: &mut Report) {
+        if self.enabled {
+            if !sys::SUPPORTED {
+                report.warning(
+                    "vjoy.enabled",
+                    "vJoy is only supported on Windows, so nothing will be sent to it",
+                );
+            }
+
+            if !(1..=sys::MAX_DEVICES).contains(&self.device) {
+                report.error(
+                    "vjoy.device",
+                    format!("Device must be from 1 to {}", sys::MAX_DEVICES),
+                );
+            }
+        }
+
+        let mut any = false;
+
+        for (id, axis) in &mappings.axis {
+            if let Some(number) = axis.output.vjoy.axis {
+                any = true;
+                if !(1..=sys::AXES.len()).contains(&(number as usize)) {
+                    report.error(
+                        format!("axis.{id}.output.vjoy.axis"),
+                        format!("Axis must be from 1 to {}", sys::AXES.len()),
+                    );
+                }
+            }
+        }
+
+        for (id, button) in &mappings.button {
+            if let Some(number) = button.output.vjoy.button {
+                any = true;
+                if !(1..=sys::MAX_BUTTONS).contains(&number) {
+                    report.error(
+                        format!("button.{id}.output.vjoy.button"),
+                        format!("Button must be from 1 to {}", sys::MAX_BUTTONS),
+                    );
+                }
+            }
+        }
+
+        if any && !self.enabled {
+            report.warning(
+                "vjoy.enabled",
+                "vJoy outputs are configured for some mappings, but will not be used since vJoy is not enabled",
+            );
+        }
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            enabled: false,
+            device: 1,
+        }
+    }
+}
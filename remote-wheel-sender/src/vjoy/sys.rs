@@ -0,0 +1,233 @@
+#[cfg(windows)]
+pub use self::windows::*;
+
+#[cfg(not(windows))]
+pub use self::other::*;
+
+/// The HID usages of the axes that a vJoy device may have, in the order that they're numbered in the
+/// configuration (from 1): X, Y, Z, Rx, Ry, Rz, Slider, and Dial.
+pub const AXES: [u32; 8] = [0x30, 0x31, 0x32, 0x33, 0x34, 0x35, 0x36, 0x37];
+
+pub const MAX_BUTTONS: u8 = 128;
+pub const MAX_DEVICES: u32 = 16;
+
+#[cfg(windows)]
+mod windows {
+    use std::ffi::{c_int, c_long, c_uchar, c_uint, CString};
+
+    use anyhow::{bail, ensure, Context as _, Result as AnyResult};
+    use windows_sys::Win32::Foundation::{BOOL, HMODULE};
+    use windows_sys::Win32::System::LibraryLoader::{GetProcAddress, LoadLibraryW};
+
+    use super::AXES;
+
+    pub const SUPPORTED: bool = true;
+
+    const LIBRARY: &str = "vJoyInterface.dll";
+
+    // Values of `VjdStat`, from GetVJDStatus.
+    const VJD_STAT_OWN: c_int = 0;
+    const VJD_STAT_FREE: c_int = 1;
+    const VJD_STAT_BUSY: c_int = 2;
+    const VJD_STAT_MISS: c_int = 3;
+
+    /// The functions of vJoyInterface.dll that are used. It's loaded when a device is acquired,
+    /// rather than linked to, so that the Sender still runs where vJoy isn't installed.
+    struct Api {
+        enabled: unsafe extern "C" fn() -> BOOL,
+        status: unsafe extern "C" fn(c_uint) -> c_int,
+        acquire: unsafe extern "C" fn(c_uint) -> BOOL,
+        relinquish: unsafe extern "C" fn(c_uint),
+        reset: unsafe extern "C" fn(c_uint) -> BOOL,
+        axis_exists: unsafe extern "C" fn(c_uint, c_uint) -> BOOL,
+        axis_min: unsafe extern "C" fn(c_uint, c_uint, *mut c_long) -> BOOL,
+        axis_max: unsafe extern "C" fn(c_uint, c_uint, *mut c_long) -> BOOL,
+        buttons: unsafe extern "C" fn(c_uint) -> c_int,
+        set_axis: unsafe extern "C" fn(c_long, c_uint, c_uint) -> BOOL,
+        set_button: unsafe extern "C" fn(BOOL, c_uint, c_uchar) -> BOOL,
+    }
+
+    /// A vJoy device that this has acquired. It's relinquished when this is dropped.
+    pub struct Device {
+        api: Api,
+        id: u32,
+        /// The range of each axis in [`AXES`], if the device has it.
+        ranges: [Option<(i32, i32)>; AXES.len()],
+        buttons: u8,
+    }
+
+    impl Device {
+        pub fn acquire(id: u32) -> AnyResult<Device> {
+            let api = unsafe { Api::load() }?;
+
+            ensure!(
+                unsafe { (api.enabled)() } != 0,
+                "vJoy is installed, but not enabled. Enable it with the Configure vJoy application."
+            );
+
+            match unsafe { (api.status)(id) } {
+                VJD_STAT_OWN => {}
+                VJD_STAT_FREE => ensure!(
+                    unsafe { (api.acquire)(id) } != 0,
+                    "Failed to acquire vJoy device {id}"
+                ),
+                VJD_STAT_BUSY => bail!("vJoy device {id} is in use by another application"),
+                VJD_STAT_MISS => bail!(
+                    "vJoy device {id} doesn't exist. Add it with the Configure vJoy application."
+                ),
+                status => bail!("vJoy device {id} is in an unknown state ({status})"),
+            }
+
+            let mut device = Device {
+                api,
+                id,
+                ranges: [None; AXES.len()],
+                buttons: 0,
+            };
+
+            unsafe { (device.api.reset)(id) };
+
+            for (range, usage) in std::iter::zip(&mut device.ranges, AXES) {
+                if unsafe { (device.api.axis_exists)(id, usage) } == 0 {
+                    continue;
+                }
+
+                let (mut min, mut max) = (0, 0);
+                let found = unsafe {
+                    (device.api.axis_min)(id, usage, &mut min) != 0
+                        && (device.api.axis_max)(id, usage, &mut max) != 0
+                };
+
+                if found && max > min {
+                    *range = Some((min, max));
+                }
+            }
+
+            let buttons = unsafe { (device.api.buttons)(id) };
+            device.buttons = buttons.clamp(0, super::MAX_BUTTONS.into()) as u8;
+
+            Ok(device)
+        }
+
+        pub fn has_axis(&self, axis: u8) -> bool {
+            self.range(axis).is_some()
+        }
+
+        pub fn has_button(&self, button: u8) -> bool {
+            (1..=self.buttons).contains(&button)
+        }
+
+        /// Sets an axis (numbered from 1) from a value from 0 to 1, which is mapped to its range.
+        pub fn set_axis(&mut self, axis: u8, value: f64) -> AnyResult<()> {
+            let (min, max) = self
+                .range(axis)
+                .with_context(|| format!("vJoy device {} has no axis {axis}", self.id))?;
+
+            let value = min as f64 + value.clamp(0.0, 1.0) * (max - min) as f64;
+            let usage = AXES[axis as usize - 1];
+
+            ensure!(
+                unsafe { (self.api.set_axis)(value.round() as c_long, self.id, usage) } != 0,
+                "Failed to set axis {axis} of vJoy device {}",
+                self.id
+            );
+
+            Ok(())
+        }
+
+        /// Sets a button (numbered from 1).
+        pub fn set_button(&mut self, button: u8, pressed: bool) -> AnyResult<()> {
+            ensure!(
+                unsafe { (self.api.set_button)(pressed.into(), self.id, button) } != 0,
+                "Failed to set button {button} of vJoy device {}",
+                self.id
+            );
+
+            Ok(())
+        }
+
+        fn range(&self, axis: u8) -> Option<(i32, i32)> {
+            let index = usize::from(axis).checked_sub(1)?;
+            self.ranges.get(index).copied().flatten()
+        }
+    }
+
+    impl Drop for Device {
+        fn drop(&mut self) {
+            unsafe {
+                (self.api.reset)(self.id);
+                (self.api.relinquish)(self.id);
+            }
+        }
+    }
+
+    impl Api {
+        unsafe fn load() -> AnyResult<Api> {
+            let name = LIBRARY.encode_utf16().chain([0]).collect::<Vec<_>>();
+            let module = LoadLibraryW(name.as_ptr());
+            if module == 0 {
+                bail!(
+                    "The vJoy driver doesn't seem to be installed (failed to load {LIBRARY}: {}). Install vJoy, or disable vJoy output.",
+                    std::io::Error::last_os_error()
+                );
+            }
+
+            // The library is never unloaded, since it may be needed again if the task restarts.
+            Ok(Api {
+                enabled: function(module, "vJoyEnabled")?,
+                status: function(module, "GetVJDStatus")?,
+                acquire: function(module, "AcquireVJD")?,
+                relinquish: function(module, "RelinquishVJD")?,
+                reset: function(module, "ResetVJD")?,
+                axis_exists: function(module, "GetVJDAxisExist")?,
+                axis_min: function(module, "GetVJDAxisMin")?,
+                axis_max: function(module, "GetVJDAxisMax")?,
+                buttons: function(module, "GetVJDButtonNumber")?,
+                set_axis: function(module, "SetAxis")?,
+                set_button: function(module, "SetBtn")?,
+            })
+        }
+    }
+
+    /// Looks up a function in a library. `F` must be a function pointer of the right type.
+    unsafe fn function<F: Copy>(module: HMODULE, name: &str) -> AnyResult<F> {
+        let cname = CString::new(name).unwrap();
+        let address = GetProcAddress(module, cname.as_ptr().cast()).with_context(|| {
+            format!("{LIBRARY} has no function {name}; vJoy may be out of date")
+        })?;
+
+        Ok(std::mem::transmute_copy(&address))
+    }
+}
+
+#[cfg(not(windows))]
+mod other {
+    use anyhow::{bail, Result as AnyResult};
+
+    pub const SUPPORTED: bool = false;
+
+    /// A vJoy device, which can never be acquired here.
+    pub enum Device {}
+
+    impl Device {
+        pub fn acquire(_: u32) -> AnyResult<Device> {
+            bail!("vJoy output is only supported on Windows")
+        }
+
+        pub fn has_axis(&self, _: u8) -> bool {
+            match *self {}
+        }
+
+        pub fn has_button(&self, _: u8) -> bool {
+            match *self {}
+        }
+
+        pub fn set_axis(&mut self, _: u8, _: f64) -> AnyResult<()> {
+            match *self {}
+        }
+
+        pub fn set_button(&mut self, _: u8, _: bool) -> AnyResult<()> {
+            match *self {}
+        }
+    }
+}
@@ -10,186 +10,301 @@ use std::io::Cursor;
 use std::str::FromStr;
 use std::sync::atomic::AtomicBool;
 use std::sync::Arc;
-use std::time::{Duration, Instant};
+use std::time::{Duration, Instant, SystemTime};
 
 use anyhow::{bail, ensure, Context as _, Result as AnyResult};
 use async_broadcast::{Receiver as BroadcastRx, RecvError as BroadcastRxErr};
+use enumset::EnumSet;
 use futures::prelude::*;
 use glam::{Quat, Vec3A};
-use hashbrown::HashMap;
-use smol::net::UdpSocket;
+use hashbrown::{HashMap, HashSet};
+use smol::channel::Sender as ChannelTx;
 use string_cache::DefaultAtom;
 
 use avatar::AvatarState;
 use bone::Bone;
-pub use config::{AxisOutputConfig, ButtonOutputConfig, Config};
+pub use config::{AxisOutputConfig, ButtonOutputConfig, Config, ProfileConfig};
 
 use crate::config::MappingConfig;
-use crate::output::OutputEvent;
+use crate::output::{OutputEvent, Timestamped};
+use crate::press::Gesture;
+use crate::profile::{ActiveProfile, Profiles};
+use crate::reachability::{self, Destination};
+use crate::rebind::RebindingSocket;
+use crate::route::Routes;
+use crate::stats::OutputStats;
+use crate::timetag::Timetag;
 
 use self::avatar::Pose;
+use self::config::{OutputConfig, PassthroughConfig, PeriodConfig, RotationAxis};
 
 pub async fn run(
     config: Config,
-    mappings: Arc<MappingConfig>,
-    mut recv: BroadcastRx<OutputEvent>,
+    profiles: Arc<Profiles>,
+    mut recv: BroadcastRx<Timestamped<OutputEvent>>,
+    stats: Arc<OutputStats>,
 ) -> AnyResult<()> {
     log::info!("VMC task starting...");
 
-    let socket = UdpSocket::bind(config.input.address)
-        .await
-        .with_context(|| format!("Failed to bind to UDP socket {}", config.input.address))?;
+    let input_options = config.input.socket_options();
+    let socket =
+        RebindingSocket::bind_with("VMC input", config.input.address, input_options).await?;
     let mut recv_buffer = vec![0u8; 16384];
 
-    let avatar = AvatarState::new();
-    let mut devices = config.device;
-    let mut packets = PacketBuffer::new();
-    let mut tracking = TrackingData::new();
+    // Each input is received by its own task, and merged here. The first is the main input, which
+    // output is also sent from.
+    let (inbound_tx, inbound_rx) = smol::channel::bounded(16);
+    let mut masks = vec![config.input.bone_mask()];
+    let mut recv_tasks = Vec::with_capacity(1 + config.input.source.len());
+    let mut addresses = vec![config.input.address.to_string()];
 
-    let mut num_received = 0u32;
+    let avatar = AvatarState::new(&config.ik);
+    let mut profile = ActiveProfile::new(&profiles);
+    let mut devices = profile.current().vmc.device.clone();
+    let mut props = Props::default();
+    let mut unknown_positions = HashSet::new();
+    let mut packets = PacketBuffer::new(&config.output);
+    let mut tracking = TrackingData::new(&config.passthrough);
+
+    let (mut axis_outputs, mut button_outputs) = routes(&profile.current().mappings);
+
+    let mut last_update = Instant::now();
+
+    let tracking_updates = stats.counter("tracking updates");
+    let timed_updates = stats.counter("timed updates");
+    let processing_time = stats.timing("processing time");
     let mut processing_time_current = Duration::ZERO;
-    let mut processing_time_max = Duration::ZERO;
-    let mut processing_time_min = Duration::MAX;
-    let mut processing_time_total = Duration::ZERO;
 
-    let mut report_timer = config
-        .report_interval
-        .map(Duration::from_secs_f64)
+    let destination = Destination::get("VMC", config.output.address);
+    recv_tasks.push(smol::spawn(run_recv(
+        0,
+        socket.clone(),
+        inbound_tx.clone(),
+        Some(destination.clone()),
+    )));
+
+    for (name, source) in &config.input.source {
+        let socket_name = format!("VMC input {name}");
+        let source_socket =
+            RebindingSocket::bind_with(socket_name, source.address, input_options).await?;
+        recv_tasks.push(smol::spawn(run_recv(
+            masks.len(),
+            source_socket,
+            inbound_tx.clone(),
+            None,
+        )));
+        masks.push(source.bone_mask());
+        addresses.push(format!("{} ({name})", source.address));
+    }
+
+    drop(inbound_tx);
+    let _probe_task = config
+        .output
+        .probe_interval
+        .map(|interval| smol::spawn(reachability::probe(vec![destination.clone()], interval)));
+
+    let mut send_timer = config
+        .send_rate
+        .filter(|&r| r > 0.0)
+        .map(|r| Duration::from_secs_f64(r.recip()))
         .map(smol::Timer::interval)
         .unwrap_or_else(smol::Timer::never);
 
+    // If output is set to be sent again, failing to send it starts an outage instead of stopping.
+    let mut outage = config.output.retry_interval.map(|_| false);
+    let mut retry_timer = config
+        .output
+        .retry_interval
+        .map(|i| smol::Timer::interval(Duration::from_secs_f64(i)))
+        .unwrap_or_else(smol::Timer::never);
+
     log::info!(
         "VMC task started. Listening on {}, sending to {}.",
-        config.input.address,
+        addresses.join(", "),
         config.output.address
     );
 
     loop {
         futures::select_biased! {
-            recv = socket.recv_from(recv_buffer.as_mut_slice()).fuse() => {
+            inbound = inbound_rx.recv().fuse() => {
                 let recv_time = Instant::now();
-
-                let (size, addr) = match recv {
-                    Ok(r) => r,
-                    Err(e) => {
-                        log::debug!("Failed to receive VMC data: {e}");
-                        continue;
-                    },
-                };
-
-                log::debug!("Received packet of {size} byte(s) from {addr}.");
-
-                let data = &recv_buffer[..size];
-                let packet = match rosc::decoder::decode_udp(data) {
-                    Ok((_, packet)) => packet,
-                    Err(e) => {
-                        log::warn!("Failed to decode {size}-byte packet from {addr}: {e}");
-                        continue;
-                    },
+                let Ok((input, packet)) = inbound else {
+                    log::info!("VMC task stopping (receive tasks have stopped)...");
+                    break;
                 };
 
-                if tracking.update(&packet) {
-                    for device in devices.values_mut() {
-                        device.update(0.0, &tracking.pose);
-                    }
-
-                    avatar.apply_to(&devices, &mut tracking.pose);
-                    apply_device_trackers(devices.values(), &mut tracking);
-                    packets.apply_data(&tracking);
-
-                    let mut cursor = Cursor::new(&mut recv_buffer);
-                    let data_len = packets.encode(&mut rosc::encoder::WriteOutput(&mut cursor))
-                        .context("Failed to encode VMC bundle")?;
-                    let data = &recv_buffer[..data_len];
+                if tracking.update(&packet, masks[input]) {
+                    let dt = recv_time.saturating_duration_since(last_update).as_secs_f64();
+                    last_update = recv_time;
 
-                    socket.send_to(data, config.output.address).await
-                        .context("failed to send VMC bundle")?;
-
-                    let processing_time = processing_time_current + recv_time.elapsed();
-                    num_received += 1;
+                    let data_len = build_bundle(&mut devices, &props, &avatar, &mut tracking, &mut packets, &mut recv_buffer, dt)?;
+                    send_bundle(&socket, &recv_buffer[..data_len], &destination, &stats, &mut outage).await?;
 
+                    tracking_updates.increment();
+                    processing_time.record(processing_time_current + recv_time.elapsed());
                     processing_time_current = Duration::ZERO;
-                    processing_time_max = processing_time_max.max(processing_time);
-                    processing_time_min = processing_time_min.min(processing_time);
-                    processing_time_total += processing_time;
                 } else {
                     processing_time_current += recv_time.elapsed();
                 }
             },
 
-            _ = report_timer.next().fuse() => {
-                let report_interval = config.report_interval.unwrap();
-                let avg_rate = num_received as f64 / report_interval;
-                log::info!("VMC updates sent due to received tracking data: {num_received} ({avg_rate:.1}/sec)");
+            _ = send_timer.next().fuse() => {
+                let now = Instant::now();
+                let dt = now.saturating_duration_since(last_update).as_secs_f64();
+                last_update = now;
 
-                if num_received > 0 {
-                    let avg_processing = 1e6 * processing_time_total.as_secs_f64() / num_received as f64;
-                    let max_processing = 1e6 * processing_time_max.as_secs_f64();
-                    let min_processing = 1e6 * processing_time_min.as_secs_f64();
-                    log::info!("VMC processing time: avg {avg_processing:.1} us, range {min_processing:.1}-{max_processing:.1} us");
-                }
+                let data_len = build_bundle(&mut devices, &props, &avatar, &mut tracking, &mut packets, &mut recv_buffer, dt)?;
+                send_bundle(&socket, &recv_buffer[..data_len], &destination, &stats, &mut outage).await?;
+                timed_updates.increment();
+            },
 
-                num_received = 0;
-                processing_time_max = Duration::ZERO;
-                processing_time_min = Duration::MAX;
-                processing_time_total = Duration::ZERO;
+            _ = retry_timer.next().fuse() => {
+                // Every bundle has the latest values of everything, so sending one is all that's
+                // needed for them to arrive once output gets through again.
+                if outage == Some(true) {
+                    let now = Instant::now();
+                    let dt = now.saturating_duration_since(last_update).as_secs_f64();
+                    last_update = now;
+
+                    let data_len = build_bundle(&mut devices, &props, &avatar, &mut tracking, &mut packets, &mut recv_buffer, dt)?;
+                    send_bundle(&socket, &recv_buffer[..data_len], &destination, &stats, &mut outage).await?;
+                }
             },
 
-            msg = recv.recv().fuse() => match msg {
-                Ok(OutputEvent::UpdateAxis(id, value)) => {
-                    if let Some(axis) = mappings.axis.get(&id) {
-                        for (name, range) in axis.output.vmc.on_update.blendshape.iter() {
-                            let mapped_value = range[0] + value as f32 * (range[1] - range[0]);
-                            tracking.update_blendshape(name, mapped_value / 100.0);
-                        }
+            msg = recv.recv().fuse() => {
+                let msg = msg.map(|e| stats.received(e, recv.len()));
+                if let Ok(ref event) = msg {
+                    // The new profile's devices (and bones) start out at rest, until their mappings next
+                    // change.
+                    if let Some(switched) = profile.handle(event) {
+                        log::info!("VMC output switched to profile \"{}\".", switched.name);
+                        devices = switched.vmc.device.clone();
+                        tracking.bone_rotations.fill(Vec3A::ZERO);
+                        (axis_outputs, button_outputs) = routes(&switched.mappings);
+                    }
+                }
+
+                match msg {
+                    Ok(OutputEvent::UpdateAxis(id, value)) => {
+                        if let Some(vmc) = axis_outputs.get(&id) {
+                            stats.sent_mapping(&id);
 
-                        for (name, range) in axis.output.vmc.on_update.device.iter() {
-                            if let Some(device) = devices.get_mut(name) {
-                                let mapped_value = range[0] + value as f32 * (range[1] - range[0]);
-                                device.set_value(mapped_value);
+                            for (name, range) in vmc.on_update.blendshape.iter() {
+                                let mapped_value = range.map(value as f32);
+                                tracking.update_blendshape(name, mapped_value / 100.0);
                             }
-                        }
-                    }
-                },
 
-                Ok(OutputEvent::UpdateButton(id, pressed)) => {
-                    if let Some(button) = mappings.button.get(&id) {
-                        for (name, range) in button.output.vmc.on_update.blendshape.iter() {
-                            let mapped_value = if pressed { range[1] } else { range[0] };
-                            tracking.update_blendshape(name, mapped_value / 100.0);
-                        }
+                            for (name, rotation) in vmc.bone.iter() {
+                                if let Ok(bone) = Bone::from_str(name) {
+                                    tracking.set_bone_rotation(bone, rotation.axis, rotation.range.map(value as f32));
+                                }
+                            }
+
+                            for (name, range) in vmc.on_update.device.iter() {
+                                if let Some(device) = devices.get_mut(name) {
+                                    let mapped_value = range.map(value as f32);
+                                    device.set_value(mapped_value);
+                                }
+                            }
 
-                        for (name, range) in button.output.vmc.on_update.device.iter() {
-                            if let Some(device) = devices.get_mut(name) {
-                                let mapped_value = if pressed { range[1] } else { range[0] };
-                                device.set_value(mapped_value);
+                            for (name, (hand, min, max)) in vmc.on_update.device_grip.iter() {
+                                if let Some(device) = devices.get_mut(name) {
+                                    let mapped_value = min + value as f32 * (max - min);
+                                    device.set_grip(hand.limbs(), mapped_value);
+                                }
+                            }
+
+                            for (name, (hand, min, max)) in vmc.on_update.device_grip_curl.iter() {
+                                if let Some(device) = devices.get_mut(name) {
+                                    let mapped_value = min + value as f32 * (max - min);
+                                    device.set_grip_curl(hand.limbs(), mapped_value);
+                                }
+                            }
+
+                            for (name, range) in vmc.on_update.device_scale.iter() {
+                                props.scales.insert(name.clone(), range.map(value as f32));
+                            }
+
+                            for (name, &visible) in vmc.on_update.device_visible.iter() {
+                                props.set_visible(name, (value >= 0.5) == visible);
                             }
                         }
+                    },
+
+                    Ok(ref event @ (OutputEvent::UpdateButton(ref id, pressed) | OutputEvent::RestoreButton(ref id, pressed))) => {
+                        if let Some(vmc) = button_outputs.get(id) {
+                            stats.sent_mapping(id);
 
-                        let on_state = if pressed { &button.output.vmc.on_press } else { &button.output.vmc.on_release };
+                            for (name, range) in vmc.on_update.blendshape.iter() {
+                                let mapped_value = if pressed { range.range[1] } else { range.range[0] };
+                                tracking.update_blendshape(name, mapped_value / 100.0);
+                            }
 
-                        for (name, value) in on_state.blendshape.iter() {
-                            tracking.update_blendshape(name, *value);
+                            for (name, range) in vmc.on_update.device.iter() {
+                                if let Some(device) = devices.get_mut(name) {
+                                    let mapped_value = if pressed { range.range[1] } else { range.range[0] };
+                                    device.set_value(mapped_value);
+                                }
+                            }
+
+                            for (name, (hand, released, pressed_value)) in vmc.on_update.device_grip.iter() {
+                                if let Some(device) = devices.get_mut(name) {
+                                    let mapped_value = if pressed { *pressed_value } else { *released };
+                                    device.set_grip(hand.limbs(), mapped_value);
+                                }
+                            }
+
+                            for (name, (hand, released, pressed_value)) in vmc.on_update.device_grip_curl.iter() {
+                                if let Some(device) = devices.get_mut(name) {
+                                    let mapped_value = if pressed { *pressed_value } else { *released };
+                                    device.set_grip_curl(hand.limbs(), mapped_value);
+                                }
+                            }
+
+                            for (name, range) in vmc.on_update.device_scale.iter() {
+                                let mapped_value = if pressed { range.range[1] } else { range.range[0] };
+                                props.scales.insert(name.clone(), mapped_value);
+                            }
+
+                            for (name, &visible) in vmc.on_update.device_visible.iter() {
+                                props.set_visible(name, pressed == visible);
+                            }
+
+                            if let OutputEvent::RestoreButton(..) = event {
+                                continue;
+                            }
+
+                            let on_state = if pressed { &vmc.on_press } else { &vmc.on_release };
+                            apply_state(on_state, &mut tracking, &mut devices, &mut props, &mut unknown_positions);
                         }
+                    },
+
+                    Ok(OutputEvent::ButtonGesture(ref id, gesture)) => {
+                        if let Some(vmc) = button_outputs.get(id) {
+                            let on_gesture = match gesture {
+                                Gesture::LongPress => &vmc.on_long_press,
+                                Gesture::DoublePress => &vmc.on_double_press,
+                            };
 
-                        for (name, value) in on_state.device.iter() {
-                            if let Some(device) = devices.get_mut(name) {
-                                device.set_value(*value);
+                            if !on_gesture.is_empty() {
+                                stats.sent_mapping(id);
+                                apply_state(on_gesture, &mut tracking, &mut devices, &mut props, &mut unknown_positions);
                             }
                         }
-                    }
-                },
+                    },
 
-                Ok(_) => {},
+                    Ok(_) => {},
 
-                Err(BroadcastRxErr::Overflowed(n)) => {
-                    log::warn!("VMC sender missed {n} update(s)!");
-                },
+                    Err(BroadcastRxErr::Overflowed(n)) => {
+                        log::warn!("VMC sender missed {n} update(s)!");
+                        stats.missed(n);
+                    },
 
-                Err(BroadcastRxErr::Closed) => {
-                    log::info!("VMC task stopping (no inputs remaining)...");
-                    break;
-                },
+                    Err(BroadcastRxErr::Closed) => {
+                        log::info!("VMC task stopping (no inputs remaining)...");
+                        break;
+                    },
+                }
             },
         };
     }
@@ -198,14 +313,147 @@ pub async fn run(
     Ok(())
 }
 
+/// Finds what each mapping is set to do to VMC output, for every mapping that does anything.
+fn routes(mappings: &MappingConfig) -> (Routes<&AxisOutputConfig>, Routes<&ButtonOutputConfig>) {
+    let axes = Routes::axes(mappings, |_, a| {
+        let vmc = &a.output.vmc;
+        (!vmc.is_empty()).then_some(vmc)
+    });
+    let buttons = Routes::buttons(mappings, |_, b| {
+        let vmc = &b.output.vmc;
+        (!vmc.is_empty()).then_some(vmc)
+    });
+
+    (axes, buttons)
+}
+
+/// Applies the values that a button sets when it's pressed, released, or given a gesture.
+fn apply_state(
+    on_state: &config::StateConfig,
+    tracking: &mut TrackingData,
+    devices: &mut HashMap<DefaultAtom, device::Device>,
+    props: &mut Props,
+    unknown_positions: &mut HashSet<(DefaultAtom, String)>,
+) {
+    for (name, value) in on_state.blendshape.iter() {
+        tracking.update_blendshape(name, *value);
+    }
+
+    for (name, value) in on_state.device.iter() {
+        if let Some(device) = devices.get_mut(name) {
+            device.set_value(*value);
+        }
+    }
+
+    for (name, (hand, value)) in on_state.device_grip.iter() {
+        if let Some(device) = devices.get_mut(name) {
+            device.set_grip(hand.limbs(), *value);
+        }
+    }
+
+    for (name, (hand, value)) in on_state.device_grip_curl.iter() {
+        if let Some(device) = devices.get_mut(name) {
+            device.set_grip_curl(hand.limbs(), *value);
+        }
+    }
+
+    for (name, value) in on_state.device_scale.iter() {
+        props.scales.insert(name.clone(), *value);
+    }
+
+    for (name, &visible) in on_state.device_visible.iter() {
+        props.set_visible(name, visible);
+    }
+
+    for (name, position) in on_state.device_position.iter() {
+        if let Some(device) = devices.get_mut(name) {
+            if !device.set_named_value(position)
+                && unknown_positions.insert((name.clone(), position.clone()))
+            {
+                log::warn!(
+                    "VMC device {name} has no position named {position:?}, so it will be ignored."
+                );
+            }
+        }
+    }
+}
+
+/// Receives VMC data on one input, passing it on with the input's index.
+async fn run_recv(
+    input: usize,
+    socket: Arc<RebindingSocket>,
+    inbound_tx: ChannelTx<(usize, rosc::OscPacket)>,
+    icmp_destination: Option<Arc<Destination>>,
+) {
+    let mut buffer = vec![0u8; 16384];
+
+    loop {
+        let (size, addr) = match socket.recv_from(&mut buffer).await {
+            Ok(r) => r,
+            Err(e) => {
+                // This is where errors that come back from the output address are reported, on
+                // some OSes.
+                if let Some(destination) = icmp_destination
+                    .as_ref()
+                    .filter(|_| reachability::is_icmp_error(&e))
+                {
+                    destination.failed(&e);
+                }
+
+                log::debug!("Failed to receive VMC data: {e}");
+                continue;
+            }
+        };
+
+        log::debug!("Received packet of {size} byte(s) from {addr}.");
+
+        let packet = match rosc::decoder::decode_udp(&buffer[..size]) {
+            Ok((_, packet)) => packet,
+            Err(e) => {
+                log::warn!("Failed to decode {size}-byte packet from {addr}: {e}");
+                continue;
+            }
+        };
+
+        if inbound_tx.send((input, packet)).await.is_err() {
+            break;
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct TrackingData {
     blendshapes: HashMap<DefaultAtom, (f32, u32)>,
     devices: HashMap<(Device, DefaultAtom), (TrackingPoint, usize)>,
     pose: Pose,
+    /// Angles about each bone's own X, Y and Z axes, in radians, that mappings rotate it by on top
+    /// of its tracked rotation. These are only applied as bones are sent, so they don't build up.
+    bone_rotations: Vec<Vec3A>,
+
+    /// The indices of devices that have been removed since the last bundle, in order, so that their
+    /// packets can be removed as well.
+    removed_devices: Vec<usize>,
+
+    /// Messages with addresses that aren't otherwise handled, to be forwarded as-is.
+    passthrough: Vec<rosc::OscPacket>,
+    max_passthrough: usize,
 
     time: f32,
     tracking: bool,
+
+    /// The calibration state and mode, and the send periods, if the source sends them.
+    calibration: Option<[i32; 2]>,
+    period: Option<[i32; 6]>,
+}
+
+/// How the props that follow devices' trackers are shown, as set by mappings.
+#[derive(Debug, Default)]
+struct Props {
+    /// Devices whose trackers are left out of bundles entirely, since receivers keep the last
+    /// transform they were sent.
+    hidden: HashSet<DefaultAtom>,
+    /// The scale of each device's prop, if it's been set. Otherwise, it's 1.
+    scales: HashMap<DefaultAtom, f32>,
 }
 
 #[derive(Clone, Copy, Debug, Default)]
@@ -219,8 +467,16 @@ struct PacketBuffer {
     pre_packets: Vec<rosc::OscPacket>,
     bone_packets: Vec<rosc::OscPacket>,
     device_packets: Vec<rosc::OscPacket>,
+    scale_packets: Vec<rosc::OscPacket>,
     blendshape_packets: Vec<rosc::OscPacket>,
+    passthrough_packets: Vec<rosc::OscPacket>,
     post_packets: Vec<rosc::OscPacket>,
+
+    /// What to send when the source doesn't send its own.
+    calibration: Option<[i32; 2]>,
+    period: Option<[i32; 6]>,
+
+    timetag: Timetag,
 }
 
 #[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
@@ -231,7 +487,7 @@ enum Device {
 }
 
 impl PacketBuffer {
-    fn new() -> PacketBuffer {
+    fn new(config: &OutputConfig) -> PacketBuffer {
         PacketBuffer {
             pre_packets: vec![rosc::OscPacket::Message(rosc::OscMessage {
                 addr: String::from("/VMC/Ext/Root/Pos"),
@@ -266,33 +522,47 @@ impl PacketBuffer {
                 .collect(),
 
             device_packets: vec![],
+            scale_packets: vec![],
 
             blendshape_packets: vec![rosc::OscPacket::Message(rosc::OscMessage {
                 addr: String::from("/VMC/Ext/Blend/Apply"),
                 args: vec![],
             })],
 
+            passthrough_packets: vec![],
+
             post_packets: vec![
                 rosc::OscPacket::Message(rosc::OscMessage {
                     addr: String::from("/VMC/Ext/OK"),
-                    args: vec![rosc::OscType::Int(0)],
+                    args: Vec::with_capacity(3),
                 }),
                 rosc::OscPacket::Message(rosc::OscMessage {
                     addr: String::from("/VMC/Ext/T"),
                     args: vec![rosc::OscType::Float(0.0)],
                 }),
             ],
+
+            calibration: config.calibration.map(|c| [c.state as i32, c.mode as i32]),
+            period: config.period.map(PeriodConfig::to_args),
+
+            timetag: config.timetag,
         }
     }
 
-    fn apply_data(&mut self, tracking: &TrackingData) {
+    fn apply_data(&mut self, tracking: &mut TrackingData) {
         update_point(tracking.pose.root_transform(), &mut self.pre_packets[0]);
 
         for (bone, packet) in std::iter::zip(Bone::iter(), &mut self.bone_packets) {
-            update_point(tracking.pose.local_transform(bone), packet);
+            update_point(tracking.bone_transform(bone), packet);
         }
 
         {
+            for index in tracking.removed_devices.drain(..) {
+                if index < self.device_packets.len() {
+                    self.device_packets.remove(index);
+                }
+            }
+
             let num_needed = tracking.devices.len() - self.device_packets.len();
             self.device_packets.reserve(num_needed);
 
@@ -362,11 +632,55 @@ impl PacketBuffer {
             self.blendshape_packets.push(apply_packet);
         }
 
+        // What the source sends is passed on in place of what's configured.
         let rosc::OscPacket::Message(message) = &mut self.post_packets[0] else { unreachable!() };
-        message.args[0] = rosc::OscType::Int(if tracking.tracking { 1 } else { 0 });
+        message.args.clear();
+        message
+            .args
+            .push(rosc::OscType::Int(if tracking.tracking { 1 } else { 0 }));
+        if let Some(calibration) = tracking.calibration.or(self.calibration) {
+            message.args.extend(calibration.map(rosc::OscType::Int));
+        }
 
         let rosc::OscPacket::Message(message) = &mut self.post_packets[1] else { unreachable!() };
         message.args[0] = rosc::OscType::Float(tracking.time);
+
+        if let Some(period) = tracking.period.or(self.period) {
+            if self.post_packets.len() < 3 {
+                self.post_packets
+                    .push(rosc::OscPacket::Message(rosc::OscMessage {
+                        addr: String::from("/VMC/Ext/Set/Period"),
+                        args: Vec::new(),
+                    }));
+            }
+
+            let rosc::OscPacket::Message(message) = &mut self.post_packets[2] else { unreachable!() };
+            message.args.clear();
+            message.args.extend(period.map(rosc::OscType::Int));
+        }
+
+        // Unrecognized messages are only forwarded once, with the bundle that followed them.
+        self.passthrough_packets.clear();
+        std::mem::swap(&mut self.passthrough_packets, &mut tracking.passthrough);
+    }
+
+    /// Sets the scale messages for each device whose prop's scale is sent.
+    fn apply_scales(
+        &mut self,
+        devices: &HashMap<DefaultAtom, device::Device>,
+        scales: &HashMap<DefaultAtom, f32>,
+    ) {
+        self.scale_packets.clear();
+        for (name, device) in devices {
+            if let Some(address) = device.scale_address() {
+                let scale = scales.get(name).copied().unwrap_or(1.0);
+                self.scale_packets
+                    .push(rosc::OscPacket::Message(rosc::OscMessage {
+                        addr: address.to_string(),
+                        args: vec![rosc::OscType::Float(scale)],
+                    }));
+            }
+        }
     }
 
     fn encode<O: rosc::encoder::Output>(&mut self, out: &mut O) -> AnyResult<usize>
@@ -377,7 +691,9 @@ impl PacketBuffer {
         buffer.reserve(
             self.bone_packets.len()
                 + self.device_packets.len()
+                + self.scale_packets.len()
                 + self.blendshape_packets.len()
+                + self.passthrough_packets.len()
                 + self.post_packets.len(),
         );
 
@@ -387,14 +703,20 @@ impl PacketBuffer {
         let device_start = buffer.len();
         buffer.append(&mut self.device_packets);
 
+        let scale_start = buffer.len();
+        buffer.append(&mut self.scale_packets);
+
         let blendshape_start = buffer.len();
         buffer.append(&mut self.blendshape_packets);
 
+        let passthrough_start = buffer.len();
+        buffer.append(&mut self.passthrough_packets);
+
         let post_start = buffer.len();
         buffer.append(&mut self.post_packets);
 
         let packet = rosc::OscPacket::Bundle(rosc::OscBundle {
-            timetag: (0, 0).into(),
+            timetag: self.timetag.at(SystemTime::now()).into(),
             content: buffer,
         });
 
@@ -405,8 +727,11 @@ impl PacketBuffer {
         let mut buffer = bundle.content;
 
         self.post_packets.extend(buffer.drain(post_start..));
+        self.passthrough_packets
+            .extend(buffer.drain(passthrough_start..));
         self.blendshape_packets
             .extend(buffer.drain(blendshape_start..));
+        self.scale_packets.extend(buffer.drain(scale_start..));
         self.device_packets.extend(buffer.drain(device_start..));
         self.bone_packets.extend(buffer.drain(bone_start..));
         self.pre_packets = buffer;
@@ -423,24 +748,61 @@ impl PacketBuffer {
 }
 
 impl TrackingData {
-    fn new() -> TrackingData {
+    fn new(passthrough: &PassthroughConfig) -> TrackingData {
         TrackingData {
             blendshapes: HashMap::new(),
             devices: HashMap::new(),
             pose: Pose::new(),
+            bone_rotations: vec![Vec3A::ZERO; Bone::NUM],
+            removed_devices: Vec::new(),
+
+            passthrough: Vec::new(),
+            max_passthrough: if passthrough.enabled {
+                passthrough.max_messages
+            } else {
+                0
+            },
 
             time: -1.0,
             tracking: false,
+
+            calibration: None,
+            period: None,
+        }
+    }
+
+    fn set_bone_rotation(&mut self, bone: Bone, axis: RotationAxis, degrees: f32) {
+        let angles = &mut self.bone_rotations[bone as u8 as usize];
+        match axis {
+            RotationAxis::X => angles.x = degrees.to_radians(),
+            RotationAxis::Y => angles.y = degrees.to_radians(),
+            RotationAxis::Z => angles.z = degrees.to_radians(),
         }
     }
 
-    fn update(&mut self, packet: &rosc::OscPacket) -> bool {
+    /// A bone's tracked transform, with the rotation that mappings have given it.
+    fn bone_transform(&self, bone: Bone) -> (Vec3A, Quat) {
+        let (pos, rot) = self.pose.local_transform(bone);
+        let angles = self.bone_rotations[bone as u8 as usize];
+        if angles == Vec3A::ZERO {
+            return (pos, rot);
+        }
+
+        let offset = Quat::from_rotation_x(angles.x)
+            * Quat::from_rotation_y(angles.y)
+            * Quat::from_rotation_z(angles.z);
+        (pos, rot * offset)
+    }
+
+    /// Takes in a packet from an input, keeping only the bones in its mask. The root is only taken
+    /// from inputs that keep every bone.
+    fn update(&mut self, packet: &rosc::OscPacket, bones: EnumSet<Bone>) -> bool {
         let mut flush = false;
         let result: AnyResult<()> = (|| {
             match *packet {
                 rosc::OscPacket::Bundle(ref bundle) => {
                     for child in &bundle.content {
-                        flush |= self.update(child);
+                        flush |= self.update(child, bones);
                     }
                 }
 
@@ -454,13 +816,17 @@ impl TrackingData {
                                 "Unexpected name of root (expected \"root\", got \"{}\").",
                                 name
                             );
-                            self.pose.set_root_transform(point.pos, point.rot);
+                            if bones == EnumSet::all() {
+                                self.pose.set_root_transform(point.pos, point.rot);
+                            }
                         }
 
                         "/VMC/Ext/Bone/Pos" => {
                             let (name, point) = message.arg_tracking()?;
                             let bone = Bone::from_str(name).context("Failed to parse bone")?;
-                            self.pose.set_local_transform(bone, point.pos, point.rot);
+                            if bones.contains(bone) {
+                                self.pose.set_local_transform(bone, point.pos, point.rot);
+                            }
                         }
 
                         "/VMC/Ext/Con/Pos" => {
@@ -494,15 +860,32 @@ impl TrackingData {
 
                         "/VMC/Ext/OK" => {
                             ensure!(
-                                message.args.len() == 1,
-                                "Incorrect number of arguments to {} (expected 1, got {}).",
+                                message.args.len() == 1 || message.args.len() == 3,
+                                "Incorrect number of arguments to {} (expected 1 or 3, got {}).",
                                 message.addr,
                                 message.args.len()
                             );
                             self.tracking = 1 == message.arg_i32(0)?;
+                            if message.args.len() == 3 {
+                                self.calibration = Some([message.arg_i32(1)?, message.arg_i32(2)?]);
+                            }
                             flush = true;
                         }
 
+                        "/VMC/Ext/Set/Period" => {
+                            ensure!(
+                                message.args.len() == 6,
+                                "Incorrect number of arguments to {} (expected 6, got {}).",
+                                message.addr,
+                                message.args.len()
+                            );
+                            let mut period = [0; 6];
+                            for (i, p) in period.iter_mut().enumerate() {
+                                *p = message.arg_i32(i)?;
+                            }
+                            self.period = Some(period);
+                        }
+
                         "/VMC/Ext/T" => {
                             ensure!(
                                 message.args.len() == 1,
@@ -513,6 +896,10 @@ impl TrackingData {
                             self.time = message.arg_f32(0)?;
                         }
 
+                        _ if self.passthrough.len() < self.max_passthrough => {
+                            self.passthrough.push(packet.clone());
+                        }
+
                         _ => bail!("Unrecognized VMC address: {}", message.addr),
                     }
                 }
@@ -556,6 +943,31 @@ impl TrackingData {
             .and_modify(|(v, _)| *v = *point)
             .or_insert((*point, num_devices));
     }
+
+    /// Removes a device, so that nothing is sent for it until it's updated again.
+    fn remove_device(&mut self, device: Device, name: DefaultAtom) {
+        let Some((_, index)) = self.devices.remove(&(device, name)) else {
+            return;
+        };
+
+        for (_, i) in self.devices.values_mut() {
+            if *i > index {
+                *i -= 1;
+            }
+        }
+
+        self.removed_devices.push(index);
+    }
+}
+
+impl Props {
+    fn set_visible(&mut self, device: &DefaultAtom, visible: bool) {
+        if visible {
+            self.hidden.remove(device);
+        } else {
+            self.hidden.insert(device.clone());
+        }
+    }
 }
 
 fn update_point((pos, rot): (Vec3A, Quat), packet: &mut rosc::OscPacket) {
@@ -653,13 +1065,84 @@ impl OscMessageExt for rosc::OscMessage {
     }
 }
 
-fn apply_device_trackers<'a>(
-    devices: impl IntoIterator<Item = &'a device::Device>,
+/// Sends a bundle to the destination. If `outage` is set, a failure only starts an outage (during
+/// which bundles are sent again periodically) and `outage` is updated, rather than it being returned.
+async fn send_bundle(
+    socket: &RebindingSocket,
+    data: &[u8],
+    destination: &Destination,
+    stats: &OutputStats,
+    outage: &mut Option<bool>,
+) -> AnyResult<()> {
+    let result = socket.send_to(data, destination.addr()).await;
+    match result {
+        Ok(_) => {
+            stats.sent();
+            destination.sent();
+        }
+
+        Err(ref e) => {
+            stats.send_failed();
+            destination.failed(e);
+        }
+    }
+
+    let Some(outage) = outage else {
+        return result.map(|_| ()).context("failed to send VMC bundle");
+    };
+
+    // Data can be sent without an error even if it isn't arriving.
+    let failing = result.is_err() || !destination.is_reachable();
+    if failing && !*outage {
+        log::warn!(
+            "VMC output is not getting through. The latest data will be sent again until it does."
+        );
+    } else if !failing && *outage {
+        log::info!("VMC output is getting through again.");
+    }
+
+    *outage = failing;
+    Ok(())
+}
+
+/// Poses the avatar using the latest tracking data and device states, and encodes the resulting
+/// bundle into `buffer`, returning its length.
+fn build_bundle(
+    devices: &mut HashMap<DefaultAtom, device::Device>,
+    props: &Props,
+    avatar: &AvatarState,
+    tracking: &mut TrackingData,
+    packets: &mut PacketBuffer,
+    buffer: &mut Vec<u8>,
+    dt: f64,
+) -> AnyResult<usize> {
+    for device in devices.values_mut() {
+        device.update(dt, &tracking.pose);
+    }
+
+    avatar.apply_to(devices, &mut tracking.pose);
+    apply_device_trackers(devices, &props.hidden, tracking);
+    packets.apply_data(tracking);
+    packets.apply_scales(devices, &props.scales);
+
+    let mut cursor = Cursor::new(buffer);
+    packets
+        .encode(&mut rosc::encoder::WriteOutput(&mut cursor))
+        .context("Failed to encode VMC bundle")
+}
+
+fn apply_device_trackers(
+    devices: &HashMap<DefaultAtom, device::Device>,
+    hidden: &HashSet<DefaultAtom>,
     tracking: &mut TrackingData,
 ) {
-    for device in devices {
-        device.trackers(|name, pos, rot| {
-            tracking.update_device(Device::Tracker, name, &TrackingPoint { pos, rot });
-        });
+    for (name, device) in devices {
+        if hidden.contains(name) {
+            device.trackers(|name, _, _| tracking.remove_device(Device::Tracker, name));
+        } else {
+            device.trackers(|name, pos, rot| {
+                tracking.update_device(Device::Tracker, name, &TrackingPoint { pos, rot });
+            });
+        }
     }
 }
@@ -8,26 +8,44 @@ use string_cache::DefaultAtom;
 use crate::vmc::device::ForwardPose;
 
 use super::bone::{Bone, Limb};
+use super::config::{IkConfig, LimbConfig};
 use super::device::Device;
 use super::ik::{solve_tri, AngularConstraint, Chain, Link, TriSettings};
 
-pub(super) struct AvatarState;
+pub(super) struct AvatarState {
+    limbs: [LimbSettings; Limb::NUM],
+}
+
+/// How a limb is posed by IK, including any overrides from the configuration.
+struct LimbSettings {
+    elbow_axis: Vec3A,
+    constraints: Vec<AngularConstraint>,
+}
 
 impl AvatarState {
-    pub fn new() -> AvatarState {
-        AvatarState
+    pub fn new(config: &IkConfig) -> AvatarState {
+        AvatarState {
+            limbs: std::array::from_fn(|i| {
+                let limb = Limb::try_from(i as u8).unwrap();
+                LimbSettings::new(limb, config.limb(limb))
+            }),
+        }
     }
 
     pub fn apply_to(&self, devices: &HashMap<DefaultAtom, Device>, pose: &mut Pose) {
         let mut limbs = [(Vec4::ZERO, Quat::IDENTITY); Limb::NUM];
         let mut touched_limbs = EnumSet::empty();
 
+        // Devices with a higher priority get the first claim on limbs and bones.
+        let mut devices = devices.values().collect::<Vec<_>>();
+        devices.sort_by_key(|d| std::cmp::Reverse(d.priority()));
+
         for limb in EnumSet::<Limb>::all() {
             let (pos, rot) = pose.global_transform(limb.end_bone());
             limbs[limb as u8 as usize] = (Vec4::from((pos, 1.0)), rot);
         }
 
-        for device in devices.values() {
+        for device in &devices {
             device.pose_inverse(pose, |limb, weight, new_pos, new_rot| {
                 let data = &mut limbs[limb as u8 as usize];
 
@@ -46,15 +64,16 @@ impl AvatarState {
 
         for limb in touched_limbs {
             let data = &limbs[limb as u8 as usize];
+            let settings = &self.limbs[limb as u8 as usize];
             let _ = solve_tri(
                 &TriSettings {
-                    elbow_axis: limb.elbow_axis(),
+                    elbow_axis: settings.elbow_axis,
                     max_iterations: 20,
                     rot_tolerance: 0.001,
                 },
                 &mut TrackingChain {
                     bones: limb.bones(),
-                    constraints: limb.angular_constraints(),
+                    constraints: &settings.constraints,
                     data: pose,
                 },
                 data.0.into(),
@@ -64,7 +83,7 @@ impl AvatarState {
 
         let mut touched_bones = EnumSet::empty();
 
-        for device in devices.values() {
+        for device in &devices {
             device.pose_forward(|bone, weight, target| {
                 debug_assert!((0.0..=1.0).contains(&weight));
                 if weight == 0.0 {
@@ -228,6 +247,52 @@ impl PoseBone {
     }
 }
 
+impl LimbSettings {
+    fn new(limb: Limb, config: &LimbConfig) -> LimbSettings {
+        let elbow_axis = config
+            .elbow_axis
+            .map(|a| Vec3A::from(a).normalize())
+            .unwrap_or(limb.elbow_axis());
+
+        let constraints = limb
+            .angular_constraints()
+            .iter()
+            .map(|&constraint| match constraint {
+                AngularConstraint::None => constraint,
+
+                // The elbow bends around its hinge, so the two must agree.
+                AngularConstraint::Hinge(_, range) => match config.elbow_range {
+                    Some(range) => AngularConstraint::Hinge(elbow_axis.into(), range).to_radians(),
+                    None => AngularConstraint::Hinge(elbow_axis.into(), range),
+                },
+
+                AngularConstraint::Euler(euler, yaw, roll, pitch) => {
+                    if config.arm_yaw.is_none()
+                        && config.arm_roll.is_none()
+                        && config.arm_pitch.is_none()
+                    {
+                        return constraint;
+                    }
+
+                    let degrees = |(min, max): (f32, f32)| (min.to_degrees(), max.to_degrees());
+                    AngularConstraint::Euler(
+                        euler,
+                        config.arm_yaw.unwrap_or(degrees(yaw)),
+                        config.arm_roll.unwrap_or(degrees(roll)),
+                        config.arm_pitch.unwrap_or(degrees(pitch)),
+                    )
+                    .to_radians()
+                }
+            })
+            .collect();
+
+        LimbSettings {
+            elbow_axis,
+            constraints,
+        }
+    }
+}
+
 struct TrackingChain<'d> {
     bones: &'d [Bone],
     constraints: &'d [AngularConstraint],
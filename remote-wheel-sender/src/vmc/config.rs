@@ -1,72 +1,755 @@
 use std::net::{Ipv4Addr, SocketAddr};
+use std::str::FromStr;
+use std::time::Duration;
 
+use enumset::EnumSet;
 use hashbrown::HashMap;
 use linear_map::LinearMap;
-use serde::Deserialize;
+use schemars::JsonSchema;
+use serde::de::value::{MapAccessDeserializer, SeqAccessDeserializer};
+use serde::de::{Deserializer, Error as _, MapAccess, SeqAccess};
+use serde::{Deserialize, Serialize, Serializer};
 use string_cache::DefaultAtom;
 
+use super::bone::{Bone, Limb};
 use super::device::Device;
+use super::math::FloatExt;
+use crate::check::Report;
+use crate::config::MappingConfig;
+use crate::rebind::SocketOptions;
+use crate::timetag::Timetag;
 
-#[derive(Debug, Default, Deserialize)]
+#[derive(Clone, Debug, Default, Deserialize, JsonSchema, Serialize)]
 #[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
 pub struct Config {
     enabled: bool,
-    pub(super) report_interval: Option<f64>,
+
+    /// No longer used, since VMC statistics are part of the statistics report. It's still accepted
+    /// so that older configurations load, with a warning.
+    #[serde(deserialize_with = "crate::units::optional_seconds", skip_serializing)]
+    #[schemars(with = "Option<crate::units::Seconds>")]
+    report_interval: Option<f64>,
+    pub(super) send_rate: Option<f64>,
 
     pub(super) input: InputConfig,
     pub(super) output: OutputConfig,
+    pub(super) passthrough: PassthroughConfig,
+    pub(super) ik: IkConfig,
 
+    #[schemars(with = "std::collections::HashMap<String, Device>")]
     pub(super) device: HashMap<DefaultAtom, Device>,
 }
 
-#[derive(Debug, Deserialize)]
+/// The VMC settings that a profile can change, for the configuration schema.
+#[allow(unused)]
+#[derive(Default, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct ProfileConfig {
+    #[serde(default)]
+    device: std::collections::HashMap<String, Device>,
+}
+
+#[derive(Clone, Debug, Deserialize, JsonSchema, Serialize)]
 #[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
 pub struct InputConfig {
     pub(super) address: SocketAddr,
+
+    /// If set, only these bones are taken from this input.
+    pub(super) bones: Option<Vec<String>>,
+
+    /// Further inputs, each on its own address, whose tracking is merged with this one's.
+    #[schemars(with = "std::collections::HashMap<String, SourceConfig>")]
+    pub(super) source: HashMap<DefaultAtom, SourceConfig>,
+
+    /// Applies to this input and each further one.
+    #[serde(deserialize_with = "crate::units::optional_seconds")]
+    #[schemars(schema_with = "crate::units::optional_seconds_schema")]
+    pub(super) stall_timeout: Option<f64>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Clone, Debug, Deserialize, JsonSchema, Serialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct SourceConfig {
+    pub(super) address: SocketAddr,
+    #[serde(default)]
+    pub(super) bones: Option<Vec<String>>,
+}
+
+#[derive(Clone, Debug, Deserialize, JsonSchema, Serialize)]
 #[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
 pub struct OutputConfig {
     pub(super) address: SocketAddr,
+
+    /// If set, /VMC/Ext/OK is sent in its longer form, with the calibration state and mode.
+    pub(super) calibration: Option<CalibrationConfig>,
+
+    /// If set, /VMC/Ext/Set/Period is sent with each bundle.
+    pub(super) period: Option<PeriodConfig>,
+
+    pub(super) timetag: Timetag,
+
+    #[serde(deserialize_with = "crate::units::optional_seconds")]
+    #[schemars(schema_with = "crate::units::optional_seconds_schema")]
+    pub(super) probe_interval: Option<f64>,
+
+    #[serde(deserialize_with = "crate::units::optional_seconds")]
+    #[schemars(schema_with = "crate::units::optional_seconds_schema")]
+    pub(super) retry_interval: Option<f64>,
 }
 
-#[derive(Debug, Default, Deserialize)]
+#[derive(Clone, Copy, Debug, Default, Deserialize, JsonSchema, Serialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct CalibrationConfig {
+    pub(super) state: CalibrationState,
+    pub(super) mode: CalibrationMode,
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, JsonSchema, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum CalibrationState {
+    Uncalibrated = 0,
+    WaitingForCalibration = 1,
+    Calibrating = 2,
+    #[default]
+    Calibrated = 3,
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, JsonSchema, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum CalibrationMode {
+    #[default]
+    Normal = 0,
+    MrHand = 1,
+    MrFloor = 2,
+}
+
+/// How many frames apart each kind of data is sent, as announced by /VMC/Ext/Set/Period.
+#[derive(Clone, Copy, Debug, Deserialize, JsonSchema, Serialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct PeriodConfig {
+    pub(super) status: i32,
+    pub(super) root: i32,
+    pub(super) bone: i32,
+    pub(super) blendshape: i32,
+    pub(super) camera: i32,
+    pub(super) devices: i32,
+}
+
+#[derive(Clone, Debug, Deserialize, JsonSchema, Serialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct PassthroughConfig {
+    pub(super) enabled: bool,
+    pub(super) max_messages: usize,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, JsonSchema, Serialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct IkConfig {
+    pub(super) left_hand: LimbConfig,
+    pub(super) right_hand: LimbConfig,
+}
+
+/// Overrides for how a limb is posed by IK. Any that aren't set use the defaults, which suit a
+/// typical VRM avatar. Angles are in degrees.
+#[derive(Clone, Debug, Default, Deserialize, JsonSchema, Serialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct LimbConfig {
+    pub(super) elbow_axis: Option<[f32; 3]>,
+    pub(super) elbow_range: Option<(f32, f32)>,
+    pub(super) arm_yaw: Option<(f32, f32)>,
+    pub(super) arm_roll: Option<(f32, f32)>,
+    pub(super) arm_pitch: Option<(f32, f32)>,
+}
+
+#[derive(Debug, Default, Deserialize, JsonSchema, Serialize)]
 #[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
 pub struct AxisOutputConfig {
-    pub(super) on_update: EventConfig<[f32; 2]>,
+    pub(super) on_update: UpdateConfig,
+
+    /// Bones to rotate about one of their own axes, on top of their tracked rotation, by name.
+    #[schemars(with = "std::collections::HashMap<String, BoneRotationConfig>")]
+    pub(super) bone: LinearMap<String, BoneRotationConfig>,
 }
 
-#[derive(Debug, Default, Deserialize)]
+/// A rotation of a bone, by an angle that the axis' value is mapped to.
+#[derive(Clone, Copy, Debug, Deserialize, JsonSchema, Serialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct BoneRotationConfig {
+    pub(super) axis: RotationAxis,
+    /// In degrees.
+    pub(super) range: ValueRange,
+}
+
+/// One of a bone's own axes.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, JsonSchema, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum RotationAxis {
+    X,
+    Y,
+    Z,
+}
+
+#[derive(Debug, Default, Deserialize, JsonSchema, Serialize)]
 #[serde(deny_unknown_fields, rename_all = "kebab-case")]
 pub struct ButtonOutputConfig {
     #[serde(default)]
-    pub(super) on_update: EventConfig<[f32; 2]>,
+    pub(super) on_update: UpdateConfig,
+
+    #[serde(default)]
+    pub(super) on_press: StateConfig,
+
+    #[serde(default)]
+    pub(super) on_release: StateConfig,
 
+    /// Applied when the button is held for its long-press-ms.
     #[serde(default)]
-    pub(super) on_press: EventConfig<f32>,
+    pub(super) on_long_press: StateConfig,
 
+    /// Applied when the button is pressed again within its double-press-ms.
     #[serde(default)]
-    pub(super) on_release: EventConfig<f32>,
+    pub(super) on_double_press: StateConfig,
+}
+
+impl AxisOutputConfig {
+    pub(super) fn is_empty(&self) -> bool {
+        self.on_update.is_empty() && self.bone.is_empty()
+    }
+}
+
+impl ButtonOutputConfig {
+    pub(super) fn is_empty(&self) -> bool {
+        self.on_update.is_empty() && self.states().all(|(_, state)| state.is_empty())
+    }
+
+    /// Each of the sets of values that are applied by an event, along with its key.
+    fn states(&self) -> impl Iterator<Item = (&'static str, &StateConfig)> {
+        [
+            ("on-press", &self.on_press),
+            ("on-release", &self.on_release),
+            ("on-long-press", &self.on_long_press),
+            ("on-double-press", &self.on_double_press),
+        ]
+        .into_iter()
+    }
 }
 
-#[derive(Debug, Default, Deserialize)]
+/// Values which are mapped from the range of an axis or the state of a button.
+pub type UpdateConfig = EventConfig<ValueRange, (GripHand, f32, f32), Unavailable>;
+
+/// Values which are set when a button is pressed or released.
+pub type StateConfig = EventConfig<f32, (GripHand, f32), String>;
+
+#[derive(Debug, Deserialize, JsonSchema, Serialize)]
 #[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
-pub struct EventConfig<T> {
+pub struct EventConfig<T, G, P> {
+    #[schemars(with = "std::collections::HashMap<String, T>")]
     pub(super) blendshape: LinearMap<DefaultAtom, T>,
+    #[schemars(with = "std::collections::HashMap<String, T>")]
     pub(super) device: LinearMap<DefaultAtom, T>,
+    #[schemars(with = "std::collections::HashMap<String, G>")]
+    pub(super) device_grip: LinearMap<DefaultAtom, G>,
+    #[schemars(with = "std::collections::HashMap<String, G>")]
+    pub(super) device_grip_curl: LinearMap<DefaultAtom, G>,
+    #[schemars(with = "std::collections::HashMap<String, P>")]
+    pub(super) device_position: LinearMap<DefaultAtom, P>,
+    #[schemars(with = "std::collections::HashMap<String, T>")]
+    pub(super) device_scale: LinearMap<DefaultAtom, T>,
+    #[schemars(with = "std::collections::HashMap<String, bool>")]
+    pub(super) device_visible: LinearMap<DefaultAtom, bool>,
+}
+
+// Derived, this would needlessly require `T: Default` and so on.
+impl<T, G, P> Default for EventConfig<T, G, P> {
+    fn default() -> Self {
+        EventConfig {
+            blendshape: LinearMap::new(),
+            device: LinearMap::new(),
+            device_grip: LinearMap::new(),
+            device_grip_curl: LinearMap::new(),
+            device_position: LinearMap::new(),
+            device_scale: LinearMap::new(),
+            device_visible: LinearMap::new(),
+        }
+    }
+}
+
+impl<T, G, P> EventConfig<T, G, P> {
+    pub(super) fn is_empty(&self) -> bool {
+        self.blendshape.is_empty()
+            && self.device.is_empty()
+            && self.device_grip.is_empty()
+            && self.device_grip_curl.is_empty()
+            && self.device_position.is_empty()
+            && self.device_scale.is_empty()
+            && self.device_visible.is_empty()
+    }
+
+    /// The devices whose props are changed, along with the key that changes them.
+    fn prop_devices(&self) -> impl Iterator<Item = (&'static str, &DefaultAtom)> {
+        let scales = self.device_scale.keys().map(|n| ("device-scale", n));
+        let visible = self.device_visible.keys().map(|n| ("device-visible", n));
+        scales.chain(visible)
+    }
+
+    /// The devices whose grip is changed, along with the key that changes them.
+    fn grip_devices(&self) -> impl Iterator<Item = (&'static str, &DefaultAtom)> {
+        let grips = self.device_grip.keys().map(|n| ("device-grip", n));
+        let curls = self
+            .device_grip_curl
+            .keys()
+            .map(|n| ("device-grip-curl", n));
+        grips.chain(curls)
+    }
+}
+
+/// A setting which isn't available for an event, so no value will be accepted for it.
+#[derive(Debug)]
+pub enum Unavailable {}
+
+impl<'de> Deserialize<'de> for Unavailable {
+    fn deserialize<D: Deserializer<'de>>(_: D) -> Result<Self, D::Error> {
+        Err(D::Error::custom(
+            "this is only available for on-press, on-release, on-long-press and on-double-press",
+        ))
+    }
+}
+
+impl Serialize for Unavailable {
+    fn serialize<S: Serializer>(&self, _: S) -> Result<S::Ok, S::Error> {
+        match *self {}
+    }
+}
+
+impl JsonSchema for Unavailable {
+    fn schema_name() -> String {
+        "Unavailable".into()
+    }
+
+    fn json_schema(_: &mut schemars::gen::SchemaGenerator) -> schemars::schema::Schema {
+        schemars::schema::Schema::Bool(false)
+    }
+}
+
+/// A range that a value from 0 to 1 is mapped to, optionally shaped by an easing curve. This may be
+/// given either as `[min, max]`, or as `{ range = [min, max], ease = N }`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ValueRange {
+    pub(super) range: [f32; 2],
+    pub(super) ease: f32,
+}
+
+/// How a `ValueRange` is written, for (de)serialization and the schema.
+#[derive(Deserialize, JsonSchema, Serialize)]
+#[serde(untagged)]
+enum ValueRangeRepr {
+    Linear([f32; 2]),
+    Curved(CurvedRange),
+}
+
+#[derive(Deserialize, JsonSchema, Serialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+struct CurvedRange {
+    range: [f32; 2],
+    #[serde(default = "linear_ease")]
+    ease: f32,
+}
+
+fn linear_ease() -> f32 {
+    1.0
+}
+
+impl ValueRange {
+    /// Maps a value from 0 to 1 into the range, following the easing curve. Without a curve (an
+    /// `ease` of 1), this is exactly linear and values outside of 0 to 1 are extrapolated.
+    pub(super) fn map(&self, t: f32) -> f32 {
+        let [min, max] = self.range;
+        let t = if self.ease == 1.0 {
+            t
+        } else {
+            t.ease(self.ease)
+        };
+        min + t * (max - min)
+    }
+}
+
+impl<'de> Deserialize<'de> for ValueRange {
+    fn deserialize<D: Deserializer<'de>>(de: D) -> Result<Self, D::Error> {
+        de.deserialize_any(ValueRangeVisitor)
+    }
+}
+
+impl Serialize for ValueRange {
+    fn serialize<S: Serializer>(&self, ser: S) -> Result<S::Ok, S::Error> {
+        if self.ease == 1.0 {
+            ValueRangeRepr::Linear(self.range)
+        } else {
+            ValueRangeRepr::Curved(CurvedRange {
+                range: self.range,
+                ease: self.ease,
+            })
+        }
+        .serialize(ser)
+    }
+}
+
+impl JsonSchema for ValueRange {
+    fn schema_name() -> String {
+        "ValueRange".into()
+    }
+
+    fn json_schema(gen: &mut schemars::gen::SchemaGenerator) -> schemars::schema::Schema {
+        ValueRangeRepr::json_schema(gen)
+    }
+}
+
+struct ValueRangeVisitor;
+
+impl<'de> serde::de::Visitor<'de> for ValueRangeVisitor {
+    type Value = ValueRange;
+
+    fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "a range ([min, max]) or a range with an easing curve ({{ range, ease }})"
+        )
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, seq: A) -> Result<Self::Value, A::Error> {
+        let range = <[f32; 2]>::deserialize(SeqAccessDeserializer::new(seq))?;
+        Ok(ValueRange { range, ease: 1.0 })
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<Self::Value, A::Error> {
+        let CurvedRange { range, ease } =
+            CurvedRange::deserialize(MapAccessDeserializer::new(map))?;
+        if ease == 0.0 || !ease.is_finite() {
+            return Err(A::Error::custom("ease must be a non-zero number"));
+        }
+
+        Ok(ValueRange { range, ease })
+    }
+}
+
+/// Which of a device's hands a grip value applies to.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, JsonSchema, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum GripHand {
+    #[default]
+    Both,
+    Left,
+    Right,
 }
 
 impl Config {
     pub fn enabled(&self) -> bool {
         self.enabled
     }
+
+    /// The angle, in degrees, that a value of an axis turns a wheel device to, if the axis's output
+    /// turns one.
+    pub fn wheel_angle(&self, output: &AxisOutputConfig, value: f64) -> Option<f32> {
+        output
+            .on_update
+            .device
+            .iter()
+            .find_map(|(name, range)| match self.device.get(name) {
+                Some(Device::Wheel(wheel)) => Some(wheel.angle(range.map(value as f32))),
+                _ => None,
+            })
+    }
+
+    pub fn validate(&self, mappings: &MappingConfig, report: &mut Report) {
+        if self.report_interval.is_some() {
+            report.warning(
+                "vmc.report-interval",
+                "No longer used; VMC statistics are included in the report set by stats.report-interval",
+            );
+        }
+
+        if !self.enabled {
+            return;
+        }
+
+        if self.send_rate.is_some_and(|r| r <= 0.0) {
+            report.error("vmc.send-rate", "Send rate must be positive");
+        }
+
+        report.bind("vmc.input.address", self.input.address);
+        validate_bones("vmc.input.bones", self.input.bones.as_deref(), report);
+
+        crate::rebind::validate_stall_timeout(
+            "vmc.input.stall-timeout",
+            self.input.address,
+            self.input.stall_timeout,
+            report,
+        );
+
+        for (name, source) in &self.input.source {
+            let path = format!("vmc.input.source.{name}");
+            report.bind(format!("{path}.address"), source.address);
+            validate_bones(&format!("{path}.bones"), source.bones.as_deref(), report);
+        }
+        report.target("vmc.output.address", self.output.address);
+
+        if let Some(ref period) = self.output.period {
+            period.validate(report);
+        }
+
+        if self.output.probe_interval.is_some_and(|i| i <= 0.0) {
+            report.error(
+                "vmc.output.probe-interval",
+                "Probe interval must be positive",
+            );
+        }
+
+        if self.output.retry_interval.is_some_and(|i| i <= 0.0) {
+            report.error(
+                "vmc.output.retry-interval",
+                "Retry interval must be positive",
+            );
+        }
+
+        for limb in Limb::iter() {
+            self.ik.limb(limb).validate(limb, report);
+        }
+
+        for (id, axis) in &mappings.axis {
+            for name in axis.output.vmc.bone.keys() {
+                if Bone::from_str(name).is_err() {
+                    report.error(
+                        format!("axis.{id}.output.vmc.bone.{name}"),
+                        format!("Unknown bone {name:?}"),
+                    );
+                }
+            }
+        }
+
+        let mut check_devices = |path: String, devices: &mut dyn Iterator<Item = &DefaultAtom>| {
+            for name in devices {
+                if !self.device.contains_key(name) {
+                    report.warning(
+                        format!("{path}.{name}"),
+                        "No VMC device with this name is defined",
+                    );
+                }
+            }
+        };
+
+        for (id, axis) in &mappings.axis {
+            let output = &axis.output.vmc;
+            check_devices(
+                format!("axis.{id}.output.vmc.on-update.device"),
+                &mut output.on_update.device.keys(),
+            );
+
+            for (key, name) in output.on_update.prop_devices() {
+                check_devices(
+                    format!("axis.{id}.output.vmc.on-update.{key}"),
+                    &mut std::iter::once(name),
+                );
+            }
+        }
+
+        for (id, button) in &mappings.button {
+            let output = &button.output.vmc;
+            let path = format!("button.{id}.output.vmc");
+            check_devices(
+                format!("{path}.on-update.device"),
+                &mut output.on_update.device.keys(),
+            );
+            for (event, state) in output.states() {
+                check_devices(format!("{path}.{event}.device"), &mut state.device.keys());
+                check_devices(
+                    format!("{path}.{event}.device-position"),
+                    &mut state.device_position.keys(),
+                );
+            }
+
+            let props = (output.on_update.prop_devices().map(|p| ("on-update", p))).chain(
+                output
+                    .states()
+                    .flat_map(|(event, state)| state.prop_devices().map(move |p| (event, p))),
+            );
+            for (event, (key, name)) in props {
+                check_devices(format!("{path}.{event}.{key}"), &mut std::iter::once(name));
+            }
+        }
+
+        let mut check_grips =
+            |path: String, devices: &mut dyn Iterator<Item = (&str, &DefaultAtom)>| {
+                for (key, name) in devices {
+                    match self.device.get(name) {
+                        Some(Device::Wheel(_)) => {}
+                        Some(Device::Handbrake(_)) if key == "device-grip" => {}
+
+                        Some(Device::Handbrake(_)) => report.warning(
+                            format!("{path}.{key}.{name}"),
+                            "Handbrake devices only have a device-grip, which moves the hand onto them",
+                        ),
+
+                        Some(_) => report.warning(
+                            format!("{path}.{key}.{name}"),
+                            "Only wheel and handbrake devices have a grip",
+                        ),
+
+                        None => report.warning(
+                            format!("{path}.{key}.{name}"),
+                            "No VMC device with this name is defined",
+                        ),
+                    }
+                }
+            };
+
+        for (id, axis) in &mappings.axis {
+            let output = &axis.output.vmc;
+            check_grips(
+                format!("axis.{id}.output.vmc.on-update"),
+                &mut output.on_update.grip_devices(),
+            );
+        }
+
+        for (id, button) in &mappings.button {
+            let output = &button.output.vmc;
+            let path = format!("button.{id}.output.vmc");
+            check_grips(
+                format!("{path}.on-update"),
+                &mut output.on_update.grip_devices(),
+            );
+            for (event, state) in output.states() {
+                check_grips(format!("{path}.{event}"), &mut state.grip_devices());
+            }
+        }
+
+        for (id, button) in &mappings.button {
+            let output = &button.output.vmc;
+            let gestures = [
+                (
+                    "on-long-press",
+                    &output.on_long_press,
+                    button.long_press_ms,
+                    "long-press-ms",
+                ),
+                (
+                    "on-double-press",
+                    &output.on_double_press,
+                    button.double_press_ms,
+                    "double-press-ms",
+                ),
+            ];
+
+            for (event, state, time, key) in gestures {
+                if time.is_none() && !state.is_empty() {
+                    report.warning(
+                        format!("button.{id}.output.vmc.{event}"),
+                        format!("The button has no {key}, so this will never be applied"),
+                    );
+                }
+            }
+        }
+    }
+}
+
+impl IkConfig {
+    pub(super) fn limb(&self, limb: Limb) -> &LimbConfig {
+        match limb {
+            Limb::LeftHand => &self.left_hand,
+            Limb::RightHand => &self.right_hand,
+        }
+    }
+}
+
+impl LimbConfig {
+    fn validate(&self, limb: Limb, report: &mut Report) {
+        let path = match limb {
+            Limb::LeftHand => "vmc.ik.left-hand",
+            Limb::RightHand => "vmc.ik.right-hand",
+        };
+
+        if self
+            .elbow_axis
+            .is_some_and(|a| a.iter().all(|&c| c == 0.0) || a.iter().any(|c| !c.is_finite()))
+        {
+            report.error(
+                format!("{path}.elbow-axis"),
+                "Elbow axis must be a non-zero direction",
+            );
+        }
+
+        let ranges = [
+            ("elbow-range", self.elbow_range),
+            ("arm-yaw", self.arm_yaw),
+            ("arm-roll", self.arm_roll),
+            ("arm-pitch", self.arm_pitch),
+        ];
+
+        for (key, range) in ranges {
+            if range.is_some_and(|(min, max)| min > max) {
+                report.error(
+                    format!("{path}.{key}"),
+                    "The minimum angle must not be greater than the maximum",
+                );
+            }
+        }
+    }
+}
+
+impl GripHand {
+    pub(super) fn limbs(self) -> EnumSet<Limb> {
+        match self {
+            GripHand::Both => EnumSet::all(),
+            GripHand::Left => Limb::LeftHand.mask(),
+            GripHand::Right => Limb::RightHand.mask(),
+        }
+    }
+}
+
+impl InputConfig {
+    /// The bones that are taken from this input.
+    pub(super) fn bone_mask(&self) -> EnumSet<Bone> {
+        bone_mask(self.bones.as_deref())
+    }
+
+    /// The options for the sockets that input is received on.
+    pub(super) fn socket_options(&self) -> SocketOptions {
+        SocketOptions {
+            stall_timeout: self.stall_timeout.map(Duration::from_secs_f64),
+            ..SocketOptions::default()
+        }
+    }
 }
 
 impl Default for InputConfig {
     fn default() -> InputConfig {
         InputConfig {
             address: SocketAddr::new(Ipv4Addr::LOCALHOST.into(), 3332),
+            bones: None,
+            source: HashMap::new(),
+            stall_timeout: None,
+        }
+    }
+}
+
+impl SourceConfig {
+    /// The bones that are taken from this input.
+    pub(super) fn bone_mask(&self) -> EnumSet<Bone> {
+        bone_mask(self.bones.as_deref())
+    }
+}
+
+fn bone_mask(bones: Option<&[String]>) -> EnumSet<Bone> {
+    match bones {
+        Some(bones) => bones
+            .iter()
+            .filter_map(|b| Bone::from_str(b).ok())
+            .collect(),
+        None => EnumSet::all(),
+    }
+}
+
+fn validate_bones(path: &str, bones: Option<&[String]>, report: &mut Report) {
+    for bone in bones.unwrap_or_default() {
+        if Bone::from_str(bone).is_err() {
+            report.error(path, format!("Unknown bone {bone:?}"));
         }
     }
 }
@@ -75,6 +758,58 @@ impl Default for OutputConfig {
     fn default() -> OutputConfig {
         OutputConfig {
             address: SocketAddr::new(Ipv4Addr::LOCALHOST.into(), 3333),
+            calibration: None,
+            period: None,
+            timetag: Timetag::default(),
+            probe_interval: None,
+            retry_interval: None,
+        }
+    }
+}
+
+impl PeriodConfig {
+    pub(super) fn to_args(self) -> [i32; 6] {
+        [
+            self.status,
+            self.root,
+            self.bone,
+            self.blendshape,
+            self.camera,
+            self.devices,
+        ]
+    }
+
+    fn validate(&self, report: &mut Report) {
+        let names = ["status", "root", "bone", "blendshape", "camera", "devices"];
+        for (name, period) in std::iter::zip(names, self.to_args()) {
+            if period < 1 {
+                report.error(
+                    format!("vmc.output.period.{name}"),
+                    "Period must be at least 1 frame",
+                );
+            }
+        }
+    }
+}
+
+impl Default for PeriodConfig {
+    fn default() -> PeriodConfig {
+        PeriodConfig {
+            status: 1,
+            root: 1,
+            bone: 1,
+            blendshape: 1,
+            camera: 1,
+            devices: 1,
+        }
+    }
+}
+
+impl Default for PassthroughConfig {
+    fn default() -> PassthroughConfig {
+        PassthroughConfig {
+            enabled: true,
+            max_messages: 64,
         }
     }
 }
@@ -1,6 +1,8 @@
 use anyhow::{Error as AnyError, Result as AnyResult};
+use enumset::EnumSet;
 use glam::{Quat, Vec3A};
-use serde::Deserialize;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
 use string_cache::DefaultAtom;
 
 use super::{
@@ -8,21 +10,43 @@ use super::{
     bone::{Bone, Limb},
 };
 
+mod handbrake;
+mod look;
+mod shifter;
 mod wheel;
+pub use handbrake::{Handbrake, HandbrakeConfig};
+pub use look::{Look, LookConfig};
+pub use shifter::{Shifter, ShifterConfig};
 pub use wheel::{Wheel, WheelConfig};
 
-#[derive(Debug, Deserialize)]
-#[serde(try_from = "DeviceConfig")]
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(try_from = "DeviceConfig", into = "DeviceConfig")]
 pub enum Device {
+    Handbrake(Handbrake),
+    Look(Look),
+    Shifter(Shifter),
     Wheel(Wheel),
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, JsonSchema, Serialize)]
 #[serde(deny_unknown_fields, rename_all = "kebab-case", tag = "type")]
 enum DeviceConfig {
+    Handbrake(HandbrakeConfig),
+    Look(LookConfig),
+    Shifter(ShifterConfig),
     Wheel(WheelConfig),
 }
 
+impl JsonSchema for Device {
+    fn schema_name() -> String {
+        "Device".into()
+    }
+
+    fn json_schema(gen: &mut schemars::gen::SchemaGenerator) -> schemars::schema::Schema {
+        DeviceConfig::json_schema(gen)
+    }
+}
+
 #[allow(unused)]
 #[derive(Clone, Debug)]
 pub enum ForwardPose {
@@ -33,40 +57,112 @@ pub enum ForwardPose {
 impl Device {
     pub fn pose_forward(&self, f: impl FnMut(Bone, f32, ForwardPose)) {
         match *self {
+            Device::Handbrake(ref h) => h.pose_forward(f),
+            Device::Look(ref l) => l.pose_forward(f),
+            Device::Shifter(ref s) => s.pose_forward(f),
             Device::Wheel(ref w) => w.pose_forward(f),
         }
     }
 
     pub fn pose_inverse(&self, pose: &Pose, f: impl FnMut(Limb, f32, Vec3A, Quat)) {
         match *self {
+            Device::Handbrake(ref h) => h.pose_inverse(pose, f),
+            Device::Look(ref l) => l.pose_inverse(pose, f),
+            Device::Shifter(ref s) => s.pose_inverse(pose, f),
             Device::Wheel(ref w) => w.pose_inverse(pose, f),
         }
     }
 
+    pub fn priority(&self) -> i32 {
+        match *self {
+            Device::Handbrake(ref h) => h.priority(),
+            Device::Look(_) => 0,
+            Device::Shifter(ref s) => s.priority(),
+            Device::Wheel(_) => 0,
+        }
+    }
+
+    pub fn set_grip(&mut self, limbs: EnumSet<Limb>, value: f32) {
+        match *self {
+            Device::Handbrake(ref mut h) => h.set_grip(limbs, value),
+            Device::Look(_) | Device::Shifter(_) => {}
+            Device::Wheel(ref mut w) => w.set_grip(limbs, value),
+        }
+    }
+
+    pub fn set_grip_curl(&mut self, limbs: EnumSet<Limb>, value: f32) {
+        match *self {
+            Device::Handbrake(_) | Device::Look(_) | Device::Shifter(_) => {}
+            Device::Wheel(ref mut w) => w.set_grip_curl(limbs, value),
+        }
+    }
+
     pub fn set_value(&mut self, value: f32) {
         match *self {
+            Device::Handbrake(ref mut h) => h.set_value(value),
+            Device::Look(ref mut l) => l.set_value(value),
+            Device::Shifter(ref mut s) => s.set_value(value),
             Device::Wheel(ref mut w) => w.set_value(value),
         }
     }
 
+    /// Sets the device to a named value, returning whether the device recognized the name.
+    pub fn set_named_value(&mut self, name: &str) -> bool {
+        match *self {
+            Device::Handbrake(_) | Device::Look(_) => false,
+            Device::Shifter(ref mut s) => s.set_named_value(name),
+            Device::Wheel(_) => false,
+        }
+    }
+
+    /// The OSC address that the scale of the device's prop is sent to, if any.
+    pub fn scale_address(&self) -> Option<&str> {
+        match *self {
+            Device::Handbrake(ref h) => h.scale_address(),
+            Device::Look(_) => None,
+            Device::Shifter(ref s) => s.scale_address(),
+            Device::Wheel(ref w) => w.scale_address(),
+        }
+    }
+
     pub fn trackers(&self, f: impl FnMut(DefaultAtom, Vec3A, Quat)) {
         match *self {
+            Device::Handbrake(ref h) => h.trackers(f),
+            Device::Look(ref l) => l.trackers(f),
+            Device::Shifter(ref s) => s.trackers(f),
             Device::Wheel(ref w) => w.trackers(f),
         }
     }
 
     pub fn update(&mut self, dt: f64, pose: &Pose) {
         match *self {
+            Device::Handbrake(ref mut h) => h.update(dt, pose),
+            Device::Look(ref mut l) => l.update(dt, pose),
+            Device::Shifter(ref mut s) => s.update(dt, pose),
             Device::Wheel(ref mut w) => w.update(dt, pose),
         }
     }
 }
 
+impl From<Device> for DeviceConfig {
+    fn from(device: Device) -> Self {
+        match device {
+            Device::Handbrake(h) => DeviceConfig::Handbrake(h.into_config()),
+            Device::Look(l) => DeviceConfig::Look(l.into_config()),
+            Device::Shifter(s) => DeviceConfig::Shifter(s.into_config()),
+            Device::Wheel(w) => DeviceConfig::Wheel(w.into_config()),
+        }
+    }
+}
+
 impl TryFrom<DeviceConfig> for Device {
     type Error = AnyError;
 
     fn try_from(config: DeviceConfig) -> AnyResult<Self> {
         match config {
+            DeviceConfig::Handbrake(h) => Ok(Device::Handbrake(h.try_into()?)),
+            DeviceConfig::Look(l) => Ok(Device::Look(l.try_into()?)),
+            DeviceConfig::Shifter(s) => Ok(Device::Shifter(s.try_into()?)),
             DeviceConfig::Wheel(w) => Ok(Device::Wheel(w.try_into()?)),
         }
     }
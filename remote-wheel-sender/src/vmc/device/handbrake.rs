@@ -0,0 +1,205 @@
+use std::f32::consts::TAU;
+
+use anyhow::{ensure, Error as AnyError, Result as AnyResult};
+use enumset::EnumSet;
+use glam::{EulerRot, Quat, Vec3A};
+use schemars::JsonSchema;
+use serde::{Deserialize, Deserializer, Serialize};
+use string_cache::DefaultAtom;
+
+use crate::vmc::{
+    avatar::Pose,
+    bone::{Bone, Limb},
+};
+
+use super::shifter::{curl_fingers, Hand};
+use super::ForwardPose;
+
+/// A lever that's pulled back about its base, which one hand leaves the wheel to grab while it's
+/// gripped.
+#[derive(Clone, Debug)]
+pub struct Handbrake {
+    /// What this was created from, so that it can be shown as configured.
+    config: HandbrakeConfig,
+
+    pos: Vec3A,
+    rot: Quat,
+
+    /// How far the lever is pulled, from 0 (released) to 1 (pulled by the full pull-angle).
+    pull: f32,
+    /// How firmly the hand should hold the lever, from 0 (on the wheel) to 1.
+    grip: f32,
+    /// The same, moved towards `grip` over the blend time, so that the hand doesn't jump.
+    blend: f32,
+}
+
+#[derive(Clone, Debug, Deserialize, JsonSchema, Serialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct HandbrakeConfig {
+    position: [f32; 3],
+    rotation: [f32; 3],
+
+    length: f32,
+    #[serde(deserialize_with = "parse_degrees")]
+    #[schemars(schema_with = "crate::units::degrees_schema")]
+    pull_angle: f32,
+    hand: Hand,
+    hand_offset: [f32; 3],
+    #[schemars(with = "Option<String>")]
+    tracker: Option<DefaultAtom>,
+    scale_address: Option<String>,
+
+    #[serde(deserialize_with = "crate::units::seconds")]
+    #[schemars(schema_with = "crate::units::seconds_schema")]
+    blend_time: f64,
+}
+
+impl TryFrom<HandbrakeConfig> for Handbrake {
+    type Error = AnyError;
+
+    fn try_from(config: HandbrakeConfig) -> AnyResult<Self> {
+        ensure!(config.length > 0.0, "Handbrake length must be positive.");
+        ensure!(
+            config
+                .scale_address
+                .as_ref()
+                .is_none_or(|a| a.starts_with('/')),
+            "Handbrake scale address must start with a /."
+        );
+        ensure!(
+            config.blend_time >= 0.0,
+            "Handbrake blend time must be at least 0 seconds."
+        );
+
+        let rot = Quat::from_euler(
+            EulerRot::YXZ,
+            config.rotation[1].to_radians(),
+            config.rotation[0].to_radians(),
+            config.rotation[2].to_radians(),
+        );
+
+        Ok(Handbrake {
+            pos: config.position.into(),
+            rot,
+            config,
+
+            pull: 0.0,
+            grip: 0.0,
+            blend: 0.0,
+        })
+    }
+}
+
+impl Default for HandbrakeConfig {
+    fn default() -> HandbrakeConfig {
+        HandbrakeConfig {
+            position: [0.2, 0.9, 0.1],
+            rotation: [0.0, 0.0, 0.0],
+
+            length: 0.25,
+            pull_angle: 30.0,
+            hand: Hand::Right,
+            hand_offset: [0.0, 0.0, 0.0],
+            tracker: None,
+            scale_address: None,
+
+            blend_time: 0.3,
+        }
+    }
+}
+
+impl Handbrake {
+    pub fn into_config(self) -> HandbrakeConfig {
+        self.config
+    }
+
+    pub fn scale_address(&self) -> Option<&str> {
+        self.config.scale_address.as_deref()
+    }
+
+    pub fn pose_forward(&self, f: impl FnMut(Bone, f32, ForwardPose)) {
+        let weight = self.weight();
+        if weight > 0.0 {
+            curl_fingers(self.config.hand, weight, f);
+        }
+    }
+
+    pub fn pose_inverse(&self, _: &Pose, mut f: impl FnMut(Limb, f32, Vec3A, Quat)) {
+        let weight = self.weight();
+        if weight <= 0.0 {
+            return;
+        }
+
+        let (limb, yaw) = match self.config.hand {
+            Hand::Left => (Limb::LeftHand, 0.25 * TAU),
+            Hand::Right => (Limb::RightHand, -0.25 * TAU),
+        };
+
+        let lever_rot = self.lever_rot();
+        let grip_pos = self.pos + lever_rot * Vec3A::new(0.0, self.config.length, 0.0);
+        let hand_offset = Vec3A::from(self.config.hand_offset);
+
+        f(
+            limb,
+            weight,
+            grip_pos + lever_rot * hand_offset,
+            lever_rot * Quat::from_rotation_y(yaw),
+        );
+    }
+
+    pub fn priority(&self) -> i32 {
+        // As with a shifter, the hand leaves the wheel while it's held, so it's posed first.
+        1
+    }
+
+    /// Sets how firmly the configured hand holds the lever, if it's one of `limbs`.
+    pub fn set_grip(&mut self, limbs: EnumSet<Limb>, value: f32) {
+        let limb = match self.config.hand {
+            Hand::Left => Limb::LeftHand,
+            Hand::Right => Limb::RightHand,
+        };
+
+        if limbs.contains(limb) {
+            self.grip = value.clamp(0.0, 1.0);
+        }
+    }
+
+    /// Sets how far the lever is pulled, from 0 to 1.
+    pub fn set_value(&mut self, value: f32) {
+        self.pull = value.clamp(0.0, 1.0);
+    }
+
+    pub fn trackers(&self, mut f: impl FnMut(DefaultAtom, Vec3A, Quat)) {
+        if let Some(ref tracker) = self.config.tracker {
+            f(tracker.clone(), self.pos, self.lever_rot());
+        }
+    }
+
+    pub fn update(&mut self, dt: f64, _: &Pose) {
+        let step = if self.config.blend_time > 0.0 {
+            (dt / self.config.blend_time) as f32
+        } else {
+            1.0
+        };
+
+        let diff = self.grip - self.blend;
+        self.blend += diff.clamp(-step, step);
+    }
+
+    /// The rotation of the lever, tipped back about its base by how far it's pulled.
+    fn lever_rot(&self) -> Quat {
+        let angle = self.pull * self.config.pull_angle.to_radians();
+        self.rot * Quat::from_rotation_x(-angle)
+    }
+
+    /// How much of the hand is on the lever rather than the wheel, eased so that it sets off and
+    /// arrives gently.
+    fn weight(&self) -> f32 {
+        let t = self.blend;
+        t * t * (3.0 - 2.0 * t)
+    }
+}
+
+fn parse_degrees<'de, D: Deserializer<'de>>(de: D) -> Result<f32, D::Error> {
+    crate::units::degrees(de).map(|d| d as f32)
+}
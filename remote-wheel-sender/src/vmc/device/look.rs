@@ -0,0 +1,150 @@
+use anyhow::{ensure, Error as AnyError, Result as AnyResult};
+use glam::{EulerRot, Quat, Vec3A};
+use schemars::JsonSchema;
+use serde::{Deserialize, Deserializer, Serialize};
+use string_cache::DefaultAtom;
+
+use crate::vmc::{
+    avatar::Pose,
+    bone::{Bone, Limb},
+};
+
+use super::ForwardPose;
+
+/// Turns the avatar's head and eyes towards the inside of a corner, in proportion to the angle of
+/// the wheel that it's given.
+#[derive(Clone, Debug)]
+pub struct Look {
+    /// What this was created from, so that it can be shown as configured.
+    config: LookConfig,
+
+    /// How far to look into the turn, from -1 (fully left) to 1 (fully right).
+    target: f32,
+    /// The same, smoothed towards `target` over time.
+    current: f32,
+}
+
+#[derive(Clone, Debug, Deserialize, JsonSchema, Serialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct LookConfig {
+    #[serde(deserialize_with = "parse_degrees")]
+    #[schemars(schema_with = "crate::units::degrees_schema")]
+    max_yaw: f32,
+    #[serde(deserialize_with = "parse_degrees")]
+    #[schemars(schema_with = "crate::units::degrees_schema")]
+    max_pitch: f32,
+    #[serde(deserialize_with = "parse_degrees")]
+    #[schemars(schema_with = "crate::units::degrees_schema")]
+    full_angle: f32,
+
+    neck: f32,
+    head: f32,
+    eyes: f32,
+
+    weight: f32,
+    #[serde(deserialize_with = "crate::units::seconds")]
+    #[schemars(schema_with = "crate::units::seconds_schema")]
+    smoothing: f64,
+}
+
+impl TryFrom<LookConfig> for Look {
+    type Error = AnyError;
+
+    fn try_from(config: LookConfig) -> AnyResult<Self> {
+        ensure!(
+            config.full_angle > 0.0,
+            "Look full-angle must be greater than 0 degrees."
+        );
+        ensure!(
+            [config.neck, config.head, config.eyes]
+                .iter()
+                .all(|&s| s >= 0.0),
+            "Look neck, head, and eyes must not be negative."
+        );
+        ensure!(
+            (0.0..=1.0).contains(&config.weight),
+            "Look weight must be from 0 to 1."
+        );
+        ensure!(
+            config.smoothing >= 0.0,
+            "Look smoothing must be at least 0 seconds."
+        );
+
+        Ok(Look {
+            config,
+            target: 0.0,
+            current: 0.0,
+        })
+    }
+}
+
+impl Default for LookConfig {
+    fn default() -> LookConfig {
+        LookConfig {
+            max_yaw: 20.0,
+            max_pitch: 0.0,
+            full_angle: 180.0,
+
+            neck: 0.3,
+            head: 0.7,
+            eyes: 0.0,
+
+            weight: 0.5,
+            smoothing: 0.25,
+        }
+    }
+}
+
+impl Look {
+    pub fn into_config(self) -> LookConfig {
+        self.config
+    }
+
+    /// Turns the neck, head, and eyes each by their share of the look, blended over tracking in
+    /// proportion to how far into the turn to look, so that tracking is left alone on a straight.
+    pub fn pose_forward(&self, mut f: impl FnMut(Bone, f32, ForwardPose)) {
+        let weight = self.config.weight * self.current.abs();
+        if weight <= 0.0 {
+            return;
+        }
+
+        let yaw = self.current.signum() * self.config.max_yaw.to_radians();
+        let pitch = self.config.max_pitch.to_radians();
+
+        let shares = [
+            (Bone::Neck, self.config.neck),
+            (Bone::Head, self.config.head),
+            (Bone::LeftEye, self.config.eyes),
+            (Bone::RightEye, self.config.eyes),
+        ];
+
+        for (bone, share) in shares {
+            if share > 0.0 {
+                let rot = Quat::from_euler(EulerRot::YXZ, share * yaw, share * pitch, 0.0);
+                f(bone, weight, ForwardPose::Local(rot));
+            }
+        }
+    }
+
+    pub fn pose_inverse(&self, _pose: &Pose, _f: impl FnMut(Limb, f32, Vec3A, Quat)) {}
+
+    /// Sets the angle of the wheel, in degrees, which is looked into in proportion to full-angle.
+    pub fn set_value(&mut self, value: f32) {
+        self.target = (value / self.config.full_angle).clamp(-1.0, 1.0);
+    }
+
+    pub fn trackers(&self, _f: impl FnMut(DefaultAtom, Vec3A, Quat)) {}
+
+    pub fn update(&mut self, dt: f64, _pose: &Pose) {
+        if self.config.smoothing > 0.0 {
+            let t = 1.0 - f64::exp(-dt / self.config.smoothing);
+            self.current += t as f32 * (self.target - self.current);
+        } else {
+            self.current = self.target;
+        }
+    }
+}
+
+fn parse_degrees<'de, D: Deserializer<'de>>(de: D) -> Result<f32, D::Error> {
+    crate::units::degrees(de).map(|d| d as f32)
+}
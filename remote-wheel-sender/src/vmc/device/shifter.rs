@@ -0,0 +1,283 @@
+use std::f32::consts::TAU;
+
+use anyhow::{ensure, Error as AnyError, Result as AnyResult};
+use glam::{EulerRot, Quat, Vec3A};
+use hashbrown::HashMap;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use string_cache::DefaultAtom;
+
+use crate::vmc::{
+    avatar::Pose,
+    bone::{Bone, Limb},
+};
+
+use super::ForwardPose;
+
+#[derive(Clone, Debug)]
+pub struct Shifter {
+    /// What this was created from, so that it can be shown as configured.
+    config: ShifterConfig,
+
+    pos: Vec3A,
+    rot: Quat,
+
+    columns: u32,
+    rows: u32,
+    spacing: [f32; 2],
+    hand: Hand,
+    hand_offset: Vec3A,
+    tracker: Option<DefaultAtom>,
+    positions: HashMap<String, u32>,
+
+    gear: Option<(u32, u32)>,
+}
+
+#[derive(Clone, Debug, Deserialize, JsonSchema, Serialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct ShifterConfig {
+    position: [f32; 3],
+    rotation: [f32; 3],
+
+    columns: u32,
+    rows: u32,
+    spacing: [f32; 2],
+    hand: Hand,
+    hand_offset: [f32; 3],
+    #[schemars(with = "Option<String>")]
+    tracker: Option<DefaultAtom>,
+    scale_address: Option<String>,
+    #[schemars(with = "std::collections::HashMap<String, u32>")]
+    positions: HashMap<String, u32>,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, JsonSchema, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Hand {
+    Left,
+    Right,
+}
+
+impl TryFrom<ShifterConfig> for Shifter {
+    type Error = AnyError;
+
+    fn try_from(config: ShifterConfig) -> AnyResult<Self> {
+        ensure!(config.columns > 0, "Shifter must have at least one column.");
+        ensure!(config.rows > 0, "Shifter must have at least one row.");
+        ensure!(
+            config
+                .scale_address
+                .as_ref()
+                .is_none_or(|a| a.starts_with('/')),
+            "Shifter scale address must start with a /."
+        );
+
+        let gears = config.columns * config.rows;
+        for (name, &gear) in &config.positions {
+            ensure!(
+                gear <= gears,
+                "Shifter position {name:?} is gear {gear}, but the shifter only has {gears} gears."
+            );
+        }
+
+        let rot = Quat::from_euler(
+            EulerRot::YXZ,
+            config.rotation[1].to_radians(),
+            config.rotation[0].to_radians(),
+            config.rotation[2].to_radians(),
+        );
+
+        Ok(Shifter {
+            config: config.clone(),
+
+            pos: config.position.into(),
+            rot,
+
+            columns: config.columns,
+            rows: config.rows,
+            spacing: config.spacing,
+            hand: config.hand,
+            hand_offset: config.hand_offset.into(),
+            tracker: config.tracker,
+            positions: config.positions,
+
+            gear: None,
+        })
+    }
+}
+
+impl Default for ShifterConfig {
+    fn default() -> ShifterConfig {
+        ShifterConfig {
+            position: [0.0, 0.0, 0.0],
+            rotation: [0.0, 0.0, 0.0],
+
+            columns: 3,
+            rows: 2,
+            spacing: [0.05, 0.1],
+            hand: Hand::Right,
+            hand_offset: [0.0, 0.06, -0.04],
+            tracker: None,
+            scale_address: None,
+            positions: HashMap::new(),
+        }
+    }
+}
+
+impl Shifter {
+    pub fn into_config(self) -> ShifterConfig {
+        self.config
+    }
+
+    pub fn scale_address(&self) -> Option<&str> {
+        self.config.scale_address.as_deref()
+    }
+
+    pub fn pose_forward(&self, f: impl FnMut(Bone, f32, ForwardPose)) {
+        if self.gear.is_some() {
+            // A looser grip than on the wheel, since the knob is smaller and held from above.
+            curl_fingers(self.hand, 1.0, f);
+        }
+    }
+
+    pub fn pose_inverse(&self, _: &Pose, mut f: impl FnMut(Limb, f32, Vec3A, Quat)) {
+        let Some(knob_pos) = self.knob_pos() else {
+            return;
+        };
+
+        let (limb, yaw) = match self.hand {
+            Hand::Left => (Limb::LeftHand, 0.25 * TAU),
+            Hand::Right => (Limb::RightHand, -0.25 * TAU),
+        };
+
+        f(
+            limb,
+            1.0,
+            knob_pos + self.rot * self.hand_offset,
+            self.rot * Quat::from_rotation_y(yaw),
+        );
+    }
+
+    pub fn priority(&self) -> i32 {
+        // The shifter hand leaves the wheel while in gear, so it needs to be posed first.
+        1
+    }
+
+    pub fn set_value(&mut self, value: f32) {
+        let gear = value.round();
+        self.gear = (gear >= 1.0 && gear <= (self.columns * self.rows) as f32).then(|| {
+            let index = gear as u32 - 1;
+            (index / self.rows, index % self.rows)
+        });
+    }
+
+    /// Puts the shifter into a gear by name: one of its configured positions, "N" for neutral, or a
+    /// gear number. Returns whether the name was recognized.
+    pub fn set_named_value(&mut self, name: &str) -> bool {
+        let gear = match self.positions.get(name) {
+            Some(&gear) => gear,
+            None if name.eq_ignore_ascii_case("n") => 0,
+            None => match name.parse() {
+                Ok(gear) if gear <= self.columns * self.rows => gear,
+                _ => return false,
+            },
+        };
+
+        self.set_value(gear as f32);
+        true
+    }
+
+    pub fn trackers(&self, mut f: impl FnMut(DefaultAtom, Vec3A, Quat)) {
+        if let Some(ref tracker) = self.tracker {
+            f(
+                tracker.clone(),
+                self.knob_pos().unwrap_or(self.pos),
+                self.rot,
+            );
+        }
+    }
+
+    pub fn update(&mut self, _: f64, _: &Pose) {}
+
+    fn knob_pos(&self) -> Option<Vec3A> {
+        let (column, row) = self.gear?;
+
+        let x = (column as f32 - 0.5 * (self.columns - 1) as f32) * self.spacing[0];
+        let z = (0.5 * (self.rows - 1) as f32 - row as f32) * self.spacing[1];
+        Some(self.pos + self.rot * Vec3A::new(x, 0.0, z))
+    }
+}
+
+/// Curls the fingers of `hand` around a knob or lever, blended over tracking by `weight`.
+pub(super) fn curl_fingers(hand: Hand, weight: f32, mut f: impl FnMut(Bone, f32, ForwardPose)) {
+    let (sign, bones) = match hand {
+        Hand::Left => (
+            1.0,
+            [
+                [
+                    Bone::LeftIndexProximal,
+                    Bone::LeftIndexIntermediate,
+                    Bone::LeftIndexDistal,
+                ],
+                [
+                    Bone::LeftMiddleProximal,
+                    Bone::LeftMiddleIntermediate,
+                    Bone::LeftMiddleDistal,
+                ],
+                [
+                    Bone::LeftRingProximal,
+                    Bone::LeftRingIntermediate,
+                    Bone::LeftRingDistal,
+                ],
+                [
+                    Bone::LeftLittleProximal,
+                    Bone::LeftLittleIntermediate,
+                    Bone::LeftLittleDistal,
+                ],
+            ],
+        ),
+        Hand::Right => (
+            -1.0,
+            [
+                [
+                    Bone::RightIndexProximal,
+                    Bone::RightIndexIntermediate,
+                    Bone::RightIndexDistal,
+                ],
+                [
+                    Bone::RightMiddleProximal,
+                    Bone::RightMiddleIntermediate,
+                    Bone::RightMiddleDistal,
+                ],
+                [
+                    Bone::RightRingProximal,
+                    Bone::RightRingIntermediate,
+                    Bone::RightRingDistal,
+                ],
+                [
+                    Bone::RightLittleProximal,
+                    Bone::RightLittleIntermediate,
+                    Bone::RightLittleDistal,
+                ],
+            ],
+        ),
+    };
+
+    for [proximal, intermediate, distal] in bones {
+        f(
+            proximal,
+            weight,
+            ForwardPose::Local(Quat::from_rotation_z(sign * 0.15 * TAU)),
+        );
+        f(
+            intermediate,
+            weight,
+            ForwardPose::Local(Quat::from_rotation_z(sign * 0.2 * TAU)),
+        );
+        f(
+            distal,
+            weight,
+            ForwardPose::Local(Quat::from_rotation_z(sign * 0.1 * TAU)),
+        );
+    }
+}
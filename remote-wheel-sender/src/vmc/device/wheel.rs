@@ -1,6 +1,8 @@
 use anyhow::{ensure, Context as _, Error as AnyError, Result as AnyResult};
+use enumset::EnumSet;
 use glam::{EulerRot, Quat, Vec3A};
-use serde::Deserialize;
+use schemars::JsonSchema;
+use serde::{Deserialize, Deserializer, Serialize};
 use string_cache::DefaultAtom;
 
 mod technique;
@@ -8,32 +10,93 @@ mod technique;
 use crate::vmc::{
     avatar::Pose,
     bone::{Bone, Limb},
+    config::GripHand,
 };
-use technique::{Technique, TechniqueConfig};
+use technique::{IdleConfig, Technique, TechniqueConfig};
 
 use super::ForwardPose;
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct Wheel {
+    /// What this was created from, so that it can be shown as configured. It's boxed since it's
+    /// rarely looked at, and would otherwise make up most of the wheel.
+    config: Box<WheelConfig>,
+
+    config_pos: Vec3A,
+    config_rot: Quat,
+    relative_to: RelativeTo,
+
     pos: Vec3A,
     rot: Quat,
     radius: f32,
+    rotation_range: f32,
+    value_range: [f32; 2],
 
     angle: f32,
     base_rot: Quat,
+    grip: [f32; Limb::NUM],
+    /// How far each hand's fingers curl around the wheel, from 0 (relaxed) to 1 (a full grip).
+    grip_curl: [f32; Limb::NUM],
+    /// The hands that hold the wheel. Any others are left entirely to tracking.
+    hands: EnumSet<Limb>,
     tracker: Option<DefaultAtom>,
 
+    /// How long it's been since the wheel was last given a value, in seconds, or `None` if it
+    /// hasn't been given one yet.
+    since_value: Option<f64>,
+    /// The angle that the wheel is returning to center from, once it hasn't been given a value for
+    /// too long.
+    return_from: Option<f32>,
+
     technique: Technique,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Clone, Debug, Deserialize, JsonSchema, Serialize)]
 #[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
 pub struct WheelConfig {
     position: [f32; 3],
     rotation: [f32; 3],
+    relative_to: RelativeTo,
     radius: f32,
+    #[serde(deserialize_with = "parse_degrees")]
+    #[schemars(schema_with = "crate::units::degrees_schema")]
+    rotation_range: f32,
+    value_range: [f32; 2],
+    #[schemars(with = "Option<String>")]
     tracker: Option<DefaultAtom>,
+    scale_address: Option<String>,
+    hands: GripHand,
     technique: TechniqueConfig,
+    idle: IdleConfig,
+    return_to_center: ReturnToCenterConfig,
+}
+
+/// Turning the wheel back to center once it hasn't been given a value for a while, e.g. because the
+/// game or the controller has gone away, so that the hands aren't left frozen mid-turn.
+#[derive(Clone, Debug, Deserialize, JsonSchema, Serialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct ReturnToCenterConfig {
+    enabled: bool,
+
+    #[serde(deserialize_with = "crate::units::seconds")]
+    #[schemars(schema_with = "crate::units::seconds_schema")]
+    timeout: f64,
+
+    #[serde(deserialize_with = "crate::units::seconds")]
+    #[schemars(schema_with = "crate::units::seconds_schema")]
+    duration: f64,
+}
+
+/// What a wheel's position and rotation are relative to.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, JsonSchema, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum RelativeTo {
+    /// The avatar's root, so the wheel follows it when it's moved (e.g. recentered).
+    Root,
+    /// The avatar's hips bone.
+    Hips,
+    #[default]
+    World,
 }
 
 impl TryFrom<WheelConfig> for Wheel {
@@ -41,6 +104,29 @@ impl TryFrom<WheelConfig> for Wheel {
 
     fn try_from(config: WheelConfig) -> AnyResult<Self> {
         ensure!(config.radius > 0.0, "Wheel radius must be positive.");
+        ensure!(
+            config.rotation_range >= 0.0,
+            "Wheel rotation range must not be negative."
+        );
+        ensure!(
+            config.value_range[0] != config.value_range[1],
+            "Wheel value range must not be empty."
+        );
+        ensure!(
+            config
+                .scale_address
+                .as_ref()
+                .is_none_or(|a| a.starts_with('/')),
+            "Wheel scale address must start with a /."
+        );
+        ensure!(
+            config.return_to_center.timeout > 0.0,
+            "Wheel return-to-center timeout must be greater than 0 seconds."
+        );
+        ensure!(
+            config.return_to_center.duration >= 0.0,
+            "Wheel return-to-center duration must be at least 0 seconds."
+        );
 
         let rot = Quat::from_euler(
             EulerRot::YXZ,
@@ -50,17 +136,29 @@ impl TryFrom<WheelConfig> for Wheel {
         );
 
         Ok(Wheel {
+            config: Box::new(config.clone()),
+
+            config_pos: config.position.into(),
+            config_rot: rot,
+            relative_to: config.relative_to,
+
             pos: config.position.into(),
             radius: config.radius,
+            rotation_range: config.rotation_range,
+            value_range: config.value_range,
             rot,
 
             angle: 0.0,
             base_rot: rot,
+            grip: [1.0; Limb::NUM],
+            grip_curl: [1.0; Limb::NUM],
+            hands: config.hands.limbs(),
             tracker: config.tracker,
 
-            technique: config
-                .technique
-                .try_into()
+            since_value: None,
+            return_from: None,
+
+            technique: Technique::new(config.technique, config.idle, config.hands.limbs())
                 .context("Failed to initialize driving wheel handling technique")?,
         })
     }
@@ -71,26 +169,105 @@ impl Default for WheelConfig {
         WheelConfig {
             position: [0.0, 0.0, 0.0],
             rotation: [0.0, 0.0, 0.0],
+            relative_to: RelativeTo::World,
             radius: 0.17,
+            rotation_range: 0.0,
+            value_range: [0.0, 1.0],
             tracker: None,
+            scale_address: None,
+            hands: GripHand::Both,
             technique: TechniqueConfig::default(),
+            idle: IdleConfig::default(),
+            return_to_center: ReturnToCenterConfig::default(),
+        }
+    }
+}
+
+impl Default for ReturnToCenterConfig {
+    fn default() -> Self {
+        ReturnToCenterConfig {
+            enabled: false,
+            timeout: 5.0,
+            duration: 1.0,
         }
     }
 }
 
 impl Wheel {
-    pub fn pose_forward(&self, f: impl FnMut(Bone, f32, ForwardPose)) {
-        self.technique.pose_forward(self, f)
+    pub fn into_config(self) -> WheelConfig {
+        *self.config
     }
 
-    pub fn pose_inverse(&self, pose: &Pose, f: impl FnMut(Limb, f32, Vec3A, Quat)) {
-        self.technique.pose_inverse(pose, self, f)
+    pub fn scale_address(&self) -> Option<&str> {
+        self.config.scale_address.as_deref()
+    }
+
+    pub fn pose_forward(&self, mut f: impl FnMut(Bone, f32, ForwardPose)) {
+        self.technique.pose_forward(self, |bone, weight, pose| {
+            if let Some(grip) = self.bone_grip(bone) {
+                f(bone, weight * grip, pose);
+            }
+        })
+    }
+
+    pub fn pose_inverse(&self, pose: &Pose, mut f: impl FnMut(Limb, f32, Vec3A, Quat)) {
+        self.technique
+            .pose_inverse(pose, self, |limb, weight, pos, rot| {
+                if self.hands.contains(limb) {
+                    f(limb, weight * self.grip[limb as u8 as usize], pos, rot);
+                }
+            })
+    }
+
+    /// Sets how firmly the given hands hold the wheel, from 0 (left entirely to tracking) to 1.
+    pub fn set_grip(&mut self, limbs: EnumSet<Limb>, value: f32) {
+        for limb in limbs {
+            self.grip[limb as u8 as usize] = value.clamp(0.0, 1.0);
+        }
+    }
+
+    /// Sets how far the given hands' fingers curl around the wheel, from 0 (relaxed) to 1.
+    pub fn set_grip_curl(&mut self, limbs: EnumSet<Limb>, value: f32) {
+        for limb in limbs {
+            self.grip_curl[limb as u8 as usize] = value.clamp(0.0, 1.0);
+        }
     }
 
     pub fn set_value(&mut self, value: f32) {
-        self.angle = value;
-        self.rot = self.base_rot * Quat::from_rotation_z(-value.to_radians());
-        self.technique.set_rotation(value);
+        self.since_value = Some(0.0);
+        self.return_from = None;
+        self.turn_to(self.angle(value));
+    }
+
+    fn turn_to(&mut self, angle: f32) {
+        self.angle = angle;
+        self.rot = self.base_rot * Quat::from_rotation_z(-angle.to_radians());
+        self.technique.set_rotation(angle);
+    }
+
+    /// The angle, in degrees, that a value turns the wheel to.
+    pub fn angle(&self, value: f32) -> f32 {
+        // A rotation range of 0 means that the value is already an angle, in degrees.
+        if self.rotation_range > 0.0 {
+            let [min, max] = self.value_range;
+            let t = ((value - min) / (max - min)).clamp(0.0, 1.0);
+            (t - 0.5) * self.rotation_range
+        } else {
+            value
+        }
+    }
+
+    /// Places the wheel relative to whatever it's configured to be relative to in `pose`.
+    fn place(&mut self, pose: &Pose) {
+        let (anchor_pos, anchor_rot) = match self.relative_to {
+            RelativeTo::Root => pose.root_transform(),
+            RelativeTo::Hips => pose.global_transform(Bone::Hips),
+            RelativeTo::World => return,
+        };
+
+        self.pos = anchor_pos + anchor_rot * self.config_pos;
+        self.base_rot = anchor_rot * self.config_rot;
+        self.rot = self.base_rot * Quat::from_rotation_z(-self.angle.to_radians());
     }
 
     pub fn trackers(&self, mut f: impl FnMut(DefaultAtom, Vec3A, Quat)) {
@@ -100,6 +277,70 @@ impl Wheel {
     }
 
     pub fn update(&mut self, dt: f64, pose: &Pose) {
+        self.place(pose);
+        self.update_return(dt);
         self.technique.update(dt, pose)
     }
+
+    /// Starts turning the wheel back to center once no value has been given for the timeout, and
+    /// carries on turning it until it gets there or is given a value.
+    fn update_return(&mut self, dt: f64) {
+        let config = &self.config.return_to_center;
+        let Some(since_value) = self.since_value.filter(|_| config.enabled) else {
+            return;
+        };
+
+        let now = since_value + dt;
+        self.since_value = Some(now);
+
+        if since_value < config.timeout {
+            if now >= config.timeout {
+                log::warn!(
+                    "The wheel hasn't been given a value for {} second(s), so it will return to center. Check that its input is still connected.",
+                    config.timeout
+                );
+                self.return_from = Some(self.angle);
+            }
+
+            return;
+        }
+
+        let Some(from) = self.return_from else {
+            return;
+        };
+
+        let elapsed = now - config.timeout;
+        let t = if config.duration > 0.0 {
+            (elapsed / config.duration).min(1.0) as f32
+        } else {
+            1.0
+        };
+
+        if t >= 1.0 {
+            self.return_from = None;
+        }
+
+        // Ease in and out, so that the wheel doesn't jerk into motion or stop abruptly.
+        let eased = t * t * (3.0 - 2.0 * t);
+        self.turn_to(from * (1.0 - eased));
+    }
+
+    /// How far the fingers of a hand curl around the wheel, from 0 (relaxed) to 1.
+    fn grip_curl(&self, limb: Limb) -> f32 {
+        self.grip_curl[limb as u8 as usize]
+    }
+
+    /// How firmly the hand that `bone` belongs to holds the wheel, or `None` if that hand doesn't
+    /// hold it at all.
+    fn bone_grip(&self, bone: Bone) -> Option<f32> {
+        match Limb::iter().find(|l| l.end_bone().affected().contains(bone)) {
+            Some(l) if !self.hands.contains(l) => None,
+            Some(l) => Some(self.grip[l as u8 as usize]),
+            None => Some(1.0),
+        }
+    }
+}
+
+fn parse_degrees<'de, D: Deserializer<'de>>(de: D) -> Result<f32, D::Error> {
+    crate::units::degrees(de).map(|d| d as f32)
 }
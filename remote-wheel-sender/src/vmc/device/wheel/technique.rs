@@ -1,6 +1,8 @@
-use anyhow::{Error as AnyError, Result as AnyResult};
+use anyhow::Result as AnyResult;
+use enumset::EnumSet;
 use glam::{Quat, Vec3A};
-use serde::Deserialize;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
 
 use crate::vmc::{
     avatar::Pose,
@@ -9,15 +11,24 @@ use crate::vmc::{
 };
 
 mod glue;
+mod idle;
 mod rotational;
 
-#[derive(Debug)]
-pub enum Technique {
+pub use idle::IdleConfig;
+
+#[derive(Clone, Debug)]
+pub struct Technique {
+    kind: Kind,
+    idle: Option<idle::Idle>,
+}
+
+#[derive(Clone, Debug)]
+enum Kind {
     Glue(glue::Technique),
     Rotational(rotational::Technique),
 }
 
-#[derive(Clone, Debug, Deserialize)]
+#[derive(Clone, Debug, Deserialize, JsonSchema, Serialize)]
 #[serde(deny_unknown_fields, rename_all = "kebab-case", tag = "type")]
 pub enum TechniqueConfig {
     Glue(glue::TechniqueConfig),
@@ -25,31 +36,75 @@ pub enum TechniqueConfig {
 }
 
 impl Technique {
-    pub fn pose_forward(&self, wheel: &Wheel, f: impl FnMut(Bone, f32, ForwardPose)) {
-        match *self {
-            Technique::Glue(ref t) => t.pose_forward(wheel, f),
-            Technique::Rotational(ref t) => t.pose_forward(wheel, f),
+    /// Creates a technique from `config`, for a wheel that's held by `hands`, with the idle motion
+    /// from `idle` on top of it.
+    pub fn new(config: TechniqueConfig, idle: IdleConfig, hands: EnumSet<Limb>) -> AnyResult<Self> {
+        let kind = match config {
+            TechniqueConfig::Glue(c) => Kind::Glue(c.into()),
+            TechniqueConfig::Rotational(c) => {
+                Kind::Rotational(rotational::Technique::new(c, hands)?)
+            }
+        };
+
+        Ok(Technique {
+            kind,
+            idle: idle::Idle::new(idle)?,
+        })
+    }
+
+    pub fn pose_forward(&self, wheel: &Wheel, mut f: impl FnMut(Bone, f32, ForwardPose)) {
+        // The idle motion changes the technique's pose rather than adding its own, so that it's
+        // weighted the same and only ever moves what the technique already holds.
+        let mut f = |bone, weight, pose| match self.idle {
+            Some(ref idle) => f(bone, weight, idle.pose_forward(bone, pose)),
+            None => f(bone, weight, pose),
+        };
+
+        match self.kind {
+            Kind::Glue(ref t) => t.pose_forward(wheel, &mut f),
+            Kind::Rotational(ref t) => t.pose_forward(wheel, &mut f),
         }
     }
 
-    pub fn pose_inverse(&self, pose: &Pose, wheel: &Wheel, f: impl FnMut(Limb, f32, Vec3A, Quat)) {
-        match *self {
-            Technique::Glue(ref t) => t.pose_inverse(pose, wheel, f),
-            Technique::Rotational(ref t) => t.pose_inverse(pose, wheel, f),
+    pub fn pose_inverse(
+        &self,
+        pose: &Pose,
+        wheel: &Wheel,
+        mut f: impl FnMut(Limb, f32, Vec3A, Quat),
+    ) {
+        let mut f = |limb, weight, pos, rot| match self.idle {
+            Some(ref idle) => {
+                let (pos, rot) = idle.pose_inverse(wheel, limb, pos, rot);
+                f(limb, weight, pos, rot)
+            }
+            None => f(limb, weight, pos, rot),
+        };
+
+        match self.kind {
+            Kind::Glue(ref t) => t.pose_inverse(pose, wheel, &mut f),
+            Kind::Rotational(ref t) => t.pose_inverse(pose, wheel, &mut f),
         }
     }
 
     pub fn set_rotation(&mut self, angle: f32) {
-        match *self {
-            Technique::Glue(ref mut t) => t.set_rotation(angle),
-            Technique::Rotational(ref mut t) => t.set_rotation(angle),
+        match self.kind {
+            Kind::Glue(ref mut t) => t.set_rotation(angle),
+            Kind::Rotational(ref mut t) => t.set_rotation(angle),
+        }
+
+        if let Some(ref mut idle) = self.idle {
+            idle.set_rotation(angle);
         }
     }
 
     pub fn update(&mut self, dt: f64, pose: &Pose) {
-        match *self {
-            Technique::Glue(ref mut t) => t.update(dt, pose),
-            Technique::Rotational(ref mut t) => t.update(dt, pose),
+        match self.kind {
+            Kind::Glue(ref mut t) => t.update(dt, pose),
+            Kind::Rotational(ref mut t) => t.update(dt, pose),
+        }
+
+        if let Some(ref mut idle) = self.idle {
+            idle.update(dt);
         }
     }
 }
@@ -59,14 +114,3 @@ impl Default for TechniqueConfig {
         TechniqueConfig::Glue(Default::default())
     }
 }
-
-impl TryFrom<TechniqueConfig> for Technique {
-    type Error = AnyError;
-
-    fn try_from(config: TechniqueConfig) -> AnyResult<Self> {
-        match config {
-            TechniqueConfig::Glue(c) => Ok(Technique::Glue(c.into())),
-            TechniqueConfig::Rotational(c) => Ok(Technique::Rotational(c.try_into()?)),
-        }
-    }
-}
@@ -1,9 +1,10 @@
 use std::f32::consts::TAU;
 
 use glam::{EulerRot, Quat, Vec2, Vec3A};
+use schemars::JsonSchema;
 use serde::{
     de::{Error, Unexpected},
-    Deserialize, Deserializer,
+    Deserialize, Deserializer, Serialize, Serializer,
 };
 
 use crate::vmc::{
@@ -12,19 +13,29 @@ use crate::vmc::{
     device::{ForwardPose, Wheel},
 };
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct Technique {
     left_hand_angle: f32,
     right_hand_angle: f32,
 }
 
-#[derive(Clone, Debug, Deserialize)]
+#[derive(Clone, Debug, Deserialize, JsonSchema, Serialize)]
 #[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
 pub struct TechniqueConfig {
-    #[serde(default = "left_hand_default", deserialize_with = "parse_position")]
+    #[serde(
+        default = "left_hand_default",
+        deserialize_with = "parse_position",
+        serialize_with = "write_position"
+    )]
+    #[schemars(schema_with = "crate::units::degrees_schema")]
     left_hand: f32,
 
-    #[serde(default = "right_hand_default", deserialize_with = "parse_position")]
+    #[serde(
+        default = "right_hand_default",
+        deserialize_with = "parse_position",
+        serialize_with = "write_position"
+    )]
+    #[schemars(schema_with = "crate::units::degrees_schema")]
     right_hand: f32,
 }
 
@@ -37,7 +48,7 @@ fn right_hand_default() -> f32 {
 }
 
 impl Technique {
-    pub fn pose_forward(&self, _: &Wheel, mut f: impl FnMut(Bone, f32, ForwardPose)) {
+    pub fn pose_forward(&self, wheel: &Wheel, mut f: impl FnMut(Bone, f32, ForwardPose)) {
         let bones = [
             (Bone::LeftIndexProximal, Quat::from_rotation_z(0.1 * TAU)),
             (
@@ -108,6 +119,16 @@ impl Technique {
         ];
 
         for (bone, rot) in bones {
+            let curl = Limb::iter()
+                .find(|l| l.end_bone().affected().contains(bone))
+                .map_or(1.0, |l| wheel.grip_curl(l));
+
+            // At a full curl, the pose is left exactly as it is.
+            let rot = if curl < 1.0 {
+                Quat::IDENTITY.slerp(rot, curl)
+            } else {
+                rot
+            };
             f(bone, 1.0, ForwardPose::Local(rot));
         }
     }
@@ -135,7 +156,7 @@ impl Technique {
 }
 
 fn parse_position<'de, D: Deserializer<'de>>(de: D) -> Result<f32, D::Error> {
-    let raw = f32::deserialize(de)?;
+    let raw = crate::units::degrees(de)? as f32;
     (0.0..=360.0)
         .contains(&raw)
         .then(|| f32::to_radians(90.0 - raw).rem_euclid(TAU))
@@ -147,6 +168,11 @@ fn parse_position<'de, D: Deserializer<'de>>(de: D) -> Result<f32, D::Error> {
         })
 }
 
+/// The inverse of [`parse_position`].
+fn write_position<S: Serializer>(angle: &f32, ser: S) -> Result<S::Ok, S::Error> {
+    (90.0 - angle.to_degrees()).rem_euclid(360.0).serialize(ser)
+}
+
 impl From<TechniqueConfig> for Technique {
     fn from(config: TechniqueConfig) -> Self {
         Technique {
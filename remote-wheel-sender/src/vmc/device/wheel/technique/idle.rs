@@ -0,0 +1,199 @@
+use std::f32::consts::{PI, TAU};
+
+use anyhow::{ensure, Result as AnyResult};
+use glam::{Quat, Vec3A};
+use schemars::JsonSchema;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::vmc::{
+    bone::{Bone, Limb},
+    device::{ForwardPose, Wheel},
+};
+
+/// How far the wheel can turn, in degrees, while still counting as held still.
+const STILL_THRESHOLD: f32 = 1.0;
+
+/// How long the idle motion takes to fade in, in seconds. It stops as soon as the wheel turns.
+const FADE_TIME: f64 = 1.0;
+
+/// The part of each period over which a hand shuffles its grip.
+const SHUFFLE_PART: f32 = 0.25;
+
+/// Subtle motion of the hands while the wheel is held still, so that they don't look frozen.
+#[derive(Clone, Debug)]
+pub struct Idle {
+    amplitude: f32,
+    period: f64,
+    delay: f64,
+
+    /// The angle that the wheel is being held still at, in degrees.
+    still_angle: f32,
+    still_time: f64,
+    /// How far through the current period the motion is, from 0 to 1.
+    phase: f64,
+    /// Whether the left hand is the one to shuffle in this period. The hands take turns.
+    left_shuffles: bool,
+    /// How much of the motion is applied, from 0 to 1.
+    weight: f32,
+}
+
+#[derive(Clone, Debug, Deserialize, JsonSchema, Serialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct IdleConfig {
+    enabled: bool,
+
+    #[serde(deserialize_with = "parse_degrees", serialize_with = "write_degrees")]
+    #[schemars(schema_with = "crate::units::degrees_schema")]
+    amplitude: f32,
+
+    #[serde(deserialize_with = "crate::units::seconds")]
+    #[schemars(schema_with = "crate::units::seconds_schema")]
+    period: f64,
+
+    #[serde(deserialize_with = "crate::units::seconds")]
+    #[schemars(schema_with = "crate::units::seconds_schema")]
+    delay: f64,
+}
+
+impl Idle {
+    /// Creates the idle motion from `config`, or nothing if it's disabled.
+    pub fn new(config: IdleConfig) -> AnyResult<Option<Self>> {
+        if !config.enabled {
+            return Ok(None);
+        }
+
+        ensure!(
+            config.amplitude >= 0.0,
+            "idle amplitude must be at least 0 degrees!"
+        );
+        ensure!(
+            config.period > 0.0,
+            "idle period must be greater than 0 seconds!"
+        );
+        ensure!(
+            config.delay >= 0.0,
+            "idle delay must be at least 0 seconds!"
+        );
+
+        Ok(Some(Idle {
+            amplitude: config.amplitude,
+            period: config.period,
+            delay: config.delay,
+
+            still_angle: 0.0,
+            still_time: 0.0,
+            phase: 0.0,
+            left_shuffles: true,
+            weight: 0.0,
+        }))
+    }
+
+    /// Curls the fingers of a finger bone's pose slightly, in and out over each period.
+    pub fn pose_forward(&self, bone: Bone, pose: ForwardPose) -> ForwardPose {
+        let Some(finger) = finger(bone) else {
+            return pose;
+        };
+
+        if self.weight <= 0.0 {
+            return pose;
+        }
+
+        // Each finger is a little behind the one before it, so they don't move in lockstep.
+        let wave = f32::sin(TAU * self.phase as f32 - 0.15 * TAU * finger as f32);
+        let sign = if Limb::LeftHand.end_bone().affected().contains(bone) {
+            1.0
+        } else {
+            -1.0
+        };
+        let curl = Quat::from_rotation_z(sign * self.weight * self.amplitude * wave);
+
+        match pose {
+            ForwardPose::Local(rot) => ForwardPose::Local(rot * curl),
+            ForwardPose::Global(rot) => ForwardPose::Global(rot * curl),
+        }
+    }
+
+    /// Slides a hand a little way along the rim and back once in a while, alternating hands, as if
+    /// re-gripping the wheel.
+    pub fn pose_inverse(&self, wheel: &Wheel, limb: Limb, pos: Vec3A, rot: Quat) -> (Vec3A, Quat) {
+        let shuffling = if self.left_shuffles {
+            Limb::LeftHand
+        } else {
+            Limb::RightHand
+        };
+
+        let t = self.phase as f32 / SHUFFLE_PART;
+        if self.weight <= 0.0 || limb != shuffling || t >= 1.0 {
+            return (pos, rot);
+        }
+
+        let angle = self.weight * self.amplitude * f32::sin(PI * t);
+        let slide = Quat::from_axis_angle((wheel.rot * Vec3A::Z).into(), angle);
+        (wheel.pos + slide * (pos - wheel.pos), slide * rot)
+    }
+
+    pub fn set_rotation(&mut self, angle: f32) {
+        if (angle - self.still_angle).abs() > STILL_THRESHOLD {
+            self.still_angle = angle;
+            self.still_time = 0.0;
+            self.weight = 0.0;
+        }
+    }
+
+    pub fn update(&mut self, dt: f64) {
+        self.still_time += dt;
+        if self.still_time < self.delay {
+            return;
+        }
+
+        if self.weight == 0.0 {
+            self.phase = 0.0;
+        }
+
+        self.weight = (self.weight as f64 + dt / FADE_TIME).min(1.0) as f32;
+        self.phase += dt / self.period;
+        if self.phase >= 1.0 {
+            self.phase = self.phase.fract();
+            self.left_shuffles = !self.left_shuffles;
+        }
+    }
+}
+
+impl Default for IdleConfig {
+    fn default() -> Self {
+        IdleConfig {
+            enabled: false,
+            amplitude: 3.0f32.to_radians(),
+            period: 4.0,
+            delay: 5.0,
+        }
+    }
+}
+
+/// Which finger a bone belongs to, counting from the index finger, if it's a finger bone other than
+/// a thumb's.
+fn finger(bone: Bone) -> Option<u8> {
+    match bone {
+        Bone::LeftIndexProximal | Bone::LeftIndexIntermediate | Bone::LeftIndexDistal => Some(0),
+        Bone::RightIndexProximal | Bone::RightIndexIntermediate | Bone::RightIndexDistal => Some(0),
+        Bone::LeftMiddleProximal | Bone::LeftMiddleIntermediate | Bone::LeftMiddleDistal => Some(1),
+        Bone::RightMiddleProximal | Bone::RightMiddleIntermediate | Bone::RightMiddleDistal => {
+            Some(1)
+        }
+        Bone::LeftRingProximal | Bone::LeftRingIntermediate | Bone::LeftRingDistal => Some(2),
+        Bone::RightRingProximal | Bone::RightRingIntermediate | Bone::RightRingDistal => Some(2),
+        Bone::LeftLittleProximal | Bone::LeftLittleIntermediate | Bone::LeftLittleDistal => Some(3),
+        Bone::RightLittleProximal | Bone::RightLittleIntermediate | Bone::RightLittleDistal => {
+            Some(3)
+        }
+        _ => None,
+    }
+}
+
+fn parse_degrees<'de, D: Deserializer<'de>>(de: D) -> Result<f32, D::Error> {
+    crate::units::degrees(de).map(|d| (d as f32).to_radians())
+}
+
+fn write_degrees<S: Serializer>(angle: &f32, ser: S) -> Result<S::Ok, S::Error> {
+    angle.to_degrees().serialize(ser)
+}
@@ -1,8 +1,10 @@
 use std::{cmp::Ordering, f32::consts::TAU};
 
-use anyhow::{ensure, Error as AnyError, Result as AnyResult};
+use anyhow::{ensure, Result as AnyResult};
+use enumset::EnumSet;
 use glam::{EulerRot, Quat, Vec2, Vec3A};
-use serde::{Deserialize, Deserializer};
+use schemars::JsonSchema;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
 use crate::vmc::{
     avatar::Pose,
@@ -11,7 +13,7 @@ use crate::vmc::{
     math::FloatExt,
 };
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct Technique {
     cross_start: f32,
     cross_grip: f32,
@@ -25,17 +27,38 @@ pub struct Technique {
     turn_out: f32,
     turn_end: f32,
 
+    release_angle: Option<f32>,
+    release_blend: f32,
+    release_rest: f32,
+    release_out: f32,
+
     rotation_base: f32,
     rotation_offset: f32,
+    /// How far the rotation offset can go in each direction before the hands re-grip the wheel.
+    wrap: [f32; 2],
+
+    /// The hand that has let go of the wheel, if any, and how far it is towards its rest position.
+    released: Option<Limb>,
+    release: f32,
 }
 
-#[derive(Clone, Debug, Deserialize)]
+#[derive(Clone, Debug, Deserialize, JsonSchema, Serialize)]
 #[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
 pub struct TechniqueConfig {
-    #[serde(default = "cross_start_default", deserialize_with = "parse_degrees")]
+    #[serde(
+        default = "cross_start_default",
+        deserialize_with = "parse_degrees",
+        serialize_with = "write_degrees"
+    )]
+    #[schemars(schema_with = "crate::units::degrees_schema")]
     cross_start: f32,
 
-    #[serde(default = "cross_grip_default", deserialize_with = "parse_degrees")]
+    #[serde(
+        default = "cross_grip_default",
+        deserialize_with = "parse_degrees",
+        serialize_with = "write_degrees"
+    )]
+    #[schemars(schema_with = "crate::units::degrees_schema")]
     cross_grip: f32,
 
     #[serde(default = "cross_out_default")]
@@ -44,13 +67,28 @@ pub struct TechniqueConfig {
     #[serde(default = "cross_retract_default")]
     cross_retract: f32,
 
-    #[serde(default = "cross_end_default", deserialize_with = "parse_degrees")]
+    #[serde(
+        default = "cross_end_default",
+        deserialize_with = "parse_degrees",
+        serialize_with = "write_degrees"
+    )]
+    #[schemars(schema_with = "crate::units::degrees_schema")]
     cross_end: f32,
 
-    #[serde(default = "turn_start_default", deserialize_with = "parse_degrees")]
+    #[serde(
+        default = "turn_start_default",
+        deserialize_with = "parse_degrees",
+        serialize_with = "write_degrees"
+    )]
+    #[schemars(schema_with = "crate::units::degrees_schema")]
     turn_start: f32,
 
-    #[serde(default = "turn_grip_default", deserialize_with = "parse_degrees")]
+    #[serde(
+        default = "turn_grip_default",
+        deserialize_with = "parse_degrees",
+        serialize_with = "write_degrees"
+    )]
+    #[schemars(schema_with = "crate::units::degrees_schema")]
     turn_grip: f32,
 
     #[serde(default = "turn_lift_default")]
@@ -59,10 +97,41 @@ pub struct TechniqueConfig {
     #[serde(default = "turn_out_default")]
     turn_out: f32,
 
-    #[serde(default = "turn_end_default", deserialize_with = "parse_degrees")]
+    #[serde(
+        default = "turn_end_default",
+        deserialize_with = "parse_degrees",
+        serialize_with = "write_degrees"
+    )]
+    #[schemars(schema_with = "crate::units::degrees_schema")]
     turn_end: f32,
+
+    #[serde(
+        deserialize_with = "parse_optional_degrees",
+        serialize_with = "write_optional_degrees"
+    )]
+    #[schemars(schema_with = "crate::units::degrees_schema")]
+    release_angle: Option<f32>,
+
+    #[serde(
+        default = "release_blend_default",
+        deserialize_with = "parse_degrees",
+        serialize_with = "write_degrees"
+    )]
+    #[schemars(schema_with = "crate::units::degrees_schema")]
+    release_blend: f32,
+
+    #[serde(deserialize_with = "parse_degrees", serialize_with = "write_degrees")]
+    #[schemars(schema_with = "crate::units::degrees_schema")]
+    release_rest: f32,
+
+    #[serde(default = "release_out_default")]
+    release_out: f32,
 }
 
+/// How far back under `release-angle` the wheel must turn before a released hand grips it again,
+/// so that it doesn't flicker between the two at the boundary.
+const RELEASE_HYSTERESIS: f32 = 10.0 * TAU / 360.0;
+
 fn cross_start_default() -> f32 {
     110.0f32.to_radians()
 }
@@ -103,8 +172,16 @@ fn turn_end_default() -> f32 {
     290.0f32.to_radians()
 }
 
+fn release_blend_default() -> f32 {
+    30.0f32.to_radians()
+}
+
+fn release_out_default() -> f32 {
+    0.1
+}
+
 impl Technique {
-    pub fn pose_forward(&self, _: &Wheel, mut f: impl FnMut(Bone, f32, ForwardPose)) {
+    pub fn pose_forward(&self, wheel: &Wheel, mut f: impl FnMut(Bone, f32, ForwardPose)) {
         let left_bones = [
             (Bone::LeftIndexProximal, Quat::from_rotation_z(0.1 * TAU)),
             (
@@ -177,14 +254,26 @@ impl Technique {
             (Bone::RightThumbDistal, Quat::from_rotation_y(0.03 * TAU)),
         ];
 
-        let left_open = self.pose_forward_single(-self.rotation_offset);
+        let left_open = self.pose_forward_single(-self.rotation_offset)
+            * self.grip(Limb::LeftHand)
+            * wheel.grip_curl(Limb::LeftHand);
         for (bone, rot) in left_bones {
-            f(bone, 1.0, ForwardPose::Local(Quat::IDENTITY.slerp(rot, left_open)));
+            f(
+                bone,
+                1.0,
+                ForwardPose::Local(Quat::IDENTITY.slerp(rot, left_open)),
+            );
         }
 
-        let right_open = self.pose_forward_single(self.rotation_offset);
+        let right_open = self.pose_forward_single(self.rotation_offset)
+            * self.grip(Limb::RightHand)
+            * wheel.grip_curl(Limb::RightHand);
         for (bone, rot) in right_bones {
-            f(bone, 1.0, ForwardPose::Local(Quat::IDENTITY.slerp(rot, right_open)));
+            f(
+                bone,
+                1.0,
+                ForwardPose::Local(Quat::IDENTITY.slerp(rot, right_open)),
+            );
         }
     }
 
@@ -275,6 +364,23 @@ impl Technique {
                 global_pos = global_pos.lerp(retract_point, retract * self.cross_retract);
             }
 
+            // A released hand rests just outside of the rim, where it won't turn with the wheel.
+            let release = 1.0 - self.grip(limb);
+            if release > 0.0 {
+                let rest_angle = scale * self.release_rest + angle;
+                let rest_pos = wheel.pos
+                    + wheel.base_rot
+                        * Vec3A::from((
+                            (1.0 + self.release_out) * wheel.radius * Vec2::from_angle(rest_angle),
+                            0.0,
+                        ));
+                let rest_rot = wheel.base_rot
+                    * Quat::from_euler(EulerRot::YXZ, yaw, yaw - yaw.signum() * rest_angle, 0.0);
+
+                global_pos = global_pos.lerp(rest_pos, release);
+                global_rot = global_rot.slerp(rest_rot, release);
+            }
+
             f(limb, 1.0, global_pos, global_rot);
         }
     }
@@ -282,24 +388,53 @@ impl Technique {
     pub fn set_rotation(&mut self, angle: f32) {
         let mut base = self.rotation_base;
         let mut offset = angle.to_radians() - base;
-        let wrap = f32::max(self.cross_end + self.cross_grip, self.turn_end + self.turn_grip);
+        let [min, max] = self.wrap;
 
-        while offset < -wrap {
+        while offset < min {
             base -= TAU;
             offset += TAU;
         }
 
-        while offset > wrap {
+        while offset > max {
             base += TAU;
             offset -= TAU;
         }
 
         self.rotation_base = base;
         self.rotation_offset = offset;
+
+        if let Some(release_angle) = self.release_angle {
+            let rotation = base + offset;
+            let outside = rotation.abs();
+
+            if outside >= release_angle {
+                // The hand that lets go is the one that would be turning with the wheel.
+                self.released = Some(if rotation > 0.0 {
+                    Limb::LeftHand
+                } else {
+                    Limb::RightHand
+                });
+            } else if outside <= release_angle - RELEASE_HYSTERESIS {
+                self.released = None;
+            }
+
+            self.release = outside
+                .inv_lerp(release_angle, release_angle + self.release_blend)
+                .clamp(0.0, 1.0);
+        }
     }
 
     pub fn update(&mut self, _: f64, _: &Pose) {}
 
+    /// How firmly a hand is gripping the wheel, from 0 (let go) to 1.
+    fn grip(&self, limb: Limb) -> f32 {
+        if self.released == Some(limb) {
+            1.0 - self.release
+        } else {
+            1.0
+        }
+    }
+
     fn pose_forward_single(&self, offset: f32) -> f32 {
         match offset.partial_cmp(&0.0) {
             Some(Ordering::Greater) => {
@@ -379,10 +514,8 @@ impl Technique {
     }
 }
 
-impl TryFrom<TechniqueConfig> for Technique {
-    type Error = AnyError;
-
-    fn try_from(config: TechniqueConfig) -> AnyResult<Self> {
+impl Technique {
+    pub fn new(config: TechniqueConfig, hands: EnumSet<Limb>) -> AnyResult<Self> {
         ensure!(config.cross_grip >= 0.0, "cross-grip must be at least 0 degrees!");
         ensure!(config.cross_retract >= 0.0, "cross-retract must be at least than 0!");
         ensure!(config.turn_grip >= 0.0, "turn-grip must be at least 0 degrees!");
@@ -396,6 +529,30 @@ impl TryFrom<TechniqueConfig> for Technique {
         ensure!(config.turn_end >= config.turn_start, "turn-end must be greater than turn-start!");
         ensure!(config.turn_end < TAU, "turn-end must be less than 360 degrees!");
 
+        if let Some(release_angle) = config.release_angle {
+            ensure!(release_angle > RELEASE_HYSTERESIS, "release-angle must be greater than 10 degrees!");
+        }
+        ensure!(config.release_blend > 0.0, "release-blend must be greater than 0 degrees!");
+        ensure!(config.release_out >= 0.0, "release-out must be at least 0!");
+
+        let cross = config.cross_end + config.cross_grip;
+        let turn = config.turn_end + config.turn_grip;
+
+        // With both hands, each re-grips when the other has finished crossing or turning. A single
+        // hand has to re-grip on its own once it has crossed over (or turned back) far enough.
+        let wrap = if hands.contains(Limb::LeftHand) && hands.contains(Limb::RightHand) {
+            let wrap = f32::max(cross, turn);
+            [-wrap, wrap]
+        } else {
+            ensure!(cross + turn >= TAU, "With one hand, cross-end and turn-end (with their grips) must add up to at least 360 degrees!");
+
+            if hands.contains(Limb::LeftHand) {
+                [-cross, turn]
+            } else {
+                [-turn, cross]
+            }
+        };
+
         Ok(Technique {
             cross_start: config.cross_start,
             cross_grip: config.cross_grip,
@@ -409,8 +566,17 @@ impl TryFrom<TechniqueConfig> for Technique {
             turn_out: config.turn_out,
             turn_end: config.turn_end,
 
+            release_angle: config.release_angle,
+            release_blend: config.release_blend,
+            release_rest: config.release_rest,
+            release_out: config.release_out,
+
             rotation_base: 0.0,
             rotation_offset: 0.0,
+            wrap,
+
+            released: None,
+            release: 0.0,
         })
     }
 }
@@ -429,10 +595,27 @@ impl Default for TechniqueConfig {
             turn_lift: turn_lift_default(),
             turn_out: turn_out_default(),
             turn_end: turn_end_default(),
+
+            release_angle: None,
+            release_blend: release_blend_default(),
+            release_rest: 0.0,
+            release_out: release_out_default(),
         }
     }
 }
 
 fn parse_degrees<'de, D: Deserializer<'de>>(de: D) -> Result<f32, D::Error> {
-    f32::deserialize(de).map(f32::to_radians)
+    crate::units::degrees(de).map(|d| (d as f32).to_radians())
+}
+
+fn parse_optional_degrees<'de, D: Deserializer<'de>>(de: D) -> Result<Option<f32>, D::Error> {
+    parse_degrees(de).map(Some)
+}
+
+fn write_degrees<S: Serializer>(angle: &f32, ser: S) -> Result<S::Ok, S::Error> {
+    angle.to_degrees().serialize(ser)
+}
+
+fn write_optional_degrees<S: Serializer>(angle: &Option<f32>, ser: S) -> Result<S::Ok, S::Error> {
+    angle.map(f32::to_degrees).serialize(ser)
 }
@@ -1,38 +1,274 @@
 use std::borrow::Cow;
+use std::collections::HashMap;
 use std::io::ErrorKind;
 use std::path::{Path, PathBuf};
 
-use anyhow::{bail, Context as _, Result as AnyResult};
-use serde::Deserialize;
-use smol::net::SocketAddr;
+use anyhow::{bail, ensure, Context as _, Result as AnyResult};
+use serde::{Deserialize, Deserializer};
+use smol::net::{IpAddr, Ipv4Addr, SocketAddr};
 
 #[derive(Debug, Deserialize)]
 pub struct AppConfig {
     pub display: DisplayConfig,
     pub osc: OscConfig,
+    #[serde(default)]
+    pub trace: TraceConfig,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct DisplayConfig {
     #[serde(default)]
     pub background: Color,
-    pub wheel: PathBuf,
+
+    #[serde(default)]
+    pub transparent: bool,
+
+    #[serde(deserialize_with = "parse_wheels")]
+    pub wheel: Vec<WheelConfig>,
+
+    /// The space that each wheel is drawn in, which its image is scaled to fit. Without it, each
+    /// wheel gets a square as large as its image.
+    #[serde(default)]
+    pub size: Option<[f32; 2]>,
+
+    /// Images larger than this in either dimension are scaled down when they're loaded.
+    #[serde(default = "default_max_texture_size", rename = "max-texture-size")]
+    pub max_texture_size: u32,
+
+    /// How long the wheel may take to sweep to a new rotation, in milliseconds. 0 shows each
+    /// rotation as soon as it's received.
+    #[serde(default = "default_smoothing_ms", rename = "smoothing-ms")]
+    pub smoothing_ms: u64,
+
+    /// Wheel images that can be switched to by name while running.
+    #[serde(default)]
+    pub images: HashMap<String, PathBuf>,
+
+    #[serde(default)]
+    pub pedal: Vec<PedalConfig>,
+
+    #[serde(default)]
+    pub indicator: Vec<IndicatorConfig>,
+
+    #[serde(default)]
+    pub readout: Option<ReadoutConfig>,
+
+    #[serde(default)]
+    pub ticks: Option<TicksConfig>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct WheelConfig {
+    pub image: PathBuf,
+    #[serde(default = "default_wheel_address")]
+    pub address: String,
+    #[serde(default)]
+    pub label: Option<String>,
+    #[serde(default = "default_image_address", rename = "image-address")]
+    pub image_address: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PedalConfig {
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    pub address: String,
+    pub color: Color,
+    #[serde(default)]
+    pub side: Side,
+    #[serde(default = "default_pedal_width")]
+    pub width: f32,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct IndicatorConfig {
+    pub address: String,
+    #[serde(default)]
+    pub label: Option<String>,
+    #[serde(default = "default_indicator_on", rename = "on-color")]
+    pub on_color: Color,
+    #[serde(default = "default_indicator_off", rename = "off-color")]
+    pub off_color: Color,
+    #[serde(default)]
+    pub position: IndicatorPosition,
+    #[serde(default)]
+    pub shape: IndicatorShape,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ReadoutConfig {
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    #[serde(default = "default_readout_size")]
+    pub size: f32,
+    #[serde(default = "default_white")]
+    pub color: Color,
+    #[serde(default = "default_readout_timeout")]
+    pub timeout: f64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TicksConfig {
+    pub angles: Vec<f64>,
+    #[serde(default = "default_white")]
+    pub color: Color,
+    #[serde(default = "default_tick_length")]
+    pub length: f32,
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Side {
+    Left,
+    #[default]
+    Right,
+}
+
+/// Where an indicator is drawn: stacked in a corner of the wheels, or in a strip below them.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum IndicatorPosition {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+    #[default]
+    Below,
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum IndicatorShape {
+    #[default]
+    Circle,
+    Rect,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct OscConfig {
-    pub address: SocketAddr,
+    /// Where OSC messages are received. Without it, only the WebSocket listener is used.
+    #[serde(default)]
+    pub address: Option<SocketAddr>,
+    #[serde(default)]
+    pub transport: Transport,
+
+    /// The interface that a multicast group is joined on, if `address` is one.
+    #[serde(default, rename = "multicast-interface")]
+    pub multicast_interface: Option<MulticastInterface>,
+
+    /// Where WebSocket clients may connect to send rotations as JSON.
+    #[serde(default, rename = "ws-address")]
+    pub ws_address: Option<SocketAddr>,
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Transport {
+    #[default]
+    Udp,
+    Tcp,
+}
+
+/// A network interface: the address of an IPv4 interface, or the index of an IPv6 one.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq)]
+#[serde(untagged)]
+pub enum MulticastInterface {
+    Address(Ipv4Addr),
+    Index(u32),
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct TraceConfig {
+    /// A file to append each received rotation to.
+    #[serde(default)]
+    pub record: Option<PathBuf>,
+
+    /// A file to play rotations from, instead of receiving OSC.
+    #[serde(default)]
+    pub replay: Option<PathBuf>,
+
+    #[serde(default, rename = "loop")]
+    pub looped: bool,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_smoothing_ms() -> u64 {
+    100
+}
+
+fn default_max_texture_size() -> u32 {
+    2048
+}
+
+fn default_pedal_width() -> f32 {
+    30.0
+}
+
+fn default_indicator_on() -> Color {
+    Color(34, 204, 34, 255)
+}
+
+fn default_indicator_off() -> Color {
+    Color(68, 68, 68, 255)
+}
+
+fn default_readout_size() -> f32 {
+    24.0
+}
+
+fn default_readout_timeout() -> f64 {
+    1.0
+}
+
+fn default_tick_length() -> f32 {
+    20.0
+}
+
+fn default_white() -> Color {
+    Color(255, 255, 255, 255)
+}
+
+fn default_wheel_address() -> String {
+    String::from("/wheel/rotation")
+}
+
+fn default_image_address() -> String {
+    String::from("/wheel/image")
+}
+
+fn parse_wheels<'de, D: Deserializer<'de>>(de: D) -> Result<Vec<WheelConfig>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged, expecting = "an image path or a list of wheels")]
+    enum Wheels {
+        One(PathBuf),
+        Many(Vec<WheelConfig>),
+    }
+
+    match Wheels::deserialize(de)? {
+        Wheels::One(image) => Ok(vec![WheelConfig {
+            image,
+            address: default_wheel_address(),
+            label: None,
+            image_address: default_image_address(),
+        }]),
+        Wheels::Many(wheels) => Ok(wheels),
+    }
 }
 
 impl AppConfig {
-    pub fn read_from(path: impl AsRef<Path>) -> AnyResult<AppConfig> {
-        Self::read_from_path(path.as_ref())
+    /// Reads the configuration from `path`. If it doesn't exist and `write_default` is set, the
+    /// default configuration is written there and used.
+    pub fn read_from(path: impl AsRef<Path>, write_default: bool) -> AnyResult<AppConfig> {
+        Self::read_from_path(path.as_ref(), write_default)
     }
 
-    fn read_from_path(path: &Path) -> AnyResult<AppConfig> {
+    fn read_from_path(path: &Path, write_default: bool) -> AnyResult<AppConfig> {
         let raw: Cow<str> = match std::fs::read_to_string(path) {
             Ok(s) => Cow::Owned(s),
-            Err(e) if e.kind() == ErrorKind::NotFound => {
+            Err(e) if write_default && e.kind() == ErrorKind::NotFound => {
                 let default = include_str!("default-config.toml");
 
                 std::fs::write(path, default).with_context(|| {
@@ -49,10 +285,87 @@ impl AppConfig {
             })?,
         };
 
-        let config = toml::from_str(raw.as_ref())
+        let mut config: AppConfig = toml::from_str(raw.as_ref())
             .with_context(|| format!("Failed to parse configuration from <{}>", path.display()))?;
+
+        config
+            .display
+            .validate()
+            .and_then(|()| config.osc.validate())
+            .with_context(|| format!("Invalid configuration in <{}>", path.display()))?;
+
+        config.resolve_paths(path.parent().unwrap_or(Path::new("")));
         Ok(config)
     }
+
+    /// Makes image and trace paths relative to the configuration file's directory, rather than the
+    /// working directory.
+    fn resolve_paths(&mut self, dir: &Path) {
+        let images = self.display.wheel.iter_mut().map(|w| &mut w.image);
+        for image in images.chain(self.display.images.values_mut()) {
+            // "default" is the embedded image, not a file.
+            if image.as_path() != Path::new("default") {
+                *image = dir.join(&*image);
+            }
+        }
+
+        let traces = [&mut self.trace.record, &mut self.trace.replay];
+        for trace in traces.into_iter().flatten() {
+            *trace = dir.join(&*trace);
+        }
+    }
+}
+
+impl DisplayConfig {
+    fn validate(&self) -> AnyResult<()> {
+        if let Some([width, height]) = self.size {
+            ensure!(
+                width >= 1.0 && height >= 1.0,
+                "The display size must be at least 1 pixel in each dimension."
+            );
+        }
+
+        ensure!(
+            self.max_texture_size > 0,
+            "max-texture-size must be at least 1 pixel."
+        );
+        Ok(())
+    }
+}
+
+impl OscConfig {
+    fn validate(&self) -> AnyResult<()> {
+        let Some(address) = self.address else {
+            ensure!(
+                self.ws_address.is_some(),
+                "Either an OSC address or a ws-address must be given to receive rotations on."
+            );
+            return Ok(());
+        };
+
+        let ip = address.ip();
+        if !ip.is_multicast() {
+            return Ok(());
+        }
+
+        ensure!(
+            self.transport == Transport::Udp,
+            "Multicast group {} can only be listened on over UDP.",
+            address
+        );
+
+        match (ip, self.multicast_interface) {
+            (IpAddr::V4(_), Some(MulticastInterface::Index(_))) => bail!(
+                "Multicast group {} is an IPv4 group, so multicast-interface must be the address of an interface.",
+                address
+            ),
+            (IpAddr::V6(_), Some(MulticastInterface::Address(_))) => bail!(
+                "Multicast group {} is an IPv6 group, so multicast-interface must be the index of an interface.",
+                address
+            ),
+            _ => Ok(()),
+        }
+    }
 }
 
 #[derive(Clone, Copy, Debug, Default, serde_with::DeserializeFromStr)]
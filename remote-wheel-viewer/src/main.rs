@@ -1,22 +1,53 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
+use std::collections::HashMap;
 use std::io::Cursor;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::process::ExitCode;
 use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
 
-use anyhow::{anyhow, Context as _, Result as AnyResult};
-use eframe::egui::{Frame, TextureFilter, TextureOptions};
+use anyhow::{anyhow, bail, ensure, Context as _, Result as AnyResult};
+use eframe::egui::{
+    Align2, Color32, FontId, Frame, Painter, Pos2, Rect, Stroke, TextureFilter, TextureOptions,
+    Vec2,
+};
 use eframe::epaint::{ColorImage, ImageData, TextureHandle};
+use eframe::glow::{self, HasContext as _};
 use eframe::{CreationContext, NativeOptions};
 use futures::prelude::*;
+use image::imageops::FilterType;
 use image::{ImageFormat, RgbaImage};
 use rosc::{OscMessage, OscPacket, OscType};
 use smol::channel::{Receiver, Sender, TryRecvError};
-use smol::net::{SocketAddr, UdpSocket};
+use smol::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, TcpListener, TcpStream, UdpSocket};
 
 mod config;
-use config::AppConfig;
+mod trace;
+mod ws;
+use config::{
+    AppConfig, IndicatorPosition, IndicatorShape, MulticastInterface, OscConfig, ReadoutConfig,
+    Side, TicksConfig, Transport,
+};
+use trace::Recorder;
+
+const CONFIG_FILE: &str = "remote-wheel-viewer.toml";
+
+const LABEL_HEIGHT: f32 = 24.0;
+const PEDAL_SPACING: f32 = 10.0;
+
+const INDICATOR_SIZE: f32 = 16.0;
+const INDICATOR_SPACING: f32 = 8.0;
+const INDICATOR_STRIP_HEIGHT: f32 = 32.0;
+
+/// The largest change in rotation, in degrees, that the wheel sweeps through. Anything larger snaps
+/// straight to the new rotation, rather than spinning the wheel around.
+const MAX_SWEEP: f64 = 180.0;
+
+const WHEEL_TEXTURE: TextureOptions = TextureOptions {
+    magnification: TextureFilter::Linear,
+    minification: TextureFilter::Linear,
+};
 
 fn main() -> ExitCode {
     match run() {
@@ -45,37 +76,66 @@ fn main() -> ExitCode {
 }
 
 fn run() -> AnyResult<()> {
+    // The default configuration is only written where it would be looked for by default.
+    let (path, write_default) = match config_arg()? {
+        Some(path) => (path, false),
+        None => (PathBuf::from(CONFIG_FILE), true),
+    };
+
     let config =
-        AppConfig::read_from("remote-wheel-viewer.toml").context("Failed to load configuration")?;
+        AppConfig::read_from(&path, write_default).context("Failed to load configuration")?;
+    eprintln!("Configuration: <{}>", path.display());
 
-    let wheel_image = if config.display.wheel == Path::new("default") {
-        image::io::Reader::with_format(
-            Cursor::new(include_bytes!("default-wheel.png")),
-            ImageFormat::Png,
-        )
-        .decode()
-        .context("Failed to decode wheel image")?
+    ensure!(
+        !config.display.wheel.is_empty(),
+        "At least one wheel must be configured"
+    );
+
+    let wheel_images = config
+        .display
+        .wheel
+        .iter()
+        .map(|w| load_wheel_image(&w.image, config.display.max_texture_size))
+        .collect::<AnyResult<Vec<_>>>()?;
+
+    let wheel_spaces = wheel_images
+        .iter()
+        .map(|image| wheel_space(&config, image))
+        .collect::<Vec<_>>();
+    let wheels_width: f32 = wheel_spaces.iter().map(|s| s.x).sum();
+    let wheels_height = wheel_spaces.iter().map(|s| s.y).fold(0.0, f32::max);
+    let labels_height = if config.display.wheel.iter().any(|w| w.label.is_some()) {
+        LABEL_HEIGHT
     } else {
-        image::io::Reader::open(&config.display.wheel)
-            .context("Failed to open wheel image")?
-            .decode()
-            .context("Failed to decode wheel image")?
+        0.0
     };
+    let strip_height = indicator_strip_height(&config);
 
-    let wheel_image = wheel_image.to_rgba8();
-    let (wheel_width, wheel_height) = wheel_image.dimensions();
-    let wheel_square = u32::max(wheel_width, wheel_height);
+    let pedals_width: f32 = config
+        .display
+        .pedal
+        .iter()
+        .filter(|p| p.enabled)
+        .map(|p| p.width + PEDAL_SPACING)
+        .sum();
 
     let options = NativeOptions {
-        initial_window_size: Some((wheel_square as f32, wheel_square as f32).into()),
+        initial_window_size: Some(
+            (
+                wheels_width + pedals_width,
+                wheels_height + labels_height + strip_height,
+            )
+                .into(),
+        ),
         resizable: false,
+        transparent: config.display.transparent,
         ..NativeOptions::default()
     };
 
     eframe::run_native(
         "Remote Wheel Viewer",
         options,
-        Box::new(move |cc| Box::new(App::new(cc, config, wheel_image))),
+        Box::new(move |cc| Box::new(App::new(cc, config, wheel_images))),
     )
     .map_err(|e| anyhow!("{}", e))
     .context("Failed to run application")?;
@@ -83,10 +143,134 @@ fn run() -> AnyResult<()> {
     Ok(())
 }
 
+/// The height of the strip below the wheels that holds indicators, if any are placed there.
+fn indicator_strip_height(config: &AppConfig) -> f32 {
+    let below = config
+        .display
+        .indicator
+        .iter()
+        .any(|i| i.position == IndicatorPosition::Below);
+
+    if below {
+        INDICATOR_STRIP_HEIGHT
+    } else {
+        0.0
+    }
+}
+
+/// Reads the path of the configuration file from the command line (`--config <path>`), if given.
+fn config_arg() -> AnyResult<Option<PathBuf>> {
+    let mut config = None;
+    let mut args = std::env::args_os().skip(1);
+
+    while let Some(arg) = args.next() {
+        match arg.to_str() {
+            Some("--config") => {
+                let path = args.next().context("--config requires a path")?;
+                config = Some(PathBuf::from(path));
+            }
+
+            _ => bail!(
+                "Unrecognized argument: {}. Usage: remote-wheel-viewer [--config <path>]",
+                arg.to_string_lossy()
+            ),
+        }
+    }
+
+    Ok(config)
+}
+
+/// Loads a wheel image, scaling it down (keeping its aspect ratio) if it's larger than `max_size` in
+/// either dimension, so that a huge image doesn't take up a huge texture.
+fn load_wheel_image(path: &Path, max_size: u32) -> AnyResult<RgbaImage> {
+    let image = if path == Path::new("default") {
+        image::io::Reader::with_format(
+            Cursor::new(include_bytes!("default-wheel.png")),
+            ImageFormat::Png,
+        )
+        .decode()
+        .context("Failed to decode wheel image")?
+    } else {
+        image::io::Reader::open(path)
+            .with_context(|| format!("Failed to open wheel image <{}>", path.display()))?
+            .decode()
+            .with_context(|| format!("Failed to decode wheel image <{}>", path.display()))?
+    };
+
+    let (width, height) = (image.width(), image.height());
+    let image = if width > max_size || height > max_size {
+        eprintln!(
+            "Wheel image <{}> is {width}x{height}, so it will be scaled down to fit within {max_size}x{max_size}.",
+            path.display()
+        );
+        image.resize(max_size, max_size, FilterType::Triangle)
+    } else {
+        image
+    };
+
+    Ok(image.to_rgba8())
+}
+
+/// Whether the window's framebuffer can hold transparency. Without an alpha channel, a translucent
+/// clear color reads back as opaque.
+fn supports_transparency(gl: &glow::Context) -> bool {
+    let mut pixel = [0u8; 4];
+    unsafe {
+        gl.bind_framebuffer(glow::FRAMEBUFFER, None);
+        gl.clear_color(0.0, 0.0, 0.0, 0.5);
+        gl.clear(glow::COLOR_BUFFER_BIT);
+        gl.read_pixels(
+            0,
+            0,
+            1,
+            1,
+            glow::RGBA,
+            glow::UNSIGNED_BYTE,
+            glow::PixelPackData::Slice(&mut pixel),
+        );
+    }
+
+    pixel[3] < 255
+}
+
+fn texture_data(image: &RgbaImage) -> ImageData {
+    let (width, height) = image.dimensions();
+    ImageData::Color(ColorImage::from_rgba_unmultiplied(
+        [width as usize, height as usize],
+        image,
+    ))
+}
+
+/// The space that a wheel is drawn in: the configured size, or else a square as large as its image.
+fn wheel_space(config: &AppConfig, image: &RgbaImage) -> Vec2 {
+    match config.display.size {
+        Some(size) => size.into(),
+        None => {
+            let (width, height) = image.dimensions();
+            Vec2::splat(u32::max(width, height) as f32)
+        }
+    }
+}
+
+/// The size to draw an image at so that it fits in `space`, keeping its aspect ratio. It's only
+/// scaled up to fill the space if `grow` is set.
+fn fit_size(image: Vec2, space: Vec2, grow: bool) -> Vec2 {
+    let scale = f32::min(space.x / image.x, space.y / image.y);
+    let scale = if grow { scale } else { scale.min(1.0) };
+    image * scale
+}
+
 struct App {
     background: eframe::egui::Color32,
-    rotation: f64,
-    wheel_texture: TextureHandle,
+    wheels: Vec<Wheel>,
+    /// Whether wheel images are scaled up to fill their space, rather than only down to fit it.
+    grow: bool,
+    pedals: Vec<Pedal>,
+    indicators: Vec<Indicator>,
+    strip_height: f32,
+    readout: Option<ReadoutConfig>,
+    ticks: Option<TicksConfig>,
+    smoothing: Duration,
 
     async_thread: Option<JoinHandle<()>>,
     event_rx: Receiver<AppEvent>,
@@ -94,12 +278,61 @@ struct App {
 }
 
 impl App {
-    fn new(cc: &CreationContext, config: AppConfig, wheel_image: RgbaImage) -> Self {
-        let (wheel_width, wheel_height) = wheel_image.dimensions();
-        let wheel_data = ImageData::Color(ColorImage::from_rgba_unmultiplied(
-            [wheel_width as usize, wheel_height as usize],
-            &wheel_image,
-        ));
+    fn new(cc: &CreationContext, mut config: AppConfig, wheel_images: Vec<RgbaImage>) -> Self {
+        let wheels = std::iter::zip(&config.display.wheel, wheel_images)
+            .enumerate()
+            .map(|(index, (wheel, image))| Wheel {
+                label: wheel.label.clone(),
+                rotation: 0.0,
+                updated: None,
+                previous: None,
+                from: 0.0,
+                size: wheel_space(&config, &image),
+                texture: cc.egui_ctx.load_texture(
+                    format!("wheel{index}"),
+                    texture_data(&image),
+                    WHEEL_TEXTURE,
+                ),
+            })
+            .collect();
+
+        let pedals = config
+            .display
+            .pedal
+            .iter()
+            .filter(|p| p.enabled)
+            .map(|p| Pedal {
+                color: p.color.into(),
+                side: p.side,
+                width: p.width,
+                value: 0.0,
+            })
+            .collect();
+
+        let indicators = config
+            .display
+            .indicator
+            .iter()
+            .map(|i| Indicator {
+                label: i.label.clone(),
+                on_color: i.on_color.into(),
+                off_color: i.off_color.into(),
+                position: i.position,
+                shape: i.shape,
+                on: false,
+            })
+            .collect();
+        let strip_height = indicator_strip_height(&config);
+
+        let readout = config.display.readout.take().filter(|r| r.enabled);
+        let ticks = config.display.ticks.take();
+        let smoothing = Duration::from_millis(config.display.smoothing_ms);
+
+        if config.display.transparent && !cc.gl.as_deref().is_some_and(supports_transparency) {
+            eprintln!(
+                "A transparent window is not supported here, so only the background color will be used."
+            );
+        }
 
         let (event_tx, event_rx) = smol::channel::unbounded();
         let (run_tx, run_rx) = smol::channel::unbounded();
@@ -107,15 +340,14 @@ impl App {
 
         App {
             background: config.display.background.into(),
-            rotation: 0.0,
-            wheel_texture: cc.egui_ctx.load_texture(
-                "wheel",
-                wheel_data,
-                TextureOptions {
-                    magnification: TextureFilter::Linear,
-                    minification: TextureFilter::Linear,
-                },
-            ),
+            wheels,
+            grow: config.display.size.is_some(),
+            pedals,
+            indicators,
+            strip_height,
+            readout,
+            ticks,
+            smoothing,
 
             async_thread: Some(std::thread::spawn(move || {
                 async_thread(config, egui, event_tx, run_rx)
@@ -134,8 +366,30 @@ impl eframe::App for App {
     fn update(&mut self, ctx: &eframe::egui::Context, frame: &mut eframe::Frame) {
         loop {
             match self.event_rx.try_recv() {
-                Ok(AppEvent::RotationUpdate(f)) => {
-                    self.rotation = f;
+                Ok(AppEvent::RotationUpdate(index, f, time)) => {
+                    if let Some(wheel) = self.wheels.get_mut(index) {
+                        wheel.set_rotation(f, time, self.smoothing);
+                    }
+                }
+
+                Ok(AppEvent::PedalUpdate(index, f)) => {
+                    if let Some(pedal) = self.pedals.get_mut(index) {
+                        pedal.value = f.clamp(0.0, 1.0);
+                    }
+                }
+
+                Ok(AppEvent::IndicatorUpdate(index, on)) => {
+                    if let Some(indicator) = self.indicators.get_mut(index) {
+                        indicator.on = on;
+                    }
+                }
+
+                // The wheel keeps its place in the window, so a differently-sized image is fitted
+                // into the space of the original.
+                Ok(AppEvent::ImageChange(index, image)) => {
+                    if let Some(wheel) = self.wheels.get_mut(index) {
+                        wheel.texture.set(texture_data(&image), WHEEL_TEXTURE);
+                    }
                 }
 
                 Err(TryRecvError::Closed) => frame.close(),
@@ -146,16 +400,96 @@ impl eframe::App for App {
         eframe::egui::CentralPanel::default()
             .frame(Frame::none())
             .show(ctx, |ui| {
-                ui.centered_and_justified(|ui| {
-                    ui.add(
-                        eframe::egui::widgets::Image::new(
-                            self.wheel_texture.id(),
-                            self.wheel_texture.size_vec2(),
-                        )
-                        .rotate(self.rotation.to_radians() as f32, [0.5, 0.5].into()),
+                let full_rect = ui.max_rect();
+                let left_width: f32 = self
+                    .pedals
+                    .iter()
+                    .filter(|p| p.side == Side::Left)
+                    .map(|p| p.width + PEDAL_SPACING)
+                    .sum();
+                let right_width: f32 = self
+                    .pedals
+                    .iter()
+                    .filter(|p| p.side == Side::Right)
+                    .map(|p| p.width + PEDAL_SPACING)
+                    .sum();
+
+                let mut left_x = full_rect.left() + PEDAL_SPACING;
+                let mut right_x = full_rect.right() - right_width + PEDAL_SPACING;
+                for pedal in &self.pedals {
+                    let x = match pedal.side {
+                        Side::Left => &mut left_x,
+                        Side::Right => &mut right_x,
+                    };
+
+                    let rect = Rect::from_min_max(
+                        Pos2::new(*x, full_rect.top() + PEDAL_SPACING),
+                        Pos2::new(*x + pedal.width, full_rect.bottom() - PEDAL_SPACING),
                     );
-                });
+                    *x += pedal.width + PEDAL_SPACING;
+
+                    pedal.paint(ui.painter(), rect);
+                }
+
+                let now = Instant::now();
+                let wheels_left = full_rect.left() + left_width;
+                let mut wheel_x = wheels_left;
+                for wheel in &self.wheels {
+                    let (rotation, moving) = wheel.displayed_rotation(now, self.smoothing);
+                    if moving {
+                        ctx.request_repaint();
+                    }
+
+                    let wheel_rect =
+                        Rect::from_min_size(Pos2::new(wheel_x, full_rect.top()), wheel.size);
+                    wheel_x += wheel.size.x;
+
+                    // The image is placed by its exact center rather than laid out, since rounding
+                    // its corner to a whole pixel would move the center of an odd-sized image off
+                    // of the point that it's rotated around.
+                    let image_size = fit_size(wheel.texture.size_vec2(), wheel.size, self.grow);
+                    eframe::egui::widgets::Image::new(wheel.texture.id(), image_size)
+                        .rotate(rotation.to_radians() as f32, Vec2::splat(0.5))
+                        .paint_at(ui, Rect::from_center_size(wheel_rect.center(), image_size));
+
+                    if let Some(ref ticks) = self.ticks {
+                        ticks.paint(ui.painter(), wheel_rect);
+                    }
+
+                    if let Some(ref readout) = self.readout {
+                        readout.paint(ui.painter(), wheel_rect, wheel);
+                    }
+
+                    if let Some(ref label) = wheel.label {
+                        ui.painter().text(
+                            Pos2::new(
+                                wheel_rect.center().x,
+                                full_rect.bottom() - self.strip_height - 0.5 * LABEL_HEIGHT,
+                            ),
+                            Align2::CENTER_CENTER,
+                            label,
+                            FontId::proportional(0.75 * LABEL_HEIGHT),
+                            Color32::WHITE,
+                        );
+                    }
+                }
+
+                let wheels_height = self.wheels.iter().map(|w| w.size.y).fold(0.0, f32::max);
+                let wheels_rect = Rect::from_min_max(
+                    Pos2::new(wheels_left, full_rect.top()),
+                    Pos2::new(wheel_x, full_rect.top() + wheels_height),
+                );
+                let strip_rect = Rect::from_min_max(
+                    Pos2::new(wheels_left, full_rect.bottom() - self.strip_height),
+                    Pos2::new(wheel_x, full_rect.bottom()),
+                );
+                paint_indicators(ui.painter(), &self.indicators, wheels_rect, strip_rect);
             });
+
+        // Make sure that the readout changes to "no data" once the timeout has passed.
+        if let Some(ref readout) = self.readout {
+            ctx.request_repaint_after(Duration::from_secs_f64(readout.timeout));
+        }
     }
 
     fn on_exit(&mut self, _: Option<&eframe::glow::Context>) {
@@ -168,8 +502,232 @@ impl eframe::App for App {
     }
 }
 
+struct Wheel {
+    label: Option<String>,
+    /// The space that the wheel is drawn in, which its image is fitted into.
+    size: Vec2,
+    texture: TextureHandle,
+
+    /// The latest rotation, and when it was received.
+    rotation: f64,
+    updated: Option<Instant>,
+
+    /// When the rotation before it was received, and the rotation that the wheel was drawn at then,
+    /// which it sweeps from.
+    previous: Option<Instant>,
+    from: f64,
+}
+
+impl Wheel {
+    fn set_rotation(&mut self, rotation: f64, time: Instant, smoothing: Duration) {
+        let (from, _) = self.displayed_rotation(time, smoothing);
+        self.from = if (rotation - from).abs() > MAX_SWEEP {
+            rotation
+        } else {
+            from
+        };
+
+        self.previous = self.updated;
+        self.updated = Some(time);
+        self.rotation = rotation;
+    }
+
+    /// The rotation that the wheel should be drawn at, at `now`, and whether it's still moving
+    /// towards the latest rotation.
+    fn displayed_rotation(&self, now: Instant, smoothing: Duration) -> (f64, bool) {
+        let Some(updated) = self.updated else {
+            return (self.rotation, false);
+        };
+
+        // The wheel takes as long to sweep to a rotation as that rotation took to arrive, so that
+        // it keeps moving between regular updates, but no longer than the smoothing time.
+        let duration = self
+            .previous
+            .map_or(smoothing, |previous| {
+                updated.saturating_duration_since(previous)
+            })
+            .min(smoothing);
+
+        if duration.is_zero() {
+            return (self.rotation, false);
+        }
+
+        let t = now.saturating_duration_since(updated).as_secs_f64() / duration.as_secs_f64();
+        if t < 1.0 {
+            (self.from + t * (self.rotation - self.from), true)
+        } else {
+            (self.rotation, false)
+        }
+    }
+}
+
+struct Pedal {
+    color: Color32,
+    side: Side,
+    width: f32,
+    value: f32,
+}
+
+impl Pedal {
+    fn paint(&self, painter: &eframe::egui::Painter, rect: Rect) {
+        let fill_rect = Rect::from_min_max(
+            Pos2::new(rect.left(), rect.bottom() - self.value * rect.height()),
+            rect.max,
+        );
+
+        painter.rect_filled(fill_rect, 0.0, self.color);
+        painter.rect_stroke(rect, 0.0, Stroke::new(2.0, self.color));
+    }
+}
+
+struct Indicator {
+    label: Option<String>,
+    on_color: Color32,
+    off_color: Color32,
+    position: IndicatorPosition,
+    shape: IndicatorShape,
+    on: bool,
+}
+
+impl Indicator {
+    fn paint(&self, painter: &Painter, lamp: Rect) {
+        let color = if self.on {
+            self.on_color
+        } else {
+            self.off_color
+        };
+
+        match self.shape {
+            IndicatorShape::Circle => {
+                painter.circle_filled(lamp.center(), 0.5 * lamp.width(), color)
+            }
+            IndicatorShape::Rect => painter.rect_filled(lamp, 0.25 * lamp.width(), color),
+        }
+    }
+}
+
+/// Draws each indicator with its label beside it. Indicators in a corner of the wheels are stacked
+/// inwards from it, and those below the wheels are drawn in a row centered in the strip.
+fn paint_indicators(painter: &Painter, indicators: &[Indicator], wheels: Rect, strip: Rect) {
+    let font = FontId::proportional(0.75 * INDICATOR_SIZE);
+    let lamp_size = Vec2::splat(INDICATOR_SIZE);
+    let gap = 0.5 * INDICATOR_SPACING;
+
+    let mut corner_offsets = [0.0; 4];
+    let mut row = Vec::new();
+
+    for indicator in indicators {
+        let (corner, left, top) = match indicator.position {
+            IndicatorPosition::TopLeft => (0, true, true),
+            IndicatorPosition::TopRight => (1, false, true),
+            IndicatorPosition::BottomLeft => (2, true, false),
+            IndicatorPosition::BottomRight => (3, false, false),
+            IndicatorPosition::Below => {
+                let label = indicator
+                    .label
+                    .clone()
+                    .map(|l| painter.layout_no_wrap(l, font.clone(), Color32::WHITE));
+                row.push((indicator, label));
+                continue;
+            }
+        };
+
+        let offset = &mut corner_offsets[corner];
+        let x = if left {
+            wheels.left() + INDICATOR_SPACING
+        } else {
+            wheels.right() - INDICATOR_SPACING - INDICATOR_SIZE
+        };
+        let y = if top {
+            wheels.top() + INDICATOR_SPACING + *offset
+        } else {
+            wheels.bottom() - INDICATOR_SPACING - INDICATOR_SIZE - *offset
+        };
+        *offset += INDICATOR_SIZE + INDICATOR_SPACING;
+
+        let lamp = Rect::from_min_size(Pos2::new(x, y), lamp_size);
+        indicator.paint(painter, lamp);
+
+        if let Some(ref label) = indicator.label {
+            let (pos, align) = if left {
+                (
+                    lamp.right_center() + Vec2::new(gap, 0.0),
+                    Align2::LEFT_CENTER,
+                )
+            } else {
+                (
+                    lamp.left_center() - Vec2::new(gap, 0.0),
+                    Align2::RIGHT_CENTER,
+                )
+            };
+            painter.text(pos, align, label, font.clone(), Color32::WHITE);
+        }
+    }
+
+    let row_width = row
+        .iter()
+        .map(|(_, label)| INDICATOR_SIZE + label.as_ref().map_or(0.0, |l| gap + l.size().x))
+        .sum::<f32>()
+        + INDICATOR_SPACING * row.len().saturating_sub(1) as f32;
+
+    let mut x = strip.center().x - 0.5 * row_width;
+    let y = strip.center().y;
+    for (indicator, label) in row {
+        let lamp = Rect::from_center_size(Pos2::new(x + 0.5 * INDICATOR_SIZE, y), lamp_size);
+        indicator.paint(painter, lamp);
+        x += INDICATOR_SIZE;
+
+        if let Some(label) = label {
+            let width = label.size().x;
+            painter.galley(Pos2::new(x + gap, y - 0.5 * label.size().y), label);
+            x += gap + width;
+        }
+
+        x += INDICATOR_SPACING;
+    }
+}
+
+impl ReadoutConfig {
+    fn paint(&self, painter: &eframe::egui::Painter, rect: Rect, wheel: &Wheel) {
+        let timeout = Duration::from_secs_f64(self.timeout);
+        let text = match wheel.updated {
+            Some(time) if time.elapsed() <= timeout => format!("{:.1}°", wheel.rotation),
+            _ => String::from("no data"),
+        };
+
+        painter.text(
+            rect.center_top() + Vec2::new(0.0, 0.25 * self.size),
+            Align2::CENTER_TOP,
+            text,
+            FontId::proportional(self.size),
+            self.color.into(),
+        );
+    }
+}
+
+impl TicksConfig {
+    fn paint(&self, painter: &eframe::egui::Painter, rect: Rect) {
+        let center = rect.center();
+        let radius = 0.5 * rect.width().min(rect.height());
+
+        for angle in &self.angles {
+            // Angles are clockwise from the top, the same as the wheel's rotation.
+            let (sin, cos) = (angle.to_radians() as f32).sin_cos();
+            let dir = Vec2::new(sin, -cos);
+
+            painter.line_segment(
+                [center + dir * (radius - self.length), center + dir * radius],
+                Stroke::new(3.0, self.color),
+            );
+        }
+    }
+}
+
 enum AppEvent {
-    RotationUpdate(f64),
+    RotationUpdate(usize, f64, Instant),
+    PedalUpdate(usize, f32),
+    IndicatorUpdate(usize, bool),
+    ImageChange(usize, RgbaImage),
 }
 
 fn async_thread(
@@ -178,91 +736,426 @@ fn async_thread(
     event_tx: Sender<AppEvent>,
     run_rx: Receiver<()>,
 ) {
-    let listen_fut = listen_osc(config.osc.address, egui, event_tx);
+    // A trace is replayed instead of receiving OSC, so that it isn't mixed with live rotations.
+    if let Some(path) = config.trace.replay {
+        let replay_fut = trace::replay(&path, config.trace.looped, egui, event_tx);
+
+        smol::block_on(async move {
+            futures::select_biased! {
+                _ = run_rx.recv().fuse() => {},
+                r = replay_fut.fuse() => r.expect("Failed to replay trace"),
+            }
+        });
+
+        return;
+    }
+
+    let listen_fut = listen(config, egui, event_tx);
 
     smol::block_on(async move {
         futures::select_biased! {
             _ = run_rx.recv().fuse() => {},
-            r = listen_fut.fuse() => r.expect("Failed to listen for OSC messages"),
+            r = listen_fut.fuse() => r.expect("Failed to listen for rotations"),
         }
     });
 }
 
-async fn listen_osc(
-    addr: SocketAddr,
+/// Receives rotations (and everything else) over OSC, and over WebSocket if it's configured, until
+/// either fails.
+async fn listen(
+    config: AppConfig,
     egui: eframe::egui::Context,
     sender: Sender<AppEvent>,
 ) -> AnyResult<()> {
+    let wheel_addrs = config
+        .display
+        .wheel
+        .iter()
+        .map(|w| {
+            rosc::address::OscAddress::new(w.address.clone())
+                .with_context(|| format!("Failed to create OSC address {} for wheel", w.address))
+        })
+        .collect::<AnyResult<Vec<_>>>()?;
+
+    let image_addrs = config
+        .display
+        .wheel
+        .iter()
+        .map(|w| {
+            rosc::address::OscAddress::new(w.image_address.clone()).with_context(|| {
+                format!(
+                    "Failed to create OSC address {} for wheel image",
+                    w.image_address
+                )
+            })
+        })
+        .collect::<AnyResult<Vec<_>>>()?;
+
+    let pedal_addrs = config
+        .display
+        .pedal
+        .iter()
+        .filter(|p| p.enabled)
+        .map(|p| {
+            rosc::address::OscAddress::new(p.address.clone())
+                .with_context(|| format!("Failed to create OSC address {} for pedal", p.address))
+        })
+        .collect::<AnyResult<Vec<_>>>()?;
+
+    let indicator_addrs = config
+        .display
+        .indicator
+        .iter()
+        .map(|i| {
+            rosc::address::OscAddress::new(i.address.clone()).with_context(|| {
+                format!("Failed to create OSC address {} for indicator", i.address)
+            })
+        })
+        .collect::<AnyResult<Vec<_>>>()?;
+
+    let recorder = config.trace.record.map(Recorder::open).transpose()?;
+
+    let handler = OscHandler {
+        wheel_addrs,
+        image_addrs,
+        pedal_addrs,
+        indicator_addrs,
+        images: config.display.images,
+        max_texture_size: config.display.max_texture_size,
+        recorder,
+        egui,
+        sender,
+    };
+
+    let osc_fut = async {
+        match (config.osc.address, config.osc.transport) {
+            (Some(address), Transport::Udp) => listen_udp(address, &config.osc, &handler).await,
+            (Some(address), Transport::Tcp) => listen_tcp(address, &handler).await,
+            (None, _) => future::pending().await,
+        }
+    };
+
+    let ws_fut = async {
+        match config.osc.ws_address {
+            Some(address) => ws::listen(address, &handler).await,
+            None => future::pending().await,
+        }
+    };
+
+    futures::try_join!(osc_fut, ws_fut)?;
+    Ok(())
+}
+
+async fn listen_udp(
+    address: SocketAddr,
+    config: &OscConfig,
+    handler: &OscHandler,
+) -> AnyResult<()> {
+    // A multicast group is joined on a socket bound to its port on any address, since not every OS
+    // can bind to the group itself.
+    let addr = match address.ip() {
+        IpAddr::V4(ip) if ip.is_multicast() => (Ipv4Addr::UNSPECIFIED, address.port()).into(),
+        IpAddr::V6(ip) if ip.is_multicast() => (Ipv6Addr::UNSPECIFIED, address.port()).into(),
+        _ => address,
+    };
+
     let socket = UdpSocket::bind(addr)
         .await
         .with_context(|| format!("Failed to bind to UDP address {}", addr))?;
 
-    let rotation_addr = rosc::address::OscAddress::new(String::from("/wheel/rotation"))
-        .context("Failed to create OSC address for wheel rotation")?;
+    match (address.ip(), config.multicast_interface) {
+        (IpAddr::V4(group), interface) if group.is_multicast() => {
+            let interface = match interface {
+                Some(MulticastInterface::Address(address)) => address,
+                _ => Ipv4Addr::UNSPECIFIED,
+            };
+
+            socket
+                .join_multicast_v4(group, interface)
+                .with_context(|| format!("Failed to join multicast group {}", group))?;
+            eprintln!("Joined multicast group {}.", group);
+        }
+
+        (IpAddr::V6(group), interface) if group.is_multicast() => {
+            let interface = match interface {
+                Some(MulticastInterface::Index(index)) => index,
+                _ => 0,
+            };
+
+            socket
+                .join_multicast_v6(&group, interface)
+                .with_context(|| format!("Failed to join multicast group {}", group))?;
+            eprintln!("Joined multicast group {}.", group);
+        }
+
+        _ => {}
+    }
 
     let mut buf = [0; 4096];
-    let mut messages = Vec::new();
 
     while let Ok(size) = socket.recv(&mut buf).await {
-        let msg = &buf[..size];
-
-        match rosc::decoder::decode_udp(msg) {
-            Ok((_, packet)) => {
-                fn collect_messages(packet: OscPacket, messages: &mut Vec<OscMessage>) {
-                    match packet {
-                        OscPacket::Bundle(bundle) => {
-                            for packet in bundle.content {
-                                collect_messages(packet, messages);
-                            }
-                        }
+        handler.handle_data(&buf[..size]).await;
+    }
+
+    Ok(())
+}
 
-                        OscPacket::Message(message) => messages.push(message),
+async fn listen_tcp(addr: SocketAddr, handler: &OscHandler) -> AnyResult<()> {
+    let listener = TcpListener::bind(addr)
+        .await
+        .with_context(|| format!("Failed to bind to TCP address {}", addr))?;
+
+    let mut connections = futures::stream::FuturesUnordered::new();
+
+    loop {
+        futures::select! {
+            accepted = listener.accept().fuse() => match accepted {
+                Ok((stream, peer)) => connections.push(read_tcp(stream, peer, handler)),
+                Err(e) => eprintln!("Failed to accept OSC connection: {}", e),
+            },
+
+            _ = connections.select_next_some() => {},
+        }
+    }
+}
+
+async fn read_tcp(mut stream: TcpStream, peer: SocketAddr, handler: &OscHandler) {
+    let mut buf = [0; 4096];
+    let mut decoder = SlipDecoder::default();
+
+    loop {
+        let size = match stream.read(&mut buf).await {
+            Ok(0) => break,
+            Ok(size) => size,
+            Err(e) => {
+                eprintln!("OSC connection from {} was lost: {}", peer, e);
+                break;
+            }
+        };
+
+        for &byte in &buf[..size] {
+            if let Some(packet) = decoder.push(byte) {
+                handler.handle_data(&packet).await;
+            }
+        }
+    }
+}
+
+/// Splits a stream of bytes into packets, according to the SLIP framing used by OSC 1.1.
+#[derive(Default)]
+struct SlipDecoder {
+    packet: Vec<u8>,
+    escaped: bool,
+    overflowed: bool,
+}
+
+impl SlipDecoder {
+    const END: u8 = 0xC0;
+    const ESC: u8 = 0xDB;
+    const ESC_END: u8 = 0xDC;
+    const ESC_ESC: u8 = 0xDD;
+
+    /// The largest packet that will be accepted. Anything larger is discarded, rather than letting a
+    /// misbehaving sender use up memory.
+    const MAX_LEN: usize = 65536;
+
+    fn push(&mut self, byte: u8) -> Option<Vec<u8>> {
+        let byte = match (self.escaped, byte) {
+            (false, Self::END) => {
+                let packet = std::mem::take(&mut self.packet);
+                let overflowed = std::mem::take(&mut self.overflowed);
+                return (!packet.is_empty() && !overflowed).then_some(packet);
+            }
+
+            (false, Self::ESC) => {
+                self.escaped = true;
+                return None;
+            }
+
+            (true, Self::ESC_END) => Self::END,
+            (true, Self::ESC_ESC) => Self::ESC,
+            (_, byte) => byte,
+        };
+
+        self.escaped = false;
+        if self.packet.len() < Self::MAX_LEN {
+            self.packet.push(byte);
+        } else if !self.overflowed {
+            eprintln!("Discarding OSC packet larger than {} bytes.", Self::MAX_LEN);
+            self.overflowed = true;
+        }
+
+        None
+    }
+}
+
+struct OscHandler {
+    wheel_addrs: Vec<rosc::address::OscAddress>,
+    image_addrs: Vec<rosc::address::OscAddress>,
+    pedal_addrs: Vec<rosc::address::OscAddress>,
+    indicator_addrs: Vec<rosc::address::OscAddress>,
+    images: HashMap<String, PathBuf>,
+    max_texture_size: u32,
+    recorder: Option<Recorder>,
+    egui: eframe::egui::Context,
+    sender: Sender<AppEvent>,
+}
+
+impl OscHandler {
+    async fn handle_data(&self, data: &[u8]) {
+        match rosc::decoder::decode_udp(data) {
+            Ok((_, packet)) => self.handle_packet(packet).await,
+            Err(e) => eprintln!("Failed to decode data packet: {}", e),
+        }
+    }
+
+    /// Passes a received rotation on to the wheel at `index`, recording it if a trace is being
+    /// recorded.
+    async fn send_rotation(&self, index: usize, value: f64) {
+        let now = Instant::now();
+        if let Some(ref recorder) = self.recorder {
+            recorder.record(index, value, now);
+        }
+
+        let _ = self
+            .sender
+            .send(AppEvent::RotationUpdate(index, value, now))
+            .await;
+        self.egui.request_repaint();
+    }
+
+    async fn handle_packet(&self, packet: OscPacket) {
+        fn collect_messages(packet: OscPacket, messages: &mut Vec<OscMessage>) {
+            match packet {
+                OscPacket::Bundle(bundle) => {
+                    for packet in bundle.content {
+                        collect_messages(packet, messages);
                     }
                 }
 
-                collect_messages(packet, &mut messages);
+                OscPacket::Message(message) => messages.push(message),
+            }
+        }
+
+        let mut messages = Vec::new();
+        collect_messages(packet, &mut messages);
+
+        for message in messages {
+            let message_matcher = match rosc::address::Matcher::new(&message.addr) {
+                Ok(m) => m,
+                Err(e) => {
+                    eprintln!(
+                        "Failed to parse received OSC address ({}): {}",
+                        message.addr, e
+                    );
+                    continue;
+                }
+            };
+
+            for (index, wheel_addr) in self.wheel_addrs.iter().enumerate() {
+                if !message_matcher.match_address(wheel_addr) {
+                    continue;
+                }
 
-                for message in messages.drain(..) {
-                    let message_matcher = match rosc::address::Matcher::new(&message.addr) {
-                        Ok(m) => m,
-                        Err(e) => {
+                for arg in &message.args {
+                    let value = match *arg {
+                        OscType::Float(f) => f as f64,
+                        OscType::Double(f) => f,
+                        _ => {
                             eprintln!(
-                                "Failed to parse received OSC address ({}): {}",
-                                message.addr, e
+                                "Ignoring unrecognized value {:?} sent to {}.",
+                                arg, message.addr
                             );
                             continue;
                         }
                     };
 
-                    if message_matcher.match_address(&rotation_addr) {
-                        for arg in &message.args {
-                            match *arg {
-                                OscType::Float(f) => {
-                                    let _ = sender.send(AppEvent::RotationUpdate(f as f64)).await;
-                                    egui.request_repaint();
-                                }
-
-                                OscType::Double(f) => {
-                                    let _ = sender.send(AppEvent::RotationUpdate(f)).await;
-                                    egui.request_repaint();
-                                }
-
-                                _ => eprintln!(
-                                    "Ignoring unrecognized value {:?} sent to {}.",
-                                    arg, message.addr
-                                ),
-                            }
+                    self.send_rotation(index, value).await;
+                }
+            }
+
+            for (index, image_addr) in self.image_addrs.iter().enumerate() {
+                if !message_matcher.match_address(image_addr) {
+                    continue;
+                }
+
+                for arg in &message.args {
+                    let OscType::String(ref name) = *arg else {
+                        eprintln!(
+                            "Ignoring unrecognized value {:?} sent to {}.",
+                            arg, message.addr
+                        );
+                        continue;
+                    };
+
+                    let Some(path) = self.images.get(name).cloned() else {
+                        eprintln!(
+                            "Ignoring unknown wheel image {name:?} sent to {}.",
+                            message.addr
+                        );
+                        continue;
+                    };
+
+                    let max_size = self.max_texture_size;
+                    match smol::unblock(move || load_wheel_image(&path, max_size)).await {
+                        Ok(image) => {
+                            let _ = self.sender.send(AppEvent::ImageChange(index, image)).await;
+                            self.egui.request_repaint();
                         }
+                        Err(e) => eprintln!("Failed to switch to wheel image {name:?}: {e:#}"),
                     }
                 }
             }
 
-            Err(e) => {
-                eprintln!("Failed to decode data packet: {}", e);
-                continue;
+            for (index, pedal_addr) in self.pedal_addrs.iter().enumerate() {
+                if !message_matcher.match_address(pedal_addr) {
+                    continue;
+                }
+
+                for arg in &message.args {
+                    let value = match *arg {
+                        OscType::Float(f) => f,
+                        OscType::Double(f) => f as f32,
+                        _ => {
+                            eprintln!(
+                                "Ignoring unrecognized value {:?} sent to {}.",
+                                arg, message.addr
+                            );
+                            continue;
+                        }
+                    };
+
+                    let _ = self.sender.send(AppEvent::PedalUpdate(index, value)).await;
+                    self.egui.request_repaint();
+                }
+            }
+
+            // Numbers are also accepted, as on when they're not 0, for senders without booleans.
+            for (index, indicator_addr) in self.indicator_addrs.iter().enumerate() {
+                if !message_matcher.match_address(indicator_addr) {
+                    continue;
+                }
+
+                for arg in &message.args {
+                    let on = match *arg {
+                        OscType::Bool(b) => b,
+                        OscType::Int(i) => i != 0,
+                        OscType::Long(i) => i != 0,
+                        OscType::Float(f) => f != 0.0,
+                        OscType::Double(f) => f != 0.0,
+                        _ => {
+                            eprintln!(
+                                "Ignoring unrecognized value {:?} sent to {}.",
+                                arg, message.addr
+                            );
+                            continue;
+                        }
+                    };
+
+                    let _ = self.sender.send(AppEvent::IndicatorUpdate(index, on)).await;
+                    self.egui.request_repaint();
+                }
             }
         }
     }
-
-    Ok(())
 }
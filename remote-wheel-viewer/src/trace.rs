@@ -0,0 +1,173 @@
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, LineWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use anyhow::{Context as _, Result as AnyResult};
+use futures::prelude::*;
+use serde::{Deserialize, Serialize};
+use smol::channel::Sender;
+
+use crate::AppEvent;
+
+/// One rotation in a trace file, which has one of these per line as JSON, e.g.
+/// `{"time":1.25,"wheel":0,"rotation":-42.5}`.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+struct Entry {
+    /// Seconds since recording started.
+    time: f64,
+    /// The index of the wheel, in the order that they're configured.
+    #[serde(default)]
+    wheel: usize,
+    /// In degrees.
+    rotation: f64,
+}
+
+/// Appends each rotation that's received to a trace file.
+pub struct Recorder {
+    path: PathBuf,
+    start: Instant,
+    file: Mutex<LineWriter<File>>,
+}
+
+impl Recorder {
+    pub fn open(path: PathBuf) -> AnyResult<Self> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .with_context(|| format!("Failed to open trace file <{}>", path.display()))?;
+
+        eprintln!("Recording rotations to <{}>.", path.display());
+        Ok(Recorder {
+            path,
+            start: Instant::now(),
+            file: Mutex::new(LineWriter::new(file)),
+        })
+    }
+
+    pub fn record(&self, wheel: usize, rotation: f64, time: Instant) {
+        let entry = Entry {
+            time: time.saturating_duration_since(self.start).as_secs_f64(),
+            wheel,
+            rotation,
+        };
+
+        let mut line = serde_json::to_string(&entry).expect("Failed to serialize trace entry");
+        line.push('\n');
+
+        if let Err(e) = self.file.lock().unwrap().write_all(line.as_bytes()) {
+            eprintln!(
+                "Failed to write to trace file <{}>: {}",
+                self.path.display(),
+                e
+            );
+        }
+    }
+}
+
+/// Reads a trace file, skipping (with a warning) any line that isn't a valid entry.
+fn read(path: &Path) -> AnyResult<Vec<Entry>> {
+    let file = File::open(path)
+        .with_context(|| format!("Failed to open trace file <{}>", path.display()))?;
+
+    let mut entries = Vec::new();
+    for (number, line) in BufReader::new(file).lines().enumerate() {
+        let line =
+            line.with_context(|| format!("Failed to read trace file <{}>", path.display()))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let entry = serde_json::from_str::<Entry>(&line)
+            .map_err(anyhow::Error::from)
+            .and_then(|entry| {
+                Duration::try_from_secs_f64(entry.time)
+                    .context("time must be a number of seconds, at least 0")?;
+                Ok(entry)
+            });
+
+        match entry {
+            Ok(entry) => entries.push(entry),
+            Err(e) => eprintln!(
+                "Skipping malformed line {} of trace file <{}>: {:#}",
+                number + 1,
+                path.display(),
+                e
+            ),
+        }
+    }
+
+    Ok(entries)
+}
+
+/// Sends the rotations in a trace file as if they were being received, with the same timing. This
+/// never returns successfully, so that the window stays open once the trace has finished.
+pub async fn replay(
+    path: &Path,
+    looped: bool,
+    egui: eframe::egui::Context,
+    sender: Sender<AppEvent>,
+) -> AnyResult<()> {
+    let entries = smol::unblock({
+        let path = path.to_path_buf();
+        move || read(&path)
+    })
+    .await?;
+
+    eprintln!(
+        "Replaying {} rotation(s) from <{}>.",
+        entries.len(),
+        path.display()
+    );
+
+    if !entries.is_empty() {
+        loop {
+            let length = play(&entries, &egui, &sender).await;
+            if !looped {
+                break;
+            }
+
+            if length.is_zero() {
+                eprintln!("The trace takes no time to play, so it won't be looped.");
+                break;
+            }
+        }
+    }
+
+    eprintln!("Finished replaying <{}>.", path.display());
+    future::pending().await
+}
+
+/// Plays a trace once, returning how long it took to play.
+async fn play(
+    entries: &[Entry],
+    egui: &eframe::egui::Context,
+    sender: &Sender<AppEvent>,
+) -> Duration {
+    // Each entry is scheduled from the start rather than after the one before it, so that delays
+    // don't add up. A trace that was appended to starts its times again from 0, which carries on
+    // straight from the one before it.
+    let first = Instant::now();
+    let mut start = first;
+    let mut previous = Duration::ZERO;
+
+    for entry in entries {
+        let offset = Duration::from_secs_f64(entry.time);
+        if offset < previous {
+            start += previous;
+        }
+
+        previous = offset;
+        let time = start + offset;
+        smol::Timer::at(time).await;
+
+        let _ = sender
+            .send(AppEvent::RotationUpdate(entry.wheel, entry.rotation, time))
+            .await;
+        egui.request_repaint();
+    }
+
+    (start + previous).saturating_duration_since(first)
+}
@@ -0,0 +1,79 @@
+use anyhow::{Context as _, Result as AnyResult};
+use async_tungstenite::tungstenite::Message;
+use futures::prelude::*;
+use serde::Deserialize;
+use smol::net::{SocketAddr, TcpListener, TcpStream};
+
+use crate::OscHandler;
+
+/// A rotation sent by a WebSocket client as JSON, e.g. `{"rotation":-123.4}`, or
+/// `{"wheel":1,"rotation":90}` for a wheel other than the first.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Update {
+    /// The index of the wheel, in the order that they're configured.
+    #[serde(default)]
+    wheel: usize,
+    /// In degrees.
+    rotation: f64,
+}
+
+/// Accepts WebSocket clients on `addr`, any number at once, passing the rotations they send to
+/// `handler` the same as those received over OSC.
+pub async fn listen(addr: SocketAddr, handler: &OscHandler) -> AnyResult<()> {
+    let listener = TcpListener::bind(addr)
+        .await
+        .with_context(|| format!("Failed to bind to WebSocket address {}", addr))?;
+    eprintln!("Listening for WebSocket clients on {}.", addr);
+
+    let mut connections = futures::stream::FuturesUnordered::new();
+
+    loop {
+        futures::select! {
+            accepted = listener.accept().fuse() => match accepted {
+                Ok((stream, peer)) => connections.push(serve(stream, peer, handler)),
+                Err(e) => eprintln!("Failed to accept WebSocket connection: {}", e),
+            },
+
+            _ = connections.select_next_some() => {},
+        }
+    }
+}
+
+async fn serve(stream: TcpStream, peer: SocketAddr, handler: &OscHandler) {
+    let mut socket = match async_tungstenite::accept_async(stream).await {
+        Ok(socket) => socket,
+        Err(e) => {
+            eprintln!("WebSocket handshake with {} failed: {}", peer, e);
+            return;
+        }
+    };
+
+    while let Some(message) = socket.next().await {
+        let text = match message {
+            Ok(Message::Text(text)) => text,
+            Ok(Message::Close(_)) => break,
+            // Pings are answered by the socket itself.
+            Ok(Message::Ping(_) | Message::Pong(_) | Message::Frame(_)) => continue,
+            Ok(Message::Binary(_)) => {
+                eprintln!(
+                    "Ignoring binary message from WebSocket client {}; rotations must be sent as JSON text.",
+                    peer
+                );
+                continue;
+            }
+            Err(e) => {
+                eprintln!("WebSocket connection from {} was lost: {}", peer, e);
+                break;
+            }
+        };
+
+        match serde_json::from_str::<Update>(&text) {
+            Ok(update) => handler.send_rotation(update.wheel, update.rotation).await,
+            Err(e) => eprintln!(
+                "Ignoring malformed message from WebSocket client {}: {}",
+                peer, e
+            ),
+        }
+    }
+}